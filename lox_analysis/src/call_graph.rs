@@ -0,0 +1,250 @@
+use crate::resolve_references;
+use lox_ast::{
+    visit::{walk_expr, walk_stmt, Visitor},
+    *,
+};
+use lox_lexer::Span;
+use lox_parser::parser::Ast;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Write,
+    mem,
+};
+
+/// A function or method of a [`CallGraph`].
+#[derive(Debug, Clone)]
+pub struct Node {
+    /// The name of the function, qualified by its class or enclosing function,
+    /// e.g. `Point.init`.
+    pub name: String,
+    /// The name of the declaration, `None` for the top level of the script.
+    pub span: Option<Span>,
+}
+
+/// The functions of a script and which functions each of them calls.
+///
+/// Calls are found syntactically: a call of a name is an edge to the function
+/// or the initializer of the class it resolves to, and a call of `x.m` is an
+/// edge to every method named `m`. Functions used as values are considered
+/// called by the function using them, since they may be called later.
+#[derive(Debug)]
+pub struct CallGraph {
+    /// The nodes, the first one being the top level of the script.
+    pub nodes: Vec<Node>,
+    pub edges: BTreeSet<(usize, usize)>,
+}
+
+impl CallGraph {
+    pub fn new(ast: &Ast) -> Self {
+        let (references, _) = resolve_references(ast);
+        let mut builder = Builder {
+            declarations: references
+                .iter()
+                .filter_map(|reference| Some((reference.span, reference.declaration?)))
+                .collect(),
+            nodes: vec![Node {
+                name: "<script>".to_string(),
+                span: None,
+            }],
+            current: 0,
+            functions: HashMap::new(),
+            methods: HashMap::new(),
+            calls: vec![],
+        };
+        ast.iter().for_each(|stmt| walk_stmt(&mut builder, stmt));
+
+        let mut edges = BTreeSet::new();
+        for (caller, callee) in builder.calls.iter() {
+            match callee {
+                Callee::Declaration(span) => {
+                    if let Some(&callee) = builder.functions.get(span) {
+                        edges.insert((*caller, callee));
+                    }
+                }
+                Callee::Method(name) => {
+                    for &callee in builder.methods.get(name).into_iter().flatten() {
+                        edges.insert((*caller, callee));
+                    }
+                }
+            }
+        }
+        Self {
+            nodes: builder.nodes,
+            edges,
+        }
+    }
+
+    /// The functions which can't be reached from the top level of the script.
+    pub fn dead_functions(&self) -> Vec<usize> {
+        let mut reachable = vec![false; self.nodes.len()];
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            if !reachable[node] {
+                reachable[node] = true;
+                stack.extend(
+                    self.edges
+                        .range((node, 0)..(node + 1, 0))
+                        .map(|&(_, callee)| callee),
+                );
+            }
+        }
+        (0..self.nodes.len())
+            .filter(|&node| !reachable[node])
+            .collect()
+    }
+
+    /// Renders the graph in the DOT language of Graphviz, drawing dead
+    /// functions dashed.
+    pub fn to_dot(&self) -> String {
+        let dead = self.dead_functions();
+        let mut dot = "digraph calls {\n".to_string();
+        for (i, node) in self.nodes.iter().enumerate() {
+            let label = node.name.replace('\\', "\\\\").replace('"', "\\\"");
+            let style = if dead.contains(&i) {
+                ", style=dashed"
+            } else {
+                ""
+            };
+            let _ = writeln!(dot, "    n{i} [label=\"{label}\"{style}];");
+        }
+        for (caller, callee) in self.edges.iter() {
+            let _ = writeln!(dot, "    n{caller} -> n{callee};");
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+enum Callee {
+    /// A function or class, by the span of its declaration.
+    Declaration(Span),
+    Method(String),
+}
+
+struct Builder {
+    /// The declaration of each resolved name, by the span of the name.
+    declarations: HashMap<Span, Span>,
+    nodes: Vec<Node>,
+    current: usize,
+    /// Functions by the span of their declaration, classes by the span of
+    /// their declaration mapping to their initializer.
+    functions: HashMap<Span, usize>,
+    methods: HashMap<String, Vec<usize>>,
+    calls: Vec<(usize, Callee)>,
+}
+
+impl Builder {
+    fn add_function(&mut self, function: &FnDecl, class: Option<&ClassDecl>) -> usize {
+        let name = match class {
+            Some(class) => format!("{}.{}", class.var, function.var),
+            None if self.current == 0 => function.var.to_string(),
+            None => format!("{}::{}", self.nodes[self.current].name, function.var),
+        };
+        self.nodes.push(Node {
+            name,
+            span: Some(function.var.ident.span),
+        });
+        self.nodes.len() - 1
+    }
+
+    fn visit_body(&mut self, node: usize, function: &FnDecl) {
+        let previous = mem::replace(&mut self.current, node);
+        function.body.iter().for_each(|stmt| walk_stmt(self, stmt));
+        self.current = previous;
+    }
+}
+
+impl Visitor for Builder {
+    type Result = ();
+
+    fn visit_if(&mut self, if_stmt: &If) -> Self::Result {
+        walk_expr(self, &if_stmt.condition);
+        walk_stmt(self, &if_stmt.then_branch);
+        if let Some(else_branch) = &if_stmt.else_branch {
+            walk_stmt(self, else_branch);
+        }
+    }
+
+    fn visit_while(&mut self, while_stmt: &While) -> Self::Result {
+        walk_expr(self, &while_stmt.condition);
+        walk_stmt(self, &while_stmt.body);
+    }
+
+    fn visit_block(&mut self, block: &Block) -> Self::Result {
+        block
+            .statements
+            .iter()
+            .for_each(|stmt| walk_stmt(self, stmt));
+    }
+
+    fn visit_var_decl(&mut self, var_decl: &VarDecl) -> Self::Result {
+        if let Some(initializer) = &var_decl.initializer {
+            walk_expr(self, initializer);
+        }
+    }
+
+    fn visit_function(&mut self, function: &FnDecl) -> Self::Result {
+        let node = self.add_function(function, None);
+        self.functions.insert(function.var.ident.span, node);
+        self.visit_body(node, function);
+    }
+
+    fn visit_class(&mut self, class: &ClassDecl) -> Self::Result {
+        if let Some(super_class) = &class.super_class {
+            self.visit_var(super_class);
+        }
+        for method in class.methods.iter() {
+            let node = self.add_function(method, Some(class));
+            let name = &*method.var.ident.name;
+            if name == "init" {
+                self.functions.insert(class.var.ident.span, node);
+            }
+            self.methods.entry(name.to_string()).or_default().push(node);
+            self.visit_body(node, method);
+        }
+    }
+
+    fn visit_return(&mut self, return_stmt: &Return) -> Self::Result {
+        if let Some(expr) = &return_stmt.expr {
+            walk_expr(self, expr);
+        }
+    }
+
+    fn visit_fn_call(&mut self, fn_call: &FnCall) -> Self::Result {
+        walk_expr(self, &fn_call.callee);
+        for argument in fn_call.arguments.iter() {
+            walk_expr(self, argument);
+        }
+    }
+
+    fn visit_get(&mut self, get: &Get) -> Self::Result {
+        self.calls
+            .push((self.current, Callee::Method(get.field.name.to_string())));
+        walk_expr(self, &get.object)
+    }
+
+    fn visit_set(&mut self, set: &Set) -> Self::Result {
+        walk_expr(self, &set.target.object);
+        walk_expr(self, &set.value)
+    }
+
+    fn visit_super(&mut self, super_expr: &Super) -> Self::Result {
+        self.calls.push((
+            self.current,
+            Callee::Method(super_expr.method.name.to_string()),
+        ));
+    }
+
+    fn visit_literal(&mut self, _literal: &Literal) -> Self::Result {}
+
+    fn visit_var(&mut self, var: &Variable) -> Self::Result {
+        // `this` resolves to the name of its class, but isn't a call.
+        if &*var.ident.name == "this" {
+            return;
+        }
+        if let Some(&declaration) = self.declarations.get(&var.ident.span) {
+            self.calls
+                .push((self.current, Callee::Declaration(declaration)));
+        }
+    }
+}
@@ -0,0 +1,14 @@
+use lox_lexer::{Position, Span};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum AnalysisError {
+    #[error("{0}: no variable, function or class here")]
+    NoIdentifier(Position),
+    #[error("`{0}` isn't a valid identifier")]
+    InvalidName(String),
+    #[error("{pos}: `{name}` isn't declared in this program")]
+    Undeclared { pos: Span, name: String },
+    #[error("{pos}: renaming to `{name}` would change what this refers to")]
+    Collision { pos: Span, name: String },
+}
@@ -0,0 +1,32 @@
+//! Queries and refactorings over resolved programs, for editors and tools.
+
+pub mod call_graph;
+pub mod error;
+pub mod metrics;
+pub mod query;
+pub mod rename;
+#[cfg(test)]
+mod test;
+
+pub use crate::{
+    call_graph::CallGraph,
+    error::AnalysisError,
+    metrics::{metrics, FunctionMetrics},
+    query::{definition, find_symbol, references, Symbol},
+    rename::rename,
+};
+
+use lox_parser::parser::Ast;
+use lox_resolver::{Reference, Resolver, ResolverOptions};
+
+/// Resolves a copy of `ast`, returning the references in it and the number of
+/// errors of the resolver.
+fn resolve_references(ast: &Ast) -> (Box<[Reference]>, usize) {
+    let mut ast = ast.clone();
+    let mut resolver = Resolver::new(ResolverOptions {
+        references: true,
+        ..Default::default()
+    });
+    let errors = resolver.resolve(&mut ast).map_or(0, |errors| errors.len());
+    (resolver.take_references(), errors)
+}
@@ -0,0 +1,222 @@
+use lox_ast::{
+    visit::{walk_binary, walk_expr, walk_stmt, walk_ternary, Visitor},
+    *,
+};
+use lox_lexer::{json::quote, Span};
+use lox_parser::parser::Ast;
+use std::{fmt::Write, mem};
+
+/// Size and complexity of a function, or of the top level of a script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionMetrics {
+    /// The name of the function, qualified by its class or enclosing function,
+    /// `<script>` for the top level.
+    pub name: String,
+    /// The name of the declaration, `None` for the top level.
+    pub span: Option<Span>,
+    /// The number of statements in the body, excluding blocks and the bodies of
+    /// nested functions.
+    pub statements: usize,
+    /// The deepest nesting of `if`, `while` and `for` statements, `else if`
+    /// not being nested in the `if`.
+    pub nesting: usize,
+    /// One plus the number of branches: `if`, loops, `?:`, `and` and `or`.
+    pub complexity: usize,
+}
+
+impl FunctionMetrics {
+    fn new(name: String, span: Option<Span>) -> Self {
+        Self {
+            name,
+            span,
+            statements: 0,
+            nesting: 0,
+            complexity: 1,
+        }
+    }
+}
+
+/// Computes the metrics of the top level of a script and of each function and
+/// method in it, in order of declaration.
+pub fn metrics(ast: &Ast) -> Vec<FunctionMetrics> {
+    let mut collector = Collector {
+        metrics: vec![],
+        current: FunctionMetrics::new("<script>".to_string(), None),
+        depth: 0,
+    };
+    collector.visit_statements(ast);
+    let mut metrics = collector.metrics;
+    metrics.insert(0, collector.current);
+    metrics
+}
+
+/// Renders metrics as a table with a row per function.
+pub fn to_table(metrics: &[FunctionMetrics]) -> String {
+    let width = metrics
+        .iter()
+        .map(|metrics| metrics.name.len())
+        .chain(["function".len()])
+        .max()
+        .unwrap_or_default();
+    let mut table = format!(
+        "{:width$}  {:>5}  {:>10}  {:>7}  {:>10}\n",
+        "function", "line", "statements", "nesting", "complexity"
+    );
+    for metrics in metrics {
+        let line = metrics
+            .span
+            .map_or(String::new(), |span| span.start.line.to_string());
+        let _ = writeln!(
+            table,
+            "{:width$}  {:>5}  {:>10}  {:>7}  {:>10}",
+            metrics.name, line, metrics.statements, metrics.nesting, metrics.complexity
+        );
+    }
+    table
+}
+
+/// Renders metrics as a JSON array with an object per function.
+pub fn to_json(metrics: &[FunctionMetrics]) -> String {
+    let mut json = "[".to_string();
+    for (i, metrics) in metrics.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        let line = metrics
+            .span
+            .map_or("null".to_string(), |span| span.start.line.to_string());
+        let _ = write!(
+            json,
+            "\n  {{\"name\": {}, \"line\": {line}, \"statements\": {}, \"nesting\": {}, \"complexity\": {}}}",
+            quote(&metrics.name),
+            metrics.statements,
+            metrics.nesting,
+            metrics.complexity
+        );
+    }
+    json.push_str("\n]\n");
+    json
+}
+
+struct Collector {
+    /// The metrics of the functions which have been visited.
+    metrics: Vec<FunctionMetrics>,
+    current: FunctionMetrics,
+    depth: usize,
+}
+
+impl Collector {
+    fn visit_statements(&mut self, statements: &[Statement]) {
+        for stmt in statements {
+            if !matches!(stmt, Statement::Block(_)) {
+                self.current.statements += 1;
+            }
+            walk_stmt(self, stmt);
+        }
+    }
+
+    /// Visits a statement nested in a branch or a loop.
+    fn visit_nested(&mut self, stmt: &Statement) {
+        self.depth += 1;
+        self.current.nesting = self.current.nesting.max(self.depth);
+        self.visit_statements(std::slice::from_ref(stmt));
+        self.depth -= 1;
+    }
+
+    fn visit_function(&mut self, name: String, function: &FnDecl) {
+        let metrics = FunctionMetrics::new(name, Some(function.var.ident.span));
+        let previous = mem::replace(&mut self.current, metrics);
+        let depth = mem::take(&mut self.depth);
+        // Keep the functions in order of declaration, before nested ones.
+        let index = self.metrics.len();
+        self.visit_statements(&function.body);
+        let metrics = mem::replace(&mut self.current, previous);
+        self.metrics.insert(index, metrics);
+        self.depth = depth;
+    }
+
+    fn qualified_name(&self, name: &Variable) -> String {
+        if self.current.span.is_none() {
+            name.to_string()
+        } else {
+            format!("{}::{}", self.current.name, name)
+        }
+    }
+}
+
+impl Visitor for Collector {
+    type Result = ();
+
+    fn visit_if(&mut self, if_stmt: &If) -> Self::Result {
+        self.current.complexity += 1;
+        walk_expr(self, &if_stmt.condition);
+        self.visit_nested(&if_stmt.then_branch);
+        match if_stmt.else_branch.as_deref() {
+            Some(Statement::If(else_if)) => {
+                self.current.statements += 1;
+                self.visit_if(else_if);
+            }
+            Some(else_branch) => self.visit_nested(else_branch),
+            None => {}
+        }
+    }
+
+    fn visit_while(&mut self, while_stmt: &While) -> Self::Result {
+        self.current.complexity += 1;
+        walk_expr(self, &while_stmt.condition);
+        self.visit_nested(&while_stmt.body);
+    }
+
+    fn visit_block(&mut self, block: &Block) -> Self::Result {
+        self.visit_statements(&block.statements);
+    }
+
+    fn visit_var_decl(&mut self, var_decl: &VarDecl) -> Self::Result {
+        if let Some(initializer) = &var_decl.initializer {
+            walk_expr(self, initializer);
+        }
+    }
+
+    fn visit_function(&mut self, function: &FnDecl) -> Self::Result {
+        let name = self.qualified_name(&function.var);
+        Collector::visit_function(self, name, function);
+    }
+
+    fn visit_class(&mut self, class: &ClassDecl) -> Self::Result {
+        let class_name = self.qualified_name(&class.var);
+        for method in class.methods.iter() {
+            Collector::visit_function(self, format!("{class_name}.{}", method.var), method);
+        }
+    }
+
+    fn visit_return(&mut self, return_stmt: &Return) -> Self::Result {
+        if let Some(expr) = &return_stmt.expr {
+            walk_expr(self, expr);
+        }
+    }
+
+    fn visit_binary(&mut self, binary: &BinaryExpr) -> Self::Result {
+        if let BinaryOp::And | BinaryOp::Or = binary.operator {
+            self.current.complexity += 1;
+        }
+        walk_binary(self, binary)
+    }
+
+    fn visit_ternary(&mut self, ternary: &Ternary) -> Self::Result {
+        self.current.complexity += 1;
+        walk_ternary(self, ternary)
+    }
+
+    fn visit_fn_call(&mut self, fn_call: &FnCall) -> Self::Result {
+        walk_expr(self, &fn_call.callee);
+        for argument in fn_call.arguments.iter() {
+            walk_expr(self, argument);
+        }
+    }
+
+    fn visit_super(&mut self, _super_expr: &Super) -> Self::Result {}
+
+    fn visit_literal(&mut self, _literal: &Literal) -> Self::Result {}
+
+    fn visit_var(&mut self, _var: &Variable) -> Self::Result {}
+}
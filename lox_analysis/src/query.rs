@@ -0,0 +1,198 @@
+use crate::{error::AnalysisError, resolve_references};
+use lox_ast::{
+    visit::{walk_expr, walk_stmt, Visitor},
+    *,
+};
+use lox_lexer::{LineIndex, Span};
+use lox_parser::parser::Ast;
+
+/// A declared name and its uses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    /// `None` for globals of the host and names which aren't declared.
+    pub definition: Option<Span>,
+    /// The uses of the name, excluding the definition, ordered by position.
+    pub references: Vec<Span>,
+}
+
+/// Finds the symbol of the identifier at byte `offset` of `src`, `ast` being
+/// parsed from `src`.
+///
+/// Variables, functions, classes, `this` and `super` are looked up with the
+/// resolver. Fields and methods are matched by name only, since which class an
+/// instance belongs to isn't known statically: a property is defined by the
+/// first method of that name, or by the first assignment to it if there's no
+/// such method.
+pub fn find_symbol(src: &str, ast: &Ast, offset: usize) -> Result<Symbol, AnalysisError> {
+    let index = LineIndex::new(src);
+    let position = index.position(offset);
+    let (references, _) = resolve_references(ast);
+
+    // `super` and its `this` share a span, the text tells which one is meant.
+    if let Some(target) = references.iter().find(|reference| {
+        reference.span.contains(position) && index.text(reference.span) == reference.name
+    }) {
+        let definition = match target.declaration {
+            // `super` is declared by the superclass of the subclass.
+            Some(declaration) if target.name == "super" => references
+                .iter()
+                .find(|reference| reference.span == declaration && reference.name != "super")
+                .and_then(|reference| reference.declaration),
+            declaration => declaration,
+        };
+        return Ok(Symbol {
+            name: target.name.to_string(),
+            definition,
+            references: references
+                .iter()
+                .filter(|reference| {
+                    reference.name == target.name
+                        && reference.declaration == target.declaration
+                        && Some(reference.span) != target.declaration
+                })
+                .map(|reference| reference.span)
+                .collect(),
+        });
+    }
+
+    let mut properties = Properties::default();
+    ast.iter().for_each(|stmt| walk_stmt(&mut properties, stmt));
+    let target = properties
+        .properties
+        .iter()
+        .find(|property| property.span.contains(position))
+        .ok_or(AnalysisError::NoIdentifier(position))?;
+    let named = || {
+        properties
+            .properties
+            .iter()
+            .filter(|property| property.name == target.name)
+    };
+    let definition = named()
+        .find(|property| property.kind == PropertyKind::Method)
+        .or_else(|| named().find(|property| property.kind == PropertyKind::Set))
+        .map(|property| property.span);
+    let mut references: Vec<_> = named()
+        .map(|property| property.span)
+        .filter(|&span| Some(span) != definition)
+        .collect();
+    references.sort_by_key(|span| span.start);
+    Ok(Symbol {
+        name: target.name.to_string(),
+        definition,
+        references,
+    })
+}
+
+/// Finds the definition of the identifier at byte `offset` of `src`.
+pub fn definition(src: &str, ast: &Ast, offset: usize) -> Result<Option<Span>, AnalysisError> {
+    find_symbol(src, ast, offset).map(|symbol| symbol.definition)
+}
+
+/// Finds the uses of the identifier at byte `offset` of `src`.
+pub fn references(src: &str, ast: &Ast, offset: usize) -> Result<Vec<Span>, AnalysisError> {
+    find_symbol(src, ast, offset).map(|symbol| symbol.references)
+}
+
+#[derive(PartialEq, Eq)]
+enum PropertyKind {
+    Method,
+    Get,
+    Set,
+}
+
+struct Property {
+    span: Span,
+    name: String,
+    kind: PropertyKind,
+}
+
+/// Collects the names of methods and of the properties used with `.`.
+#[derive(Default)]
+struct Properties {
+    properties: Vec<Property>,
+}
+
+impl Properties {
+    fn push(&mut self, ident: &Ident, kind: PropertyKind) {
+        self.properties.push(Property {
+            span: ident.span,
+            name: ident.name.to_string(),
+            kind,
+        });
+    }
+}
+
+impl Visitor for Properties {
+    type Result = ();
+
+    fn visit_if(&mut self, if_stmt: &If) -> Self::Result {
+        walk_expr(self, &if_stmt.condition);
+        walk_stmt(self, &if_stmt.then_branch);
+        if let Some(else_branch) = &if_stmt.else_branch {
+            walk_stmt(self, else_branch);
+        }
+    }
+
+    fn visit_while(&mut self, while_stmt: &While) -> Self::Result {
+        walk_expr(self, &while_stmt.condition);
+        walk_stmt(self, &while_stmt.body);
+    }
+
+    fn visit_block(&mut self, block: &Block) -> Self::Result {
+        block
+            .statements
+            .iter()
+            .for_each(|stmt| walk_stmt(self, stmt));
+    }
+
+    fn visit_var_decl(&mut self, var_decl: &VarDecl) -> Self::Result {
+        if let Some(initializer) = &var_decl.initializer {
+            walk_expr(self, initializer);
+        }
+    }
+
+    fn visit_function(&mut self, function: &FnDecl) -> Self::Result {
+        function.body.iter().for_each(|stmt| walk_stmt(self, stmt));
+    }
+
+    fn visit_class(&mut self, class: &ClassDecl) -> Self::Result {
+        for method in class.methods.iter() {
+            self.push(&method.var.ident, PropertyKind::Method);
+            self.visit_function(method);
+        }
+    }
+
+    fn visit_return(&mut self, return_stmt: &Return) -> Self::Result {
+        if let Some(expr) = &return_stmt.expr {
+            walk_expr(self, expr);
+        }
+    }
+
+    fn visit_fn_call(&mut self, fn_call: &FnCall) -> Self::Result {
+        walk_expr(self, &fn_call.callee);
+        for argument in fn_call.arguments.iter() {
+            walk_expr(self, argument);
+        }
+    }
+
+    fn visit_get(&mut self, get: &Get) -> Self::Result {
+        self.push(&get.field, PropertyKind::Get);
+        walk_expr(self, &get.object)
+    }
+
+    fn visit_set(&mut self, set: &Set) -> Self::Result {
+        self.push(&set.target.field, PropertyKind::Set);
+        walk_expr(self, &set.target.object);
+        walk_expr(self, &set.value)
+    }
+
+    fn visit_super(&mut self, super_expr: &Super) -> Self::Result {
+        self.push(&super_expr.method, PropertyKind::Get);
+    }
+
+    fn visit_literal(&mut self, _literal: &Literal) -> Self::Result {}
+
+    fn visit_var(&mut self, _var: &Variable) -> Self::Result {}
+}
@@ -0,0 +1,169 @@
+use crate::{error::AnalysisError, resolve_references};
+use lox_ast::{
+    visit_mut::{walk_expr, walk_stmt, VisitorMut},
+    *,
+};
+use lox_lexer::{Lexer, Position, Span, TokenType};
+use lox_parser::parser::Ast;
+use std::collections::HashSet;
+
+/// Computes the edits renaming the variable, function or class at `position`
+/// to `new_name`, each replacing the text of a span.
+///
+/// A rename is rejected if any name of the program, renamed or not, would
+/// refer to another declaration afterwards, e.g. if a local in between would
+/// capture a renamed use, or if the new name is already declared in the scope.
+pub fn rename(
+    ast: &Ast,
+    position: Position,
+    new_name: &str,
+) -> Result<Vec<(Span, String)>, AnalysisError> {
+    let mut lexer = Lexer::new(new_name);
+    if !matches!(lexer.next_token().token_type, TokenType::Identifier(_))
+        || !matches!(lexer.next_token().token_type, TokenType::Eof)
+    {
+        return Err(AnalysisError::InvalidName(new_name.to_string()));
+    }
+
+    let (references, errors) = resolve_references(ast);
+    let target = references
+        .iter()
+        .find(|reference| reference.span.contains(position))
+        .ok_or(AnalysisError::NoIdentifier(position))?;
+    let declaration = target
+        .declaration
+        .ok_or_else(|| AnalysisError::Undeclared {
+            pos: target.span,
+            name: target.name.to_string(),
+        })?;
+    // `this` and `super` refer to the name of their class, but aren't renamed.
+    let spans: HashSet<_> = references
+        .iter()
+        .filter(|reference| reference.declaration == Some(declaration))
+        .filter(|reference| reference.name == target.name)
+        .map(|reference| reference.span)
+        .collect();
+
+    let mut renamed = ast.clone();
+    let mut renamer = Renamer {
+        spans: &spans,
+        new_name,
+    };
+    renamed
+        .iter_mut()
+        .for_each(|stmt| walk_stmt(&mut renamer, stmt));
+    let (renamed_references, renamed_errors) = resolve_references(&renamed);
+    let collision = references
+        .iter()
+        .zip(renamed_references.iter())
+        .find(|(before, after)| {
+            before.span != after.span || before.declaration != after.declaration
+        })
+        .map(|(before, _)| before.span);
+    if let Some(pos) = collision.or((renamed_errors > errors).then_some(declaration)) {
+        return Err(AnalysisError::Collision {
+            pos,
+            name: new_name.to_string(),
+        });
+    }
+
+    let mut edits: Vec<_> = spans
+        .into_iter()
+        .map(|span| (span, new_name.to_string()))
+        .collect();
+    edits.sort_by_key(|(span, _)| span.start);
+    Ok(edits)
+}
+
+struct Renamer<'a> {
+    spans: &'a HashSet<Span>,
+    new_name: &'a str,
+}
+
+impl Renamer<'_> {
+    fn rename(&self, var: &mut Variable) {
+        if self.spans.contains(&var.ident.span) {
+            var.ident.name = self.new_name.into();
+        }
+    }
+
+    fn rename_function(&mut self, function: &mut FnDecl) {
+        self.rename(&mut function.var);
+        function
+            .params
+            .iter_mut()
+            .for_each(|param| self.rename(param));
+        function
+            .body
+            .iter_mut()
+            .for_each(|stmt| walk_stmt(self, stmt));
+    }
+}
+
+impl VisitorMut for Renamer<'_> {
+    type Result = ();
+
+    fn visit_if(&mut self, if_stmt: &mut If) -> Self::Result {
+        walk_expr(self, &mut if_stmt.condition);
+        walk_stmt(self, &mut if_stmt.then_branch);
+        if let Some(else_branch) = &mut if_stmt.else_branch {
+            walk_stmt(self, else_branch);
+        }
+    }
+
+    fn visit_while(&mut self, while_stmt: &mut While) -> Self::Result {
+        walk_expr(self, &mut while_stmt.condition);
+        walk_stmt(self, &mut while_stmt.body);
+    }
+
+    fn visit_block(&mut self, block: &mut Block) -> Self::Result {
+        block
+            .statements
+            .iter_mut()
+            .for_each(|stmt| walk_stmt(self, stmt));
+    }
+
+    fn visit_var_decl(&mut self, var_decl: &mut VarDecl) -> Self::Result {
+        self.rename(&mut var_decl.var);
+        if let Some(initializer) = &mut var_decl.initializer {
+            walk_expr(self, initializer);
+        }
+    }
+
+    fn visit_function(&mut self, function: &mut FnDecl) -> Self::Result {
+        self.rename_function(function);
+    }
+
+    fn visit_class(&mut self, class: &mut ClassDecl) -> Self::Result {
+        self.rename(&mut class.var);
+        if let Some(super_class) = &mut class.super_class {
+            self.rename(super_class);
+        }
+        class
+            .methods
+            .iter_mut()
+            .for_each(|method| self.rename_function(method));
+    }
+
+    fn visit_return(&mut self, return_stmt: &mut Return) -> Self::Result {
+        if let Some(expr) = &mut return_stmt.expr {
+            walk_expr(self, expr);
+        }
+    }
+
+    fn visit_fn_call(&mut self, fn_call: &mut FnCall) -> Self::Result {
+        walk_expr(self, &mut fn_call.callee);
+        fn_call
+            .arguments
+            .iter_mut()
+            .for_each(|argument| walk_expr(self, argument));
+    }
+
+    fn visit_super(&mut self, _super_expr: &mut Super) -> Self::Result {}
+
+    fn visit_literal(&mut self, _literal: &mut Literal) -> Self::Result {}
+
+    fn visit_var(&mut self, var: &mut Variable) -> Self::Result {
+        self.rename(var);
+    }
+}
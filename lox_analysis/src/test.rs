@@ -0,0 +1,251 @@
+use crate::{find_symbol, metrics, metrics::to_json, rename, AnalysisError, CallGraph};
+use lox_lexer::{Position, Span};
+
+/// The position of the `n`th occurrence of the word `word` in `src`.
+fn position(src: &str, word: &str, n: usize) -> Position {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let offset = src
+        .match_indices(word)
+        .map(|(offset, _)| offset)
+        .filter(|&offset| {
+            !src[..offset].ends_with(is_word) && !src[offset + word.len()..].starts_with(is_word)
+        })
+        .nth(n)
+        .unwrap();
+    let before = &src[..offset];
+    Position {
+        line: before.matches('\n').count() as u32 + 1,
+        column: before.rsplit('\n').next().unwrap().chars().count() as u32 + 1,
+    }
+}
+
+fn text(src: &str, span: Span) -> String {
+    let line = src.lines().nth(span.start.line as usize - 1).unwrap();
+    line[span.start.column as usize - 1..span.end.column as usize - 1].to_string()
+}
+
+#[test]
+fn rename_local() {
+    let src = "var a = 1;\nfun f(a) {\n  var b = a;\n  { print a + b; }\n}\nprint a;";
+    let ast = lox_parser::parse(src).into_result().unwrap();
+    let edits = rename(&ast, position(src, "a", 1), "x").unwrap();
+    let positions: Vec<_> = edits.iter().map(|(span, _)| span.start).collect();
+    assert_eq!(
+        positions,
+        [
+            position(src, "a", 1),
+            position(src, "a", 2),
+            position(src, "a", 3)
+        ]
+    );
+    assert!(edits
+        .iter()
+        .all(|(span, name)| text(src, *span) == "a" && name == "x"));
+}
+
+#[test]
+fn rename_class() {
+    let src = "class A { m() { return this; } }\nclass B < A {}\nvar a = A();";
+    let ast = lox_parser::parse(src).into_result().unwrap();
+    let edits = rename(&ast, position(src, "A", 1), "Base").unwrap();
+    assert_eq!(edits.len(), 3);
+    assert!(edits.iter().all(|(span, _)| text(src, *span) == "A"));
+}
+
+#[test]
+fn reject_collisions() {
+    let src = "var a = 1;\nfun f() {\n  var b = 2;\n  print a + b;\n}\nvar c;";
+    let ast = lox_parser::parse(src).into_result().unwrap();
+    // `a` would be captured by the local `b`.
+    assert!(matches!(
+        rename(&ast, position(src, "a", 0), "b"),
+        Err(AnalysisError::Collision { .. })
+    ));
+    // The local would shadow the global `a` used in the same function.
+    assert!(matches!(
+        rename(&ast, position(src, "b", 0), "a"),
+        Err(AnalysisError::Collision { .. })
+    ));
+    // Redeclaring a global changes what its uses refer to.
+    assert!(matches!(
+        rename(&ast, position(src, "c", 0), "a"),
+        Err(AnalysisError::Collision { .. })
+    ));
+    assert!(rename(&ast, position(src, "b", 0), "c").is_ok());
+}
+
+#[test]
+fn reject_invalid_targets() {
+    let src = "print clock();\nvar a;";
+    let ast = lox_parser::parse(src).into_result().unwrap();
+    assert!(matches!(
+        rename(&ast, position(src, "a", 0), "while"),
+        Err(AnalysisError::InvalidName(_))
+    ));
+    assert!(matches!(
+        rename(&ast, position(src, "clock", 0), "time"),
+        Err(AnalysisError::Undeclared { .. })
+    ));
+    assert!(matches!(
+        rename(&ast, position(src, "print", 0), "x"),
+        Err(AnalysisError::NoIdentifier(_))
+    ));
+}
+
+const CLASSES: &str = "var count = 0;
+class A {
+  init(n) { this.n = n; }
+  get() { return this.n; }
+}
+class B < A {
+  get() { return super.get() + count; }
+}
+fun bump() { count = count + 1; return B(1).get(); }
+print clock();";
+
+/// A span as its line and text.
+type Located = (u32, String);
+
+/// The definition and references of the symbol at the `n`th occurrence of
+/// `word`.
+fn symbol(word: &str, n: usize) -> Result<(Option<Located>, Vec<Located>), AnalysisError> {
+    let ast = lox_parser::parse(CLASSES).into_result().unwrap();
+    let position = position(CLASSES, word, n);
+    let offset = lox_lexer::LineIndex::new(CLASSES).offset(position);
+    let describe = |span: Span| (span.start.line, text(CLASSES, span));
+    find_symbol(CLASSES, &ast, offset).map(|symbol| {
+        (
+            symbol.definition.map(describe),
+            symbol.references.into_iter().map(describe).collect(),
+        )
+    })
+}
+
+fn spans(spans: &[(u32, &str)]) -> Vec<Located> {
+    spans
+        .iter()
+        .map(|&(line, text)| (line, text.to_string()))
+        .collect()
+}
+
+#[test]
+fn find_variables() {
+    let (definition, references) = symbol("count", 2).unwrap();
+    assert_eq!(definition, Some((1, "count".to_string())));
+    assert_eq!(
+        references,
+        spans(&[(7, "count"), (9, "count"), (9, "count")])
+    );
+
+    let (definition, references) = symbol("clock", 0).unwrap();
+    assert_eq!(definition, None);
+    assert_eq!(references, spans(&[(10, "clock")]));
+}
+
+#[test]
+fn find_this_and_super() {
+    let (definition, references) = symbol("this", 1).unwrap();
+    assert_eq!(definition, Some((2, "A".to_string())));
+    assert_eq!(references, spans(&[(3, "this"), (4, "this")]));
+
+    let (definition, references) = symbol("super", 0).unwrap();
+    assert_eq!(definition, Some((2, "A".to_string())));
+    assert_eq!(references, spans(&[(7, "super")]));
+}
+
+#[test]
+fn find_properties() {
+    let (definition, references) = symbol("get", 2).unwrap();
+    assert_eq!(definition, Some((4, "get".to_string())));
+    assert_eq!(references, spans(&[(7, "get"), (7, "get"), (9, "get")]));
+
+    let (definition, references) = symbol("n", 3).unwrap();
+    assert_eq!(definition, Some((3, "n".to_string())));
+    assert_eq!(references, spans(&[(4, "n")]));
+}
+
+#[test]
+fn find_nothing() {
+    assert!(matches!(
+        symbol("print", 0),
+        Err(AnalysisError::NoIdentifier(_))
+    ));
+}
+
+#[test]
+fn call_graph() {
+    let src = "
+fun helper() {}
+fun unused() { helper(); }
+fun outer() {
+    fun inner() { return helper; }
+    return inner();
+}
+class A {
+    init() { this.go(); }
+    go() {}
+}
+outer();
+A();";
+    let graph = CallGraph::new(&lox_parser::parse(src).into_result().unwrap());
+    let names: Vec<_> = graph.nodes.iter().map(|node| node.name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "<script>",
+            "helper",
+            "unused",
+            "outer",
+            "outer::inner",
+            "A.init",
+            "A.go"
+        ]
+    );
+    assert_eq!(
+        graph.edges.iter().copied().collect::<Vec<_>>(),
+        [(0, 3), (0, 5), (2, 1), (3, 4), (4, 1), (5, 6)]
+    );
+    assert_eq!(graph.dead_functions(), [2]);
+    assert!(graph
+        .to_dot()
+        .contains("    n2 [label=\"unused\", style=dashed];\n"));
+}
+
+#[test]
+fn function_metrics() {
+    let src = "
+fun classify(n) {
+    if (n < 0 and n != -1) {
+        return \"negative\";
+    } else if (n == 0) {
+        return \"zero\";
+    } else {
+        while (n > 10) {
+            if (n > 100) n = n / 10; else n = n - 1;
+        }
+    }
+    fun nested() { return n > 0 ? n : -n; }
+    return nested();
+}
+class A {
+    m() {}
+}
+print classify(5);";
+    let metrics = metrics(&lox_parser::parse(src).into_result().unwrap());
+    let summary: Vec<_> = metrics
+        .iter()
+        .map(|m| (m.name.as_str(), m.statements, m.nesting, m.complexity))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("<script>", 3, 0, 1),
+            ("classify", 10, 3, 6),
+            ("classify::nested", 1, 0, 2),
+            ("A.m", 0, 0, 1),
+        ]
+    );
+    assert!(to_json(&metrics[3..]).contains(
+        "{\"name\": \"A.m\", \"line\": 16, \"statements\": 0, \"nesting\": 0, \"complexity\": 1}"
+    ));
+}
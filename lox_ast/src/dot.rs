@@ -0,0 +1,277 @@
+//! Renders an AST as a Graphviz DOT graph, e.g. to see how a program is
+//! parsed with `dot -Tsvg`.
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::{Display, Write};
+
+use lox_lexer::Span;
+
+use crate::{
+    expr::*,
+    ident::Variable,
+    stmt::*,
+    visit::{walk_expr, walk_stmt, Visitor},
+};
+
+/// Describes `ast` as a DOT digraph, where each node is labelled with the
+/// kind of the statement or expression, its operator or name if any, and its
+/// span, and each edge with the role of the child.
+pub fn to_dot(ast: &[Statement]) -> String {
+    let mut writer = DotWriter {
+        output: String::from(
+            "digraph ast {\n  node [shape=box, fontname=monospace];\n  n0 [label=\"program\"];\n",
+        ),
+        nodes: 1,
+    };
+    for stmt in ast {
+        let child = walk_stmt(&mut writer, stmt);
+        writer.edge(0, child, "");
+    }
+    writer.output.push_str("}\n");
+    writer.output
+}
+
+struct DotWriter {
+    output: String,
+    /// Number of nodes written, the next node's id.
+    nodes: usize,
+}
+
+impl DotWriter {
+    fn node(&mut self, label: impl Display, span: Span) -> usize {
+        let id = self.nodes;
+        self.nodes += 1;
+        let label = escape(&format!("{label}\n{span}"));
+        // Writing to a `String` can't fail.
+        let _ = writeln!(self.output, "  n{id} [label=\"{label}\"];");
+        id
+    }
+
+    fn edge(&mut self, from: usize, to: usize, role: &str) {
+        let _ = if role.is_empty() {
+            writeln!(self.output, "  n{from} -> n{to};")
+        } else {
+            writeln!(self.output, "  n{from} -> n{to} [label=\"{role}\"];")
+        };
+    }
+
+    fn child_expr(&mut self, parent: usize, expr: &Expr, role: &str) {
+        let child = walk_expr(self, expr);
+        self.edge(parent, child, role);
+    }
+
+    fn child_stmt(&mut self, parent: usize, stmt: &Statement, role: &str) {
+        let child = walk_stmt(self, stmt);
+        self.edge(parent, child, role);
+    }
+
+    fn function(&mut self, kind: &str, function: &FnDecl) -> usize {
+        let params = function
+            .params
+            .iter()
+            .map(|param| param.ident.name.as_ref())
+            .collect::<Vec<_>>();
+        let id = self.node(
+            format!("{kind} {}({})", function.var, params.join(", ")),
+            function.span,
+        );
+        for stmt in function.body.iter() {
+            self.child_stmt(id, stmt, "");
+        }
+        id
+    }
+}
+
+/// Escapes `s` for a quoted DOT string, turning newlines into centered line
+/// breaks.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn binary_operator(operator: BinaryOp) -> &'static str {
+    match operator {
+        BinaryOp::And => "and",
+        BinaryOp::Divide => "/",
+        BinaryOp::Equal => "==",
+        BinaryOp::Greater => ">",
+        BinaryOp::GreaterEqual => ">=",
+        BinaryOp::Less => "<",
+        BinaryOp::LessEqual => "<=",
+        BinaryOp::Minus => "-",
+        BinaryOp::Multiply => "*",
+        BinaryOp::NotEqual => "!=",
+        BinaryOp::Or => "or",
+        BinaryOp::Plus => "+",
+    }
+}
+
+impl Visitor for DotWriter {
+    type Result = usize;
+
+    fn visit_print(&mut self, print: &Print) -> Self::Result {
+        let id = self.node("print", print.span);
+        self.child_expr(id, &print.expr, "");
+        id
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) -> Self::Result {
+        let id = self.node("expression", expression.span);
+        self.child_expr(id, &expression.expr, "");
+        id
+    }
+
+    fn visit_if(&mut self, if_stmt: &If) -> Self::Result {
+        let id = self.node("if", if_stmt.span);
+        self.child_expr(id, &if_stmt.condition, "condition");
+        self.child_stmt(id, &if_stmt.then_branch, "then");
+        if let Some(else_branch) = &if_stmt.else_branch {
+            self.child_stmt(id, else_branch, "else");
+        }
+        id
+    }
+
+    fn visit_while(&mut self, while_stmt: &While) -> Self::Result {
+        let id = self.node("while", while_stmt.span);
+        self.child_expr(id, &while_stmt.condition, "condition");
+        self.child_stmt(id, &while_stmt.body, "body");
+        id
+    }
+
+    fn visit_block(&mut self, block: &Block) -> Self::Result {
+        let id = self.node("block", block.span);
+        for stmt in block.statements.iter() {
+            self.child_stmt(id, stmt, "");
+        }
+        id
+    }
+
+    fn visit_var_decl(&mut self, var_decl: &VarDecl) -> Self::Result {
+        let id = self.node(format!("var {}", var_decl.var), var_decl.span);
+        if let Some(initializer) = &var_decl.initializer {
+            self.child_expr(id, initializer, "initializer");
+        }
+        id
+    }
+
+    fn visit_function(&mut self, function: &FnDecl) -> Self::Result {
+        self.function("fun", function)
+    }
+
+    fn visit_class(&mut self, class: &ClassDecl) -> Self::Result {
+        let label = match &class.super_class {
+            Some(super_class) => format!("class {} < {super_class}", class.var),
+            None => format!("class {}", class.var),
+        };
+        let id = self.node(label, class.span);
+        for method in class.methods.iter() {
+            let child = self.function("method", method);
+            self.edge(id, child, "");
+        }
+        id
+    }
+
+    fn visit_return(&mut self, return_stmt: &Return) -> Self::Result {
+        let id = self.node("return", return_stmt.span);
+        if let Some(expr) = &return_stmt.expr {
+            self.child_expr(id, expr, "");
+        }
+        id
+    }
+
+    fn visit_binary(&mut self, binary: &BinaryExpr) -> Self::Result {
+        let id = self.node(
+            format!("binary {}", binary_operator(binary.operator)),
+            binary.get_span(),
+        );
+        self.child_expr(id, &binary.left, "left");
+        self.child_expr(id, &binary.right, "right");
+        id
+    }
+
+    fn visit_unary(&mut self, unary: &UnaryExpr) -> Self::Result {
+        let operator = match unary.operator {
+            UnaryOp::Negative => "-",
+            UnaryOp::Not => "!",
+        };
+        let id = self.node(format!("unary {operator}"), unary.get_span());
+        self.child_expr(id, &unary.operand, "");
+        id
+    }
+
+    fn visit_ternary(&mut self, ternary: &Ternary) -> Self::Result {
+        let id = self.node("ternary", ternary.get_span());
+        self.child_expr(id, &ternary.condition, "condition");
+        self.child_expr(id, &ternary.truthy, "then");
+        self.child_expr(id, &ternary.falsy, "else");
+        id
+    }
+
+    fn visit_assign(&mut self, assign: &Assign) -> Self::Result {
+        let id = self.node(format!("assign {}", assign.var), assign.get_span());
+        self.child_expr(id, &assign.value, "");
+        id
+    }
+
+    fn visit_group(&mut self, group: &Group) -> Self::Result {
+        let id = self.node("group", group.span);
+        self.child_expr(id, &group.expr, "");
+        id
+    }
+
+    fn visit_fn_call(&mut self, fn_call: &FnCall) -> Self::Result {
+        let id = self.node("call", fn_call.get_span());
+        self.child_expr(id, &fn_call.callee, "callee");
+        for (i, argument) in fn_call.arguments.iter().enumerate() {
+            self.child_expr(id, argument, &format!("argument {i}"));
+        }
+        id
+    }
+
+    fn visit_get(&mut self, get: &Get) -> Self::Result {
+        let id = self.node(format!("get .{}", get.field.name), get.get_span());
+        self.child_expr(id, &get.object, "object");
+        id
+    }
+
+    fn visit_set(&mut self, set: &Set) -> Self::Result {
+        let id = self.node(format!("set .{}", set.target.field.name), set.get_span());
+        self.child_expr(id, &set.target.object, "object");
+        self.child_expr(id, &set.value, "value");
+        id
+    }
+
+    fn visit_super(&mut self, super_expr: &Super) -> Self::Result {
+        self.node(
+            format!("super.{}", super_expr.method.name),
+            super_expr.get_span(),
+        )
+    }
+
+    fn visit_literal(&mut self, literal: &Literal) -> Self::Result {
+        let label = match &literal.value {
+            Lit::Number(n) => format!("literal {n}"),
+            Lit::Integer(n) => format!("literal {n}"),
+            Lit::String(s) => format!("literal {s:?}"),
+            Lit::Bool(b) => format!("literal {b}"),
+            Lit::Nil => "literal nil".to_string(),
+        };
+        self.node(label, literal.span)
+    }
+
+    fn visit_var(&mut self, var: &Variable) -> Self::Result {
+        self.node(format!("variable {var}"), var.ident.span)
+    }
+}
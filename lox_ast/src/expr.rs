@@ -1,3 +1,6 @@
+use alloc::{boxed::Box, string::String};
+use core::sync::atomic::{AtomicU64, Ordering};
+
 use crate::{ast_enum, visit::Visitor, visit_mut::VisitorMut};
 use lox_lexer::{Keyword, Position, Span, TokenType};
 
@@ -24,7 +27,7 @@ pub enum BinaryOp {
     Plus,
 }
 
-impl From<TokenType> for BinaryOp {
+impl From<TokenType<'_>> for BinaryOp {
     fn from(token_type: TokenType) -> Self {
         match token_type {
             TokenType::BangEqual => Self::NotEqual,
@@ -64,7 +67,7 @@ pub enum UnaryOp {
     Not,
 }
 
-impl From<TokenType> for UnaryOp {
+impl From<TokenType<'_>> for UnaryOp {
     fn from(token_type: TokenType) -> Self {
         match token_type {
             TokenType::Bang => Self::Not,
@@ -104,6 +107,9 @@ impl Ternary {
     }
 }
 
+/// Parentheses around an expression. They are kept so that spans cover them,
+/// and so that passes can tell `(a = b)` from `a = b`; passes which only care
+/// about what an expression does can see through them with [`Expr::ungroup`].
 #[derive(Debug, Clone)]
 pub struct Group {
     pub span: Span,
@@ -120,6 +126,8 @@ impl Group {
 #[derive(Debug, Clone)]
 pub enum Lit {
     Number(f64),
+    /// Only produced if `LanguageConfig::integers` is enabled.
+    Integer(i64),
     String(String),
     Bool(bool),
     Nil,
@@ -169,6 +177,49 @@ impl Assign {
 pub struct Get {
     pub object: Box<Expr>,
     pub field: Ident,
+    /// The method found the last time the expression was evaluated.
+    pub method_cache: MethodCache,
+}
+
+/// An inline cache of the method a [`Get`] found, as the id of the class of
+/// the instance and the index of the method in the class, so that calling a
+/// method on instances of the same class doesn't look its name up again.
+/// Class ids must be unique among all the interpreters which run the AST.
+#[derive(Debug)]
+pub struct MethodCache(AtomicU64);
+
+impl MethodCache {
+    /// Bits of the method index, the others hold the class id.
+    const INDEX_BITS: u32 = 24;
+    /// Never matches a class, as ids which don't fit aren't cached.
+    const EMPTY: u64 = u64::MAX;
+
+    #[inline]
+    pub fn get(&self, class: u64) -> Option<usize> {
+        let entry = self.0.load(Ordering::Relaxed);
+        (entry >> Self::INDEX_BITS == class)
+            .then_some((entry & ((1 << Self::INDEX_BITS) - 1)) as usize)
+    }
+
+    #[inline]
+    pub fn set(&self, class: u64, index: usize) {
+        if class < Self::EMPTY >> Self::INDEX_BITS && index < 1 << Self::INDEX_BITS {
+            self.0
+                .store(class << Self::INDEX_BITS | index as u64, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Default for MethodCache {
+    fn default() -> Self {
+        Self(AtomicU64::new(Self::EMPTY))
+    }
+}
+
+impl Clone for MethodCache {
+    fn clone(&self) -> Self {
+        Self(AtomicU64::new(self.0.load(Ordering::Relaxed)))
+    }
 }
 
 impl Get {
@@ -197,6 +248,8 @@ impl Set {
 #[derive(Debug, Clone)]
 pub struct Super {
     pub var: Variable,
+    /// The instance the method is bound to, resolved like a `this` expression.
+    pub this: Variable,
     pub method: Ident,
 }
 
@@ -242,10 +295,10 @@ expr! {
 }
 
 impl Expr {
-    pub fn group(expr: Self, start: Position, end: Position) -> Self {
+    pub fn group(expr: Self, span: Span) -> Self {
         Self::Group(Group {
             expr: p(expr),
-            span: Span { start, end },
+            span,
         })
     }
 
@@ -268,6 +321,7 @@ impl Expr {
         Self::Get(Get {
             object: p(object),
             field,
+            method_cache: MethodCache::default(),
         })
     }
 
@@ -297,4 +351,32 @@ impl Expr {
     pub fn literal(value: Lit, span: Span) -> Self {
         Self::Literal(Literal { span, value })
     }
+
+    /// The expression inside any parentheses around `self`.
+    pub fn ungroup(&self) -> &Expr {
+        match self {
+            Expr::Group(group) => group.expr.ungroup(),
+            expr => expr,
+        }
+    }
+
+    /// Whether evaluating the expression may have an effect other than
+    /// producing its value, ignoring runtime errors.
+    pub fn has_side_effects(&self) -> bool {
+        match self {
+            Expr::Binary(binary) => {
+                binary.left.has_side_effects() || binary.right.has_side_effects()
+            }
+            Expr::Unary(unary) => unary.operand.has_side_effects(),
+            Expr::Group(group) => group.expr.has_side_effects(),
+            Expr::Ternary(ternary) => {
+                ternary.condition.has_side_effects()
+                    || ternary.truthy.has_side_effects()
+                    || ternary.falsy.has_side_effects()
+            }
+            Expr::Get(get) => get.object.has_side_effects(),
+            Expr::Literal(_) | Expr::Var(_) | Expr::Super(_) => false,
+            Expr::Assign(_) | Expr::FnCall(_) | Expr::Set(_) => true,
+        }
+    }
 }
@@ -0,0 +1,135 @@
+//! Evaluation of expressions made only of literals, e.g. to find constant
+//! conditions.
+
+use alloc::string::{String, ToString};
+use core::cmp::Ordering;
+
+use crate::{
+    expr::*,
+    number::{DisplayNumber, Number},
+};
+
+impl Lit {
+    /// Whether the value counts as true in a condition, as in the interpreter
+    /// `0`, `""`, `false` and `nil` are false.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Lit::Number(n) => *n != 0.0,
+            Lit::Integer(n) => *n != 0,
+            Lit::String(s) => !s.is_empty(),
+            Lit::Bool(b) => *b,
+            Lit::Nil => false,
+        }
+    }
+
+    /// The value if it's a number, either an integer or a float.
+    pub fn as_number(&self) -> Option<Number> {
+        match self {
+            Lit::Number(n) => Some(Number::Float(*n)),
+            Lit::Integer(n) => Some(Number::Integer(*n)),
+            _ => None,
+        }
+    }
+
+    fn equals(&self, other: &Lit) -> bool {
+        match (self.as_number(), other.as_number()) {
+            (Some(n1), Some(n2)) => return n1.equals(n2),
+            (None, None) => {}
+            _ => return false,
+        }
+        match (self, other) {
+            (Lit::String(s1), Lit::String(s2)) => s1 == s2,
+            (Lit::Bool(b1), Lit::Bool(b2)) => b1 == b2,
+            (Lit::Nil, Lit::Nil) => true,
+            _ => false,
+        }
+    }
+
+    /// Formats the value as `print` does.
+    fn to_text(&self) -> String {
+        match self {
+            Lit::Number(n) => DisplayNumber(*n).to_string(),
+            Lit::Integer(n) => n.to_string(),
+            Lit::String(s) => s.to_string(),
+            Lit::Bool(b) => b.to_string(),
+            Lit::Nil => "nil".to_string(),
+        }
+    }
+}
+
+impl From<Number> for Lit {
+    fn from(value: Number) -> Self {
+        match value {
+            Number::Integer(n) => Lit::Integer(n),
+            Number::Float(n) => Lit::Number(n),
+        }
+    }
+}
+
+impl Expr {
+    /// The value of the expression if it only depends on literals, following
+    /// the semantics of the interpreter. `None` if it depends on variables or
+    /// calls, or fails with a type error. Divisions by zero aren't folded
+    /// either, since whether they fail depends on the configuration.
+    pub fn constant_value(&self) -> Option<Lit> {
+        match self {
+            Expr::Literal(literal) => Some(literal.value.clone()),
+            Expr::Group(group) => group.expr.constant_value(),
+            Expr::Unary(unary) => {
+                let operand = unary.operand.constant_value()?;
+                match (unary.operator, operand) {
+                    (UnaryOp::Negative, operand) => Some((-operand.as_number()?).into()),
+                    (UnaryOp::Not, operand) => Some(Lit::Bool(!operand.is_truthy())),
+                }
+            }
+            Expr::Binary(binary) => binary.constant_value(),
+            Expr::Ternary(ternary) => {
+                if ternary.condition.constant_value()?.is_truthy() {
+                    ternary.truthy.constant_value()
+                } else {
+                    ternary.falsy.constant_value()
+                }
+            }
+            Expr::Var(_)
+            | Expr::Assign(_)
+            | Expr::FnCall(_)
+            | Expr::Get(_)
+            | Expr::Set(_)
+            | Expr::Super(_) => None,
+        }
+    }
+}
+
+impl BinaryExpr {
+    fn constant_value(&self) -> Option<Lit> {
+        let left = self.left.constant_value()?;
+        // The right operand isn't evaluated if the left one decides the result.
+        match (self.operator, left.is_truthy()) {
+            (BinaryOp::And, false) | (BinaryOp::Or, true) => return Some(left),
+            (BinaryOp::And | BinaryOp::Or, _) => return self.right.constant_value(),
+            _ => {}
+        }
+        let right = self.right.constant_value()?;
+        Some(match (self.operator, left, right) {
+            (BinaryOp::Equal, left, right) => Lit::Bool(left.equals(&right)),
+            (BinaryOp::NotEqual, left, right) => Lit::Bool(!left.equals(&right)),
+            (BinaryOp::Plus, Lit::String(s1), right) => Lit::String(s1 + &right.to_text()),
+            (BinaryOp::Plus, left, Lit::String(s2)) => Lit::String(left.to_text() + &s2),
+            (operator, left, right) => {
+                let (n1, n2) = (left.as_number()?, right.as_number()?);
+                let ordering = n1.compare(n2);
+                match operator {
+                    BinaryOp::Plus => (n1 + n2).into(),
+                    BinaryOp::Minus => (n1 - n2).into(),
+                    BinaryOp::Multiply => (n1 * n2).into(),
+                    BinaryOp::Divide if !n2.is_zero() => (n1 / n2).into(),
+                    BinaryOp::Greater => Lit::Bool(ordering.is_some_and(Ordering::is_gt)),
+                    BinaryOp::GreaterEqual => Lit::Bool(ordering.is_some_and(Ordering::is_ge)),
+                    BinaryOp::Less => Lit::Bool(ordering.is_some_and(Ordering::is_lt)),
+                    BinaryOp::LessEqual => Lit::Bool(ordering.is_some_and(Ordering::is_le)),
+                    _ => return None,
+                }
+            }
+        })
+    }
+}
@@ -1,9 +1,13 @@
+use alloc::sync::Arc;
+use core::{
+    fmt::Display,
+    sync::atomic::{AtomicU16, Ordering},
+};
 use lox_lexer::Span;
-use std::fmt::Display;
 
 pub type IdentIndex = u16;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct IdentTarget {
     pub scope_count: u16,
     pub index: IdentIndex,
@@ -11,14 +15,57 @@ pub struct IdentTarget {
 
 #[derive(Debug, Clone)]
 pub struct Ident {
-    pub name: String,
+    /// Shared by the identifiers with the same name in a file.
+    pub name: Arc<str>,
     pub span: Span,
 }
 
 impl Ident {
     #[inline]
-    pub fn from_name(name: String, span: Span) -> Self {
-        Self { name, span }
+    pub fn from_name(name: impl Into<Arc<str>>, span: Span) -> Self {
+        Self {
+            name: name.into(),
+            span,
+        }
+    }
+}
+
+/// An inline cache of the slot of a global variable in the interpreter, set
+/// on the first access so that the next ones don't look the name up. Since
+/// an AST can be run by several interpreters, the slot must be checked
+/// before it's used.
+///
+/// It's as small as [`IdentIndex`] to keep [`Variable`], and so expressions,
+/// from growing, so only the first 65535 globals are cached.
+#[derive(Debug)]
+pub struct GlobalSlot(AtomicU16);
+
+impl GlobalSlot {
+    const EMPTY: u16 = u16::MAX;
+
+    #[inline]
+    pub fn get(&self) -> Option<usize> {
+        let slot = self.0.load(Ordering::Relaxed);
+        (slot != Self::EMPTY).then_some(slot as usize)
+    }
+
+    #[inline]
+    pub fn set(&self, slot: usize) {
+        if let Ok(slot) = u16::try_from(slot) {
+            self.0.store(slot, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Default for GlobalSlot {
+    fn default() -> Self {
+        Self(AtomicU16::new(Self::EMPTY))
+    }
+}
+
+impl Clone for GlobalSlot {
+    fn clone(&self) -> Self {
+        Self(AtomicU16::new(self.0.load(Ordering::Relaxed)))
     }
 }
 
@@ -26,10 +73,12 @@ impl Ident {
 pub struct Variable {
     pub ident: Ident,
     pub target: Option<IdentTarget>,
+    /// Only used if `target` is `None`, i.e. for globals.
+    pub global_slot: GlobalSlot,
 }
 
 impl Display for Variable {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{}", self.ident.name)
     }
 }
@@ -40,13 +89,14 @@ impl From<Ident> for Variable {
         Self {
             ident,
             target: None,
+            global_slot: GlobalSlot::default(),
         }
     }
 }
 
 impl Variable {
     #[inline]
-    pub fn from_name(name: String, span: Span) -> Self {
+    pub fn from_name(name: impl Into<Arc<str>>, span: Span) -> Self {
         Ident::from_name(name, span).into()
     }
 
@@ -1,7 +1,16 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod dot;
 pub mod expr;
+mod fold;
 pub mod ident;
 mod macros;
+pub mod number;
 pub mod stmt;
+#[cfg(feature = "std")]
+pub mod validate;
 pub mod visit;
 pub mod visit_mut;
 
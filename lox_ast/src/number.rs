@@ -0,0 +1,139 @@
+//! Semantics of numbers which the interpreter, the VM and constant folding
+//! must agree on, rather than inheriting them from `f64`.
+
+use core::{
+    cmp::Ordering,
+    fmt::Display,
+    ops::{Add, Div, Mul, Neg, Sub},
+};
+
+/// Whether `==` holds between two numbers. It's reflexive, so unlike in
+/// IEEE 754 `NaN` equals itself, as in jlox. Ordering comparisons with `NaN`
+/// are always false.
+pub fn equals(n1: f64, n2: f64) -> bool {
+    n1 == n2 || (n1.is_nan() && n2.is_nan())
+}
+
+/// Formats a number as `print` does, with no fractional part for integers
+/// and `NaN`, `Infinity` and `-Infinity` as in jlox.
+pub struct DisplayNumber(pub f64);
+
+impl Display for DisplayNumber {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.0 {
+            n if n.is_nan() => f.write_str("NaN"),
+            f64::INFINITY => f.write_str("Infinity"),
+            f64::NEG_INFINITY => f.write_str("-Infinity"),
+            n => write!(f, "{n}"),
+        }
+    }
+}
+
+/// A number operand. Integers only exist if `LanguageConfig::integers` is
+/// enabled, they stay exact until an operation overflows or divides, which
+/// gives a float.
+#[derive(Debug, Clone, Copy)]
+pub enum Number {
+    Integer(i64),
+    Float(f64),
+}
+
+impl Number {
+    pub fn to_f64(self) -> f64 {
+        match self {
+            Number::Integer(n) => n as f64,
+            Number::Float(n) => n,
+        }
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.to_f64() == 0.0
+    }
+
+    /// Applies the integer operation if both operands are integers and it
+    /// doesn't overflow, otherwise the float one.
+    fn apply(
+        self,
+        other: Number,
+        integer: fn(i64, i64) -> Option<i64>,
+        float: fn(f64, f64) -> f64,
+    ) -> Number {
+        if let (Number::Integer(n1), Number::Integer(n2)) = (self, other) {
+            if let Some(n) = integer(n1, n2) {
+                return Number::Integer(n);
+            }
+        }
+        Number::Float(float(self.to_f64(), other.to_f64()))
+    }
+
+    /// See [`equals`], an integer equals a float with the same value.
+    pub fn equals(self, other: Number) -> bool {
+        match (self, other) {
+            (Number::Integer(n1), Number::Integer(n2)) => n1 == n2,
+            _ => equals(self.to_f64(), other.to_f64()),
+        }
+    }
+
+    /// `None` if either operand is `NaN`.
+    pub fn compare(self, other: Number) -> Option<Ordering> {
+        match (self, other) {
+            (Number::Integer(n1), Number::Integer(n2)) => Some(n1.cmp(&n2)),
+            _ => self.to_f64().partial_cmp(&other.to_f64()),
+        }
+    }
+}
+
+impl Add for Number {
+    type Output = Number;
+
+    fn add(self, other: Number) -> Number {
+        self.apply(other, i64::checked_add, |n1, n2| n1 + n2)
+    }
+}
+
+impl Sub for Number {
+    type Output = Number;
+
+    fn sub(self, other: Number) -> Number {
+        self.apply(other, i64::checked_sub, |n1, n2| n1 - n2)
+    }
+}
+
+impl Mul for Number {
+    type Output = Number;
+
+    fn mul(self, other: Number) -> Number {
+        self.apply(other, i64::checked_mul, |n1, n2| n1 * n2)
+    }
+}
+
+/// Always gives a float, e.g. `7 / 2` is `3.5`.
+impl Div for Number {
+    type Output = Number;
+
+    fn div(self, other: Number) -> Number {
+        Number::Float(self.to_f64() / other.to_f64())
+    }
+}
+
+impl Neg for Number {
+    type Output = Number;
+
+    fn neg(self) -> Number {
+        match self {
+            Number::Integer(n) => n
+                .checked_neg()
+                .map_or(Number::Float(-(n as f64)), Number::Integer),
+            Number::Float(n) => Number::Float(-n),
+        }
+    }
+}
+
+impl Display for Number {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Number::Integer(n) => write!(f, "{n}"),
+            Number::Float(n) => write!(f, "{}", DisplayNumber(*n)),
+        }
+    }
+}
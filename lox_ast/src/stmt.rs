@@ -5,58 +5,122 @@ use crate::{
     visit::Visitor,
     visit_mut::VisitorMut,
 };
+use alloc::{boxed::Box, string::String, sync::Arc};
 use lox_lexer::Span;
 
 #[derive(Debug, Clone)]
 pub struct Print {
+    pub span: Span,
     pub expr: Expr,
 }
 
+impl Print {
+    #[inline]
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Expression {
+    pub span: Span,
     pub expr: Expr,
 }
 
+impl Expression {
+    #[inline]
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct VarDecl {
+    pub span: Span,
     pub var: Variable,
     pub initializer: Option<Expr>,
 }
 
+impl VarDecl {
+    #[inline]
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Block {
+    pub span: Span,
     pub statements: Box<[Statement]>,
     pub num_of_locals: IdentIndex,
+    /// Names of the locals by slot, only recorded by the resolver if asked
+    /// to, for debugging.
+    pub local_names: Option<Arc<[String]>>,
 }
 
 impl Block {
-    pub fn new(statements: Box<[Statement]>) -> Self {
+    pub fn new(statements: Box<[Statement]>, span: Span) -> Self {
         Self {
+            span,
             statements,
             num_of_locals: 0,
+            local_names: None,
         }
     }
+
+    #[inline]
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct If {
+    pub span: Span,
     pub condition: Expr,
     pub then_branch: Box<Statement>,
     pub else_branch: Option<Box<Statement>>,
 }
 
+impl If {
+    #[inline]
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct While {
+    pub span: Span,
     pub condition: Expr,
     pub body: Box<Statement>,
 }
 
+impl While {
+    #[inline]
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct FnDecl {
+    pub span: Span,
+    /// The `///` comments before the declaration, joined with newlines.
+    pub doc: Option<String>,
     pub var: Variable,
     pub params: Box<[Variable]>,
     pub body: Box<[Statement]>,
     pub num_of_locals: IdentIndex,
+    /// See [`Block::local_names`].
+    pub local_names: Option<Arc<[String]>>,
+}
+
+impl FnDecl {
+    #[inline]
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -65,14 +129,49 @@ pub struct Return {
     pub expr: Option<Expr>,
 }
 
+impl Return {
+    #[inline]
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ClassDecl {
+    pub span: Span,
+    /// The `///` comments before the declaration, joined with newlines.
+    pub doc: Option<String>,
     pub var: Variable,
     pub super_class: Option<Variable>,
     pub methods: Box<[FnDecl]>,
 }
 
-ast_enum! {
+impl ClassDecl {
+    #[inline]
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
+macro_rules! stmt {
+    (pub enum $enum_name: ident {$($walker: ident: $name: ident($ty: ty)),+ $(,)?}) => {
+        ast_enum! {
+            pub enum $enum_name {
+                $($walker: $name($ty),)+
+            }
+        }
+
+        impl $enum_name {
+            pub fn get_span(&self) -> Span {
+                match self {
+                    $(Self::$name(variant) => variant.get_span()),+
+                }
+            }
+        }
+    };
+}
+
+stmt! {
     pub enum Statement {
         visit_print: Print(Print),
         visit_expression: Expression(Expression),
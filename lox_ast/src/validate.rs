@@ -0,0 +1,267 @@
+//! Checks structural invariants of a resolved AST.
+//!
+//! The environments are modelled the way the interpreter creates them at
+//! runtime, so a resolver assigning targets which don't match the runtime
+//! layout is reported instead of panicking or reading the wrong slot later.
+
+use std::{collections::HashMap, io::Write};
+
+use lox_lexer::Span;
+use thiserror::Error;
+
+use crate::{
+    expr::*,
+    ident::{IdentIndex, IdentTarget, Variable},
+    stmt::*,
+    visit::{walk_expr, walk_stmt, Visitor},
+};
+
+#[derive(Debug, Error)]
+pub enum ValidationError {
+    #[error("{0}: function has more than 255 parameters")]
+    TooManyParameters(Span),
+    #[error("{span}: `{name}` is resolved to {found:?}, expected {expected:?}")]
+    MismatchedTarget {
+        name: String,
+        span: Span,
+        expected: Option<IdentTarget>,
+        found: Option<IdentTarget>,
+    },
+    #[error("{span}: slot {index} of `{name}` is out of the {len} local(s) of its scope")]
+    SlotOutOfRange {
+        name: String,
+        span: Span,
+        index: IdentIndex,
+        len: IdentIndex,
+    },
+    #[error("{span}: `{name}` shares slot {index} with `{other}`")]
+    DuplicateSlot {
+        name: String,
+        span: Span,
+        index: IdentIndex,
+        other: String,
+    },
+}
+
+struct Scope {
+    len: IdentIndex,
+    names: HashMap<String, IdentIndex>,
+    slots: HashMap<IdentIndex, String>,
+}
+
+impl Scope {
+    fn new(len: IdentIndex) -> Self {
+        Self {
+            len,
+            names: HashMap::new(),
+            slots: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Validator {
+    scopes: Vec<Scope>,
+    errors: Vec<ValidationError>,
+}
+
+/// Validates an AST after resolution, returning all violated invariants.
+pub fn validate(ast: &[Statement]) -> Vec<ValidationError> {
+    let mut validator = Validator::default();
+    for stmt in ast {
+        walk_stmt(&mut validator, stmt);
+    }
+    validator.errors
+}
+
+/// Checks the invariants of the resolved AST in debug builds, so that bugs in
+/// the parser or resolver are caught before they corrupt execution. Each
+/// violation is written to `output` as an internal error.
+pub fn is_valid(ast: &[Statement], output: &mut impl Write) -> bool {
+    if cfg!(debug_assertions) {
+        let errors = validate(ast);
+        for error in errors.iter() {
+            writeln!(output, "internal error: {error}").unwrap();
+        }
+        errors.is_empty()
+    } else {
+        true
+    }
+}
+
+impl Validator {
+    fn declare(&mut self, var: &Variable) {
+        let Some(scope) = self.scopes.last_mut() else {
+            if var.target.is_some() {
+                self.mismatch(var, None);
+            }
+            return;
+        };
+
+        let index = match var.target {
+            Some(IdentTarget {
+                scope_count: 0,
+                index,
+            }) => index,
+            _ => {
+                let expected = IdentTarget {
+                    scope_count: 0,
+                    index: scope.names.len() as IdentIndex,
+                };
+                return self.mismatch(var, Some(expected));
+            }
+        };
+
+        let name = &var.ident.name;
+        if index >= scope.len {
+            self.errors.push(ValidationError::SlotOutOfRange {
+                name: name.to_string(),
+                span: var.ident.span,
+                index,
+                len: scope.len,
+            });
+        } else if let Some(other) = scope.slots.get(&index) {
+            self.errors.push(ValidationError::DuplicateSlot {
+                name: name.to_string(),
+                span: var.ident.span,
+                index,
+                other: other.to_string(),
+            });
+        } else {
+            scope.slots.insert(index, name.to_string());
+        }
+        scope.names.insert(name.to_string(), index);
+    }
+
+    fn expected_target(&self, name: &str) -> Option<IdentTarget> {
+        self.scopes
+            .iter()
+            .rev()
+            .enumerate()
+            .find_map(|(scope_count, scope)| {
+                scope.names.get(name).map(|&index| IdentTarget {
+                    scope_count: scope_count as u16,
+                    index,
+                })
+            })
+    }
+
+    fn mismatch(&mut self, var: &Variable, expected: Option<IdentTarget>) {
+        self.errors.push(ValidationError::MismatchedTarget {
+            name: var.ident.name.to_string(),
+            span: var.ident.span,
+            expected,
+            found: var.target,
+        });
+    }
+
+    fn start_scope(&mut self, len: IdentIndex) {
+        self.scopes.push(Scope::new(len));
+    }
+
+    /// Methods are bound to an environment holding only `this` or `super`.
+    fn start_class_scope(&mut self, name: &str) {
+        let mut scope = Scope::new(1);
+        scope.names.insert(name.to_string(), 0);
+        self.scopes.push(scope);
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn validate_function(&mut self, function: &FnDecl) {
+        if function.params.len() > 255 {
+            self.errors
+                .push(ValidationError::TooManyParameters(function.get_span()));
+        }
+        self.start_scope(function.num_of_locals);
+        for param in function.params.iter() {
+            self.declare(param);
+        }
+        for stmt in function.body.iter() {
+            walk_stmt(self, stmt);
+        }
+        self.end_scope();
+    }
+}
+
+impl Visitor for Validator {
+    type Result = ();
+
+    fn visit_if(&mut self, if_stmt: &If) -> Self::Result {
+        walk_expr(self, &if_stmt.condition);
+        walk_stmt(self, &if_stmt.then_branch);
+        if let Some(else_branch) = &if_stmt.else_branch {
+            walk_stmt(self, else_branch);
+        }
+    }
+
+    fn visit_while(&mut self, while_stmt: &While) -> Self::Result {
+        walk_expr(self, &while_stmt.condition);
+        walk_stmt(self, &while_stmt.body);
+    }
+
+    fn visit_block(&mut self, block: &Block) -> Self::Result {
+        self.start_scope(block.num_of_locals);
+        for stmt in block.statements.iter() {
+            walk_stmt(self, stmt);
+        }
+        self.end_scope();
+    }
+
+    fn visit_var_decl(&mut self, var_decl: &VarDecl) -> Self::Result {
+        self.declare(&var_decl.var);
+        if let Some(initializer) = &var_decl.initializer {
+            walk_expr(self, initializer);
+        }
+    }
+
+    fn visit_function(&mut self, function: &FnDecl) -> Self::Result {
+        self.declare(&function.var);
+        self.validate_function(function);
+    }
+
+    fn visit_class(&mut self, class: &ClassDecl) -> Self::Result {
+        self.declare(&class.var);
+        if let Some(super_class) = &class.super_class {
+            self.visit_var(super_class);
+            self.start_class_scope("super");
+        }
+        self.start_class_scope("this");
+        for method in class.methods.iter() {
+            self.validate_function(method);
+        }
+        self.end_scope();
+        if class.super_class.is_some() {
+            self.end_scope();
+        }
+    }
+
+    fn visit_return(&mut self, return_stmt: &Return) -> Self::Result {
+        if let Some(expr) = &return_stmt.expr {
+            walk_expr(self, expr);
+        }
+    }
+
+    fn visit_fn_call(&mut self, fn_call: &FnCall) -> Self::Result {
+        walk_expr(self, &fn_call.callee);
+        for argument in fn_call.arguments.iter() {
+            walk_expr(self, argument);
+        }
+    }
+
+    fn visit_super(&mut self, super_expr: &Super) -> Self::Result {
+        self.visit_var(&super_expr.var);
+        self.visit_var(&super_expr.this);
+    }
+
+    fn visit_literal(&mut self, _literal: &Literal) -> Self::Result {}
+
+    fn visit_var(&mut self, var: &Variable) -> Self::Result {
+        let expected = self.expected_target(&var.ident.name);
+        if expected != var.target {
+            self.mismatch(var, expected);
+        }
+    }
+}
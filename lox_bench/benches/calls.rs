@@ -0,0 +1,32 @@
+//! Calls and blocks, each of which creates an environment.
+
+use lox_bench::{interpret, measure, prepare};
+
+fn main() {
+    let fib = prepare(
+        r#"
+        fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }
+        var result = fib(22);
+        "#,
+    );
+    let blocks = prepare(
+        r#"
+        var sum = 0;
+        for (var i = 0; i < 100000; i = i + 1) {
+            var a = i;
+            { var b = a; sum = sum + b; }
+        }
+        "#,
+    );
+    let arguments = prepare(
+        r#"
+        fun add(a, b, c, d) { return a + b + c + d; }
+        var sum = 0;
+        for (var i = 0; i < 100000; i = i + 1) sum = add(sum, i, 1, 2);
+        "#,
+    );
+
+    measure("recursive fib(22)", 30, || interpret(&fib));
+    measure("nested blocks in a loop", 30, || interpret(&blocks));
+    measure("calls with 4 arguments", 30, || interpret(&arguments));
+}
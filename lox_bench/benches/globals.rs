@@ -0,0 +1,29 @@
+//! Reading and assigning global variables, the way scripts without functions
+//! do all their work.
+
+use lox_bench::{interpret, measure, prepare};
+
+fn main() {
+    let loop_over_globals = prepare(
+        r#"
+        var total = 0;
+        var step = 1;
+        var limit = 100000;
+        for (var i = 0; i < limit; i = i + step) total = total + step;
+        "#,
+    );
+    let global_calls = prepare(
+        r#"
+        fun square(x) { return x * x; }
+        var total = 0;
+        for (var i = 0; i < 50000; i = i + 1) total = total + square(i);
+        "#,
+    );
+
+    measure("loop over global variables", 30, || {
+        interpret(&loop_over_globals)
+    });
+    measure("calls of a global function", 30, || {
+        interpret(&global_calls)
+    });
+}
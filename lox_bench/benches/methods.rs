@@ -0,0 +1,33 @@
+//! Method calls, looked up on the class of the instance or inherited from
+//! its super classes.
+
+use lox_bench::{interpret, measure, prepare};
+
+fn main() {
+    let method_calls = prepare(
+        r#"
+        class Counter {
+            init() { this.count = 0; }
+            increment() { this.count = this.count + 1; }
+        }
+        var counter = Counter();
+        for (var i = 0; i < 50000; i = i + 1) counter.increment();
+        "#,
+    );
+    let inherited_calls = prepare(
+        r#"
+        class Shape { area() { return this.width * this.height; } }
+        class Rectangle < Shape {}
+        class Square < Rectangle {}
+        class Tile < Square {
+            init(side) { this.width = side; this.height = side; }
+        }
+        var tile = Tile(2);
+        var total = 0;
+        for (var i = 0; i < 50000; i = i + 1) total = total + tile.area();
+        "#,
+    );
+
+    measure("method calls", 30, || interpret(&method_calls));
+    measure("inherited method calls", 30, || interpret(&inherited_calls));
+}
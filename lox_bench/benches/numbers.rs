@@ -0,0 +1,28 @@
+//! Arithmetic and comparisons of numbers in tight loops.
+
+use lox_bench::{interpret, measure, prepare};
+
+fn main() {
+    let arithmetic = prepare(
+        r#"
+        var x = 0;
+        var y = 1;
+        for (var i = 0; i < 100000; i = i + 1) {
+            x = x * 0.5 + y - i / 3;
+            y = -x * 2 - 1;
+        }
+        "#,
+    );
+    let comparisons = prepare(
+        r#"
+        var count = 0;
+        for (var i = 0; i < 100000; i = i + 1) {
+            if (i >= 10 and i <= 90000 or !(i > 95000)) count = count + 1;
+            while (count < i / 2) count = count + 1;
+        }
+        "#,
+    );
+
+    measure("arithmetic", 30, || interpret(&arithmetic));
+    measure("comparisons", 30, || interpret(&comparisons));
+}
@@ -0,0 +1,81 @@
+//! Throughput of the lexer and the parser on large generated files, and of
+//! the recovery from errors.
+
+use std::time::Duration;
+
+use lox_lexer::{Lexer, TokenKind, TokenType};
+
+use lox_bench::measure;
+
+/// Number of functions of the generated files.
+const FUNCTIONS: usize = 2000;
+
+/// A file of `FUNCTIONS` functions with a few statements each.
+fn generate(broken: bool) -> String {
+    let mut src = String::new();
+    for i in 0..FUNCTIONS {
+        let error = if broken { "var = ;" } else { "" };
+        src.push_str(&format!(
+            r#"
+            fun function_{i}(first, second) {{
+                var total = first + second * {i};
+                if (total > 100 and first != second) print "large" + total;
+                {error}
+                for (var index = 0; index < second; index = index + 1) total = total - index;
+                return total;
+            }}
+            "#
+        ));
+    }
+    src
+}
+
+fn count_tokens(src: &str) -> usize {
+    let mut lexer = Lexer::new(src);
+    let mut count = 0;
+    while !matches!(lexer.next_token().token_type, TokenType::Eof) {
+        count += 1;
+    }
+    count
+}
+
+fn count_raw_tokens(src: &str) -> usize {
+    let mut lexer = Lexer::new(src);
+    let mut count = 0;
+    while lexer.next_raw_token().kind != TokenKind::Eof {
+        count += 1;
+    }
+    count
+}
+
+fn per_second(count: usize, duration: Duration) -> f64 {
+    count as f64 / duration.as_secs_f64()
+}
+
+fn main() {
+    let src = generate(false);
+    let tokens = count_tokens(&src);
+    // The function, `var`, `if`, `print`, `for` with its initializer and body,
+    // and `return`.
+    let statements = FUNCTIONS * 8;
+
+    let lexing = measure("lexing", 30, || count_tokens(&src));
+    println!("{:<40} {:>12.0}", "tokens/s", per_second(tokens, lexing));
+    let raw_lexing = measure("lexing raw tokens", 30, || count_raw_tokens(&src));
+    println!(
+        "{:<40} {:>12.0}",
+        "raw tokens/s",
+        per_second(tokens, raw_lexing)
+    );
+    let parsing = measure("parsing", 30, || lox_parser::parse(&src));
+    println!(
+        "{:<40} {:>12.0}",
+        "statements/s",
+        per_second(statements, parsing)
+    );
+
+    let broken = generate(true);
+    measure("parsing with an error per function", 30, || {
+        lox_parser::parse(&broken)
+    });
+}
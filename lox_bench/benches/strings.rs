@@ -0,0 +1,26 @@
+//! Building a long string by concatenation, which copies the string built so
+//! far at each step, against a `StringBuilder`, which doesn't.
+
+use lox_bench::{interpret, measure, prepare};
+
+const PIECES: usize = 20_000;
+
+fn main() {
+    let concatenation = prepare(&format!(
+        r#"
+        var s = "";
+        for (var i = 0; i < {PIECES}; i = i + 1) s = s + "piece";
+        "#
+    ));
+    let builder = prepare(&format!(
+        r#"
+        var builder = StringBuilder();
+        for (var i = 0; i < {PIECES}; i = i + 1) sb_append(builder, "piece");
+        var s = sb_to_string(builder);
+        "#
+    ));
+
+    let slow = measure("concatenation with `+`", 10, || interpret(&concatenation));
+    let fast = measure("StringBuilder", 10, || interpret(&builder));
+    println!("speedup: {:.1}x", slow.as_secs_f64() / fast.as_secs_f64());
+}
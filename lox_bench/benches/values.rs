@@ -0,0 +1,30 @@
+//! Copying values around, e.g. reading variables, passing arguments and
+//! returning results, which must not copy the data of strings.
+
+use lox_bench::{interpret, measure, prepare};
+
+fn main() {
+    let strings = prepare(
+        r#"
+        var long = "0123456789";
+        for (var i = 0; i < 10; i = i + 1) long = long + long;
+        fun identity(value) { return value; }
+        var copy;
+        for (var i = 0; i < 100000; i = i + 1) {
+            copy = identity(long);
+        }
+        "#,
+    );
+    let numbers = prepare(
+        r#"
+        fun identity(value) { return value; }
+        var copy;
+        for (var i = 0; i < 100000; i = i + 1) {
+            copy = identity(i);
+        }
+        "#,
+    );
+
+    measure("copying a 10 KiB string", 10, || interpret(&strings));
+    measure("copying a number", 10, || interpret(&numbers));
+}
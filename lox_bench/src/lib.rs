@@ -0,0 +1,41 @@
+//! A minimal harness for the benchmarks in `benches`, run with `cargo bench`.
+
+use std::{
+    hint::black_box,
+    io,
+    time::{Duration, Instant},
+};
+
+use lox_interpreter::Interpreter;
+use lox_parser::parser::Ast;
+use lox_resolver::Resolver;
+
+/// Parses and resolves `src`, panicking on errors since benchmarks are
+/// expected to be valid programs.
+pub fn prepare(src: &str) -> Ast {
+    let mut ast = lox_parser::parse(src).into_result().unwrap();
+    assert!(Resolver::default().resolve(&mut ast).is_none());
+    ast
+}
+
+/// Runs `ast`, discarding what it prints.
+pub fn interpret(ast: &Ast) {
+    Interpreter::with_output(io::sink()).interpret(ast).unwrap();
+}
+
+/// Runs `f` `iterations` times after a warm-up run, printing and returning
+/// the duration of the fastest run, which is the least disturbed by other
+/// processes.
+pub fn measure<T>(name: &str, iterations: u32, mut f: impl FnMut() -> T) -> Duration {
+    black_box(f());
+    let fastest = (0..iterations)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(f());
+            start.elapsed()
+        })
+        .min()
+        .unwrap_or_default();
+    println!("{name:<40} {fastest:>12.3?}");
+    fastest
+}
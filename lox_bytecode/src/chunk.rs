@@ -0,0 +1,158 @@
+use lox_bytecode_ops::{
+    codec::Encode,
+    writer::{Label, OpWriter},
+    Operation, StringIntern, StringSymbol,
+};
+use lox_lexer::Span;
+#[cfg(feature = "comments")]
+use lox_lexer::{Lexer, TokenKind};
+
+/// Encoded operations together with the data needed to run and debug them.
+#[derive(Debug)]
+pub struct Chunk {
+    pub(crate) code: Vec<u8>,
+    pub(crate) spans: Vec<Span>,
+    /// The byte offset of each operation in `code`.
+    pub(crate) offsets: Vec<u32>,
+    pub(crate) strings: StringIntern,
+    pub(crate) max_stack_size: usize,
+    /// Comments of the source with the byte offset of the operation they
+    /// precede, in order.
+    #[cfg(feature = "comments")]
+    pub(crate) comments: Vec<(u32, String)>,
+}
+
+impl Chunk {
+    pub fn code(&self) -> &[u8] {
+        &self.code
+    }
+
+    /// Gets the span of the `index`th operation.
+    pub fn get_span_at(&self, index: usize) -> Span {
+        self.spans[index]
+    }
+
+    /// Gets the span of the operation starting at the byte `offset`, which
+    /// executor errors report.
+    pub fn get_span_at_offset(&self, offset: usize) -> Option<Span> {
+        let index = self.offsets.binary_search(&(offset as u32)).ok()?;
+        self.spans.get(index).copied()
+    }
+
+    pub fn strings(&self) -> &StringIntern {
+        &self.strings
+    }
+
+    /// The most values the operations can have on the stack at once.
+    pub fn max_stack_size(&self) -> usize {
+        self.max_stack_size
+    }
+
+    /// Records the comments of `src`, the source the chunk is compiled from,
+    /// which aren't inside braces. Each one is attached to the first operation
+    /// compiled from the code after it, for [`disassemble`] to show.
+    ///
+    /// [`disassemble`]: crate::disassembler::disassemble
+    #[cfg(feature = "comments")]
+    pub fn attach_comments(&mut self, src: &str) {
+        let mut lexer = Lexer::new(src).with_comments();
+        let mut depth = 0usize;
+        loop {
+            let token = lexer.next_raw_token();
+            match token.kind {
+                TokenKind::LeftBrace => depth += 1,
+                TokenKind::RightBrace => depth = depth.saturating_sub(1),
+                TokenKind::Comment | TokenKind::DocComment if depth == 0 => {
+                    let offset = self
+                        .spans
+                        .iter()
+                        .position(|span| span.start >= token.span.end)
+                        .map_or(self.code.len() as u32, |index| self.offsets[index]);
+                    self.comments.push((offset, token.text(src).to_string()));
+                }
+                TokenKind::Eof => break,
+                _ => {}
+            }
+        }
+        self.comments.sort_by_key(|&(offset, _)| offset);
+    }
+
+    /// The comments recorded by [`Chunk::attach_comments`], with the byte
+    /// offset of the operation they precede.
+    #[cfg(feature = "comments")]
+    pub fn comments(&self) -> &[(u32, String)] {
+        &self.comments
+    }
+}
+
+/// Builds a [`Chunk`] operation by operation, keeping track of how many
+/// values they leave on the stack.
+#[derive(Debug, Default)]
+pub struct ChunkBuilder {
+    writer: OpWriter,
+    spans: Vec<Span>,
+    offsets: Vec<u32>,
+    strings: StringIntern,
+    stack_size: usize,
+    max_stack_size: usize,
+}
+
+impl ChunkBuilder {
+    /// Appends `operation`, which is reported at `span` if it fails.
+    ///
+    /// Panics in debug builds if the operation pops more values than the
+    /// previous ones leave on the stack.
+    pub fn push(&mut self, operation: Operation, span: Span) {
+        self.track(&operation, span);
+        operation.encode(&mut self.writer);
+    }
+
+    /// Appends the jump built by `jump`, targeting `label`.
+    pub fn push_jump(&mut self, jump: fn(u32) -> Operation, label: Label, span: Span) {
+        self.track(&jump(0), span);
+        self.writer.write_jump(jump, label);
+    }
+
+    pub fn new_label(&mut self) -> Label {
+        self.writer.new_label()
+    }
+
+    /// Makes `label` target the next operation.
+    pub fn place_label(&mut self, label: Label) {
+        self.writer.place_label(label);
+    }
+
+    fn track(&mut self, operation: &Operation, span: Span) {
+        let (pops, pushes) = operation.stack_effect();
+        debug_assert!(
+            pops <= self.stack_size,
+            "stack underflow: `{operation:?}` pops {pops} of {} value(s)",
+            self.stack_size
+        );
+        self.stack_size = self.stack_size.saturating_sub(pops) + pushes;
+        self.max_stack_size = self.max_stack_size.max(self.stack_size);
+        self.spans.push(span);
+        self.offsets.push(self.writer.len() as u32);
+    }
+
+    pub fn intern(&mut self, s: &str) -> StringSymbol {
+        self.strings.intern(s)
+    }
+
+    /// Gets the span of the `index`th operation.
+    pub fn get_span_at(&self, index: usize) -> Span {
+        self.spans[index]
+    }
+
+    pub fn finish(mut self) -> Chunk {
+        Chunk {
+            code: self.writer.finish(),
+            spans: self.spans,
+            offsets: self.offsets,
+            strings: self.strings,
+            max_stack_size: self.max_stack_size,
+            #[cfg(feature = "comments")]
+            comments: Vec::new(),
+        }
+    }
+}
@@ -1,16 +1,16 @@
 use lox_ast::{
-    visit::{walk_binary, walk_unary, Visitor},
+    visit::{walk_binary, walk_expr, walk_unary, Visitor},
     *,
 };
-use lox_bytecode_ops::{Operation, StringIntern};
+use lox_bytecode_ops::Operation;
 use lox_lexer::Span;
 use lox_parser::parser::Ast;
 
+use crate::chunk::{Chunk, ChunkBuilder};
+
 #[derive(Debug, Default)]
 pub struct Compiler {
-    operations: Vec<Operation>,
-    spans: Vec<Span>,
-    strings: StringIntern,
+    chunk: ChunkBuilder,
 }
 
 impl Compiler {
@@ -21,80 +21,111 @@ impl Compiler {
     }
 
     pub fn get_span_at(&self, index: usize) -> Span {
-        self.spans[index]
+        self.chunk.get_span_at(index)
+    }
+
+    pub fn finish(self) -> Chunk {
+        self.chunk.finish()
     }
 
     fn add_constant(&mut self, literal: &Literal) {
         let operation = match &literal.value {
             Lit::Number(n) => Operation::LoadNumber(*n),
-            Lit::String(s) => Operation::LoadString(self.strings.intern(s)),
+            Lit::Integer(n) => Operation::LoadInteger(*n),
+            Lit::String(s) => Operation::LoadString(self.chunk.intern(s)),
             Lit::Bool(b) => Operation::LoadBool(*b),
             Lit::Nil => Operation::LoadNil,
         };
-        self.add_operation(operation, literal.span);
+        self.chunk.push(operation, literal.span);
     }
 
-    fn add_operation(&mut self, operation: Operation, span: Span) {
-        self.operations.push(operation);
-        self.spans.push(span);
+    /// Compiles `and` or `or`, whose right operand is only evaluated if `jump`
+    /// doesn't skip it.
+    fn add_logical(&mut self, binary: &BinaryExpr, jump: fn(u32) -> Operation) {
+        walk_expr(self, &binary.left);
+        let end = self.chunk.new_label();
+        self.chunk.push_jump(jump, end, binary.get_span());
+        walk_expr(self, &binary.right);
+        self.chunk.place_label(end);
     }
 }
 
 impl Visitor for Compiler {
     type Result = ();
 
-    fn visit_if(&mut self, if_stmt: &If) -> Self::Result {
+    fn visit_expression(&mut self, expression: &Expression) -> Self::Result {
+        walk_expr(self, &expression.expr);
+        self.chunk.push(Operation::Pop, expression.span);
+    }
+
+    fn visit_print(&mut self, print: &Print) -> Self::Result {
+        walk_expr(self, &print.expr);
+        self.chunk.push(Operation::Print, print.span);
+    }
+
+    fn visit_if(&mut self, _if_stmt: &If) -> Self::Result {
         todo!()
     }
 
-    fn visit_while(&mut self, while_stmt: &While) -> Self::Result {
+    fn visit_while(&mut self, _while_stmt: &While) -> Self::Result {
         todo!()
     }
 
-    fn visit_block(&mut self, block: &Block) -> Self::Result {
+    fn visit_block(&mut self, _block: &Block) -> Self::Result {
         todo!()
     }
 
-    fn visit_var_decl(&mut self, var_decl: &VarDecl) -> Self::Result {
+    fn visit_var_decl(&mut self, _var_decl: &VarDecl) -> Self::Result {
         todo!()
     }
 
-    fn visit_function(&mut self, function: &FnDecl) -> Self::Result {
+    fn visit_function(&mut self, _function: &FnDecl) -> Self::Result {
         todo!()
     }
 
-    fn visit_class(&mut self, class: &ClassDecl) -> Self::Result {
+    fn visit_class(&mut self, _class: &ClassDecl) -> Self::Result {
         todo!()
     }
 
-    fn visit_return(&mut self, return_stmt: &Return) -> Self::Result {
+    fn visit_return(&mut self, _return_stmt: &Return) -> Self::Result {
         todo!()
     }
 
-    fn visit_fn_call(&mut self, fn_call: &FnCall) -> Self::Result {
+    fn visit_fn_call(&mut self, _fn_call: &FnCall) -> Self::Result {
         todo!()
     }
 
-    fn visit_super(&mut self, super_expr: &Super) -> Self::Result {
+    fn visit_super(&mut self, _super_expr: &Super) -> Self::Result {
         todo!()
     }
 
     fn visit_unary(&mut self, unary: &UnaryExpr) -> Self::Result {
         walk_unary(self, unary);
-        self.add_operation(unary.operator.into(), unary.get_span());
+        self.chunk.push(unary.operator.into(), unary.get_span());
+    }
+
+    /// Parentheses only group operations, which keep the spans of the
+    /// expressions inside them.
+    fn visit_group(&mut self, group: &Group) -> Self::Result {
+        walk_expr(self, &group.expr)
     }
 
     fn visit_binary(&mut self, binary: &BinaryExpr) -> Self::Result {
-        walk_binary(self, binary);
-        let span = binary.get_span();
-        self.add_operation(binary.operator.into(), span)
+        match Operation::try_from(binary.operator) {
+            Ok(operation) => {
+                walk_binary(self, binary);
+                self.chunk.push(operation, binary.get_span())
+            }
+            Err(BinaryOp::And) => self.add_logical(binary, Operation::JumpIfFalseOrPop),
+            Err(_) => self.add_logical(binary, Operation::JumpIfTrueOrPop),
+        }
     }
 
     fn visit_literal(&mut self, literal: &Literal) -> Self::Result {
         self.add_constant(literal);
     }
 
-    fn visit_var(&mut self, var: &Variable) -> Self::Result {
+    fn visit_var(&mut self, _var: &Variable) -> Self::Result {
         todo!()
     }
 }
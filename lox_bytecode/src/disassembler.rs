@@ -0,0 +1,46 @@
+use std::fmt::Write;
+
+use lox_bytecode_ops::{codec::Decode, Operation};
+
+use crate::chunk::Chunk;
+
+/// Lists the operations of `chunk` one per line, after their byte offset and
+/// source line, e.g. `0009    2 LoadString("text")`. With the `comments`
+/// feature, the comments attached by [`Chunk::attach_comments`] are listed
+/// before the operations they precede.
+pub fn disassemble(chunk: &Chunk) -> String {
+    let mut dump = String::new();
+    #[cfg(feature = "comments")]
+    let mut comments = chunk.comments.iter().peekable();
+    for (&offset, span) in chunk.offsets.iter().zip(&chunk.spans) {
+        #[cfg(feature = "comments")]
+        while let Some((_, comment)) = comments.next_if(|&&(at, _)| at <= offset) {
+            write_comment(&mut dump, comment);
+        }
+
+        let line = span.start.line;
+        // Writing to a `String` can't fail.
+        let _ = match Operation::decode(&chunk.code[offset as usize..]) {
+            Ok((Operation::LoadString(symbol), _)) => writeln!(
+                dump,
+                "{offset:04} {line:>4} LoadString({:?})",
+                chunk.strings.resolve(&symbol)
+            ),
+            Ok((operation, _)) => writeln!(dump, "{offset:04} {line:>4} {operation:?}"),
+            Err(err) => writeln!(dump, "{offset:04} {line:>4} <{err}>"),
+        };
+    }
+    #[cfg(feature = "comments")]
+    for (_, comment) in comments {
+        write_comment(&mut dump, comment);
+    }
+    dump
+}
+
+/// Writes each line of `comment` aligned with the operations.
+#[cfg(feature = "comments")]
+fn write_comment(dump: &mut String, comment: &str) {
+    for line in comment.lines() {
+        let _ = writeln!(dump, "{:10}{}", "", line.trim_start());
+    }
+}
@@ -0,0 +1,41 @@
+use lox_bytecode_ops::error::{ExecutorError, RuntimeError};
+use lox_lexer::Span;
+use thiserror::Error;
+
+use crate::chunk::Chunk;
+
+/// An error of running a chunk, located in the source it was compiled from.
+#[derive(Debug, Error)]
+pub enum VmError {
+    #[error("{error}")]
+    Runtime {
+        error: RuntimeError,
+        /// The span of the failing operation, `None` if the chunk has no span
+        /// for it.
+        span: Option<Span>,
+    },
+    /// The code of the chunk is corrupted.
+    #[error("{0}")]
+    Corrupted(ExecutorError),
+}
+
+impl VmError {
+    /// Locates `error` with the span table of `chunk`.
+    pub(crate) fn new(error: ExecutorError, chunk: &Chunk) -> Self {
+        match error {
+            ExecutorError::RuntimeError { error, offset } => Self::Runtime {
+                error,
+                span: chunk.get_span_at_offset(offset),
+            },
+            error => Self::Corrupted(error),
+        }
+    }
+
+    /// The span of the code which failed, if it's known.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            VmError::Runtime { span, .. } => *span,
+            VmError::Corrupted(_) => None,
+        }
+    }
+}
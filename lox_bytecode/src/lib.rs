@@ -1,3 +1,11 @@
+pub mod chunk;
 pub mod compiler;
+pub mod disassembler;
+pub mod error;
+#[cfg(test)]
+mod test;
+pub mod value;
+pub mod vm;
 
+pub use lox_bytecode_ops::Operation;
 pub use lox_lexer::{Position, Span};
@@ -0,0 +1,567 @@
+use std::io;
+
+use lox_ast::{BinaryOp, Expr, Statement};
+use lox_bytecode_ops::{
+    codec::Encode, error::RuntimeError, writer::OpWriter, Operation, StringIntern,
+};
+use lox_gen::Generator;
+use lox_interpreter::{error::RuntimeError as InterpreterError, Buffer, Interpreter};
+use lox_lexer::{DivisionByZero, LanguageConfig, LineIndex, Span};
+use lox_resolver::Resolver;
+
+use crate::{
+    chunk::{Chunk, ChunkBuilder},
+    compiler::Compiler,
+    disassembler::disassemble,
+    error::VmError,
+    vm::Vm,
+};
+
+/// The value of the expression, or what was expected and found instead.
+type Outcome = Result<String, (&'static str, &'static str)>;
+
+/// The outcome of a division by zero in [`DivisionByZero::Error`] mode.
+const DIVISION_BY_ZERO: Outcome = Err(("non-zero divisor", "0"));
+
+fn interpret(expr: &str, config: &LanguageConfig) -> Outcome {
+    let mut ast = lox_parser::parse_with_config(&format!("var result = {expr};"), config)
+        .into_result()
+        .unwrap();
+    assert!(Resolver::default().resolve(&mut ast).is_none());
+    let mut interpreter = Interpreter::new();
+    interpreter.set_language(config);
+    match interpreter.interpret(&ast) {
+        Ok(_) => Ok(interpreter.get_global("result").unwrap().to_string()),
+        Err(err) => match *err {
+            InterpreterError::TypeError {
+                expected, found, ..
+            } => Err((expected, found)),
+            InterpreterError::DivisionByZero(_) => DIVISION_BY_ZERO,
+            err => panic!("unexpected error `{err}` in `{expr}`"),
+        },
+    }
+}
+
+fn execute(expr: &str, config: &LanguageConfig) -> Outcome {
+    let ast = lox_parser::parse_with_config(&format!("print {expr};"), config)
+        .into_result()
+        .unwrap();
+    let mut compiler = Compiler::default();
+    compiler.compile(&ast);
+    let chunk = compiler.finish();
+    let mut output = Vec::new();
+    let result = Vm::with_output(&mut output).with_config(config).run(&chunk);
+    match result {
+        Ok(_) => Ok(String::from_utf8(output).unwrap().trim_end().to_string()),
+        Err(VmError::Runtime {
+            error: RuntimeError::TypeError { expected, found },
+            ..
+        }) => Err((expected, found)),
+        Err(VmError::Runtime {
+            error: RuntimeError::DivisionByZero,
+            ..
+        }) => DIVISION_BY_ZERO,
+        Err(err) => panic!("unexpected error `{err}` in `{expr}`"),
+    }
+}
+
+/// Runs `expr` in both backends, which must agree on its outcome.
+fn differential(expr: &str) -> Outcome {
+    differential_with(expr, &LanguageConfig::default())
+}
+
+fn differential_with(expr: &str, config: &LanguageConfig) -> Outcome {
+    let outcome = interpret(expr, config);
+    assert_eq!(
+        outcome,
+        execute(expr, config),
+        "backends diverge on `{expr}`"
+    );
+    outcome
+}
+
+/// Runs the statements of `src` in both backends, which must print the same.
+fn differential_output(src: &str) -> String {
+    let mut ast = lox_parser::parse(src).into_result().unwrap();
+    assert!(Resolver::default().resolve(&mut ast).is_none());
+    let output = Buffer::default();
+    Interpreter::with_output(output.clone())
+        .interpret(&ast)
+        .unwrap();
+    let expected = output.take();
+
+    let mut compiler = Compiler::default();
+    compiler.compile(&ast);
+    let chunk = compiler.finish();
+    let mut output = Vec::new();
+    Vm::with_output(&mut output).run(&chunk).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        expected,
+        "backends print differently for `{src}`"
+    );
+    expected
+}
+
+#[test]
+fn arithmetic() {
+    assert_eq!(differential("1 + 2 * 3 - 4 / 2"), Ok("5".into()));
+    assert_eq!(differential("-(1 - 3) > 1 == !nil"), Ok("true".into()));
+    assert_eq!(differential(r#""a" + 1 + true"#), Ok("a1true".into()));
+    assert_eq!(differential(r#"nil + "b""#), Ok("nilb".into()));
+    assert_eq!(differential("(2 >= 2) != (1 <= 0)"), Ok("true".into()));
+}
+
+#[test]
+fn binary_operands_left_to_right() {
+    // Both operands are evaluated before either is checked.
+    assert_eq!(differential(r#"nil - -"a""#), Err(("number", "string")));
+    assert_eq!(differential("-true * -nil"), Err(("number", "bool")));
+    // The left operand is checked first.
+    assert_eq!(differential(r#"nil - "a""#), Err(("number", "nil")));
+    assert_eq!(differential("1 < nil"), Err(("number", "nil")));
+    assert_eq!(differential("true + 1"), Err(("number", "bool")));
+    assert_eq!(differential("1 + nil"), Err(("number or string", "nil")));
+    assert_eq!(
+        differential("true + nil"),
+        Err(("number or string", "bool"))
+    );
+}
+
+#[test]
+fn logical_short_circuit() {
+    assert_eq!(differential("false and -nil"), Ok("false".into()));
+    assert_eq!(differential(r#""" and -nil"#), Ok("".into()));
+    assert_eq!(differential("1 or -nil"), Ok("1".into()));
+    assert_eq!(differential(r#"0 or "right""#), Ok("right".into()));
+    assert_eq!(differential("nil or false and 1"), Ok("false".into()));
+    assert_eq!(differential("1 and -nil"), Err(("number", "nil")));
+    assert_eq!(differential("(1 and 2) + (false or 3)"), Ok("5".into()));
+}
+
+#[test]
+fn division_by_zero() {
+    assert_eq!(differential("1 / 0"), Ok("Infinity".into()));
+    assert_eq!(differential("-1 / (2 - 2)"), Ok("-Infinity".into()));
+    assert_eq!(differential("0 / 0"), Ok("NaN".into()));
+
+    let config = LanguageConfig {
+        division_by_zero: DivisionByZero::Error,
+        ..Default::default()
+    };
+    assert_eq!(differential_with("1 / 0", &config), DIVISION_BY_ZERO);
+    assert_eq!(differential_with("1 / -0", &config), DIVISION_BY_ZERO);
+    assert_eq!(differential_with("0 / 2", &config), Ok("0".into()));
+    // Operands are type checked first.
+    assert_eq!(
+        differential_with("nil / 0", &config),
+        Err(("number", "nil"))
+    );
+}
+
+#[test]
+fn nan_and_infinity() {
+    // `NaN` equals itself, but isn't ordered with any number.
+    assert_eq!(differential("0 / 0 == 0 / 0"), Ok("true".into()));
+    assert_eq!(differential("0 / 0 != 0 / 0"), Ok("false".into()));
+    assert_eq!(differential("0 / 0 == 1"), Ok("false".into()));
+    assert_eq!(differential("0 / 0 < 1 or 0 / 0 >= 1"), Ok("false".into()));
+    assert_eq!(differential("0 / 0 <= 0 / 0"), Ok("false".into()));
+    assert_eq!(differential("!(0 / 0)"), Ok("false".into()));
+    assert_eq!(differential(r#""" + (0 / 0 + 1)"#), Ok("NaN".into()));
+
+    assert_eq!(differential("1 / 0 == 2 / 0"), Ok("true".into()));
+    assert_eq!(differential("-1 / 0 < -1000000"), Ok("true".into()));
+    assert_eq!(differential("1 / 0 - 1 / 0"), Ok("NaN".into()));
+    assert_eq!(differential(r#""x" + -1 / 0"#), Ok("x-Infinity".into()));
+}
+
+#[test]
+fn integers() {
+    let config = LanguageConfig {
+        integers: true,
+        ..Default::default()
+    };
+    let integers = |expr| differential_with(expr, &config);
+    // Floats can't represent this sum exactly.
+    assert_eq!(
+        integers("9007199254740993 + 0"),
+        Ok("9007199254740993".into())
+    );
+    assert_eq!(
+        differential("9007199254740993 + 0"),
+        Ok("9007199254740992".into())
+    );
+
+    assert_eq!(integers("2 * 3 - 10"), Ok("-4".into()));
+    assert_eq!(integers("7 / 2"), Ok("3.5".into()));
+    assert_eq!(integers("1.5 * 2 == 3"), Ok("true".into()));
+    assert_eq!(integers("1 < 1.5 and 2 >= 2.0"), Ok("true".into()));
+    assert_eq!(integers(r#""n" + 3"#), Ok("n3".into()));
+    assert_eq!(integers("!0"), Ok("true".into()));
+    assert_eq!(integers("-true"), Err(("number", "bool")));
+
+    // Overflows promote to floats.
+    assert_eq!(
+        integers("9223372036854775807 + 1"),
+        Ok("9223372036854776000".into())
+    );
+    assert_eq!(
+        integers("-(-9223372036854775807 - 1)"),
+        Ok("9223372036854776000".into())
+    );
+    assert_eq!(
+        integers("99999999999999999999"),
+        Ok("100000000000000000000".into())
+    );
+}
+
+/// Gets the source of the operation at which `expr` fails in the VM.
+fn error_source(expr: &str) -> String {
+    let src = format!("print {expr};");
+    let mut compiler = Compiler::default();
+    compiler.compile(&lox_parser::parse(&src).into_result().unwrap());
+    let chunk = compiler.finish();
+    match Vm::with_output(io::sink()).run(&chunk) {
+        Err(err) => LineIndex::new(&src).text(err.span().unwrap()).to_string(),
+        result => panic!("`{expr}` doesn't fail but results in {result:?}"),
+    }
+}
+
+#[test]
+fn comparison_errors() {
+    for (expr, found) in [
+        (r#"1 >= "a""#, "string"),
+        ("nil <= 2", "nil"),
+        ("true > false", "bool"),
+        (r#""a" < "b""#, "string"),
+    ] {
+        assert_eq!(differential(expr), Err(("number", found)));
+        assert_eq!(error_source(expr), expr);
+    }
+    assert_eq!(error_source("1 + (2 <= -nil)"), "-nil");
+}
+
+#[test]
+fn generated_expressions() {
+    let mut generator = Generator::new(0);
+    for _ in 0..500 {
+        let _ = differential(&generator.expression());
+    }
+}
+
+#[test]
+fn group_spans() {
+    let src = "(1 + (2)) * 3;";
+    let ast = lox_parser::parse(src).into_result().unwrap();
+    let mut compiler = Compiler::default();
+    compiler.compile(&ast);
+    let chunk = compiler.finish();
+    let index = LineIndex::new(src);
+    let texts = (0..5)
+        .map(|i| index.text(chunk.get_span_at(i)))
+        .collect::<Vec<_>>();
+    assert_eq!(texts, ["1", "2", "1 + (2)", "3", "(1 + (2)) * 3"]);
+}
+
+#[test]
+fn print() {
+    assert_eq!(
+        differential_output("print 1 + 2; print \"a\" + \"b\"; print nil; print 1 / 3;"),
+        "3\nab\nnil\n0.3333333333333333\n"
+    );
+    assert_eq!(differential_output("print -0; print !1 or 2;"), "-0\n2\n");
+
+    let mut generator = Generator::new(2);
+    for _ in 0..100 {
+        let expr = generator.expression();
+        if let Ok(value) = differential(&expr) {
+            assert_eq!(differential_output(&format!("print {expr};")), value + "\n");
+        }
+    }
+}
+
+#[test]
+fn reused_vm() {
+    let compile = |src| {
+        let mut compiler = Compiler::default();
+        compiler.compile(&lox_parser::parse(src).into_result().unwrap());
+        compiler.finish()
+    };
+    let mut output = Vec::new();
+    let mut vm = Vm::with_output(&mut output);
+    assert!(vm.run(&compile("print 1 + nil;")).is_err());
+    vm.run(&compile("print \"a\";")).unwrap();
+    vm.run(&compile("print 2;")).unwrap();
+    drop(vm);
+    assert_eq!(String::from_utf8(output).unwrap(), "a\n2\n");
+}
+
+#[test]
+fn instruction_sizes() {
+    let src = "print 1 + 2 * -3 == 4 and \"a\" or true;";
+    let mut compiler = Compiler::default();
+    compiler.compile(&lox_parser::parse(src).into_result().unwrap());
+    let chunk = compiler.finish();
+    let mut offsets = Vec::new();
+    let mut offset = 0;
+    while offset < chunk.code().len() {
+        offsets.push(offset as u32);
+        offset += Operation::size_of_instruction(chunk.code()[offset]).unwrap();
+    }
+    assert_eq!(offset, chunk.code().len());
+    assert_eq!(offsets, chunk.offsets);
+}
+
+#[test]
+fn max_stack_size() {
+    let max_stack_size = |src: &str| {
+        let mut compiler = Compiler::default();
+        compiler.compile(&lox_parser::parse(src).into_result().unwrap());
+        compiler.finish().max_stack_size()
+    };
+    assert_eq!(max_stack_size(""), 0);
+    assert_eq!(max_stack_size("-1;"), 1);
+    assert_eq!(max_stack_size("1 + 2 * 3;"), 3);
+    assert_eq!(max_stack_size("(1 * 2) + (3 * 4);"), 3);
+    assert_eq!(max_stack_size("true and (1 + 2) < 3;"), 2);
+}
+
+#[test]
+#[should_panic(expected = "stack underflow")]
+fn stack_underflow() {
+    let mut chunk = ChunkBuilder::default();
+    chunk.push(Operation::LoadNil, Span::dummy());
+    chunk.push(Operation::Plus, Span::dummy());
+}
+
+#[test]
+fn deep_nesting() {
+    // Both the right operand and the group of each level count towards the
+    // depth limit of the parser, so this is as deep as it allows by default.
+    let depth = lox_lexer::DEFAULT_MAX_DEPTH / 2 - 2;
+    let src = format!("{}1{};", "1 + (".repeat(depth), ")".repeat(depth));
+    let ast = lox_parser::parse(&src).into_result().unwrap();
+    let mut compiler = Compiler::default();
+    compiler.compile(&ast);
+    assert_eq!(compiler.finish().max_stack_size(), depth + 1);
+    assert_eq!(
+        differential_output(&format!("print {src}")),
+        format!("{}\n", depth + 1)
+    );
+
+    let chain = format!("{}1", "1 + ".repeat(depth));
+    assert_eq!(differential(&chain), Ok((depth + 1).to_string()));
+}
+
+/// Encodes `operations` as they are, unlike [`ChunkBuilder`] which checks
+/// their stack effects.
+fn raw_chunk(operations: &[Operation], max_stack_size: usize) -> Chunk {
+    let mut writer = OpWriter::new();
+    operations.encode(&mut writer);
+    Chunk {
+        code: writer.flush(),
+        spans: vec![Span::dummy(); operations.len()],
+        offsets: Vec::new(),
+        strings: StringIntern::default(),
+        max_stack_size,
+        #[cfg(feature = "comments")]
+        comments: Vec::new(),
+    }
+}
+
+#[test]
+fn stack_manipulation() {
+    use Operation::*;
+
+    let run = |operations: &[Operation]| {
+        let chunk = raw_chunk(operations, 2);
+        Vm::new()
+            .run(&chunk)
+            .unwrap()
+            .map(|value| value.to_string())
+    };
+    assert_eq!(run(&[LoadNumber(1.0), Pop]), None);
+    assert_eq!(run(&[LoadNumber(2.0), Dup, Multiply]).as_deref(), Some("4"));
+    assert_eq!(
+        run(&[LoadNumber(1.0), LoadNumber(2.0), Swap, Minus]).as_deref(),
+        Some("1")
+    );
+
+    // Expression statements leave nothing on the stack.
+    assert!(differential_output("1 + 2; \"a\";").is_empty());
+    let mut compiler = Compiler::default();
+    compiler.compile(&lox_parser::parse("1; 2; 3;").into_result().unwrap());
+    assert_eq!(compiler.finish().max_stack_size(), 1);
+}
+
+#[test]
+fn corrupted_code() {
+    let run = |code: Vec<u8>| {
+        let chunk = Chunk {
+            code,
+            ..raw_chunk(&[], 2)
+        };
+        match Vm::new().run(&chunk) {
+            Err(VmError::Corrupted(err)) => err.to_string(),
+            result => panic!("corrupted code results in {result:?}"),
+        }
+    };
+    // `LoadNil`, `Not`, then a byte which isn't an opcode.
+    assert_eq!(
+        run(vec![3, 5, 0xff]),
+        "2: unknown opcode `0xff` in `03 05 [ff]`"
+    );
+    assert_eq!(
+        run(vec![0, 1, 2]),
+        "1: no enough data, expected 8 byte(s), remaining 2 byte(s)"
+    );
+    assert_eq!(run(vec![2, 7]), "1: invalid bool value `0b111`");
+}
+
+#[test]
+fn stack_errors() {
+    let chunk = raw_chunk(&[Operation::LoadNil, Operation::LoadNil], 1);
+    assert!(matches!(
+        Vm::new().run(&chunk),
+        Err(VmError::Runtime {
+            error: RuntimeError::StackOverflow,
+            ..
+        })
+    ));
+    let chunk = raw_chunk(&[Operation::LoadNil, Operation::Plus], 2);
+    assert!(matches!(
+        Vm::new().run(&chunk),
+        Err(VmError::Runtime {
+            error: RuntimeError::StackUnderflow,
+            ..
+        })
+    ));
+    let chunk = raw_chunk(&[Operation::JumpIfTrueOrPop(0)], 0);
+    assert!(matches!(
+        Vm::new().run(&chunk),
+        Err(VmError::Runtime {
+            error: RuntimeError::StackUnderflow,
+            ..
+        })
+    ));
+}
+
+/// Collects the spans of the AST nodes `expr` compiles to an operation, in
+/// the order the operations are emitted.
+fn node_spans(expr: &Expr, spans: &mut Vec<Span>) {
+    match expr {
+        Expr::Literal(literal) => spans.push(literal.span),
+        Expr::Unary(unary) => {
+            node_spans(&unary.operand, spans);
+            spans.push(unary.get_span());
+        }
+        Expr::Group(group) => node_spans(&group.expr, spans),
+        Expr::Binary(binary) if matches!(binary.operator, BinaryOp::And | BinaryOp::Or) => {
+            node_spans(&binary.left, spans);
+            spans.push(binary.get_span());
+            node_spans(&binary.right, spans);
+        }
+        Expr::Binary(binary) => {
+            node_spans(&binary.left, spans);
+            node_spans(&binary.right, spans);
+            spans.push(binary.get_span());
+        }
+        expr => panic!("`{expr:?}` can't be compiled yet"),
+    }
+}
+
+/// Checks that each compiled operation maps back to the span of the AST node
+/// it was compiled from.
+fn assert_span_round_trip(src: &str) {
+    let ast = lox_parser::parse(src).into_result().unwrap();
+    let mut expected = Vec::new();
+    for stmt in &ast {
+        match stmt {
+            Statement::Expression(expression) => {
+                node_spans(&expression.expr, &mut expected);
+                expected.push(expression.span);
+            }
+            Statement::Print(print) => {
+                node_spans(&print.expr, &mut expected);
+                expected.push(print.span);
+            }
+            stmt => panic!("`{stmt:?}` can't be compiled yet"),
+        }
+    }
+    let mut compiler = Compiler::default();
+    compiler.compile(&ast);
+    let chunk = compiler.finish();
+    let index = LineIndex::new(src);
+    assert_eq!(chunk.spans.len(), expected.len(), "in `{src}`");
+    for (i, span) in expected.into_iter().enumerate() {
+        assert_eq!(
+            chunk.get_span_at(i),
+            span,
+            "operation {i} of `{src}` should map to `{}`",
+            index.text(span)
+        );
+    }
+}
+
+#[test]
+fn span_round_trip() {
+    assert_span_round_trip("-(1 + 2) * !true;");
+    assert_span_round_trip("1 < 2 and 3 or nil == \"a\";");
+    assert_span_round_trip("1;\n2 + 3;");
+    assert_span_round_trip("print 1 == 2;");
+
+    let mut generator = Generator::new(1);
+    for _ in 0..500 {
+        assert_span_round_trip(&format!("{};", generator.expression()));
+    }
+}
+
+fn compile(src: &str) -> Chunk {
+    let ast = lox_parser::parse(src).into_result().unwrap();
+    let mut compiler = Compiler::default();
+    compiler.compile(&ast);
+    compiler.finish()
+}
+
+#[test]
+fn disassembly() {
+    let chunk = compile("print 1 + 2;\n\"text\";");
+    assert_eq!(
+        disassemble(&chunk),
+        "0000    1 LoadNumber(1.0)\n\
+         0009    1 LoadNumber(2.0)\n\
+         0018    1 Plus\n\
+         0019    1 Print\n\
+         0020    2 LoadString(\"text\")\n\
+         0025    2 Pop\n"
+    );
+}
+
+#[cfg(feature = "comments")]
+#[test]
+fn comments_in_disassembly() {
+    let src = "// Adds numbers.\nprint 1 + /* inline */ 2;\n{ // skipped\n}\n/* multi\n   line */";
+    // Blocks aren't compiled yet, so only the code before them is.
+    let mut chunk = compile(&src[..src.find('{').unwrap()]);
+    chunk.attach_comments(src);
+    assert_eq!(
+        chunk
+            .comments()
+            .iter()
+            .map(|(offset, _)| *offset)
+            .collect::<Vec<_>>(),
+        [0, 9, 20]
+    );
+    assert_eq!(
+        disassemble(&chunk),
+        "          // Adds numbers.\n\
+         0000    2 LoadNumber(1.0)\n\
+         \x20         /* inline */\n\
+         0009    2 LoadNumber(2.0)\n\
+         0018    2 Plus\n\
+         0019    2 Print\n\
+         \x20         /* multi\n\
+         \x20         line */\n"
+    );
+}
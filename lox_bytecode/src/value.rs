@@ -0,0 +1,91 @@
+use std::{fmt::Display, rc::Rc};
+
+use lox_ast::number::{DisplayNumber, Number};
+
+/// A value on the stack of the [`Vm`](crate::vm::Vm), which behaves like its
+/// counterpart in the tree-walking interpreter.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Number(f64),
+    Integer(i64),
+    String(Rc<str>),
+    Bool(bool),
+    Nil,
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        if let (Some(n1), Some(n2)) = (self.as_number(), other.as_number()) {
+            return n1.equals(n2);
+        }
+        match (self, other) {
+            (Self::String(s1), Self::String(s2)) => s1 == s2,
+            (Self::Bool(b1), Self::Bool(b2)) => b1 == b2,
+            (Self::Nil, Self::Nil) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Value {
+    /// The value if it's a number, either an integer or a float.
+    pub fn as_number(&self) -> Option<Number> {
+        match self {
+            Value::Number(n) => Some(Number::Float(*n)),
+            Value::Integer(n) => Some(Number::Integer(*n)),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> bool {
+        match self {
+            Value::Number(num) => *num != 0.0,
+            Value::Integer(num) => *num != 0,
+            Value::String(s) => !s.is_empty(),
+            Value::Bool(b) => *b,
+            Value::Nil => false,
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) | Value::Integer(_) => "number",
+            Value::String(_) => "string",
+            Value::Bool(_) => "bool",
+            Value::Nil => "nil",
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Number(n) => write!(f, "{}", DisplayNumber(*n)),
+            Value::Integer(n) => write!(f, "{n}"),
+            Value::String(s) => write!(f, "{s}"),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Nil => write!(f, "nil"),
+        }
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Self::Number(value)
+    }
+}
+
+impl From<Number> for Value {
+    fn from(value: Number) -> Self {
+        match value {
+            Number::Integer(n) => Self::Integer(n),
+            Number::Float(n) => Self::Number(n),
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
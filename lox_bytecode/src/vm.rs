@@ -0,0 +1,256 @@
+use std::{
+    cmp::Ordering,
+    io::{self, Write},
+};
+
+use lox_ast::number::Number;
+use lox_bytecode_ops::{
+    error::{ExecutorResult, RuntimeError},
+    execute_operation, OperationExecutor, StringSymbol,
+};
+use lox_lexer::{DivisionByZero, LanguageConfig};
+
+use crate::{chunk::Chunk, error::VmError, value::Value};
+
+/// Executes [`Chunk`]s on a value stack.
+///
+/// Operands are pushed left to right and popped by the operation using them,
+/// so they are evaluated before any of them is type checked, in the same
+/// order as the tree-walking interpreter.
+pub struct Vm<'a> {
+    stack: Vec<Value>,
+    /// The stack size of the chunk being run.
+    max_stack_size: usize,
+    jump: Option<usize>,
+    division_by_zero: DivisionByZero,
+    output: Box<dyn Write + 'a>,
+}
+
+impl Default for Vm<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Vm<'a> {
+    pub fn new() -> Self {
+        Self::with_output(io::stdout())
+    }
+
+    /// Creates a VM which writes the output of `print` to `output`.
+    pub fn with_output(output: impl Write + 'a) -> Self {
+        Self {
+            stack: Vec::new(),
+            max_stack_size: 0,
+            jump: None,
+            division_by_zero: DivisionByZero::default(),
+            output: Box::new(output),
+        }
+    }
+
+    /// Applies the runtime settings of `config`, as
+    /// `Interpreter::set_language` does.
+    pub fn with_config(mut self, config: &LanguageConfig) -> Self {
+        self.division_by_zero = config.division_by_zero;
+        self
+    }
+
+    /// Runs `chunk`, returning the value left on top of the stack, if any.
+    pub fn run(&mut self, chunk: &Chunk) -> Result<Option<Value>, VmError> {
+        self.stack.clear();
+        self.stack.reserve(chunk.max_stack_size());
+        self.max_stack_size = chunk.max_stack_size();
+        self.jump = None;
+        execute_operation(self, chunk, chunk.code()).map_err(|err| VmError::new(err, chunk))?;
+        Ok(self.stack.pop())
+    }
+
+    fn push(&mut self, value: impl Into<Value>) -> ExecutorResult<RuntimeError> {
+        if self.stack.len() == self.max_stack_size {
+            return Err(RuntimeError::StackOverflow);
+        }
+        self.stack.push(value.into());
+        Ok(())
+    }
+
+    fn pop_value(&mut self) -> Result<Value, RuntimeError> {
+        self.stack.pop().ok_or(RuntimeError::StackUnderflow)
+    }
+
+    fn pop_numbers(&mut self) -> Result<(Number, Number), RuntimeError> {
+        let right = self.pop_value()?;
+        let left = self.pop_value()?;
+        match (left.as_number(), right.as_number()) {
+            (Some(left), Some(right)) => Ok((left, right)),
+            (Some(_), None) => Err(type_error("number", &right)),
+            (None, _) => Err(type_error("number", &left)),
+        }
+    }
+
+    fn jump_if(&mut self, condition: bool, target: u32) -> ExecutorResult<RuntimeError> {
+        let top = self.stack.last().ok_or(RuntimeError::StackUnderflow)?;
+        if top.as_bool() == condition {
+            self.jump = Some(target as usize);
+        } else {
+            self.pop_value()?;
+        }
+        Ok(())
+    }
+}
+
+fn type_error(expected: &'static str, found: &Value) -> RuntimeError {
+    RuntimeError::TypeError {
+        expected,
+        found: found.type_name(),
+    }
+}
+
+macro_rules! binary_arith {
+    ($vm: expr, $op: tt) => {{
+        let (left, right) = $vm.pop_numbers()?;
+        $vm.push(left $op right)
+    }};
+}
+
+macro_rules! binary_cmp {
+    ($vm: expr, $ordering: ident) => {{
+        let (left, right) = $vm.pop_numbers()?;
+        $vm.push(left.compare(right).is_some_and(Ordering::$ordering))
+    }};
+}
+
+impl OperationExecutor<Chunk> for Vm<'_> {
+    fn load_number(&mut self, _chunk: &Chunk, arg0: f64) -> ExecutorResult<RuntimeError> {
+        self.push(arg0)
+    }
+
+    fn load_integer(&mut self, _chunk: &Chunk, arg0: i64) -> ExecutorResult<RuntimeError> {
+        self.push(Value::Integer(arg0))
+    }
+
+    fn load_string(&mut self, chunk: &Chunk, arg0: StringSymbol) -> ExecutorResult<RuntimeError> {
+        let s = chunk.strings().resolve(&arg0);
+        self.push(Value::String(s.into()))
+    }
+
+    fn load_bool(&mut self, _chunk: &Chunk, arg0: bool) -> ExecutorResult<RuntimeError> {
+        self.push(arg0)
+    }
+
+    fn load_nil(&mut self, _chunk: &Chunk) -> ExecutorResult<RuntimeError> {
+        self.push(Value::Nil)
+    }
+
+    fn negative(&mut self, _chunk: &Chunk) -> ExecutorResult<RuntimeError> {
+        let value = self.pop_value()?;
+        match value.as_number() {
+            Some(n) => self.push(-n),
+            None => Err(type_error("number", &value)),
+        }
+    }
+
+    fn not(&mut self, _chunk: &Chunk) -> ExecutorResult<RuntimeError> {
+        let value = self.pop_value()?;
+        self.push(!value.as_bool())
+    }
+
+    fn plus(&mut self, _chunk: &Chunk) -> ExecutorResult<RuntimeError> {
+        let right = self.pop_value()?;
+        let value = match (self.pop_value()?, right) {
+            (Value::String(s1), v2) => Value::String(format!("{s1}{v2}").into()),
+            (v1, Value::String(s2)) => Value::String(format!("{v1}{s2}").into()),
+            (v1, v2) => match (v1.as_number(), v2.as_number()) {
+                (Some(n1), Some(n2)) => (n1 + n2).into(),
+                (None, Some(_)) => return Err(type_error("number", &v1)),
+                (Some(_), None) => return Err(type_error("number or string", &v2)),
+                (None, None) => return Err(type_error("number or string", &v1)),
+            },
+        };
+        self.push(value)
+    }
+
+    fn minus(&mut self, _chunk: &Chunk) -> ExecutorResult<RuntimeError> {
+        binary_arith!(self, -)
+    }
+
+    fn multiply(&mut self, _chunk: &Chunk) -> ExecutorResult<RuntimeError> {
+        binary_arith!(self, *)
+    }
+
+    fn divide(&mut self, _chunk: &Chunk) -> ExecutorResult<RuntimeError> {
+        let (left, right) = self.pop_numbers()?;
+        if right.is_zero() && self.division_by_zero == DivisionByZero::Error {
+            return Err(RuntimeError::DivisionByZero);
+        }
+        self.push(left / right)
+    }
+
+    fn greater(&mut self, _chunk: &Chunk) -> ExecutorResult<RuntimeError> {
+        binary_cmp!(self, is_gt)
+    }
+
+    fn greater_equal(&mut self, _chunk: &Chunk) -> ExecutorResult<RuntimeError> {
+        binary_cmp!(self, is_ge)
+    }
+
+    fn less(&mut self, _chunk: &Chunk) -> ExecutorResult<RuntimeError> {
+        binary_cmp!(self, is_lt)
+    }
+
+    fn less_equal(&mut self, _chunk: &Chunk) -> ExecutorResult<RuntimeError> {
+        binary_cmp!(self, is_le)
+    }
+
+    fn equal(&mut self, _chunk: &Chunk) -> ExecutorResult<RuntimeError> {
+        let right = self.pop_value()?;
+        let left = self.pop_value()?;
+        self.push(left == right)
+    }
+
+    fn not_equal(&mut self, _chunk: &Chunk) -> ExecutorResult<RuntimeError> {
+        let right = self.pop_value()?;
+        let left = self.pop_value()?;
+        self.push(left != right)
+    }
+
+    fn jump_if_false_or_pop(&mut self, _chunk: &Chunk, arg0: u32) -> ExecutorResult<RuntimeError> {
+        self.jump_if(false, arg0)
+    }
+
+    fn jump_if_true_or_pop(&mut self, _chunk: &Chunk, arg0: u32) -> ExecutorResult<RuntimeError> {
+        self.jump_if(true, arg0)
+    }
+
+    fn print(&mut self, _chunk: &Chunk) -> ExecutorResult<RuntimeError> {
+        let value = self.pop_value()?;
+        writeln!(self.output, "{value}")?;
+        Ok(())
+    }
+
+    fn pop(&mut self, _chunk: &Chunk) -> ExecutorResult<RuntimeError> {
+        self.pop_value()?;
+        Ok(())
+    }
+
+    fn dup(&mut self, _chunk: &Chunk) -> ExecutorResult<RuntimeError> {
+        let top = self
+            .stack
+            .last()
+            .ok_or(RuntimeError::StackUnderflow)?
+            .clone();
+        self.push(top)
+    }
+
+    fn swap(&mut self, _chunk: &Chunk) -> ExecutorResult<RuntimeError> {
+        let len = self.stack.len();
+        if len < 2 {
+            return Err(RuntimeError::StackUnderflow);
+        }
+        self.stack.swap(len - 2, len - 1);
+        Ok(())
+    }
+
+    fn take_jump(&mut self) -> Option<usize> {
+        self.jump.take()
+    }
+}
@@ -1,5 +1,5 @@
-use std::mem;
-use thiserror::Error;
+use alloc::{boxed::Box, string::String};
+use core::{fmt::Display, mem};
 
 pub trait Write {
     fn write(&mut self, buf: &[u8]);
@@ -15,33 +15,71 @@ impl<Writer: Write> Encode<Writer> for bool {
     }
 }
 
-impl<Writer: Write> Encode<Writer> for f64 {
-    fn encode(&self, writer: &mut Writer) {
-        writer.write(&self.to_le_bytes());
-    }
+macro_rules! impl_encode {
+    ($($ty: ty),*) => {
+        $(impl<Writer: Write> Encode<Writer> for $ty {
+            fn encode(&self, writer: &mut Writer) {
+                writer.write(&self.to_le_bytes());
+            }
+        })*
+    };
 }
 
-#[derive(Debug, Error)]
+impl_encode! {u8, u16, u32, i16, i32, i64, f64}
+
+#[derive(Debug)]
 pub enum DecoderErrorDetail {
-    #[error("invalid bool value `{0:#b}`")]
     InvalidBool(u8),
-    #[error("no enough data, expected {expected} byte(s), remaining {rem} byte(s)")]
     NoEnoughData { expected: usize, rem: usize },
+    VarintOverflow,
+    InvalidUtf8,
+    DuplicateString(String),
+    UnknownOpcode(u8),
 }
 
-#[derive(Debug, Error)]
-#[error("{pos}: {error}")]
+impl Display for DecoderErrorDetail {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::InvalidBool(value) => write!(f, "invalid bool value `{value:#b}`"),
+            Self::NoEnoughData { expected, rem } => write!(
+                f,
+                "no enough data, expected {expected} byte(s), remaining {rem} byte(s)"
+            ),
+            Self::VarintOverflow => write!(f, "varint overflows `u32`"),
+            Self::InvalidUtf8 => write!(f, "invalid UTF-8 in string"),
+            Self::DuplicateString(s) => write!(f, "string `{s}` is interned twice"),
+            Self::UnknownOpcode(byte) => write!(f, "unknown opcode `{byte:#04x}`"),
+        }
+    }
+}
+
+impl core::error::Error for DecoderErrorDetail {}
+
+#[derive(Debug)]
 pub struct DecoderError {
     pos: usize,
     error: DecoderErrorDetail,
 }
 
+impl Display for DecoderError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}: {}", self.pos, self.error)
+    }
+}
+
+impl core::error::Error for DecoderError {}
+
 impl DecoderError {
     pub fn from_detail(pos: usize, detail: DecoderErrorDetail) -> Self {
         Self { pos, error: detail }
     }
 }
 
+/// Types which are always encoded in `SIZE` bytes.
+pub trait FixedSize {
+    const SIZE: usize;
+}
+
 pub type DecodeResult<T> = Result<(T, usize), Box<DecoderErrorDetail>>;
 
 pub trait Decode: Sized {
@@ -62,6 +100,10 @@ pub fn get_bytes<const N: usize>(buf: &[u8]) -> Result<[u8; N], Box<DecoderError
     }
 }
 
+impl FixedSize for bool {
+    const SIZE: usize = mem::size_of::<bool>();
+}
+
 impl Decode for bool {
     fn decode(buf: &[u8]) -> DecodeResult<Self> {
         const SIZE: usize = mem::size_of::<bool>();
@@ -78,7 +120,11 @@ impl Decode for bool {
 
 macro_rules! impl_decode {
     ($($ty: ty),*) => {
-        $(impl Decode for $ty {
+        $(impl FixedSize for $ty {
+            const SIZE: usize = mem::size_of::<$ty>();
+        }
+
+        impl Decode for $ty {
             fn decode(buf: &[u8]) -> DecodeResult<Self> {
                 const SIZE: usize = mem::size_of::<$ty>();
                 Ok((<$ty>::from_le_bytes(get_bytes::<SIZE>(buf)?), SIZE))
@@ -87,4 +133,42 @@ macro_rules! impl_decode {
     };
 }
 
-impl_decode! {u32, f64}
+impl_decode! {u8, u16, u32, i16, i32, i64, f64}
+
+/// Encodes `u32`s in 1 to 5 bytes, 7 bits per byte with the high bit set on
+/// all but the last one, for `#[opcodec(with = "varint")]` operands which
+/// are usually small.
+pub mod varint {
+    use alloc::boxed::Box;
+
+    use super::{DecodeResult, DecoderErrorDetail, Write};
+
+    const MAX_LEN: usize = 5;
+
+    pub fn encode<Writer: Write>(value: &u32, writer: &mut Writer) {
+        let mut value = *value;
+        while value >= 0x80 {
+            writer.write(&[value as u8 | 0x80]);
+            value >>= 7;
+        }
+        writer.write(&[value as u8]);
+    }
+
+    pub fn decode(buf: &[u8]) -> DecodeResult<u32> {
+        let mut value = 0u32;
+        for (i, &byte) in buf.iter().take(MAX_LEN).enumerate() {
+            // The last byte only has the 4 remaining bits of the `u32`.
+            if i == MAX_LEN - 1 && byte > 0x0f {
+                return Err(Box::new(DecoderErrorDetail::VarintOverflow));
+            }
+            value |= u32::from(byte & 0x7f) << (7 * i);
+            if byte & 0x80 == 0 {
+                return Ok((value, i + 1));
+            }
+        }
+        Err(Box::new(DecoderErrorDetail::NoEnoughData {
+            expected: buf.len() + 1,
+            rem: buf.len(),
+        }))
+    }
+}
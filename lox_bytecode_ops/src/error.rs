@@ -1,23 +1,123 @@
-use thiserror::Error;
+use alloc::{boxed::Box, string::String};
+use core::fmt::{Display, Write};
 
 use crate::codec::DecoderError;
 
-#[derive(Debug, Error)]
-pub enum RuntimeError {}
+#[derive(Debug)]
+pub enum RuntimeError {
+    TypeError {
+        expected: &'static str,
+        found: &'static str,
+    },
+    DivisionByZero,
+    /// More values are pushed than the chunk says it needs.
+    StackOverflow,
+    /// An operation pops a value which was never pushed.
+    StackUnderflow,
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::TypeError { expected, found } => {
+                write!(f, "TypeError: expected `{expected}`, found `{found}`")
+            }
+            Self::DivisionByZero => write!(f, "division by zero"),
+            Self::StackOverflow => write!(f, "stack overflow"),
+            Self::StackUnderflow => write!(f, "stack underflow"),
+            #[cfg(feature = "std")]
+            Self::Io(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl core::error::Error for RuntimeError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for RuntimeError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
 
-#[derive(Debug, Error)]
+#[derive(Debug)]
 pub enum ExecutorError {
-    #[error("{0}")]
-    RuntimeError(RuntimeError),
-    #[error("{0}")]
+    RuntimeError {
+        error: RuntimeError,
+        /// The byte offset of the operation which failed.
+        offset: usize,
+    },
     DecoderError(DecoderError),
+    UnknownOpcode {
+        offset: usize,
+        byte: u8,
+        /// The bytes around the opcode, starting at `context_start`.
+        context: Box<[u8]>,
+        context_start: usize,
+    },
 }
 
-impl From<RuntimeError> for ExecutorError {
-    #[inline(always)]
-    fn from(value: RuntimeError) -> Self {
-        Self::RuntimeError(value)
+impl Display for ExecutorError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::RuntimeError { error, .. } => write!(f, "{error}"),
+            Self::DecoderError(error) => write!(f, "{error}"),
+            Self::UnknownOpcode {
+                offset,
+                byte,
+                context,
+                context_start,
+            } => write!(
+                f,
+                "{offset}: unknown opcode `{byte:#04x}` in `{}`",
+                hex_dump(context, offset - context_start)
+            ),
+        }
+    }
+}
+
+impl core::error::Error for ExecutorError {}
+
+impl ExecutorError {
+    /// The number of bytes shown on each side of an unknown opcode.
+    const CONTEXT: usize = 4;
+
+    pub fn unknown_opcode(buf: &[u8], offset: usize) -> Self {
+        let context_start = offset.saturating_sub(Self::CONTEXT);
+        let context_end = buf.len().min(offset + Self::CONTEXT + 1);
+        Self::UnknownOpcode {
+            offset,
+            byte: buf[offset],
+            context: buf[context_start..context_end].into(),
+            context_start,
+        }
+    }
+}
+
+/// Formats `bytes` in hexadecimal, with the one at `marked` in brackets.
+fn hex_dump(bytes: &[u8], marked: usize) -> String {
+    let mut dump = String::new();
+    for (i, byte) in bytes.iter().enumerate() {
+        if i > 0 {
+            dump.push(' ');
+        }
+        if i == marked {
+            write!(dump, "[{byte:02x}]").unwrap();
+        } else {
+            write!(dump, "{byte:02x}").unwrap();
+        }
     }
+    dump
 }
 
 impl From<DecoderError> for ExecutorError {
@@ -1,9 +1,11 @@
+use alloc::boxed::Box;
 use lox_ast::{BinaryOp, UnaryOp};
 use lox_macros::OpCodec;
 
 use crate::{codec::*, error::*, StringSymbol};
 
 #[derive(Debug, OpCodec)]
+#[opcodec(context)]
 pub enum Operation {
     LoadNumber(f64),
     LoadString(StringSymbol),
@@ -15,20 +17,71 @@ pub enum Operation {
     Minus,
     Multiply,
     Divide,
-    And,
-    Or,
     Greater,
+    /// Not compiled as `Less` then `Not`, which would order `NaN`.
     GreaterEqual,
     Less,
+    /// Not compiled as `Greater` then `Not`, which would order `NaN`.
     LessEqual,
     Equal,
     NotEqual,
+    /// Jumps to the byte offset if the value on top of the stack is falsy,
+    /// keeping it as the result of `and`, otherwise pops it.
+    JumpIfFalseOrPop(u32),
+    /// Jumps to the byte offset if the value on top of the stack is truthy,
+    /// keeping it as the result of `or`, otherwise pops it.
+    JumpIfTrueOrPop(u32),
+    /// Only emitted for integer literals, see `LanguageConfig::integers`.
+    LoadInteger(i64),
+    /// Pops the value on top of the stack and writes it on its own line.
+    Print,
+    /// Discards the value on top of the stack, e.g. the result of an
+    /// expression statement.
+    Pop,
+    /// Pushes a copy of the value on top of the stack.
+    Dup,
+    /// Swaps the two values on top of the stack.
+    Swap,
 }
 
-impl From<BinaryOp> for Operation {
-    fn from(value: BinaryOp) -> Self {
-        match value {
-            BinaryOp::And => Self::And,
+impl Operation {
+    /// The number of values the operation pops off the stack and then pushes
+    /// onto it. Jumps count as popping, as they only keep the value when
+    /// skipping the operations which would have replaced it.
+    pub fn stack_effect(&self) -> (usize, usize) {
+        match self {
+            Self::LoadNumber(_)
+            | Self::LoadString(_)
+            | Self::LoadBool(_)
+            | Self::LoadNil
+            | Self::LoadInteger(_) => (0, 1),
+            Self::Negative | Self::Not => (1, 1),
+            Self::Plus
+            | Self::Minus
+            | Self::Multiply
+            | Self::Divide
+            | Self::Greater
+            | Self::GreaterEqual
+            | Self::Less
+            | Self::LessEqual
+            | Self::Equal
+            | Self::NotEqual => (2, 1),
+            Self::JumpIfFalseOrPop(_) | Self::JumpIfTrueOrPop(_) | Self::Print | Self::Pop => {
+                (1, 0)
+            }
+            Self::Dup => (1, 2),
+            Self::Swap => (2, 2),
+        }
+    }
+}
+
+/// Fails for `and` and `or`, which short-circuit with jumps instead.
+impl TryFrom<BinaryOp> for Operation {
+    type Error = BinaryOp;
+
+    fn try_from(value: BinaryOp) -> Result<Self, Self::Error> {
+        Ok(match value {
+            BinaryOp::And | BinaryOp::Or => return Err(value),
             BinaryOp::Divide => Self::Divide,
             BinaryOp::Equal => Self::Equal,
             BinaryOp::Greater => Self::Greater,
@@ -38,9 +91,8 @@ impl From<BinaryOp> for Operation {
             BinaryOp::Minus => Self::Minus,
             BinaryOp::Multiply => Self::Multiply,
             BinaryOp::NotEqual => Self::NotEqual,
-            BinaryOp::Or => Self::Or,
             BinaryOp::Plus => Self::Plus,
-        }
+        })
     }
 }
 
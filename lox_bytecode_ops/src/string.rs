@@ -1,6 +1,9 @@
-use crate::codec::{Decode, DecodeResult, Encode, Write};
+use alloc::{boxed::Box, string::ToString};
+use fnv::FnvBuildHasher;
 
-#[derive(Debug)]
+use crate::codec::{Decode, DecodeResult, DecoderErrorDetail, Encode, FixedSize, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct StringSymbol(pub(crate) u32);
 
 impl From<StringSymbol> for u32 {
@@ -12,7 +15,7 @@ impl From<StringSymbol> for u32 {
 
 #[derive(Debug, Default)]
 pub struct StringIntern {
-    strings: indexmap::IndexSet<Box<str>>,
+    strings: indexmap::IndexSet<Box<str>, FnvBuildHasher>,
 }
 
 impl StringIntern {
@@ -22,6 +25,63 @@ impl StringIntern {
             None => self.strings.insert_full(s.to_string().into_boxed_str()).0,
         } as u32)
     }
+
+    /// Gets the string interned as `symbol`.
+    pub fn resolve(&self, symbol: &StringSymbol) -> &str {
+        &self.strings[symbol.0 as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Iterates over the interned strings in the order of their symbols.
+    pub fn iter(&self) -> impl Iterator<Item = (StringSymbol, &str)> {
+        self.strings
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (StringSymbol(i as u32), &**s))
+    }
+}
+
+/// Encodes the number of strings, then the length in bytes and the UTF-8 of
+/// each string.
+impl<Writer: Write> Encode<Writer> for StringIntern {
+    fn encode(&self, writer: &mut Writer) {
+        (self.strings.len() as u32).encode(writer);
+        for s in self.strings.iter() {
+            (s.len() as u32).encode(writer);
+            writer.write(s.as_bytes());
+        }
+    }
+}
+
+impl Decode for StringIntern {
+    fn decode(buf: &[u8]) -> DecodeResult<Self> {
+        let (count, mut size) = u32::decode(buf)?;
+        let mut intern = Self::default();
+        for _ in 0..count {
+            let (len, len_size) = u32::decode(&buf[size..])?;
+            size += len_size;
+            let bytes = buf.get(size..size + len as usize).ok_or_else(|| {
+                DecoderErrorDetail::NoEnoughData {
+                    expected: len as usize,
+                    rem: buf.len() - size,
+                }
+            })?;
+            let s = core::str::from_utf8(bytes).map_err(|_| DecoderErrorDetail::InvalidUtf8)?;
+            size += bytes.len();
+            // A duplicate would shift the symbols of the following strings.
+            if !intern.strings.insert(s.into()) {
+                return Err(Box::new(DecoderErrorDetail::DuplicateString(s.into())));
+            }
+        }
+        Ok((intern, size))
+    }
 }
 
 impl<Writer: Write> Encode<Writer> for StringSymbol {
@@ -30,6 +90,10 @@ impl<Writer: Write> Encode<Writer> for StringSymbol {
     }
 }
 
+impl FixedSize for StringSymbol {
+    const SIZE: usize = u32::SIZE;
+}
+
 impl Decode for StringSymbol {
     fn decode(buf: &[u8]) -> DecodeResult<Self> {
         let (v, size) = u32::decode(buf)?;
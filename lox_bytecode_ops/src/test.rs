@@ -1,4 +1,9 @@
-use crate::{codec::Encode, writer::OpWriter, Operation, StringSymbol};
+use alloc::{boxed::Box, format, string::ToString, vec::Vec};
+use core::fmt::Debug;
+
+use lox_macros::OpCodec;
+
+use crate::{codec::*, error::*, writer::OpWriter, Operation, StringIntern, StringSymbol};
 
 #[test]
 fn encode_operations() {
@@ -14,14 +19,15 @@ fn encode_operations() {
         Operation::Minus,
         Operation::Multiply,
         Operation::Divide,
-        Operation::And,
-        Operation::Or,
         Operation::Greater,
         Operation::GreaterEqual,
         Operation::Less,
         Operation::LessEqual,
         Operation::Equal,
         Operation::NotEqual,
+        Operation::JumpIfFalseOrPop(2),
+        Operation::JumpIfTrueOrPop(3),
+        Operation::LoadInteger(-4),
     ];
 
     let mut writer = OpWriter::new();
@@ -34,7 +40,280 @@ fn encode_operations() {
             .chain([1])
             .chain(1u32.to_le_bytes())
             .chain([3, 3])
-            .chain(4..=17)
+            .chain(4..=15)
+            .chain([16])
+            .chain(2u32.to_le_bytes())
+            .chain([17])
+            .chain(3u32.to_le_bytes())
+            .chain([18])
+            .chain((-4i64).to_le_bytes())
             .collect::<Vec<u8>>()
     );
 }
+
+#[test]
+fn unknown_opcode() {
+    let buf = [3, 3, 1, 0, 0, 0, 0xff, 4, 5];
+    assert_eq!(
+        ExecutorError::unknown_opcode(&buf, 6).to_string(),
+        "6: unknown opcode `0xff` in `01 00 00 00 [ff] 04 05`"
+    );
+    assert_eq!(
+        ExecutorError::unknown_opcode(&[0xfe], 0).to_string(),
+        "0: unknown opcode `0xfe` in `[fe]`"
+    );
+}
+
+#[test]
+fn labels() {
+    let mut writer = OpWriter::new();
+    let start = writer.new_label();
+    let end = writer.new_label();
+    writer.place_label(start);
+    Operation::LoadNumber(1.).encode(&mut writer);
+    writer.write_jump(Operation::JumpIfFalseOrPop, end);
+    Operation::LoadNil.encode(&mut writer);
+    writer.write_jump(Operation::JumpIfTrueOrPop, start);
+    writer.place_label(end);
+
+    let mut expected = OpWriter::new();
+    [
+        Operation::LoadNumber(1.),
+        Operation::JumpIfFalseOrPop(20),
+        Operation::LoadNil,
+        Operation::JumpIfTrueOrPop(0),
+    ]
+    .as_slice()
+    .encode(&mut expected);
+    assert_eq!(writer.finish(), expected.flush());
+}
+
+#[test]
+#[should_panic(expected = "never placed")]
+fn unplaced_label() {
+    let mut writer = OpWriter::new();
+    let label = writer.new_label();
+    writer.write_jump(Operation::JumpIfFalseOrPop, label);
+    writer.finish();
+}
+
+/// Operations whose executor doesn't take a context.
+#[derive(Debug, OpCodec)]
+enum Counting {
+    Add(u32),
+    Reset,
+}
+
+#[derive(Default)]
+struct Counter(u32);
+
+impl CountingExecutor for Counter {
+    fn add(&mut self, arg0: u32) -> ExecutorResult<RuntimeError> {
+        self.0 += arg0;
+        Ok(())
+    }
+
+    fn reset(&mut self) -> ExecutorResult<RuntimeError> {
+        self.0 = 0;
+        Ok(())
+    }
+}
+
+#[test]
+fn executor_without_context() {
+    let mut writer = OpWriter::new();
+    [
+        Counting::Add(1),
+        Counting::Reset,
+        Counting::Add(2),
+        Counting::Add(3),
+    ]
+    .as_slice()
+    .encode(&mut writer);
+    let mut counter = Counter::default();
+    execute_counting(&mut counter, &writer.flush()).unwrap();
+    assert_eq!(counter.0, 5);
+}
+
+/// Operations with a compact operand and an operand which isn't encoded.
+#[derive(Debug, OpCodec)]
+enum Annotated {
+    Load(
+        #[opcodec(with = "varint")] u32,
+        #[opcodec(skip)] &'static str,
+    ),
+}
+
+#[derive(Default)]
+struct Loaded(Vec<u32>);
+
+impl AnnotatedExecutor for Loaded {
+    fn load(&mut self, arg0: u32) -> ExecutorResult<RuntimeError> {
+        self.0.push(arg0);
+        Ok(())
+    }
+}
+
+#[test]
+fn field_attributes() {
+    let operations = [
+        Annotated::Load(1, "one"),
+        Annotated::Load(300, "three hundred"),
+        Annotated::Load(u32::MAX, "max"),
+    ];
+    let mut writer = OpWriter::new();
+    operations.as_slice().encode(&mut writer);
+    let code = writer.flush();
+    assert_eq!(code, [0, 1, 0, 0xac, 2, 0, 0xff, 0xff, 0xff, 0xff, 0xf]);
+
+    // The skipped operand is only kept in memory.
+    let Annotated::Load(_, name) = operations[1];
+    assert_eq!(name, "three hundred");
+
+    let mut loaded = Loaded::default();
+    execute_annotated(&mut loaded, &code).unwrap();
+    assert_eq!(loaded.0, [1, 300, u32::MAX]);
+
+    for (code, error) in [
+        (
+            &[0, 0x80][..],
+            "1: no enough data, expected 2 byte(s), remaining 1 byte(s)",
+        ),
+        (
+            &[0, 0xff, 0xff, 0xff, 0xff, 0x1f],
+            "1: varint overflows `u32`",
+        ),
+    ] {
+        let err = execute_annotated(&mut loaded, code).unwrap_err();
+        assert_eq!(err.to_string(), error);
+    }
+}
+
+#[test]
+fn operand_sizes() {
+    let mut writer = OpWriter::new();
+    for operation in [
+        Operation::LoadNumber(1.),
+        Operation::LoadString(StringSymbol(0)),
+        Operation::LoadBool(true),
+        Operation::Plus,
+        Operation::JumpIfFalseOrPop(0),
+        Operation::LoadInteger(1),
+    ] {
+        operation.encode(&mut writer);
+        let code = writer.flush();
+        assert_eq!(Operation::size_of_instruction(code[0]), Some(code.len()));
+    }
+    assert_eq!(Operation::size_of_instruction(0xff), None);
+
+    assert_eq!(Counting::OPERAND_SIZES, [Some(4), Some(0)]);
+    assert_eq!(Annotated::OPERAND_SIZES, [None]);
+}
+
+#[test]
+fn decode_operations() {
+    let operations = [
+        Operation::LoadNumber(1.5),
+        Operation::LoadString(StringSymbol(3)),
+        Operation::JumpIfTrueOrPop(300),
+        Operation::Print,
+    ];
+    let mut writer = OpWriter::new();
+    operations.as_slice().encode(&mut writer);
+    let code = writer.flush();
+
+    let mut offset = 0;
+    for operation in &operations {
+        let (decoded, size) = Operation::decode(&code[offset..]).unwrap();
+        assert_eq!(format!("{decoded:?}"), format!("{operation:?}"));
+        offset += size;
+    }
+    assert_eq!(offset, code.len());
+
+    // Skipped operands get their default value.
+    let (decoded, size) = Annotated::decode(&[0, 0xac, 2]).unwrap();
+    assert_eq!(
+        (format!("{decoded:?}"), size),
+        ("Load(300, \"\")".to_string(), 3)
+    );
+
+    for (code, error) in [
+        (
+            &[][..],
+            "no enough data, expected 1 byte(s), remaining 0 byte(s)",
+        ),
+        (&[0xff], "unknown opcode `0xff`"),
+        (
+            &[0, 0],
+            "no enough data, expected 8 byte(s), remaining 1 byte(s)",
+        ),
+    ] {
+        assert_eq!(Operation::decode(code).unwrap_err().to_string(), error);
+    }
+}
+
+fn round_trip<T: Encode<OpWriter> + Decode + FixedSize + PartialEq + Debug>(value: T) {
+    let mut writer = OpWriter::new();
+    value.encode(&mut writer);
+    let buf = writer.flush();
+    assert_eq!(buf.len(), T::SIZE);
+    let (decoded, size) = T::decode(&buf).unwrap();
+    assert_eq!((decoded, size), (value, T::SIZE));
+    assert!(T::decode(&buf[..T::SIZE - 1]).is_err());
+}
+
+#[test]
+fn integer_widths() {
+    for value in [0, 1, u8::MAX] {
+        round_trip(value);
+    }
+    for value in [0, 0x1234, u16::MAX] {
+        round_trip(value);
+    }
+    for value in [i16::MIN, -1, 0, i16::MAX] {
+        round_trip(value);
+    }
+    for value in [i32::MIN, -1, 0, i32::MAX] {
+        round_trip(value);
+    }
+    round_trip(u32::MAX);
+    round_trip(i64::MIN);
+
+    let mut writer = OpWriter::new();
+    (-2i16).encode(&mut writer);
+    assert_eq!(writer.flush(), [0xfe, 0xff]);
+}
+
+#[test]
+fn string_table() {
+    let mut strings = StringIntern::default();
+    let a = strings.intern("a");
+    let empty = strings.intern("");
+    let unicode = strings.intern("ünï");
+    assert_eq!(strings.intern("a"), a);
+
+    let mut writer = OpWriter::new();
+    strings.encode(&mut writer);
+    let buf = writer.flush();
+    let (decoded, size) = StringIntern::decode(&buf).unwrap();
+    assert_eq!(size, buf.len());
+    assert_eq!(
+        decoded.iter().collect::<Vec<_>>(),
+        [(a, "a"), (empty, ""), (unicode, "ünï")]
+    );
+    assert_eq!(decoded.resolve(&unicode), "ünï");
+
+    let decode_error = |buf: &[u8]| StringIntern::decode(buf).unwrap_err().to_string();
+    assert_eq!(
+        decode_error(&buf[..buf.len() - 1]),
+        "no enough data, expected 5 byte(s), remaining 4 byte(s)"
+    );
+    assert_eq!(
+        decode_error(&[1, 0, 0, 0, 1, 0, 0, 0, 0xff]),
+        "invalid UTF-8 in string"
+    );
+    assert_eq!(
+        decode_error(&[2, 0, 0, 0, 1, 0, 0, 0, b'a', 1, 0, 0, 0, b'a']),
+        "string `a` is interned twice"
+    );
+}
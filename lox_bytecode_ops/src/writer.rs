@@ -1,10 +1,22 @@
-use std::mem;
+use alloc::vec::Vec;
+use core::mem;
 
-use crate::codec::Write;
+use crate::{
+    codec::{Encode, Write},
+    Operation,
+};
 
-#[derive(Default)]
+/// A position in the code which jumps can target before it is known.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Label(usize);
+
+#[derive(Debug, Default)]
 pub struct OpWriter {
     buf: Vec<u8>,
+    /// The byte offset of each label, `None` until it's placed.
+    labels: Vec<Option<u32>>,
+    /// Offsets of the jump targets to replace with the offset of a label.
+    patches: Vec<(usize, Label)>,
 }
 
 impl OpWriter {
@@ -12,7 +24,60 @@ impl OpWriter {
         Default::default()
     }
 
+    /// The number of bytes written since the last flush.
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    pub fn new_label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    /// Makes `label` target the next operation written.
+    pub fn place_label(&mut self, label: Label) {
+        debug_assert!(self.labels[label.0].is_none(), "{label:?} is placed twice");
+        self.labels[label.0] = Some(self.buf.len() as u32);
+    }
+
+    /// Writes the jump built by `jump`, which will target `label` once the
+    /// code is finished.
+    pub fn write_jump(&mut self, jump: fn(u32) -> Operation, label: Label) {
+        let operation = jump(0);
+        debug_assert!(
+            matches!(
+                operation,
+                Operation::JumpIfFalseOrPop(_) | Operation::JumpIfTrueOrPop(_)
+            ),
+            "`{operation:?}` is not a jump"
+        );
+        operation.encode(self);
+        // The target is the last operand of jumps.
+        self.patches
+            .push((self.buf.len() - mem::size_of::<u32>(), label));
+    }
+
+    /// Takes the bytes written so far. Use [`OpWriter::finish`] instead once
+    /// jumps are written.
     pub fn flush(&mut self) -> Vec<u8> {
+        debug_assert!(self.patches.is_empty(), "flushing unpatched jumps");
+        mem::take(&mut self.buf)
+    }
+
+    /// Takes the code, with the jumps targeting the offsets of their labels.
+    ///
+    /// Panics if a jump targets a label which isn't placed.
+    pub fn finish(&mut self) -> Vec<u8> {
+        for (offset, label) in mem::take(&mut self.patches) {
+            let target = self.labels[label.0]
+                .unwrap_or_else(|| panic!("{label:?} is jumped to but never placed"));
+            self.buf[offset..offset + mem::size_of::<u32>()].copy_from_slice(&target.to_le_bytes());
+        }
+        self.labels.clear();
         mem::take(&mut self.buf)
     }
 }
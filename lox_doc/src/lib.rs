@@ -0,0 +1,130 @@
+//! Renders the documentation of the functions and classes declared at the top
+//! level of a script, taken from their `///` comments.
+
+use lox_ast::{ClassDecl, FnDecl, Statement};
+use std::fmt::Write;
+
+#[cfg(test)]
+mod test;
+
+enum Item<'a> {
+    Function(&'a FnDecl),
+    Class(&'a ClassDecl),
+}
+
+fn items(ast: &[Statement]) -> impl Iterator<Item = Item<'_>> {
+    ast.iter().filter_map(|stmt| match stmt {
+        Statement::FnDecl(function) => Some(Item::Function(function)),
+        Statement::ClassDecl(class) => Some(Item::Class(class)),
+        _ => None,
+    })
+}
+
+fn function_signature(function: &FnDecl) -> String {
+    let params: Vec<_> = function
+        .params
+        .iter()
+        .map(|param| &*param.ident.name)
+        .collect();
+    format!("{}({})", function.var, params.join(", "))
+}
+
+fn class_signature(class: &ClassDecl) -> String {
+    match &class.super_class {
+        Some(super_class) => format!("{} < {}", class.var, super_class),
+        None => class.var.to_string(),
+    }
+}
+
+/// Renders the documentation as Markdown, doc comments being Markdown already.
+pub fn markdown(title: &str, ast: &[Statement]) -> String {
+    fn doc(out: &mut String, doc: &Option<String>) {
+        if let Some(doc) = doc {
+            let _ = writeln!(out, "\n{doc}");
+        }
+    }
+
+    let mut out = format!("# {title}\n");
+    for item in items(ast) {
+        match item {
+            Item::Function(function) => {
+                let _ = writeln!(out, "\n## `fun {}`", function_signature(function));
+                doc(&mut out, &function.doc);
+            }
+            Item::Class(class) => {
+                let _ = writeln!(out, "\n## `class {}`", class_signature(class));
+                doc(&mut out, &class.doc);
+                for method in class.methods.iter() {
+                    let _ = writeln!(out, "\n### `{}`", function_signature(method));
+                    doc(&mut out, &method.doc);
+                }
+            }
+        }
+    }
+    out
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Renders the documentation as a standalone HTML page, each block of doc
+/// comment lines separated by an empty line becoming a paragraph.
+pub fn html(title: &str, ast: &[Statement]) -> String {
+    fn doc(out: &mut String, doc: &Option<String>) {
+        let Some(doc) = doc else {
+            return;
+        };
+        for paragraph in doc.split("\n\n").filter(|p| !p.trim().is_empty()) {
+            let _ = writeln!(out, "<p>{}</p>", escape(paragraph.trim()));
+        }
+    }
+
+    let title = escape(title);
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n</head>\n<body>\n<h1>{title}</h1>\n"
+    );
+    for item in items(ast) {
+        match item {
+            Item::Function(function) => {
+                let _ = writeln!(
+                    out,
+                    "<h2 id=\"{}\"><code>fun {}</code></h2>",
+                    escape(&function.var.to_string()),
+                    escape(&function_signature(function))
+                );
+                doc(&mut out, &function.doc);
+            }
+            Item::Class(class) => {
+                let _ = writeln!(
+                    out,
+                    "<h2 id=\"{}\"><code>class {}</code></h2>",
+                    escape(&class.var.to_string()),
+                    escape(&class_signature(class))
+                );
+                doc(&mut out, &class.doc);
+                for method in class.methods.iter() {
+                    let _ = writeln!(
+                        out,
+                        "<h3 id=\"{}\"><code>{}</code></h3>",
+                        escape(&format!("{}.{}", class.var, method.var)),
+                        escape(&function_signature(method))
+                    );
+                    doc(&mut out, &method.doc);
+                }
+            }
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
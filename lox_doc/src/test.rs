@@ -0,0 +1,63 @@
+use crate::{html, markdown};
+
+const SRC: &str = r#"
+/// Adds two numbers.
+///
+/// Works for strings too.
+fun add(a, b) { return a + b; }
+
+//// Not documentation.
+fun undocumented() {}
+
+/// A point <x, y>.
+class Point < Base {
+    /// Creates a point.
+    init(x, y) { this.x = x; this.y = y; }
+    norm() { return this.x * this.x + this.y * this.y; }
+}
+
+/// Not attached to anything documented.
+var a = 1;
+"#;
+
+#[test]
+fn render_markdown() {
+    let ast = lox_parser::parse(SRC).into_result().unwrap();
+    assert_eq!(
+        markdown("geometry", &ast),
+        "# geometry
+
+## `fun add(a, b)`
+
+Adds two numbers.
+
+Works for strings too.
+
+## `fun undocumented()`
+
+## `class Point < Base`
+
+A point <x, y>.
+
+### `init(x, y)`
+
+Creates a point.
+
+### `norm()`
+"
+    );
+}
+
+#[test]
+fn render_html() {
+    let ast = lox_parser::parse(SRC).into_result().unwrap();
+    let html = html("geometry", &ast);
+    assert!(html.contains(
+        "<h2 id=\"add\"><code>fun add(a, b)</code></h2>\n<p>Adds two numbers.</p>\n<p>Works for strings too.</p>\n"
+    ));
+    assert!(html.contains(
+        "<h2 id=\"Point\"><code>class Point &lt; Base</code></h2>\n<p>A point &lt;x, y&gt;.</p>\n"
+    ));
+    assert!(html
+        .contains("<h3 id=\"Point.init\"><code>init(x, y)</code></h3>\n<p>Creates a point.</p>\n"));
+}
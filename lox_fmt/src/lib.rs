@@ -0,0 +1,169 @@
+//! Rewrites Lox source with canonical spacing and indentation.
+//!
+//! Formatting works on the tokens rather than the AST, so that comments and
+//! `for` loops, which the parser desugars, are kept as written. The text of
+//! each token is copied from the source, only the whitespace between tokens
+//! changes, and formatting the output again doesn't change it.
+
+use lox_lexer::{Keyword, Lexer, RawToken, TokenKind};
+use lox_parser::error::ParserError;
+
+#[cfg(test)]
+mod test;
+
+const INDENT: &str = "    ";
+
+/// Formats `src`, failing with the syntax errors of `src` if it's invalid.
+pub fn format(src: &str) -> Result<String, Box<[ParserError]>> {
+    lox_parser::parse(src).into_result()?;
+    let mut formatter = Formatter::new(src);
+    let mut lexer = Lexer::new(src).with_comments();
+    loop {
+        let token = lexer.next_raw_token();
+        if token.kind == TokenKind::Eof {
+            break;
+        }
+        formatter.token(&token);
+    }
+    formatter.out.push('\n');
+    Ok(formatter.out)
+}
+
+/// Whether an operator following `token_type` would be binary.
+fn ends_operand(kind: TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Identifier
+            | TokenKind::String
+            | TokenKind::Number
+            | TokenKind::Integer
+            | TokenKind::RightParen
+            | TokenKind::Keyword(
+                Keyword::False | Keyword::Nil | Keyword::Super | Keyword::This | Keyword::True
+            )
+    )
+}
+
+struct Formatter<'a> {
+    src: &'a str,
+    out: String,
+    indent: usize,
+    paren_depth: usize,
+    /// The last token which isn't a comment.
+    previous: Option<TokenKind>,
+    /// Whether the last token was a unary operator.
+    unary: bool,
+    /// The line where the last token, including comments, ends.
+    last_line: u32,
+    newline: bool,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(src: &'a str) -> Self {
+        Self {
+            src,
+            out: String::with_capacity(src.len()),
+            indent: 0,
+            paren_depth: 0,
+            previous: None,
+            unary: false,
+            last_line: 1,
+            newline: false,
+        }
+    }
+
+    fn text(&self, token: &RawToken) -> &'a str {
+        token.text(self.src)
+    }
+
+    /// Starts a new line, keeping at most one empty line of the source unless
+    /// it's at the start or end of a block.
+    fn start_line(&mut self, token: &RawToken) {
+        self.newline = false;
+        if self.out.is_empty() {
+            return;
+        }
+        self.out.push('\n');
+        let after_brace = self.previous == Some(TokenKind::LeftBrace);
+        let before_brace = token.kind == TokenKind::RightBrace;
+        if token.span.start.line > self.last_line + 1 && !after_brace && !before_brace {
+            self.out.push('\n');
+        }
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    fn comment(&mut self, token: &RawToken, line_comment: bool) {
+        let trailing = token.span.start.line == self.last_line && !self.out.is_empty();
+        if trailing {
+            self.out.push(' ');
+        } else {
+            self.start_line(token);
+        }
+        let text = self.text(token);
+        self.out
+            .push_str(if line_comment { text.trim_end() } else { text });
+        self.newline |= line_comment || !trailing;
+        self.last_line = token.span.end.line;
+    }
+
+    fn space_before(&self, kind: TokenKind) -> bool {
+        let Some(previous) = self.previous else {
+            return false;
+        };
+        if self.unary || matches!(previous, TokenKind::LeftParen | TokenKind::Dot) {
+            return false;
+        }
+        match kind {
+            TokenKind::RightParen | TokenKind::Comma | TokenKind::Semicolon | TokenKind::Dot => {
+                false
+            }
+            TokenKind::LeftParen => !ends_operand(previous),
+            TokenKind::RightBrace => previous != TokenKind::LeftBrace,
+            _ => true,
+        }
+    }
+
+    fn token(&mut self, token: &RawToken) {
+        match token.kind {
+            TokenKind::Comment => {
+                let line_comment = !self.text(token).starts_with("/*");
+                return self.comment(token, line_comment);
+            }
+            TokenKind::DocComment => return self.comment(token, true),
+            TokenKind::RightBrace => {
+                self.indent = self.indent.saturating_sub(1);
+                // Empty blocks are kept on one line.
+                self.newline &= self.previous != Some(TokenKind::LeftBrace);
+            }
+            TokenKind::Keyword(Keyword::Else) => {
+                self.newline &= self.previous != Some(TokenKind::RightBrace);
+            }
+            _ => {}
+        }
+
+        if self.newline {
+            self.start_line(token);
+        } else if self.space_before(token.kind) {
+            self.out.push(' ');
+        }
+        self.out.push_str(self.text(token));
+
+        self.unary = matches!(token.kind, TokenKind::Bang | TokenKind::Minus)
+            && !self.previous.is_some_and(ends_operand);
+        match token.kind {
+            TokenKind::LeftBrace => {
+                self.indent += 1;
+                self.newline = true;
+            }
+            TokenKind::RightBrace => self.newline = true,
+            TokenKind::LeftParen => self.paren_depth += 1,
+            TokenKind::RightParen => self.paren_depth = self.paren_depth.saturating_sub(1),
+            TokenKind::Semicolon => self.newline = self.paren_depth == 0,
+            _ => {}
+        }
+        self.previous = Some(token.kind);
+        self.last_line = token.span.end.line;
+    }
+}
@@ -0,0 +1,88 @@
+use crate::format;
+
+fn assert_formats(src: &str, expected: &str) {
+    let formatted = format(src).unwrap();
+    assert_eq!(formatted, expected);
+    assert_eq!(format(&formatted).unwrap(), expected, "not idempotent");
+}
+
+#[test]
+fn spacing_and_indentation() {
+    assert_formats(
+        "var a=-1;fun add(a,b){return a+b;}\nclass B<A{init(x){super.init(!x);this.x=x;}}\nif(a==1)print add(a,2);else{print \"no\" ;}",
+        r#"var a = -1;
+fun add(a, b) {
+    return a + b;
+}
+class B < A {
+    init(x) {
+        super.init(!x);
+        this.x = x;
+    }
+}
+if (a == 1) print add(a, 2);
+else {
+    print "no";
+}
+"#,
+    );
+}
+
+#[test]
+fn for_loops_and_empty_blocks() {
+    assert_formats(
+        "for(var i=0;i<3;i=i+1){}\nfor(;;){ }\nfun f() {\n\n}",
+        "for (var i = 0; i < 3; i = i + 1) {}\nfor (;;) {}\nfun f() {}\n",
+    );
+}
+
+#[test]
+fn if_else_blocks() {
+    assert_formats(
+        "if (a) {\nprint 1;\n}\nelse if (b) {\nprint 2;\n}",
+        "if (a) {\n    print 1;\n} else if (b) {\n    print 2;\n}\n",
+    );
+}
+
+#[test]
+fn comments_and_blank_lines() {
+    assert_formats(
+        r#"// leading
+var a = 1;   // trailing   
+
+
+
+/// Documented.
+fun f() { // after brace
+
+  /* block
+     comment */
+  return a /* inline */ + 1;
+
+}
+"#,
+        r#"// leading
+var a = 1; // trailing
+
+/// Documented.
+fun f() { // after brace
+    /* block
+     comment */
+    return a /* inline */ + 1;
+}
+"#,
+    );
+}
+
+#[test]
+fn shebang() {
+    assert_formats(
+        "#!/usr/bin/env lox\n\n\nprint  1;",
+        "#!/usr/bin/env lox\n\nprint 1;\n",
+    );
+}
+
+#[test]
+fn invalid_source() {
+    assert!(format("var = 1;").is_err());
+}
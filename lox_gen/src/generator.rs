@@ -0,0 +1,498 @@
+use std::mem;
+
+use crate::rng::Rng;
+
+#[derive(Debug, Clone)]
+pub struct GeneratorConfig {
+    /// Maximum nesting of operators and calls in an expression.
+    pub max_expr_depth: usize,
+    /// Maximum nesting of blocks, loops, functions and classes.
+    pub max_stmt_depth: usize,
+    /// Maximum number of statements at the top level and in each block.
+    pub max_statements: usize,
+    /// Maximum number of iterations of each loop.
+    pub max_iterations: usize,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        Self {
+            max_expr_depth: 3,
+            max_stmt_depth: 3,
+            max_statements: 6,
+            max_iterations: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Ty {
+    Number,
+    String,
+    Bool,
+}
+
+const TYPES: [Ty; 3] = [Ty::Number, Ty::String, Ty::Bool];
+
+const BINARY_OPS: [&str; 12] = [
+    "+", "-", "*", "/", "==", "!=", ">", ">=", "<", "<=", "and", "or",
+];
+
+#[derive(Debug, Clone)]
+struct Signature {
+    name: String,
+    params: Vec<Ty>,
+    ret: Ty,
+}
+
+#[derive(Debug, Clone)]
+enum Binding {
+    Var {
+        name: String,
+        ty: Ty,
+        mutable: bool,
+    },
+    Function(Signature),
+    Class {
+        name: String,
+        fields: Vec<(String, Ty)>,
+        methods: Vec<Signature>,
+    },
+}
+
+#[derive(Debug, Clone)]
+enum Callee {
+    Function(Signature),
+    /// A method, called on an instance created with the class's signature.
+    Method(Signature, Signature),
+    Field(Signature, String),
+}
+
+/// Generates random Lox programs from a seed.
+///
+/// Programs are well typed and only call functions declared before them, so
+/// they parse, resolve and run to completion without errors: loops are
+/// bounded, nothing recurses and strings never grow in loops.
+pub struct Generator {
+    rng: Rng,
+    config: GeneratorConfig,
+    scopes: Vec<Vec<Binding>>,
+    /// Fields of the class whose methods are being generated.
+    fields: Vec<(String, Ty)>,
+    names: usize,
+    output: String,
+    indent: usize,
+    expr_depth: usize,
+    stmt_depth: usize,
+}
+
+impl Generator {
+    pub fn new(seed: u64) -> Self {
+        Self::with_config(seed, Default::default())
+    }
+
+    pub fn with_config(seed: u64, config: GeneratorConfig) -> Self {
+        Self {
+            rng: Rng::new(seed),
+            config,
+            scopes: Vec::new(),
+            fields: Vec::new(),
+            names: 0,
+            output: String::new(),
+            indent: 0,
+            expr_depth: 0,
+            stmt_depth: 0,
+        }
+    }
+
+    /// Generates a program which runs without errors.
+    pub fn program(&mut self) -> String {
+        self.scopes = vec![Vec::new()];
+        self.names = 0;
+        for _ in 0..self.rng.up_to(self.config.max_statements) {
+            self.statement();
+        }
+        mem::take(&mut self.output)
+    }
+
+    /// Generates an expression of literals and operators, which may fail with
+    /// type errors when evaluated.
+    pub fn expression(&mut self) -> String {
+        if self.expr_depth >= self.config.max_expr_depth || self.rng.one_in(3) {
+            return self.any_literal();
+        }
+        self.expr_depth += 1;
+        let expr = if self.rng.one_in(3) {
+            let operator = if self.rng.one_in(2) { "-" } else { "!" };
+            let operand = self.expression();
+            format!("{operator}{}", group(operand))
+        } else {
+            let left = self.expression();
+            let operator = self.rng.choose(&BINARY_OPS).unwrap();
+            let right = self.expression();
+            format!("{} {operator} {}", group(left), group(right))
+        };
+        self.expr_depth -= 1;
+        expr
+    }
+
+    fn name(&mut self, prefix: &str) -> String {
+        self.names += 1;
+        format!("{prefix}{}", self.names - 1)
+    }
+
+    fn line(&mut self, line: &str) {
+        for _ in 0..self.indent {
+            self.output.push_str("    ");
+        }
+        self.output.push_str(line);
+        self.output.push('\n');
+    }
+
+    fn bind(&mut self, binding: Binding) {
+        self.scopes.last_mut().unwrap().push(binding);
+    }
+
+    fn bindings(&self) -> impl Iterator<Item = &Binding> {
+        self.scopes.iter().flatten()
+    }
+
+    fn ty(&mut self) -> Ty {
+        *self.rng.choose(&TYPES).unwrap()
+    }
+
+    fn statement(&mut self) {
+        let nested = self.stmt_depth < self.config.max_stmt_depth;
+        match self.rng.below(if nested { 9 } else { 4 }) {
+            0 => self.var_decl(),
+            1 => self.print(),
+            2 => self.assignment(),
+            3 => self.call_statement(),
+            4 => self.if_stmt(),
+            5 => self.for_loop(),
+            6 => self.while_loop(),
+            7 => self.function(),
+            _ => self.class(),
+        }
+    }
+
+    /// Generates statements in a new scope with `bindings`, followed by the
+    /// line returned by `last`.
+    fn block(&mut self, bindings: Vec<Binding>, last: impl FnOnce(&mut Self) -> Option<String>) {
+        self.indent += 1;
+        self.stmt_depth += 1;
+        self.scopes.push(bindings);
+        for _ in 0..self.rng.up_to(self.config.max_statements / 2) {
+            self.statement();
+        }
+        if let Some(last) = last(self) {
+            self.line(&last);
+        }
+        self.scopes.pop();
+        self.stmt_depth -= 1;
+        self.indent -= 1;
+    }
+
+    fn var_decl(&mut self) {
+        let ty = self.ty();
+        let init = self.expr(ty);
+        let name = self.name("v");
+        self.line(&format!("var {name} = {init};"));
+        self.bind(Binding::Var {
+            name,
+            ty,
+            mutable: ty != Ty::String,
+        });
+    }
+
+    fn print(&mut self) {
+        let ty = self.ty();
+        let expr = self.expr(ty);
+        self.line(&format!("print {expr};"));
+    }
+
+    fn assignment(&mut self) {
+        let targets = self
+            .bindings()
+            .filter_map(|binding| match binding {
+                Binding::Var {
+                    name,
+                    ty,
+                    mutable: true,
+                } => Some((name.clone(), *ty)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        match self.rng.choose(&targets).cloned() {
+            Some((name, ty)) => {
+                let value = self.expr(ty);
+                self.line(&format!("{name} = {value};"));
+            }
+            None => self.var_decl(),
+        }
+    }
+
+    fn call_statement(&mut self) {
+        let ty = self.ty();
+        match self.call(ty) {
+            Some(call) => self.line(&format!("{call};")),
+            None => self.print(),
+        }
+    }
+
+    fn if_stmt(&mut self) {
+        let condition = self.expr(Ty::Bool);
+        self.line(&format!("if ({condition}) {{"));
+        self.block(Vec::new(), |_| None);
+        if self.rng.one_in(2) {
+            self.line("} else {");
+            self.block(Vec::new(), |_| None);
+        }
+        self.line("}");
+    }
+
+    fn for_loop(&mut self) {
+        let counter = self.name("i");
+        let iterations = self.rng.up_to(self.config.max_iterations);
+        self.line(&format!(
+            "for (var {counter} = 0; {counter} < {iterations}; {counter} = {counter} + 1) {{"
+        ));
+        let counter = Binding::Var {
+            name: counter,
+            ty: Ty::Number,
+            mutable: false,
+        };
+        self.block(vec![counter], |_| None);
+        self.line("}");
+    }
+
+    fn while_loop(&mut self) {
+        let counter = self.name("w");
+        let iterations = self.rng.up_to(self.config.max_iterations);
+        self.line(&format!("var {counter} = 0;"));
+        self.line(&format!("while ({counter} < {iterations}) {{"));
+        self.bind(Binding::Var {
+            name: counter.clone(),
+            ty: Ty::Number,
+            mutable: false,
+        });
+        self.block(Vec::new(), |_| Some(format!("{counter} = {counter} + 1;")));
+        self.line("}");
+    }
+
+    /// Generates the parameters and body of a function returning `ret`.
+    fn function_body(&mut self, header: &str, params: &[Ty], ret: Ty) {
+        let params = params
+            .iter()
+            .map(|&ty| Binding::Var {
+                name: self.name("p"),
+                ty,
+                mutable: ty != Ty::String,
+            })
+            .collect::<Vec<_>>();
+        let names = params
+            .iter()
+            .map(|param| match param {
+                Binding::Var { name, .. } => name.as_str(),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.line(&format!("{header}({names}) {{"));
+        self.block(params, |this| Some(format!("return {};", this.expr(ret))));
+        self.line("}");
+    }
+
+    fn signature(&mut self, prefix: &str) -> Signature {
+        Signature {
+            name: self.name(prefix),
+            params: (0..self.rng.below(4)).map(|_| self.ty()).collect(),
+            ret: self.ty(),
+        }
+    }
+
+    fn function(&mut self) {
+        let signature = self.signature("f");
+        let header = format!("fun {}", signature.name);
+        self.function_body(&header, &signature.params, signature.ret);
+        self.bind(Binding::Function(signature));
+    }
+
+    fn class(&mut self) {
+        let name = self.name("C");
+        let fields = (0..self.rng.below(3))
+            .map(|_| (self.name("x"), self.ty()))
+            .collect::<Vec<_>>();
+        self.line(&format!("class {name} {{"));
+        self.indent += 1;
+        if !fields.is_empty() {
+            let params = fields.iter().map(|_| self.name("p")).collect::<Vec<_>>();
+            self.line(&format!("init({}) {{", params.join(", ")));
+            for ((field, _), param) in fields.iter().zip(params) {
+                self.line(&format!("    this.{field} = {param};"));
+            }
+            self.line("}");
+        }
+
+        let outer_fields = mem::replace(&mut self.fields, fields.clone());
+        let methods = (0..self.rng.up_to(2))
+            .map(|_| {
+                let method = self.signature("m");
+                self.function_body(&method.name, &method.params, method.ret);
+                method
+            })
+            .collect();
+        self.fields = outer_fields;
+
+        self.indent -= 1;
+        self.line("}");
+        self.bind(Binding::Class {
+            name,
+            fields,
+            methods,
+        });
+    }
+
+    fn expr(&mut self, ty: Ty) -> String {
+        if self.expr_depth >= self.config.max_expr_depth || self.rng.one_in(3) {
+            return self.leaf(ty);
+        }
+        self.expr_depth += 1;
+        let expr = match (ty, self.rng.below(4)) {
+            (Ty::Number, 0) => format!("-{}", self.operand(Ty::Number)),
+            (Ty::Number, 1) => {
+                let operator = self.rng.choose(&["+", "-", "*", "/"]).unwrap();
+                self.binary(Ty::Number, operator, Ty::Number)
+            }
+            (Ty::String, 0 | 1) => {
+                let right = self.ty();
+                self.binary(Ty::String, "+", right)
+            }
+            (Ty::Bool, 0) => {
+                let operand = self.ty();
+                format!("!{}", self.operand(operand))
+            }
+            (Ty::Bool, 1) => {
+                let operator = self.rng.choose(&["<", "<=", ">", ">="]).unwrap();
+                self.binary(Ty::Number, operator, Ty::Number)
+            }
+            (Ty::Bool, 2) => {
+                let operator = self.rng.choose(&["==", "!="]).unwrap();
+                let (left, right) = (self.ty(), self.ty());
+                self.binary(left, operator, right)
+            }
+            (Ty::Bool, 3) if self.rng.one_in(2) => {
+                let operator = self.rng.choose(&["and", "or"]).unwrap();
+                self.binary(Ty::Bool, operator, Ty::Bool)
+            }
+            _ => self.call(ty).unwrap_or_else(|| self.leaf(ty)),
+        };
+        self.expr_depth -= 1;
+        expr
+    }
+
+    fn binary(&mut self, left: Ty, operator: &str, right: Ty) -> String {
+        let left = self.operand(left);
+        let right = self.operand(right);
+        format!("{left} {operator} {right}")
+    }
+
+    fn operand(&mut self, ty: Ty) -> String {
+        let operand = self.expr(ty);
+        group(operand)
+    }
+
+    fn leaf(&mut self, ty: Ty) -> String {
+        let mut vars = self
+            .bindings()
+            .filter_map(|binding| match binding {
+                Binding::Var { name, ty: t, .. } if *t == ty => Some(name.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        vars.extend(
+            self.fields
+                .iter()
+                .filter(|(_, t)| *t == ty)
+                .map(|(field, _)| format!("this.{field}")),
+        );
+        match self.rng.choose(&vars) {
+            Some(var) if !self.rng.one_in(3) => var.clone(),
+            _ => self.literal(ty),
+        }
+    }
+
+    fn literal(&mut self, ty: Ty) -> String {
+        match ty {
+            Ty::Number if self.rng.one_in(4) => format!("{}.5", self.rng.below(10)),
+            Ty::Number => self.rng.below(10).to_string(),
+            Ty::String => format!("\"{}\"", self.rng.choose(&["", "a", "lox"]).unwrap()),
+            Ty::Bool => self.rng.one_in(2).to_string(),
+        }
+    }
+
+    fn any_literal(&mut self) -> String {
+        match self.rng.below(4) {
+            0 => "nil".to_string(),
+            n => self.literal(TYPES[n - 1]),
+        }
+    }
+
+    /// Generates a call to a function or method, or a field of an instance,
+    /// of type `ty`.
+    fn call(&mut self, ty: Ty) -> Option<String> {
+        let mut callees = Vec::new();
+        for binding in self.bindings() {
+            match binding {
+                Binding::Function(signature) if signature.ret == ty => {
+                    callees.push(Callee::Function(signature.clone()))
+                }
+                Binding::Class {
+                    name,
+                    fields,
+                    methods,
+                } => {
+                    let init = Signature {
+                        name: name.clone(),
+                        params: fields.iter().map(|(_, ty)| *ty).collect(),
+                        ret: ty,
+                    };
+                    for method in methods.iter().filter(|method| method.ret == ty) {
+                        callees.push(Callee::Method(init.clone(), method.clone()));
+                    }
+                    for (field, _) in fields.iter().filter(|(_, t)| *t == ty) {
+                        callees.push(Callee::Field(init.clone(), field.clone()));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Some(match self.rng.choose(&callees).cloned()? {
+            Callee::Function(function) => self.arguments(&function),
+            Callee::Method(class, method) => {
+                let instance = self.arguments(&class);
+                format!("{instance}.{}", self.arguments(&method))
+            }
+            Callee::Field(class, field) => format!("{}.{field}", self.arguments(&class)),
+        })
+    }
+
+    fn arguments(&mut self, callee: &Signature) -> String {
+        let arguments = callee
+            .params
+            .iter()
+            .map(|&ty| self.expr(ty))
+            .collect::<Vec<_>>();
+        format!("{}({})", callee.name, arguments.join(", "))
+    }
+}
+
+/// Parenthesizes `expr` unless it is a single token or call.
+fn group(expr: String) -> String {
+    if expr.contains(' ') || expr.starts_with(['-', '!']) {
+        format!("({expr})")
+    } else {
+        expr
+    }
+}
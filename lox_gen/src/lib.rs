@@ -0,0 +1,8 @@
+//! Generates random well-formed Lox programs for testing.
+
+mod generator;
+mod rng;
+#[cfg(test)]
+mod test;
+
+pub use generator::{Generator, GeneratorConfig};
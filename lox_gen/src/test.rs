@@ -0,0 +1,36 @@
+use lox_interpreter::Interpreter;
+use lox_resolver::Resolver;
+
+use crate::Generator;
+
+#[test]
+fn deterministic() {
+    assert_eq!(Generator::new(42).program(), Generator::new(42).program());
+    assert_ne!(Generator::new(1).program(), Generator::new(2).program());
+}
+
+#[test]
+fn programs_run() {
+    for seed in 0..200 {
+        let program = Generator::new(seed).program();
+        let mut ast = lox_parser::parse(&program)
+            .into_result()
+            .unwrap_or_else(|errors| panic!("seed {seed}: {errors:?}\n{program}"));
+        if let Some(errors) = Resolver::default().resolve(&mut ast) {
+            panic!("seed {seed}: {errors:?}\n{program}");
+        }
+        let mut interpreter = Interpreter::with_output(std::io::sink());
+        if let Err(err) = interpreter.interpret(&ast) {
+            panic!("seed {seed}: {err}\n{program}");
+        }
+    }
+}
+
+#[test]
+fn expressions_parse() {
+    let mut generator = Generator::new(0);
+    for _ in 0..200 {
+        let expr = generator.expression();
+        assert!(lox_parser::parse(&format!("{expr};")).is_ok(), "{expr}");
+    }
+}
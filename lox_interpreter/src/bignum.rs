@@ -0,0 +1,176 @@
+//! Arbitrary-precision integers for scripts, as an optional native module
+//! built on [`Handle`]s. Embedders enable it with [`define_bignum`].
+
+use std::{cmp::Ordering, fmt::Display, rc::Rc};
+
+use crate::{
+    error::IResult,
+    natives::{invalid_argument, natives},
+    value::{Handle, NativeFunction, Value},
+    Interpreter,
+};
+
+/// The type name of the handles of [`BigInt`]s.
+const TYPE_NAME: &str = "bignum";
+
+/// Digits of a limb, which is printed as 9 decimal digits.
+const BASE: u64 = 1_000_000_000;
+
+/// An integer of any size, with its magnitude in base 10^9 limbs, least
+/// significant first and without leading zero limbs.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BigInt {
+    negative: bool,
+    limbs: Vec<u32>,
+}
+
+impl BigInt {
+    fn new(negative: bool, mut limbs: Vec<u32>) -> Self {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        Self {
+            negative: negative && !limbs.is_empty(),
+            limbs,
+        }
+    }
+
+    /// Parses an optional `-` followed by decimal digits.
+    pub fn parse(s: &str) -> Option<Self> {
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, s),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let limbs = digits
+            .as_bytes()
+            .rchunks(9)
+            .map(|chunk| std::str::from_utf8(chunk).unwrap().parse().unwrap())
+            .collect();
+        Some(Self::new(negative, limbs))
+    }
+
+    fn compare_magnitude(&self, other: &Self) -> Ordering {
+        self.limbs
+            .len()
+            .cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        if self.negative == other.negative {
+            return Self::new(self.negative, add_magnitudes(&self.limbs, &other.limbs));
+        }
+        match self.compare_magnitude(other) {
+            Ordering::Less => Self::new(other.negative, sub_magnitudes(&other.limbs, &self.limbs)),
+            _ => Self::new(self.negative, sub_magnitudes(&self.limbs, &other.limbs)),
+        }
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        let mut limbs = vec![0u64; self.limbs.len() + other.limbs.len()];
+        for (i, &a) in self.limbs.iter().enumerate() {
+            let mut carry = 0;
+            for (j, &b) in other.limbs.iter().enumerate() {
+                let n = limbs[i + j] + a as u64 * b as u64 + carry;
+                limbs[i + j] = n % BASE;
+                carry = n / BASE;
+            }
+            limbs[i + other.limbs.len()] += carry;
+        }
+        Self::new(
+            self.negative != other.negative,
+            limbs.into_iter().map(|limb| limb as u32).collect(),
+        )
+    }
+}
+
+fn add_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut limbs = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0;
+    for i in 0..a.len().max(b.len()) {
+        let n = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        limbs.push((n % BASE) as u32);
+        carry = n / BASE;
+    }
+    limbs.push(carry as u32);
+    limbs
+}
+
+/// Subtracts `b` from `a`, whose magnitude must not be smaller.
+fn sub_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut limbs = Vec::with_capacity(a.len());
+    let mut borrow = 0;
+    for (i, &limb) in a.iter().enumerate() {
+        let n = limb as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = (n < 0) as i64;
+        limbs.push((n + borrow * BASE as i64) as u32);
+    }
+    limbs
+}
+
+impl Display for BigInt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Some((most, rest)) = self.limbs.split_last() else {
+            return f.write_str("0");
+        };
+        if self.negative {
+            f.write_str("-")?;
+        }
+        write!(f, "{most}")?;
+        rest.iter()
+            .rev()
+            .try_for_each(|limb| write!(f, "{limb:09}"))
+    }
+}
+
+fn to_bigint(value: &Value) -> IResult<BigInt> {
+    let parsed = match value {
+        Value::Integer(n) => BigInt::parse(&n.to_string()),
+        Value::Number(n) if n.is_finite() && n.fract() == 0.0 => BigInt::parse(&format!("{n:.0}")),
+        Value::String(s) => BigInt::parse(s),
+        _ => None,
+    };
+    parsed.ok_or_else(|| invalid_argument("big", format!("`{value}` isn't an integer")))
+}
+
+fn new_handle(n: BigInt) -> Value {
+    Value::Handle(Handle::new(TYPE_NAME, n))
+}
+
+fn operands(arguments: &[Value]) -> IResult<(&BigInt, &BigInt)> {
+    Ok((
+        arguments[0].downcast_handle(TYPE_NAME)?,
+        arguments[1].downcast_handle(TYPE_NAME)?,
+    ))
+}
+
+/// Defines the natives of the module:
+///
+/// - `big(n)` converts an integral number or a string of digits,
+/// - `badd(a, b)` and `bmul(a, b)` add and multiply two bignums,
+/// - `bto_string(a)` formats a bignum in decimal.
+pub fn define_bignum(interpreter: &mut Interpreter) {
+    for native in bignum_natives() {
+        interpreter.set_global(native.name, Value::NativeFunction(Rc::new(native)));
+    }
+}
+
+natives! {
+    fn bignum_natives;
+    big(1) => |_, arguments| Ok(new_handle(to_bigint(&arguments[0])?)),
+    badd(2) => |_, arguments| {
+        let (a, b) = operands(&arguments)?;
+        Ok(new_handle(a.add(b)))
+    },
+    bmul(2) => |_, arguments| {
+        let (a, b) = operands(&arguments)?;
+        Ok(new_handle(a.mul(b)))
+    },
+    bto_string(1) => |_, arguments| {
+        let n: &BigInt = arguments[0].downcast_handle(TYPE_NAME)?;
+        Ok(n.to_string().into())
+    },
+}
@@ -0,0 +1,26 @@
+//! An output which can be read back after giving it to an interpreter.
+
+use std::{cell::RefCell, io, rc::Rc};
+
+/// Keeps what is written to it, shared by its clones, so that what a script
+/// printed can be read from the clone kept when giving one to
+/// [`Interpreter::with_output`](crate::Interpreter::with_output).
+#[derive(Debug, Clone, Default)]
+pub struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+impl Buffer {
+    /// Takes what was written so far, replacing invalid UTF-8 with U+FFFD.
+    pub fn take(&self) -> String {
+        String::from_utf8_lossy(&self.0.take()).into_owned()
+    }
+}
+
+impl io::Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
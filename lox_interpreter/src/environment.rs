@@ -1,15 +1,66 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{cell::RefCell, rc::Rc, sync::Arc};
 
-use lox_ast::{IdentIndex, IdentTarget};
+use indexmap::IndexMap;
+use lox_ast::{IdentIndex, IdentTarget, Variable};
 
 use crate::{
     error::{IResult, RuntimeError},
     value::Value,
 };
 
+/// Names of the slots of an environment, only known if the resolver records
+/// them, see `ResolverOptions::local_names`.
+#[derive(Default, Debug)]
+pub(crate) enum Names {
+    #[default]
+    Unknown,
+    /// The single slot of `this` or `super`.
+    Implicit(&'static str),
+    Locals(Arc<[String]>),
+}
+
+impl Names {
+    pub(crate) fn of(local_names: &Option<Arc<[String]>>) -> Self {
+        match local_names {
+            Some(names) => Names::Locals(names.clone()),
+            None => Names::Unknown,
+        }
+    }
+
+    fn get(&self, index: usize) -> Option<&str> {
+        match self {
+            Names::Unknown => None,
+            Names::Implicit(name) => (index == 0).then_some(*name),
+            Names::Locals(names) => names.get(index).map(String::as_str),
+        }
+    }
+}
+
+/// Why the slot of a variable can't be accessed.
+pub(crate) enum SlotError {
+    /// There's no such slot, so the AST wasn't resolved or was changed after
+    /// resolution.
+    Missing,
+    /// The slot belongs to the variable with this name, so the resolver
+    /// assigned the wrong target. Only detected if names are recorded.
+    Mismatch(String),
+}
+
+/// The local variables of an environment, see
+/// [`Interpreter::scopes`](crate::Interpreter::scopes).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scope {
+    /// Number of environments between this one and the innermost one, as in
+    /// the targets assigned by the resolver.
+    pub depth: usize,
+    /// The variables in slot order, with their names if they are recorded.
+    pub variables: Vec<(Option<String>, Value)>,
+}
+
 #[derive(Default, Debug)]
 pub struct Environment {
     values: Vec<Value>,
+    names: Names,
     pub(crate) enclosing: Option<Env>,
 }
 
@@ -19,58 +70,243 @@ impl Environment {
     pub(crate) fn new(len: IdentIndex, enclosing: Option<Env>) -> Self {
         Self {
             values: vec![Value::Nil; len as usize],
+            names: Names::Unknown,
             enclosing,
         }
     }
 
-    pub(crate) fn assign(&mut self, mut target: IdentTarget, value: Value) {
+    pub(crate) fn with_names(mut self, names: Names) -> Self {
+        self.names = names;
+        self
+    }
+
+    /// Creates the environment holding `this` or `super`.
+    pub(crate) fn implicit(name: &'static str, value: Value, enclosing: Option<Env>) -> Self {
+        Self {
+            values: vec![value],
+            names: Names::Implicit(name),
+            enclosing,
+        }
+    }
+
+    /// Lists this environment and the ones enclosing it, innermost first.
+    pub(crate) fn scopes(&self) -> Vec<Scope> {
+        let mut scopes = vec![self.scope(0)];
+        let mut enclosing = self.enclosing.clone();
+        while let Some(env) = enclosing {
+            let env = env.borrow();
+            scopes.push(env.scope(scopes.len()));
+            enclosing = env.enclosing.clone();
+        }
+        scopes
+    }
+
+    fn scope(&self, depth: usize) -> Scope {
+        Scope {
+            depth,
+            variables: self
+                .values
+                .iter()
+                .enumerate()
+                .map(|(index, value)| (self.names.get(index).map(str::to_string), value.clone()))
+                .collect(),
+        }
+    }
+
+    pub(crate) fn assign(
+        &mut self,
+        mut target: IdentTarget,
+        name: &str,
+        value: Value,
+    ) -> Result<(), SlotError> {
         if target.scope_count == 0 {
+            self.check_slot(target.index, name)?;
             self.values[target.index as usize] = value;
+            Ok(())
         } else {
             target.scope_count -= 1;
             self.enclosing
                 .as_deref()
-                .unwrap()
+                .ok_or(SlotError::Missing)?
                 .borrow_mut()
-                .assign(target, value);
+                .assign(target, name, value)
         }
     }
 
-    pub(crate) fn get(&self, mut target: IdentTarget) -> Value {
+    pub(crate) fn get(&self, mut target: IdentTarget, name: &str) -> Result<Value, SlotError> {
         if target.scope_count == 0 {
-            self.values[target.index as usize].clone()
+            self.check_slot(target.index, name)?;
+            Ok(self.values[target.index as usize].clone())
         } else {
             target.scope_count -= 1;
-            self.enclosing.as_deref().unwrap().borrow().get(target)
+            self.enclosing
+                .as_deref()
+                .ok_or(SlotError::Missing)?
+                .borrow()
+                .get(target, name)
+        }
+    }
+
+    fn check_slot(&self, index: IdentIndex, name: &str) -> Result<(), SlotError> {
+        let index = index as usize;
+        if index >= self.values.len() {
+            return Err(SlotError::Missing);
+        }
+        match self.names.get(index) {
+            Some(other) if other != name => Err(SlotError::Mismatch(other.to_string())),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Environments of calls and blocks which returned, kept to be reused by the
+/// next ones instead of allocating new environments.
+#[derive(Default)]
+pub(crate) struct EnvironmentPool {
+    /// Free environments by number of slots, whose slots are all `nil`.
+    free: Vec<Vec<Env>>,
+}
+
+impl EnvironmentPool {
+    /// Environments with more slots aren't pooled.
+    const MAX_SLOTS: usize = 16;
+    /// Free environments kept for each number of slots, enough for the
+    /// recursion of most scripts.
+    const MAX_FREE: usize = 64;
+
+    pub(crate) fn take(&mut self, len: IdentIndex, names: Names, enclosing: Option<Env>) -> Env {
+        match self.free.get_mut(len as usize).and_then(Vec::pop) {
+            Some(env) => {
+                let mut environment = env.borrow_mut();
+                environment.names = names;
+                environment.enclosing = enclosing;
+                drop(environment);
+                env
+            }
+            None => Rc::new(Environment::new(len, enclosing).with_names(names).into()),
+        }
+    }
+
+    /// Keeps `env` for reuse, unless something still references it, e.g. a
+    /// closure which was declared in it.
+    pub(crate) fn recycle(&mut self, mut env: Env) {
+        let Some(environment) = Rc::get_mut(&mut env).map(RefCell::get_mut) else {
+            return;
+        };
+        let len = environment.values.len();
+        if len > Self::MAX_SLOTS {
+            return;
+        }
+        environment.values.fill(Value::Nil);
+        environment.names = Names::Unknown;
+        environment.enclosing = None;
+        if self.free.len() <= len {
+            self.free.resize_with(len + 1, Vec::new);
+        }
+        if self.free[len].len() < Self::MAX_FREE {
+            self.free[len].push(env);
         }
     }
 }
 
 #[derive(Default)]
 pub(crate) struct GlobalEnvironment {
-    values: HashMap<String, Value>,
+    /// Names and values of the globals, in order of definition. Their
+    /// indices are the slots cached by variables.
+    slots: IndexMap<String, Value>,
 }
 
 impl GlobalEnvironment {
     pub(crate) fn define(&mut self, name: &str, value: Value) {
-        self.values.insert(name.to_owned(), value);
+        self.define_slot(name, value);
     }
 
-    pub(crate) fn assign(&mut self, name: &str, value: Value) -> IResult<()> {
-        match self.values.get_mut(name) {
-            Some(v) => {
-                *v = value;
+    fn define_slot(&mut self, name: &str, value: Value) -> usize {
+        match self.slots.get_full_mut(name) {
+            Some((slot, _, old)) => {
+                *old = value;
+                slot
+            }
+            None => self.slots.insert_full(name.to_owned(), value).0,
+        }
+    }
+
+    /// Keeps the order of the other globals, which shifts their slots; the
+    /// inline caches are checked against the name before they're used.
+    pub(crate) fn remove(&mut self, name: &str) -> Option<Value> {
+        self.slots.shift_remove(name)
+    }
+
+    /// Finds the slot of `var`, using and updating its inline cache.
+    fn slot(&self, var: &Variable) -> Option<usize> {
+        let name = &*var.ident.name;
+        if let Some(slot) = var.global_slot.get() {
+            if self
+                .slots
+                .get_index(slot)
+                .is_some_and(|(slot_name, _)| slot_name == name)
+            {
+                return Some(slot);
+            }
+        }
+        let slot = self.slots.get_index_of(name)?;
+        var.global_slot.set(slot);
+        Some(slot)
+    }
+
+    pub(crate) fn define_var(&mut self, var: &Variable, value: Value) {
+        let slot = self.define_slot(&var.ident.name, value);
+        var.global_slot.set(slot);
+    }
+
+    pub(crate) fn get_var(&self, var: &Variable) -> IResult<Value> {
+        match self.slot(var) {
+            Some(slot) => Ok(self.slots[slot].clone()),
+            None => Err(RuntimeError::UndefinedVariable {
+                name: var.ident.name.to_string(),
+            }
+            .to_box()),
+        }
+    }
+
+    pub(crate) fn assign_var(&mut self, var: &Variable, value: Value) -> IResult<()> {
+        match self.slot(var) {
+            Some(slot) => {
+                self.slots[slot] = value;
                 Ok(())
             }
             None => Err(RuntimeError::UndefinedVariable {
-                name: name.to_owned(),
+                name: var.ident.name.to_string(),
             }
             .to_box()),
         }
     }
 
+    pub(crate) fn lookup(&self, name: &str) -> Option<&Value> {
+        self.slots.get(name)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.slots
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+    }
+
+    /// Copies the globals, keeping their slots so that inline caches stay
+    /// valid.
+    pub(crate) fn deep_copy(&self) -> Self {
+        let mut copied = Default::default();
+        Self {
+            slots: self
+                .slots
+                .iter()
+                .map(|(name, value)| (name.clone(), value.deep_copy(&mut copied)))
+                .collect(),
+        }
+    }
+
     pub(crate) fn get(&self, name: &str) -> IResult<Value> {
-        match self.values.get(name) {
+        match self.lookup(name) {
             Some(v) => Ok(v.to_owned()),
             None => Err(RuntimeError::UndefinedVariable {
                 name: name.to_owned(),
@@ -1,18 +1,30 @@
+use lox_ast::Variable;
 use lox_lexer::Span;
+use std::io;
 use thiserror::Error;
 
-use crate::value::Value;
+use crate::{environment::SlotError, permissions::Capability, value::Value};
 
 #[derive(Debug, Error)]
 pub enum RuntimeError {
-    #[error("TypeError: expected `{expected}`, found `{found}")]
+    #[error("TypeError: expected `{expected}`, found `{found}`")]
     TypeError {
         span: Span,
         expected: &'static str,
         found: &'static str,
     },
+    #[error("{0}: division by zero")]
+    DivisionByZero(Span),
     #[error("Undefined variable `{name}`")]
     UndefinedVariable { name: String },
+    #[error("{span}: variable `{name}` isn't resolved, resolve the program before running it")]
+    Unresolved { name: String, span: Span },
+    #[error("{span}: `{name}` is resolved to the slot of `{other}`")]
+    SlotMismatch {
+        name: String,
+        other: String,
+        span: Span,
+    },
     #[error("Undefined variable `{field}`")]
     UndefinedField { field: String },
     #[error("Cannot read field of type {target_type}, reading {field}")]
@@ -22,11 +34,21 @@ pub enum RuntimeError {
     },
     #[error("{target} is not callable, {span}")]
     NotCallable { target: String, span: Span },
-    #[error("Expected {expected} arguments. but got {got}, {span}")]
+    #[error(
+        "Expected {}{expected} arguments{}, but got {got}, {span}",
+        if *variadic { "at least " } else { "" },
+        parameter_list(parameters)
+    )]
     ArgumentsNotMatch {
         expected: u8,
+        /// Whether more than `expected` arguments are accepted.
+        variadic: bool,
         got: usize,
         span: Span,
+        /// Names of the parameters, empty for natives.
+        parameters: Box<[String]>,
+        /// Where the function is declared, `None` for natives.
+        declared_at: Option<Span>,
     },
     #[error("`Return` must be in a function, {0}")]
     Return(Span, Value),
@@ -34,15 +56,83 @@ pub enum RuntimeError {
     ReturnInConstructor(Span),
     #[error("Invalid super class, {0}")]
     InvalidSuperClass(Span),
+    #[error("`this` is not bound to an instance, {0}")]
+    InvalidThis(Span),
+    #[error("TypeError: expected `{expected}` handle, found `{found}`")]
+    InvalidHandle {
+        expected: &'static str,
+        found: &'static str,
+        /// The call of the native which got the wrong value.
+        span: Span,
+    },
+    #[error("Invalid argument to `{function}`: {reason}")]
+    InvalidArgument {
+        function: &'static str,
+        reason: String,
+    },
+    #[error("Cannot write output: {0}")]
+    Io(#[from] io::Error),
+    /// Raised by `exit` to stop the script, which hosts turn into the exit
+    /// code of the process.
+    #[error("Exited with code {0}")]
+    Exit(i32),
+    #[error("Permission denied: the host doesn't allow `{capability}`")]
+    PermissionDenied { capability: Capability },
 }
 
 pub type IResult<T> = Result<T, Box<RuntimeError>>;
 
 impl RuntimeError {
+    /// The span of the code which failed, if it's known.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            RuntimeError::TypeError { span, .. }
+            | RuntimeError::Unresolved { span, .. }
+            | RuntimeError::SlotMismatch { span, .. }
+            | RuntimeError::NotCallable { span, .. }
+            | RuntimeError::ArgumentsNotMatch { span, .. }
+            | RuntimeError::Return(span, _)
+            | RuntimeError::ReturnInConstructor(span)
+            | RuntimeError::InvalidSuperClass(span)
+            | RuntimeError::InvalidThis(span)
+            | RuntimeError::InvalidHandle { span, .. }
+            | RuntimeError::DivisionByZero(span) => Some(*span),
+            RuntimeError::UndefinedVariable { .. }
+            | RuntimeError::UndefinedField { .. }
+            | RuntimeError::InvalidFieldTarget { .. }
+            | RuntimeError::InvalidArgument { .. }
+            | RuntimeError::Io(_)
+            | RuntimeError::Exit(_)
+            | RuntimeError::PermissionDenied { .. } => None,
+        }
+    }
+
+    /// A secondary message pointing at related code, e.g. the declaration of
+    /// a function called with the wrong number of arguments.
+    pub fn note(&self) -> Option<(&'static str, Span)> {
+        match self {
+            RuntimeError::ArgumentsNotMatch {
+                declared_at: Some(span),
+                ..
+            } => Some(("function is declared here", *span)),
+            _ => None,
+        }
+    }
+
     pub fn to_box(self) -> Box<Self> {
         Box::new(self)
     }
 
+    pub(crate) fn slot(var: &Variable, error: SlotError) -> Box<RuntimeError> {
+        let name = var.ident.name.to_string();
+        let span = var.ident.span;
+        match error {
+            SlotError::Missing => RuntimeError::Unresolved { name, span },
+            SlotError::Mismatch(other) => RuntimeError::SlotMismatch { name, other, span },
+        }
+        .to_box()
+    }
+
     pub fn type_error(span: Span, expected: &'static str, found: &Value) -> Box<RuntimeError> {
         RuntimeError::TypeError {
             span,
@@ -52,3 +142,11 @@ impl RuntimeError {
         .to_box()
     }
 }
+
+fn parameter_list(parameters: &[String]) -> String {
+    if parameters.is_empty() {
+        String::new()
+    } else {
+        format!(" `({})`", parameters.join(", "))
+    }
+}
@@ -0,0 +1,122 @@
+//! Native functions returning futures, enabled by the `async` feature.
+//!
+//! Lox code stays synchronous: the interpreter waits for the future at the call
+//! site by parking its thread. Run the interpreter through an
+//! [`InterpreterHandle`](crate::InterpreterHandle) and await
+//! [`with_async`](crate::InterpreterHandle::with_async) so that
+//! only the interpreter thread waits and the async runtime keeps running.
+
+use std::{
+    future::Future,
+    pin::{pin, Pin},
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, Thread},
+};
+
+use crate::{
+    error::IResult,
+    interpreter::Interpreter,
+    value::{Arguments, Callable, Value},
+};
+
+pub type NativeFuture = Pin<Box<dyn Future<Output = IResult<Value>>>>;
+
+#[derive(Debug)]
+pub struct AsyncNativeFunction {
+    pub name: &'static str,
+    pub arity: u8,
+    pub fun: fn(Vec<Value>) -> NativeFuture,
+}
+
+impl PartialEq for AsyncNativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Callable for AsyncNativeFunction {
+    fn arity(&self) -> u8 {
+        self.arity
+    }
+
+    fn call(&self, _interpreter: &mut Interpreter, arguments: Arguments) -> IResult<Value> {
+        block_on((self.fun)(arguments.into_vec()))
+    }
+}
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+struct ReplyState<R> {
+    value: Option<R>,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+/// The result of a job sent to the interpreter thread.
+///
+/// # Panics
+///
+/// Polling panics if the job panicked.
+pub struct Reply<R>(Arc<Mutex<ReplyState<R>>>);
+
+pub(crate) struct ReplySender<R>(Arc<Mutex<ReplyState<R>>>);
+
+pub(crate) fn reply<R>() -> (ReplySender<R>, Reply<R>) {
+    let state = Arc::new(Mutex::new(ReplyState {
+        value: None,
+        waker: None,
+        closed: false,
+    }));
+    (ReplySender(state.clone()), Reply(state))
+}
+
+impl<R> ReplySender<R> {
+    pub(crate) fn send(self, value: R) {
+        self.0.lock().unwrap().value = Some(value);
+    }
+}
+
+impl<R> Drop for ReplySender<R> {
+    fn drop(&mut self) {
+        let mut state = self.0.lock().unwrap();
+        state.closed = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<R> Future for Reply<R> {
+    type Output = R;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.0.lock().unwrap();
+        match state.value.take() {
+            Some(value) => Poll::Ready(value),
+            None if state.closed => panic!("interpreter thread panicked"),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use std::{
+    sync::mpsc::{self, Sender},
+    thread::{self, JoinHandle},
+};
+
+use lox_parser::parser::Ast;
+
+#[cfg(feature = "async")]
+use crate::future::{self, Reply};
+use crate::interpreter::Interpreter;
+
+type Job = Box<dyn FnOnce(&mut Interpreter) + Send>;
+
+/// Owns an [`Interpreter`] on a dedicated thread.
+///
+/// Values are reference counted with `Rc`, so an interpreter can't leave the
+/// thread it was created on. The handle is `Send + Sync` and forwards work to
+/// that thread, which lets a server share one interpreter between tasks.
+pub struct InterpreterHandle {
+    sender: Option<Sender<Job>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl InterpreterHandle {
+    /// Spawns the interpreter thread, creating the interpreter with `init` on it.
+    pub fn spawn(init: impl FnOnce() -> Interpreter + Send + 'static) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let thread = thread::spawn(move || {
+            let mut interpreter = init();
+            for job in receiver {
+                job(&mut interpreter);
+            }
+        });
+
+        Self {
+            sender: Some(sender),
+            thread: Some(thread),
+        }
+    }
+
+    /// Runs `f` on the interpreter thread and waits for its result.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the interpreter thread has panicked.
+    pub fn with<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Interpreter) -> R + Send + 'static,
+    ) -> R {
+        let (sender, receiver) = mpsc::channel();
+        self.sender
+            .as_ref()
+            .unwrap()
+            .send(Box::new(move |interpreter| {
+                let _ = sender.send(f(interpreter));
+            }))
+            .expect("interpreter thread stopped");
+        receiver.recv().expect("interpreter thread panicked")
+    }
+
+    /// Runs `f` on the interpreter thread without blocking the calling task.
+    #[cfg(feature = "async")]
+    pub fn with_async<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Interpreter) -> R + Send + 'static,
+    ) -> Reply<R> {
+        let (sender, reply) = future::reply();
+        self.sender
+            .as_ref()
+            .unwrap()
+            .send(Box::new(move |interpreter| sender.send(f(interpreter))))
+            .expect("interpreter thread stopped");
+        reply
+    }
+
+    /// Interprets a resolved program, returning the rendered runtime error if any.
+    pub fn interpret(&self, ast: Ast) -> Result<(), String> {
+        self.with(move |interpreter| {
+            interpreter
+                .interpret(&ast)
+                .map(|_| ())
+                .map_err(|err| err.to_string())
+        })
+    }
+}
+
+impl Drop for InterpreterHandle {
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
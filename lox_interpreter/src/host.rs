@@ -0,0 +1,124 @@
+use std::time::Duration;
+
+/// Services the interpreter needs from its host, used by natives like `clock`.
+///
+/// Replacing the host makes runs reproducible, e.g. in tests or when replaying
+/// a session while debugging.
+pub trait HostEnvironment {
+    /// Seconds since the Unix epoch.
+    fn clock(&mut self) -> f64;
+
+    /// A random number in `[0, 1)`.
+    fn random(&mut self) -> f64;
+
+    /// The value of the environment variable `name`, read from the process
+    /// unless the host overrides it.
+    fn getenv(&mut self, name: &str) -> Option<String> {
+        std::env::var(name).ok()
+    }
+}
+
+/// A small xorshift64* generator, good enough for scripts and cheap to seed.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // The state of xorshift must never be zero.
+        Self {
+            state: (seed ^ 0x9E37_79B9_7F4A_7C15).max(1),
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Uses the system clock and a randomly seeded generator.
+#[derive(Debug)]
+pub struct SystemHost {
+    rng: Rng,
+}
+
+impl Default for SystemHost {
+    fn default() -> Self {
+        Self {
+            rng: Rng::new(system_time().as_nanos() as u64),
+        }
+    }
+}
+
+impl HostEnvironment for SystemHost {
+    fn clock(&mut self) -> f64 {
+        system_time().as_secs_f64()
+    }
+
+    fn random(&mut self) -> f64 {
+        self.rng.next_f64()
+    }
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+fn system_time() -> Duration {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap()
+}
+
+/// `wasm32-unknown-unknown` has no clock, reading it would panic, so the time
+/// is always zero. Embedders can set a host reading the clock of the browser.
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+fn system_time() -> Duration {
+    Duration::ZERO
+}
+
+/// A virtual clock and a seeded generator, producing the same values on every run.
+///
+/// The clock starts at zero and advances by `tick` seconds each time it is read.
+#[derive(Debug, Clone)]
+pub struct DeterministicHost {
+    pub time: f64,
+    pub tick: f64,
+    rng: Rng,
+}
+
+impl DeterministicHost {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            time: 0.,
+            tick: 0.,
+            rng: Rng::new(seed),
+        }
+    }
+
+    pub fn with_tick(mut self, tick: f64) -> Self {
+        self.tick = tick;
+        self
+    }
+}
+
+impl HostEnvironment for DeterministicHost {
+    fn clock(&mut self) -> f64 {
+        let time = self.time;
+        self.time += self.tick;
+        time
+    }
+
+    fn random(&mut self) -> f64 {
+        self.rng.next_f64()
+    }
+
+    /// No variable is set, as it would differ between runs.
+    fn getenv(&mut self, _name: &str) -> Option<String> {
+        None
+    }
+}
@@ -1,116 +1,391 @@
 use crate::{
-    environment::{Env, Environment, GlobalEnvironment},
+    environment::{Env, EnvironmentPool, GlobalEnvironment, Names, Scope, SlotError},
     error::{IResult, RuntimeError},
-    value::{Callable, Class, Function, Instance, NativeFunction, Value},
+    host::{HostEnvironment, SystemHost},
+    natives::define_natives,
+    permissions::{Capability, Permissions},
+    trace::Tracer,
+    value::{Arguments, Callable, Class, Function, Instance, Value},
 };
 use lox_ast::{
+    number::Number,
     visit::{walk_expr, walk_stmt, Visitor},
     *,
 };
-use lox_lexer::Span;
+use lox_lexer::{DivisionByZero, LanguageConfig, Span};
 use lox_parser::parser::Ast;
 use std::{
+    cmp::Ordering,
+    io::{self, Write},
     mem,
     rc::Rc,
-    time::{SystemTime, UNIX_EPOCH},
 };
 
+pub struct Snapshot {
+    globals: GlobalEnvironment,
+}
+
 pub struct Interpreter {
     env: Option<Env>,
     global_env: GlobalEnvironment,
+    pub(crate) output: Box<dyn Write>,
+    pub(crate) host: Box<dyn HostEnvironment>,
+    tracer: Option<Tracer>,
+    division_by_zero: DivisionByZero,
+    pub(crate) pool: EnvironmentPool,
+    permissions: Permissions,
+    /// The span of the innermost statement being executed.
+    statement: Option<Span>,
 }
 
 impl Interpreter {
     pub fn new() -> Self {
-        let mut global_env = GlobalEnvironment::default();
+        Self::with_output(io::stdout())
+    }
 
-        global_env.define(
-            "clock",
-            Value::NativeFunction(Rc::new(NativeFunction {
-                name: "clock",
-                arity: 0,
-                fun: |_, _| {
-                    Ok(Value::Number(
-                        SystemTime::now()
-                            .duration_since(UNIX_EPOCH)
-                            .unwrap()
-                            .as_secs_f64(),
-                    ))
-                },
-            })),
-        );
+    /// Creates an interpreter which writes the output of `print` to `output`.
+    pub fn with_output(output: impl Write + 'static) -> Self {
+        let mut global_env = GlobalEnvironment::default();
+        define_natives(&mut global_env);
 
         Self {
             env: None,
             global_env,
+            output: Box::new(output),
+            host: Box::<SystemHost>::default(),
+            tracer: None,
+            division_by_zero: DivisionByZero::default(),
+            pool: EnvironmentPool::default(),
+            permissions: Permissions::default(),
+            statement: None,
+        }
+    }
+
+    /// Replaces the host used by natives like `clock` and `random`.
+    pub fn set_host(&mut self, host: impl HostEnvironment + 'static) {
+        self.host = Box::new(host);
+    }
+
+    /// Restricts what natives may do, see [`Permissions`].
+    pub fn set_permissions(&mut self, permissions: Permissions) {
+        self.permissions = permissions;
+    }
+
+    pub fn permissions(&self) -> Permissions {
+        self.permissions
+    }
+
+    /// Fails with [`RuntimeError::PermissionDenied`] unless `capability` is
+    /// allowed, for natives to call before acting.
+    pub fn check_permission(&self, capability: Capability) -> IResult<()> {
+        if self.permissions.allows(capability) {
+            Ok(())
+        } else {
+            Err(RuntimeError::PermissionDenied { capability }.to_box())
+        }
+    }
+
+    /// Logs what the interpreter does with `tracer`, see [`Tracer`].
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
+    }
+
+    /// Applies the runtime settings of `config`, e.g. what dividing by zero
+    /// evaluates to.
+    pub fn set_language(&mut self, config: &LanguageConfig) {
+        self.division_by_zero = config.division_by_zero;
+    }
+
+    /// Lists the environments of the code which is running, innermost first,
+    /// e.g. for a native inspecting the state of a script. Empty at the top
+    /// level, where only globals are defined.
+    ///
+    /// Locals are only named if the resolver recorded their names, see
+    /// `ResolverOptions::local_names`.
+    pub fn scopes(&self) -> Vec<Scope> {
+        self.env
+            .as_deref()
+            .map_or_else(Vec::new, |env| env.borrow().scopes())
+    }
+
+    /// Defines a global variable, overwriting any previous value.
+    ///
+    /// The resolver should be told about it with `Resolver::define_global`
+    /// if scripts assign to it.
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.global_env.define(name, value);
+    }
+
+    /// Removes a global variable, returning its value if it was defined, e.g.
+    /// to redefine a function from scratch or to free what it holds.
+    ///
+    /// Functions refer to globals by name rather than capturing them, so the
+    /// ones using it, closures included, fail with
+    /// [`RuntimeError::UndefinedVariable`] until it's defined again, and then
+    /// use the new value. The resolver of the next scripts mustn't be told
+    /// about it anymore.
+    pub fn remove_global(&mut self, name: &str) -> Option<Value> {
+        self.global_env.remove(name)
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.global_env.lookup(name).cloned()
+    }
+
+    pub fn globals(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.global_env.iter()
+    }
+
+    /// Captures the global variables, e.g. before an exploratory evaluation.
+    ///
+    /// Instances are copied deeply, while functions, classes and handles are
+    /// captured by reference. Locals captured by closures aren't part of the
+    /// snapshot.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            globals: self.global_env.deep_copy(),
         }
     }
 
+    /// Rolls the global variables back to `snapshot`.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.global_env = snapshot.globals.deep_copy();
+    }
+
+    /// The span of the innermost statement being executed, e.g. to report
+    /// where a bug of the interpreter made it panic.
+    pub fn statement_span(&self) -> Option<Span> {
+        self.statement
+    }
+
+    /// Leaves the local scopes left by a run which didn't return, i.e. which
+    /// panicked, so that the interpreter can run other scripts with its
+    /// globals.
+    pub fn reset_scopes(&mut self) {
+        self.env = None;
+        self.statement = None;
+    }
+
+    /// Runs `ast`, which must have been resolved by `Resolver::resolve`
+    /// without errors. Variables which weren't resolved fail with
+    /// [`RuntimeError::Unresolved`].
     pub fn interpret(&mut self, ast: &Ast) -> IResult<Value> {
         for stmt in ast {
-            self.visit_stmt(stmt)?;
+            let result = self.visit_stmt(stmt);
+            #[cfg(feature = "tracing")]
+            if let Err(err) = &result {
+                tracing::warn!(error = %err, "runtime error");
+            }
+            result?;
         }
         Ok(Value::Nil)
     }
 
-    fn assign_to(&mut self, target: IdentTarget, value: Value) {
+    /// Calls a function, method or class, e.g. a callback defined by a script.
+    pub fn call(&mut self, callee: &Value, arguments: Vec<Value>) -> IResult<Value> {
+        self.call_value(callee, Arguments::from_vec(arguments), Span::dummy())
+    }
+
+    /// Calls the global function named `name`.
+    pub fn call_global(&mut self, name: &str, arguments: Vec<Value>) -> IResult<Value> {
+        let callee = self.global_env.get(name)?;
+        self.call(&callee, arguments)
+    }
+
+    fn call_value(&mut self, callee: &Value, arguments: Arguments, span: Span) -> IResult<Value> {
+        let f: &dyn Callable = match callee {
+            Value::NativeFunction(f) => f.as_ref(),
+            #[cfg(feature = "async")]
+            Value::AsyncNativeFunction(f) => f.as_ref(),
+            Value::Function(f) => f.as_ref(),
+            Value::Class(class) => class,
+            _ => {
+                return Err(RuntimeError::NotCallable {
+                    target: callee.to_string(),
+                    span,
+                }
+                .to_box())
+            }
+        };
+
+        let arity = f.arity() as usize;
+        let matches = if f.variadic() {
+            arguments.len() >= arity
+        } else {
+            arguments.len() == arity
+        };
+        if !matches {
+            let declaration = f.declaration();
+            return Err(RuntimeError::ArgumentsNotMatch {
+                expected: f.arity(),
+                variadic: f.variadic(),
+                got: arguments.len(),
+                span,
+                parameters: declaration.map_or_else(Default::default, |declaration| {
+                    declaration
+                        .params
+                        .iter()
+                        .map(|param| param.ident.name.to_string())
+                        .collect()
+                }),
+                declared_at: declaration.map(|declaration| declaration.var.ident.span),
+            }
+            .to_box());
+        }
+
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("call", callee = %callee, %span).entered();
+
+        let traced = match (&mut self.tracer, f.declaration()) {
+            (Some(tracer), Some(declaration)) => tracer.enter(&declaration.var.ident.name),
+            _ => false,
+        };
+        let result = match f.call(self, arguments) {
+            Err(err) => match *err {
+                RuntimeError::Return(_, v) => Ok(v),
+                RuntimeError::InvalidHandle {
+                    expected,
+                    found,
+                    span: handle_span,
+                } if handle_span == Span::dummy() => Err(RuntimeError::InvalidHandle {
+                    expected,
+                    found,
+                    span,
+                }
+                .to_box()),
+                v => Err(v.to_box()),
+            },
+            v => v,
+        };
+        if traced {
+            if let Some(tracer) = &mut self.tracer {
+                tracer.exit();
+            }
+        }
+
+        #[cfg(feature = "tracing")]
+        match &result {
+            Ok(value) => tracing::trace!(%value, "return"),
+            Err(err) => tracing::debug!(error = %err, "unwind"),
+        }
+
+        result
+    }
+
+    fn assign_to(&mut self, var: &Variable, target: IdentTarget, value: Value) -> IResult<()> {
         self.env
             .as_deref()
-            .unwrap()
-            .borrow_mut()
-            .assign(target, value)
+            .ok_or(SlotError::Missing)
+            .and_then(|env| env.borrow_mut().assign(target, &var.ident.name, value))
+            .map_err(|err| RuntimeError::slot(var, err))
     }
 
-    fn declare_var(&mut self, var: &Variable, value: Value) {
+    fn declare_var(&mut self, var: &Variable, value: Value) -> IResult<()> {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(name = %var, %value, global = var.target.is_none(), "define");
+        if let Some(tracer) = &mut self.tracer {
+            tracer.define(var, &value);
+        }
+
         match var.target {
-            Some(target) => self.assign_to(target, value),
-            None => self.global_env.define(&var.ident.name, value),
+            Some(target) => self.assign_to(var, target, value),
+            None => {
+                self.global_env.define_var(var, value);
+                Ok(())
+            }
         }
     }
 
     fn set_var(&mut self, var: &Variable, value: Value) -> IResult<()> {
-        match var.target {
-            Some(target) => {
-                self.assign_to(target, value);
-                Ok(())
+        if self.tracer.is_some() {
+            let old = self.get_var(var)?;
+            if let Some(tracer) = &mut self.tracer {
+                tracer.assign(var, &old, &value);
             }
-            None => self.global_env.assign(&var.ident.name, value),
+        }
+        match var.target {
+            Some(target) => self.assign_to(var, target, value),
+            None => self.global_env.assign_var(var, value),
         }
     }
 
     fn get_var(&self, var: &Variable) -> IResult<Value> {
         match var.target {
-            Some(target) => Ok(self.env.as_deref().unwrap().borrow().get(target)),
-            None => self.global_env.get(&var.ident.name),
+            Some(target) => self
+                .env
+                .as_deref()
+                .ok_or(SlotError::Missing)
+                .and_then(|env| env.borrow().get(target, &var.ident.name))
+                .map_err(|err| RuntimeError::slot(var, err)),
+            None => self.global_env.get_var(var),
         }
     }
 
-    fn get_number(&mut self, expr: &Expr) -> IResult<f64> {
-        let value = walk_expr(self, expr)?;
-        match value {
-            Value::Number(n) => Ok(n),
-            v => Err(RuntimeError::type_error(expr.get_span(), "number", &v)),
-        }
+    fn get_number(&mut self, expr: &Expr) -> IResult<Number> {
+        let value = self.visit_expr(expr)?;
+        value
+            .as_number()
+            .ok_or_else(|| RuntimeError::type_error(expr.get_span(), "number", &value))
     }
 
+    /// Runs `block` in `environment`, which is recycled afterwards unless a
+    /// closure captured it.
     pub(crate) fn execute_block(
         &mut self,
         block: &[Statement],
-        environment: Environment,
+        environment: Env,
     ) -> IResult<Value> {
-        let prev = mem::replace(&mut self.env, Some(Rc::new(environment.into())));
+        let prev = self.env.replace(environment);
 
         let result = (|| -> IResult<Value> {
             for stmt in block.iter() {
-                walk_stmt(self, stmt)?;
+                self.visit_stmt(stmt)?;
             }
             Ok(Value::Nil)
         })();
-        self.env = prev;
+        if let Some(environment) = mem::replace(&mut self.env, prev) {
+            self.pool.recycle(environment);
+        }
         result
     }
 }
 
+/// The result of a binary operator on two floats, the most common operands,
+/// without converting them to [`Number`]s. `None` if the operator isn't
+/// arithmetic nor a comparison, or divides by zero, which may be an error.
+#[inline]
+fn float_binary(operator: BinaryOp, n1: f64, n2: f64) -> Option<Value> {
+    Some(match operator {
+        BinaryOp::Plus => Value::Number(n1 + n2),
+        BinaryOp::Minus => Value::Number(n1 - n2),
+        BinaryOp::Multiply => Value::Number(n1 * n2),
+        BinaryOp::Divide if n2 != 0.0 => Value::Number(n1 / n2),
+        BinaryOp::Greater => Value::Bool(n1 > n2),
+        BinaryOp::GreaterEqual => Value::Bool(n1 >= n2),
+        BinaryOp::Less => Value::Bool(n1 < n2),
+        BinaryOp::LessEqual => Value::Bool(n1 <= n2),
+        _ => return None,
+    })
+}
+
+/// The operands of an arithmetic or comparison operator, which are both
+/// evaluated before checking that they are numbers, as the VM does.
+fn numbers(binary: &BinaryExpr, left: &Value, right: &Value) -> IResult<(Number, Number)> {
+    match (left.as_number(), right.as_number()) {
+        (Some(n1), Some(n2)) => Ok((n1, n2)),
+        (Some(_), None) => Err(RuntimeError::type_error(
+            binary.right.get_span(),
+            "number",
+            right,
+        )),
+        (None, _) => Err(RuntimeError::type_error(
+            binary.left.get_span(),
+            "number",
+            left,
+        )),
+    }
+}
+
 impl Default for Interpreter {
     fn default() -> Self {
         Self::new()
@@ -120,31 +395,55 @@ impl Default for Interpreter {
 impl Visitor for Interpreter {
     type Result = IResult<Value>;
 
+    fn visit_stmt(&mut self, stmt: &Statement) -> Self::Result {
+        if let Some(tracer) = &mut self.tracer {
+            tracer.statement(stmt);
+        }
+        let previous = self.statement.replace(stmt.get_span());
+        let result = walk_stmt(self, stmt);
+        self.statement = previous;
+        result
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) -> Self::Result {
+        let explained = self.tracer.as_mut().is_some_and(Tracer::enter_expr);
+        let result = walk_expr(self, expr);
+        if explained {
+            if let Some(tracer) = &mut self.tracer {
+                tracer.exit_expr(expr.get_span(), &result);
+            }
+        }
+        result
+    }
+
     fn visit_print(&mut self, print: &Print) -> Self::Result {
-        println!("{}", walk_expr(self, &print.expr)?);
+        let value = self.visit_expr(&print.expr)?;
+        writeln!(self.output, "{value}").map_err(|e| RuntimeError::from(e).to_box())?;
         Ok(Value::Nil)
     }
 
     fn visit_block(&mut self, block: &Block) -> Self::Result {
-        self.execute_block(
-            &block.statements,
-            Environment::new(block.num_of_locals, self.env.clone()),
-        )
+        let environment = self.pool.take(
+            block.num_of_locals,
+            Names::of(&block.local_names),
+            self.env.clone(),
+        );
+        self.execute_block(&block.statements, environment)
     }
 
     fn visit_if(&mut self, if_stmt: &If) -> Self::Result {
-        if walk_expr(self, &if_stmt.condition)?.as_bool() {
-            walk_stmt(self, &if_stmt.then_branch)?;
+        if self.visit_expr(&if_stmt.condition)?.as_bool() {
+            self.visit_stmt(&if_stmt.then_branch)?;
         } else if let Some(else_branch) = &if_stmt.else_branch {
-            walk_stmt(self, else_branch)?;
+            self.visit_stmt(else_branch)?;
         }
 
         Ok(Value::Nil)
     }
 
     fn visit_while(&mut self, while_stmt: &While) -> Self::Result {
-        while walk_expr(self, &while_stmt.condition)?.as_bool() {
-            walk_stmt(self, &while_stmt.body)?;
+        while self.visit_expr(&while_stmt.condition)?.as_bool() {
+            self.visit_stmt(&while_stmt.body)?;
         }
         Ok(Value::Nil)
     }
@@ -154,10 +453,10 @@ impl Visitor for Interpreter {
         self.declare_var(
             &function.var,
             Value::Function(Rc::new(Function {
-                declaration: function.to_owned(),
+                declaration: Rc::new(function.to_owned()),
                 closure: self.env.clone(),
             })),
-        );
+        )?;
         Ok(Value::Nil)
     }
 
@@ -177,13 +476,13 @@ impl Visitor for Interpreter {
         self.declare_var(
             &class.var,
             Value::Class(Rc::new(Class::new(class, super_class, self.env.clone()))),
-        );
+        )?;
         Ok(Value::Nil)
     }
 
     fn visit_return(&mut self, return_stmt: &Return) -> Self::Result {
         let value = match &return_stmt.expr {
-            Some(expr) => walk_expr(self, expr)?,
+            Some(expr) => self.visit_expr(expr)?,
             None => Value::Nil,
         };
 
@@ -191,47 +490,19 @@ impl Visitor for Interpreter {
     }
 
     fn visit_fn_call(&mut self, fn_call: &FnCall) -> Self::Result {
-        let callee = walk_expr(self, &fn_call.callee)?;
-        let mut arguments = Vec::with_capacity(fn_call.arguments.len());
+        let callee = self.visit_expr(&fn_call.callee)?;
+        let mut arguments = Arguments::with_capacity(fn_call.arguments.len());
         for arg in fn_call.arguments.iter() {
-            arguments.push(walk_expr(self, arg)?);
+            arguments.push(self.visit_expr(arg)?);
         }
 
-        let f: &dyn Callable = match callee {
-            Value::NativeFunction(ref f) => f.as_ref(),
-            Value::Function(ref f) => f.as_ref(),
-            Value::Class(ref class) => class,
-            _ => {
-                return Err(RuntimeError::NotCallable {
-                    target: callee.to_string(),
-                    span: fn_call.callee.get_span(),
-                }
-                .to_box())
-            }
-        };
-
-        if arguments.len() != f.arity() as usize {
-            return Err(RuntimeError::ArgumentsNotMatch {
-                expected: f.arity(),
-                got: arguments.len(),
-                span: fn_call.callee.get_span(),
-            }
-            .to_box());
-        }
-
-        match f.call(self, arguments) {
-            Err(err) => match *err {
-                RuntimeError::Return(_, v) => Ok(v),
-                v => Err(v.to_box()),
-            },
-            v => v,
-        }
+        self.call_value(&callee, arguments, fn_call.callee.get_span())
     }
 
     fn visit_get(&mut self, get: &Get) -> Self::Result {
-        let object = walk_expr(self, &get.object)?;
+        let object = self.visit_expr(&get.object)?;
         if let Value::Instance(instance) = object {
-            Instance::get(instance, &get.field.name)
+            Instance::get_cached(instance, &get.field.name, &get.method_cache)
         } else {
             Err(Box::new(RuntimeError::InvalidFieldTarget {
                 target_type: object.type_name(),
@@ -241,9 +512,9 @@ impl Visitor for Interpreter {
     }
 
     fn visit_set(&mut self, Set { target, value }: &Set) -> Self::Result {
-        let object = walk_expr(self, &target.object)?;
+        let object = self.visit_expr(&target.object)?;
         if let Value::Instance(instance) = object {
-            let value = walk_expr(self, value)?;
+            let value = self.visit_expr(value)?;
             instance
                 .borrow_mut()
                 .set(target.field.name.to_string(), value.clone());
@@ -257,102 +528,118 @@ impl Visitor for Interpreter {
     }
 
     fn visit_assign(&mut self, assign: &Assign) -> Self::Result {
-        let value = walk_expr(self, &assign.value)?;
+        let value = self.visit_expr(&assign.value)?;
         self.set_var(&assign.var, value.clone())?;
         Ok(value)
     }
 
     fn visit_literal(&mut self, literal: &Literal) -> Self::Result {
-        Ok(literal.value.clone().into())
+        Ok((&literal.value).into())
     }
 
     fn visit_binary(&mut self, binary: &BinaryExpr) -> Self::Result {
-        let BinaryExpr {
-            operator,
-            left,
-            right,
-        } = binary;
+        let operator = binary.operator;
+        if let BinaryOp::And | BinaryOp::Or = operator {
+            let left = self.visit_expr(&binary.left)?;
+            return Ok(match operator {
+                BinaryOp::And if !left.as_bool() => left,
+                BinaryOp::Or if left.as_bool() => left,
+                _ => self.visit_expr(&binary.right)?,
+            });
+        }
+
+        let left = self.visit_expr(&binary.left)?;
+        let right = self.visit_expr(&binary.right)?;
+        if let (&Value::Number(n1), &Value::Number(n2)) = (&left, &right) {
+            if let Some(value) = float_binary(operator, n1, n2) {
+                return Ok(value);
+            }
+        }
 
         macro_rules! binary_arith {
-            ($left: expr, $op: tt, $right: expr) => {
-                (self.get_number(left)? $op self.get_number(right)?).into()
-            };
+            ($op: tt) => {{
+                let (n1, n2) = numbers(binary, &left, &right)?;
+                (n1 $op n2).into()
+            }};
+        }
+
+        macro_rules! binary_cmp {
+            ($ordering: ident) => {{
+                let (n1, n2) = numbers(binary, &left, &right)?;
+                n1.compare(n2).is_some_and(Ordering::$ordering).into()
+            }};
         }
 
         Ok(match operator {
-            BinaryOp::Plus => {
-                let left = walk_expr(self, left)?;
-                let right = walk_expr(self, right)?;
-
-                match (left, right) {
-                    (Value::Number(n1), Value::Number(n2)) => (n1 + n2).into(),
-                    (Value::String(s1), v2) => (s1 + &v2.to_string()).into(),
-                    (v1, Value::String(s2)) => (v1.to_string() + &s2).into(),
-                    (v, Value::Number(_)) => {
+            BinaryOp::Plus => match (left, right) {
+                (Value::String(s1), v2) => format!("{s1}{v2}").into(),
+                (v1, Value::String(s2)) => format!("{v1}{s2}").into(),
+                (v1, v2) => match (v1.as_number(), v2.as_number()) {
+                    (Some(n1), Some(n2)) => (n1 + n2).into(),
+                    (None, Some(_)) => {
                         return Err(RuntimeError::type_error(
                             binary.left.get_span(),
                             "number",
-                            &v,
+                            &v1,
                         ))
                     }
-                    (Value::Number(_), v) => {
+                    (Some(_), None) => {
                         return Err(RuntimeError::type_error(
                             binary.right.get_span(),
                             "number or string",
-                            &v,
+                            &v2,
                         ))
                     }
-                    (v, _) => {
+                    (None, None) => {
                         return Err(RuntimeError::type_error(
                             binary.left.get_span(),
                             "number or string",
-                            &v,
+                            &v1,
                         ))
                     }
+                },
+            },
+            BinaryOp::Minus => binary_arith!(-),
+            BinaryOp::Multiply => binary_arith!(*),
+            BinaryOp::Divide => {
+                let (n1, n2) = numbers(binary, &left, &right)?;
+                if n2.is_zero() && self.division_by_zero == DivisionByZero::Error {
+                    return Err(RuntimeError::DivisionByZero(binary.right.get_span()).to_box());
                 }
+                (n1 / n2).into()
             }
-            BinaryOp::Minus => binary_arith!(left, -, right),
-            BinaryOp::Multiply => binary_arith!(left, * ,right),
-            BinaryOp::Divide => binary_arith!(left, / ,right),
-            BinaryOp::Equal => (walk_expr(self, left)? == walk_expr(self, right)?).into(),
-            BinaryOp::NotEqual => (walk_expr(self, left)? != walk_expr(self, right)?).into(),
-            BinaryOp::Greater => binary_arith!(left, > ,right),
-            BinaryOp::GreaterEqual => binary_arith!(left, >= ,right),
-            BinaryOp::Less => binary_arith!(left, < ,right),
-            BinaryOp::LessEqual => binary_arith!(left, <= ,right),
-            BinaryOp::And | BinaryOp::Or => {
-                let left = walk_expr(self, &binary.left)?;
-                match binary.operator {
-                    BinaryOp::And if !left.as_bool() => left,
-                    BinaryOp::Or if left.as_bool() => left,
-                    _ => walk_expr(self, &binary.right)?,
-                }
-            }
+            BinaryOp::Equal => (left == right).into(),
+            BinaryOp::NotEqual => (left != right).into(),
+            BinaryOp::Greater => binary_cmp!(is_gt),
+            BinaryOp::GreaterEqual => binary_cmp!(is_ge),
+            BinaryOp::Less => binary_cmp!(is_lt),
+            BinaryOp::LessEqual => binary_cmp!(is_le),
+            BinaryOp::And | BinaryOp::Or => unreachable!(),
         })
     }
 
     fn visit_unary(&mut self, unary: &UnaryExpr) -> Self::Result {
         Ok(match unary.operator {
             UnaryOp::Negative => (-self.get_number(&unary.operand)?).into(),
-            UnaryOp::Not => (!walk_expr(self, &unary.operand)?.as_bool()).into(),
+            UnaryOp::Not => (!self.visit_expr(&unary.operand)?.as_bool()).into(),
         })
     }
 
     fn visit_ternary(&mut self, ternary: &Ternary) -> Self::Result {
-        let condition = walk_expr(self, &ternary.condition)?;
+        let condition = self.visit_expr(&ternary.condition)?;
         if condition.as_bool() {
-            walk_expr(self, &ternary.truthy)
+            self.visit_expr(&ternary.truthy)
         } else {
-            walk_expr(self, &ternary.falsy)
+            self.visit_expr(&ternary.falsy)
         }
     }
 
     fn visit_var_decl(&mut self, var_decl: &VarDecl) -> Self::Result {
         let init = match &var_decl.initializer {
-            Some(expr) => walk_expr(self, expr)?,
+            Some(expr) => self.visit_expr(expr)?,
             None => Value::Nil,
         };
-        self.declare_var(&var_decl.var, init);
+        self.declare_var(&var_decl.var, init)?;
         Ok(Value::Nil)
     }
 
@@ -370,23 +657,18 @@ impl Visitor for Interpreter {
             Some(m) => m,
             None => {
                 return Err(Box::new(RuntimeError::UndefinedField {
-                    field: super_expr.method.name.clone(),
+                    field: super_expr.method.name.to_string(),
                 }))
             }
         };
 
-        let instance = match self.get_var(&Variable {
-            ident: Ident {
-                name: String::new(),
-                span: Span::dummy(),
-            },
-            target: Some(IdentTarget {
-                scope_count: super_expr.var.target.unwrap().scope_count - 1,
-                index: 0,
-            }),
-        })? {
+        let instance = match self.get_var(&super_expr.this)? {
             Value::Instance(instance) => instance,
-            _ => unreachable!(),
+            _ => {
+                return Err(Box::new(RuntimeError::InvalidThis(
+                    super_expr.this.ident.span,
+                )))
+            }
         };
 
         Ok(Value::Function(Rc::new(Instance::bind_method(
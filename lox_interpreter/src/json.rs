@@ -0,0 +1,133 @@
+//! Conversions between values and JSON for `json_encode` and `json_decode`.
+//!
+//! Only `null`, booleans, numbers and strings are supported, as Lox has no
+//! arrays or maps to hold the others.
+
+use std::{iter::Peekable, str::CharIndices};
+
+use lox_lexer::json::{decode_string, quote};
+
+use crate::value::Value;
+
+/// Encodes `value` as JSON, failing for values which have no JSON
+/// counterpart, e.g. functions or infinite numbers.
+pub(crate) fn encode(value: &Value) -> Result<String, String> {
+    match value {
+        Value::Nil => Ok("null".to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        Value::Integer(n) => Ok(n.to_string()),
+        Value::Number(n) if n.is_finite() => Ok(value.to_string()),
+        Value::Number(_) => Err(format!("{value} can't be represented in JSON")),
+        Value::String(s) => Ok(quote(s)),
+        _ => Err(format!(
+            "{} can't be represented in JSON",
+            value.type_name()
+        )),
+    }
+}
+
+/// Decodes a JSON document made of a single `null`, boolean, number or
+/// string.
+pub(crate) fn decode(text: &str) -> Result<Value, String> {
+    let mut decoder = Decoder {
+        text,
+        chars: text.char_indices().peekable(),
+    };
+    decoder.skip_whitespace();
+    let value = decoder.value()?;
+    decoder.skip_whitespace();
+    match decoder.chars.peek() {
+        None => Ok(value),
+        Some(&(offset, c)) => Err(format!("unexpected `{c}` at offset {offset}")),
+    }
+}
+
+struct Decoder<'a> {
+    text: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl Decoder<'_> {
+    fn skip_whitespace(&mut self) {
+        while self
+            .chars
+            .next_if(|&(_, c)| matches!(c, ' ' | '\t' | '\n' | '\r'))
+            .is_some()
+        {}
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        let Some(&(offset, c)) = self.chars.peek() else {
+            return Err("unexpected end of input".to_string());
+        };
+        match c {
+            'n' => self.keyword("null", Value::Nil),
+            't' => self.keyword("true", Value::Bool(true)),
+            'f' => self.keyword("false", Value::Bool(false)),
+            '"' => self.string().map(Value::from),
+            '-' | '0'..='9' => self.number(),
+            '[' | '{' => Err(format!(
+                "{} at offset {offset} aren't supported, as Lox has no containers",
+                if c == '[' { "arrays" } else { "objects" }
+            )),
+            c => Err(format!("unexpected `{c}` at offset {offset}")),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Value) -> Result<Value, String> {
+        let (offset, _) = *self.chars.peek().unwrap();
+        if !self.text[offset..].starts_with(keyword) {
+            return Err(format!("invalid literal at offset {offset}"));
+        }
+        for _ in 0..keyword.len() {
+            self.chars.next();
+        }
+        Ok(value)
+    }
+
+    /// Whether the next char is an ASCII digit, consuming it if so.
+    fn digit(&mut self) -> bool {
+        self.chars.next_if(|(_, c)| c.is_ascii_digit()).is_some()
+    }
+
+    fn digits(&mut self) -> bool {
+        let mut any = false;
+        while self.digit() {
+            any = true;
+        }
+        any
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let (start, _) = *self.chars.peek().unwrap();
+        let invalid = || format!("invalid number at offset {start}");
+        self.chars.next_if(|&(_, c)| c == '-');
+        // Leading zeros aren't allowed.
+        if self.chars.next_if(|&(_, c)| c == '0').is_none() && !self.digits() {
+            return Err(invalid());
+        }
+        if self.chars.next_if(|&(_, c)| c == '.').is_some() && !self.digits() {
+            return Err(invalid());
+        }
+        if self
+            .chars
+            .next_if(|&(_, c)| matches!(c, 'e' | 'E'))
+            .is_some()
+        {
+            self.chars.next_if(|&(_, c)| matches!(c, '+' | '-'));
+            if !self.digits() {
+                return Err(invalid());
+            }
+        }
+        let end = self.chars.peek().map_or(self.text.len(), |&(i, _)| i);
+        match self.text[start..end].parse::<f64>() {
+            Ok(n) if n.is_finite() => Ok(Value::Number(n)),
+            _ => Err(invalid()),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let (start, _) = self.chars.next().unwrap();
+        decode_string(&mut self.chars, start)
+    }
+}
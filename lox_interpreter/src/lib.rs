@@ -1,13 +1,36 @@
 use error::IResult;
-use interpreter::Interpreter;
 use lox_parser::parser::Ast;
-use value::Value;
 
+pub mod bignum;
+pub mod buffer;
 mod environment;
 pub mod error;
-mod interpreter;
-mod value;
+#[cfg(feature = "async")]
+pub mod future;
+pub mod handle;
+pub mod host;
+pub mod interpreter;
+mod json;
+pub mod module_path;
+mod natives;
+pub mod permissions;
+#[cfg(test)]
+mod test;
+pub mod trace;
+pub mod value;
 
+pub use crate::{
+    buffer::Buffer,
+    environment::Scope,
+    handle::InterpreterHandle,
+    host::HostEnvironment,
+    interpreter::Interpreter,
+    permissions::{Capability, Permissions},
+    trace::Tracer,
+    value::Value,
+};
+
+/// Runs a resolved `ast`, see [`Interpreter::interpret`].
 pub fn interpret(ast: &Ast) -> IResult<Value> {
     Interpreter::new().interpret(ast)
 }
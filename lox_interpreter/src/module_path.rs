@@ -0,0 +1,130 @@
+//! Finding the file of an imported module.
+
+use std::{
+    env,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+use crate::permissions::{Capability, Permissions};
+
+/// The environment variable listing the directories modules are searched in,
+/// separated like `PATH`.
+pub const LOX_PATH: &str = "LOX_PATH";
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("module `{name}` not found, searched: {}", searched_list(searched))]
+pub struct ModuleNotFound {
+    pub name: String,
+    /// The candidate files, in the order they were tried.
+    pub searched: Vec<PathBuf>,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ModuleError {
+    #[error(transparent)]
+    NotFound(#[from] ModuleNotFound),
+    #[error("Permission denied: the host doesn't allow `{capability}`")]
+    PermissionDenied { capability: Capability },
+}
+
+/// Fails unless `permissions` allow `capability`, like
+/// `Interpreter::check_permission` does for natives.
+fn check_permission(permissions: Permissions, capability: Capability) -> Result<(), ModuleError> {
+    if permissions.allows(capability) {
+        Ok(())
+    } else {
+        Err(ModuleError::PermissionDenied { capability })
+    }
+}
+
+fn searched_list(searched: &[PathBuf]) -> String {
+    searched
+        .iter()
+        .map(|path| format!("`{}`", path.display()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Where modules are looked for: next to the importing file first, then in
+/// each directory of the search path, in order.
+#[derive(Debug, Clone, Default)]
+pub struct ModulePath {
+    search_path: Vec<PathBuf>,
+}
+
+impl ModulePath {
+    pub fn new(search_path: Vec<PathBuf>) -> Self {
+        Self { search_path }
+    }
+
+    /// Searches the directories listed in [`LOX_PATH`], if it's set and
+    /// `permissions` allow reading the environment.
+    pub fn from_env(permissions: Permissions) -> Result<Self, ModuleError> {
+        check_permission(permissions, Capability::Env)?;
+        Ok(Self::from_path_list(
+            env::var_os(LOX_PATH).unwrap_or_default(),
+        ))
+    }
+
+    /// Searches the directories of a list separated like `PATH`.
+    pub fn from_path_list(list: impl Into<OsString>) -> Self {
+        let list = list.into();
+        Self::new(
+            env::split_paths(&list)
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .collect(),
+        )
+    }
+
+    pub fn search_path(&self) -> &[PathBuf] {
+        &self.search_path
+    }
+
+    /// Finds the file of the module `name` imported by `importer`, or by the
+    /// REPL if `None`, in which case the current directory comes first.
+    ///
+    /// `.lox` is appended to names without an extension. The path is
+    /// canonical, so that a module imported through different paths is only
+    /// loaded once. Probing the files needs `permissions` to allow reading
+    /// them.
+    pub fn resolve(
+        &self,
+        permissions: Permissions,
+        importer: Option<&Path>,
+        name: &str,
+    ) -> Result<PathBuf, ModuleError> {
+        check_permission(permissions, Capability::FsRead)?;
+        let mut file = PathBuf::from(name);
+        if file.extension().is_none() {
+            file.set_extension("lox");
+        }
+
+        let candidates: Vec<PathBuf> = if file.is_absolute() {
+            vec![file]
+        } else {
+            let base = importer
+                .and_then(Path::parent)
+                .map_or_else(|| PathBuf::from("."), Path::to_path_buf);
+            [base]
+                .iter()
+                .chain(&self.search_path)
+                .map(|dir| dir.join(&file))
+                .collect()
+        };
+
+        candidates
+            .iter()
+            .filter(|candidate| candidate.is_file())
+            .find_map(|candidate| candidate.canonicalize().ok())
+            .ok_or_else(|| {
+                ModuleNotFound {
+                    name: name.to_string(),
+                    searched: candidates,
+                }
+                .into()
+            })
+    }
+}
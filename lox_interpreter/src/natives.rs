@@ -0,0 +1,234 @@
+use std::{
+    cell::RefCell,
+    fmt::Write,
+    io,
+    process::{Command, Stdio},
+    rc::Rc,
+};
+
+use lox_ast::number::Number;
+
+use crate::{
+    environment::GlobalEnvironment,
+    error::{IResult, RuntimeError},
+    json,
+    permissions::Capability,
+    value::{Class, Handle, NativeFunction, Value},
+};
+
+/// Type name of the handles returned by `StringBuilder()`.
+const STRING_BUILDER: &str = "StringBuilder";
+
+/// Natives are declared as `name(arity) => fun`, or `name(arity, ..) => fun`
+/// if they accept extra arguments, after the name of the function returning
+/// them.
+macro_rules! natives {
+    (@variadic) => { false };
+    (@variadic ..) => { true };
+    (
+        fn $natives: ident;
+        $($name: ident($arity: literal $(, $variadic: tt)?) => $fun: expr),+ $(,)?
+    ) => {
+        fn $natives() -> impl Iterator<Item = NativeFunction> {
+            [$(NativeFunction {
+                name: stringify!($name),
+                arity: $arity,
+                variadic: $crate::natives::natives!(@variadic $($variadic)?),
+                fun: $fun,
+            }),+]
+            .into_iter()
+        }
+    };
+}
+
+pub(crate) use natives;
+
+pub(crate) fn define_natives(global_env: &mut GlobalEnvironment) {
+    for native in builtins() {
+        global_env.define(native.name, Value::NativeFunction(Rc::new(native)));
+    }
+}
+
+pub(crate) fn invalid_argument(function: &'static str, reason: String) -> Box<RuntimeError> {
+    RuntimeError::InvalidArgument { function, reason }.to_box()
+}
+
+fn string_argument<'a>(function: &'static str, value: &'a Value) -> IResult<&'a str> {
+    match value {
+        Value::String(s) => Ok(s),
+        _ => Err(invalid_argument(
+            function,
+            format!("expected a string, found {}", value.type_name()),
+        )),
+    }
+}
+
+/// The value if it's a number without a fractional part.
+fn integral(value: &Value) -> Option<i64> {
+    match value.as_number()? {
+        Number::Integer(n) => Some(n),
+        Number::Float(n) if n.fract() == 0.0 && n.abs() < i64::MAX as f64 => Some(n as i64),
+        Number::Float(_) => None,
+    }
+}
+
+/// Runs `command` with the shell of the system.
+fn shell(command: &str) -> Command {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut shell = Command::new(shell);
+    shell.args([flag, command]);
+    shell
+}
+
+/// Gets the class passed to the reflection native `function`.
+fn class_argument<'a>(function: &'static str, value: &'a Value) -> IResult<&'a Rc<Class>> {
+    match value {
+        Value::Class(class) => Ok(class),
+        _ => Err(invalid_argument(
+            function,
+            format!("expected a class, found {}", value.type_name()),
+        )),
+    }
+}
+
+/// Replaces each `{}` in `format` with the next argument, `{{` and `}}` stand
+/// for literal braces.
+fn format_arguments(format: &str, arguments: &[Value]) -> Result<String, String> {
+    let mut result = String::with_capacity(format.len());
+    let mut arguments = arguments.iter();
+    let mut chars = format.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        match (c, chars.peek().map(|&(_, c)| c)) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                result.push(c);
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                let argument = arguments
+                    .next()
+                    .ok_or_else(|| format!("missing argument for `{{}}` at offset {index}"))?;
+                // Writing to a `String` can't fail.
+                let _ = write!(result, "{argument}");
+            }
+            ('{' | '}', _) => {
+                return Err(format!(
+                    "unmatched `{c}` at offset {index}, use `{c}{c}` for a literal brace"
+                ))
+            }
+            _ => result.push(c),
+        }
+    }
+    match arguments.len() {
+        0 => Ok(result),
+        extra => Err(format!("{extra} argument(s) left without a `{{}}`")),
+    }
+}
+
+natives! {
+    fn builtins;
+    clock(0) => |interpreter, _| Ok(Value::Number(interpreter.host.clock())),
+    random(0) => |interpreter, _| Ok(Value::Number(interpreter.host.random())),
+    nan(0) => |_, _| Ok(Value::Number(f64::NAN)),
+    infinity(0) => |_, _| Ok(Value::Number(f64::INFINITY)),
+    // Appending to a builder doesn't copy what was appended before, unlike
+    // `+`, so building a string in a loop takes linear time.
+    StringBuilder(0) => |_, _| {
+        let builder = RefCell::new(String::new());
+        Ok(Value::Handle(Handle::new(STRING_BUILDER, builder)))
+    },
+    sb_append(2) => |_, arguments| {
+        let builder = arguments[0].downcast_handle::<RefCell<String>>(STRING_BUILDER)?;
+        // Writing to a `String` can't fail.
+        let _ = write!(builder.borrow_mut(), "{}", arguments[1]);
+        Ok(arguments[0].clone())
+    },
+    // Prints without a trailing newline, e.g. `printf("x={} y={}\n", x, y)`.
+    printf(1, ..) => |interpreter, arguments| {
+        let Value::String(fmt) = &arguments[0] else {
+            return Err(invalid_argument(
+                "printf",
+                format!("format must be a string, found {}", arguments[0].type_name()),
+            ));
+        };
+        let output = format_arguments(fmt, &arguments[1..])
+            .map_err(|reason| invalid_argument("printf", reason))?;
+        io::Write::write_all(&mut interpreter.output, output.as_bytes())
+            .map_err(|e| RuntimeError::from(e).to_box())?;
+        Ok(Value::Nil)
+    },
+    sb_to_string(1) => |_, arguments| {
+        let builder = arguments[0].downcast_handle::<RefCell<String>>(STRING_BUILDER)?;
+        Ok(builder.borrow().as_str().into())
+    },
+    getenv(1) => |interpreter, arguments| {
+        interpreter.check_permission(Capability::Env)?;
+        let name = string_argument("getenv", &arguments[0])?;
+        Ok(interpreter.host.getenv(name).map_or(Value::Nil, Value::from))
+    },
+    exit(1) => |_, arguments| {
+        match integral(&arguments[0]).and_then(|code| i32::try_from(code).ok()) {
+            Some(code) => Err(RuntimeError::Exit(code).to_box()),
+            None => Err(invalid_argument(
+                "exit",
+                format!("{} isn't a valid exit code", arguments[0]),
+            )),
+        }
+    },
+    // Returns the exit code of the command, or `nil` if it was killed by a
+    // signal. What the command writes to stdout goes to the output of the
+    // interpreter.
+    system(1) => |interpreter, arguments| {
+        interpreter.check_permission(Capability::Exec)?;
+        let command = string_argument("system", &arguments[0])?;
+        let output = shell(command)
+            .stderr(Stdio::inherit())
+            .output()
+            .map_err(|e| invalid_argument("system", format!("cannot run `{command}`: {e}")))?;
+        io::Write::write_all(&mut interpreter.output, &output.stdout)
+            .map_err(|e| RuntimeError::from(e).to_box())?;
+        Ok(output.status.code().map_or(Value::Nil, |code| Value::Number(code.into())))
+    },
+    json_encode(1) => |_, arguments| {
+        json::encode(&arguments[0])
+            .map(Value::from)
+            .map_err(|reason| invalid_argument("json_encode", reason))
+    },
+    json_decode(1) => |_, arguments| {
+        let text = string_argument("json_decode", &arguments[0])?;
+        json::decode(text).map_err(|reason| invalid_argument("json_decode", reason))
+    },
+    // Reflection, e.g. for pretty-printers written in Lox. Method names are
+    // listed by index, as there are no lists.
+    class_of(1) => |_, arguments| match &arguments[0] {
+        Value::Instance(instance) => Ok(Value::Class(instance.borrow().class().clone())),
+        value => Err(invalid_argument(
+            "class_of",
+            format!("expected an instance, found {}", value.type_name()),
+        )),
+    },
+    superclass(1) => |_, arguments| {
+        let class = class_argument("superclass", &arguments[0])?;
+        Ok(class.super_class.clone().map_or(Value::Nil, Value::Class))
+    },
+    method_count(1) => |_, arguments| {
+        let class = class_argument("method_count", &arguments[0])?;
+        Ok(Value::Number(class.method_names().len() as f64))
+    },
+    method_name(2) => |_, arguments| {
+        let class = class_argument("method_name", &arguments[0])?;
+        let count = class.method_names().len();
+        let index = integral(&arguments[1]).and_then(|n| usize::try_from(n).ok());
+        match index.and_then(|index| class.method_names().nth(index)) {
+            Some(name) => Ok(name.into()),
+            None => Err(invalid_argument(
+                "method_name",
+                format!("index {} is out of range for {count} method(s)", arguments[1]),
+            )),
+        }
+    },
+}
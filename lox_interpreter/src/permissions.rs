@@ -0,0 +1,85 @@
+use std::fmt::Display;
+
+/// Something natives can do on behalf of scripts which affects the world
+/// outside the interpreter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    FsRead,
+    FsWrite,
+    /// Running commands, e.g. with `system`.
+    Exec,
+    /// Reading environment variables, e.g. with `getenv`.
+    Env,
+    Network,
+}
+
+impl Display for Capability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Capability::FsRead => write!(f, "fs-read"),
+            Capability::FsWrite => write!(f, "fs-write"),
+            Capability::Exec => write!(f, "exec"),
+            Capability::Env => write!(f, "env"),
+            Capability::Network => write!(f, "network"),
+        }
+    }
+}
+
+/// The capabilities natives may use, checked with
+/// `Interpreter::check_permission` before acting.
+///
+/// The default allows everything but running commands, while
+/// [`Permissions::none`] makes it safe to run untrusted scripts with all the
+/// natives defined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Permissions {
+    pub fs_read: bool,
+    pub fs_write: bool,
+    pub exec: bool,
+    pub env: bool,
+    pub network: bool,
+}
+
+impl Permissions {
+    pub fn all() -> Self {
+        Self {
+            fs_read: true,
+            fs_write: true,
+            exec: true,
+            env: true,
+            network: true,
+        }
+    }
+
+    pub fn none() -> Self {
+        Self {
+            fs_read: false,
+            fs_write: false,
+            exec: false,
+            env: false,
+            network: false,
+        }
+    }
+
+    pub fn allows(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::FsRead => self.fs_read,
+            Capability::FsWrite => self.fs_write,
+            Capability::Exec => self.exec,
+            Capability::Env => self.env,
+            Capability::Network => self.network,
+        }
+    }
+}
+
+impl Default for Permissions {
+    fn default() -> Self {
+        Self {
+            fs_read: true,
+            fs_write: true,
+            exec: false,
+            env: true,
+            network: true,
+        }
+    }
+}
@@ -0,0 +1,928 @@
+use std::{env, fs, io, process, rc::Rc, thread};
+
+use lox_ast::{Expr, IdentTarget, Statement};
+use lox_parser::parser::Ast;
+use lox_resolver::{Resolver, ResolverOptions};
+
+use crate::{
+    bignum::define_bignum,
+    buffer::Buffer,
+    environment::Scope,
+    error::{IResult, RuntimeError},
+    handle::InterpreterHandle,
+    host::DeterministicHost,
+    interpreter::Interpreter,
+    module_path::{ModuleError, ModulePath},
+    permissions::{Capability, Permissions},
+    trace::Tracer,
+    value::{Handle, NativeFunction, Value},
+};
+
+fn resolve(src: &str) -> Ast {
+    resolve_with(src, ResolverOptions::default())
+}
+
+fn resolve_with(src: &str, options: ResolverOptions) -> Ast {
+    let mut ast = lox_parser::parse(src).into_result().unwrap();
+    assert!(Resolver::new(options).resolve(&mut ast).is_none());
+    ast
+}
+
+fn run(src: &str) -> IResult<String> {
+    let ast = resolve(src);
+    let output = Buffer::default();
+    Interpreter::with_output(output.clone()).interpret(&ast)?;
+    Ok(output.take())
+}
+
+#[test]
+fn super_call() {
+    let src = r#"
+        class A { name() { return "A"; } }
+        class B < A { name() { return "B" + super.name(); } }
+        print B().name();
+    "#;
+    assert_eq!(run(src).unwrap(), "BA\n");
+}
+
+#[test]
+fn super_call_in_nested_closure() {
+    let src = r#"
+        class A { name() { return "A"; } }
+        class B < A {
+            name() {
+                fun outer() {
+                    fun inner() { return super.name(); }
+                    return inner;
+                }
+                return outer()();
+            }
+        }
+        print B().name();
+    "#;
+    assert_eq!(run(src).unwrap(), "A\n");
+}
+
+#[test]
+fn super_binds_this_of_caller() {
+    let src = r#"
+        class A { get() { return this.value; } }
+        class B < A {
+            init(value) { this.value = value; }
+            get() {
+                fun closure() { return super.get(); }
+                return closure;
+            }
+        }
+        var getter = B(42).get();
+        print getter();
+    "#;
+    assert_eq!(run(src).unwrap(), "42\n");
+}
+
+#[test]
+fn super_refers_to_declaring_class() {
+    let src = r#"
+        class A { name() { return "A"; } }
+        class B < A { name() { return "B" + super.name(); } }
+        class C < B {}
+        print C().name();
+    "#;
+    assert_eq!(run(src).unwrap(), "BA\n");
+}
+
+/// An output which fails like a closed pipe.
+struct Closed;
+
+impl io::Write for Closed {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::BrokenPipe.into())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn output() {
+    assert_eq!(run("print 1; print \"a\" + \"b\";").unwrap(), "1\nab\n");
+
+    let ast = resolve("print 1;");
+    let err = Interpreter::with_output(Closed)
+        .interpret(&ast)
+        .unwrap_err();
+    assert!(
+        matches!(&*err, RuntimeError::Io(e) if e.kind() == io::ErrorKind::BrokenPipe),
+        "{err}"
+    );
+}
+
+#[test]
+fn host_globals() {
+    let ast = resolve(r#"var greeting = prefix + " world";"#);
+    let mut interpreter = Interpreter::with_output(io::sink());
+    interpreter.set_global("prefix", "hello".into());
+    interpreter.interpret(&ast).unwrap();
+    assert_eq!(
+        interpreter.get_global("greeting"),
+        Some("hello world".into())
+    );
+    assert_eq!(interpreter.get_global("missing"), None);
+}
+
+#[test]
+fn call_from_host() {
+    let ast = resolve(
+        r#"
+        fun add(a, b) { return a + b; }
+        class Counter {
+            init() { this.count = 0; }
+            increment() { this.count = this.count + 1; return this.count; }
+        }
+        var counter = Counter();
+        "#,
+    );
+    let mut interpreter = Interpreter::with_output(io::sink());
+    interpreter.interpret(&ast).unwrap();
+
+    let sum = interpreter.call_global("add", vec![1.0.into(), 2.0.into()]);
+    assert_eq!(sum.unwrap(), Value::Number(3.0));
+
+    let Some(Value::Instance(counter)) = interpreter.get_global("counter") else {
+        panic!("`counter` should be an instance");
+    };
+    let increment = crate::value::Instance::get(counter, "increment").unwrap();
+    interpreter.call(&increment, vec![]).unwrap();
+    assert_eq!(
+        interpreter.call(&increment, vec![]).unwrap(),
+        Value::Number(2.0)
+    );
+
+    assert!(interpreter.call_global("add", vec![]).is_err());
+}
+
+#[test]
+fn interpreter_handle_across_threads() {
+    let handle = InterpreterHandle::spawn(|| Interpreter::with_output(io::sink()));
+    thread::scope(|scope| {
+        scope.spawn(|| handle.interpret(resolve("var answer = 42;")).unwrap());
+    });
+    let answer = handle.with(|interpreter| match interpreter.get_global("answer") {
+        Some(Value::Number(n)) => n,
+        _ => f64::NAN,
+    });
+    assert_eq!(answer, 42.0);
+    assert!(handle.interpret(resolve("undefined();")).is_err());
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_native_function() {
+    use crate::future::{block_on, AsyncNativeFunction};
+    use std::{future, task::Poll};
+
+    let handle = InterpreterHandle::spawn(|| {
+        let mut interpreter = Interpreter::with_output(io::sink());
+        interpreter.set_global(
+            "fetch",
+            Value::AsyncNativeFunction(Rc::new(AsyncNativeFunction {
+                name: "fetch",
+                arity: 1,
+                fun: |arguments| {
+                    let mut value = arguments.into_iter().next();
+                    let mut pending = true;
+                    Box::pin(future::poll_fn(move |cx| {
+                        if pending {
+                            pending = false;
+                            cx.waker().wake_by_ref();
+                            Poll::Pending
+                        } else {
+                            Poll::Ready(Ok(value.take().unwrap()))
+                        }
+                    }))
+                },
+            })),
+        );
+        interpreter
+    });
+    handle
+        .interpret(resolve("var result = fetch(1) + 1;"))
+        .unwrap();
+    let result =
+        block_on(handle.with_async(|interpreter| {
+            interpreter.get_global("result") == Some(Value::Number(2.0))
+        }));
+    assert!(result);
+}
+
+#[test]
+fn deterministic_host() {
+    let ast = resolve("var t1 = clock(); var t2 = clock(); var r = random();");
+    let run = || {
+        let mut interpreter = Interpreter::with_output(io::sink());
+        interpreter.set_host(DeterministicHost::new(7).with_tick(0.5));
+        interpreter.interpret(&ast).unwrap();
+        ["t1", "t2", "r"].map(|name| interpreter.get_global(name).unwrap())
+    };
+    let [t1, t2, r] = run();
+    assert_eq!((t1, t2), (Value::Number(0.), Value::Number(0.5)));
+    assert_eq!(run()[2], r);
+}
+
+#[test]
+fn host_handles() {
+    let ast = resolve("var count = length(open()); var error = length(1);");
+    let mut interpreter = Interpreter::with_output(io::sink());
+    interpreter.set_global(
+        "open",
+        Value::NativeFunction(Rc::new(NativeFunction {
+            name: "open",
+            arity: 0,
+            variadic: false,
+            fun: |_, _| Ok(Value::Handle(Handle::new("file", vec![0u8; 3]))),
+        })),
+    );
+    interpreter.set_global(
+        "length",
+        Value::NativeFunction(Rc::new(NativeFunction {
+            name: "length",
+            arity: 1,
+            variadic: false,
+            fun: |_, arguments| {
+                let file = arguments[0].downcast_handle::<Vec<u8>>("file")?;
+                Ok(Value::Number(file.len() as f64))
+            },
+        })),
+    );
+    let err = interpreter.interpret(&ast).unwrap_err();
+    assert_eq!(interpreter.get_global("count"), Some(Value::Number(3.)));
+    assert_eq!(
+        err.to_string(),
+        "TypeError: expected `file` handle, found `number`"
+    );
+    let span = err.span().unwrap();
+    assert_eq!((span.start.column, span.end.column), (41, 47));
+}
+
+#[test]
+fn snapshot_and_restore() {
+    let mut interpreter = Interpreter::with_output(io::sink());
+    interpreter
+        .interpret(&resolve(
+            "class Point {} var point = Point(); point.x = 1; point.self = point; var n = 1;",
+        ))
+        .unwrap();
+    let snapshot = interpreter.snapshot();
+
+    interpreter
+        .interpret(&resolve("point.x = 2; n = 2; var added = true;"))
+        .unwrap();
+    interpreter.restore(&snapshot);
+
+    let ast = resolve("var x = point.self.x;");
+    interpreter.interpret(&ast).unwrap();
+    assert_eq!(interpreter.get_global("x"), Some(Value::Number(1.)));
+    assert_eq!(interpreter.get_global("n"), Some(Value::Number(1.)));
+    assert_eq!(interpreter.get_global("added"), None);
+}
+
+#[test]
+fn local_subclass() {
+    let src = r#"
+        {
+            class A { name() { return "A"; } }
+            class B < A { name() { return super.name() + "B"; } }
+            print B().name();
+        }
+    "#;
+    assert_eq!(run(src).unwrap(), "AB\n");
+}
+
+#[test]
+fn evaluation_order() {
+    let src = r#"
+        fun log(value) { print value; return value; }
+        fun callee() { print "callee"; return log; }
+        fun three(a, b, c) {}
+        class Box {}
+        fun object() { print "object"; return Box(); }
+        log(1) + log(2);
+        callee()(log(3));
+        three(log(4), log(5), log(6));
+        object().field = log(7);
+        var a;
+        a = log(8);
+    "#;
+    assert_eq!(
+        run(src).unwrap(),
+        "1\n2\ncallee\n3\n3\n4\n5\n6\nobject\n7\n8\n"
+    );
+}
+
+#[test]
+fn unresolved_program() {
+    let ast = lox_parser::parse("fun f(x) { return x; }\nprint f(1);")
+        .into_result()
+        .unwrap();
+    let err = Interpreter::with_output(io::sink())
+        .interpret(&ast)
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "from line 1 column 7 to line 1 column 8: variable `x` isn't resolved, resolve the program before running it"
+    );
+}
+
+#[test]
+fn trace_eval() {
+    let ast =
+        resolve("fun f(a) { var b = a; b = b + 1; return b; }\nvar x = 1;\nx = f(x);\nprint x;");
+    let trace = Buffer::default();
+    let mut interpreter = Interpreter::with_output(io::sink());
+    interpreter.set_tracer(Tracer::new(trace.clone()).only_in("f"));
+    interpreter.interpret(&ast).unwrap();
+    assert_eq!(
+        trace.take(),
+        "trace: line 1 column 12: var
+trace: line 1 column 16: define `b` = 1
+trace: line 1 column 23: expression
+trace: line 1 column 23: assign `b` = 2 (was 1)
+trace: line 1 column 34: return
+"
+    );
+}
+
+#[test]
+fn explain_expressions() {
+    let src = "fun double(n) { return n * 2; }\nvar x = 1;\nprint double(x + 2) - (x = 3);";
+    let trace = Buffer::default();
+    let mut interpreter = Interpreter::with_output(io::sink());
+    interpreter.set_tracer(Tracer::new(trace.clone()).explain(src, 2));
+    interpreter.interpret(&resolve(src)).unwrap();
+    assert_eq!(
+        trace.take(),
+        "trace: line 1 column 1: fun
+trace: line 1 column 5: define `double` = <function double>
+trace: line 2 column 1: var
+trace: 1 => 1
+trace: line 2 column 5: define `x` = 1
+trace: line 3 column 1: print
+trace: double(x + 2) - (x = 3) => 3
+trace: ├─ double(x + 2) => 6
+trace: │  ├─ line 1 column 17: return
+trace: │  └─ …
+trace: └─ (x = 3) => 3
+trace:    └─ …
+"
+    );
+}
+
+#[test]
+fn inspect_scopes() {
+    let ast = resolve_with(
+        r#"
+        class A {
+            get(x) {
+                var y = x + 1;
+                { var z = "z"; inspect(); }
+            }
+        }
+        A().get(1);
+        "#,
+        ResolverOptions {
+            local_names: true,
+            ..Default::default()
+        },
+    );
+    let mut interpreter = Interpreter::with_output(io::sink());
+    interpreter.set_global(
+        "inspect",
+        Value::NativeFunction(Rc::new(NativeFunction {
+            name: "inspect",
+            arity: 0,
+            variadic: false,
+            fun: |interpreter, _| {
+                let scopes = Handle::new("scopes", interpreter.scopes());
+                interpreter.set_global("scopes", Value::Handle(scopes));
+                Ok(Value::Nil)
+            },
+        })),
+    );
+    interpreter.interpret(&ast).unwrap();
+    assert!(interpreter.scopes().is_empty());
+
+    let scopes = interpreter.get_global("scopes").unwrap();
+    let scopes: Vec<_> = scopes
+        .downcast_handle::<Vec<Scope>>("scopes")
+        .unwrap()
+        .iter()
+        .map(|scope| {
+            let variables: Vec<_> = scope
+                .variables
+                .iter()
+                .map(|(name, value)| format!("{}={value}", name.as_deref().unwrap_or("?")))
+                .collect();
+            (scope.depth, variables.join(" "))
+        })
+        .collect();
+    assert_eq!(
+        scopes,
+        [
+            (0, "z=z".to_string()),
+            (1, "x=1 y=2".to_string()),
+            (2, "this=<A instance>".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn slot_mismatch() {
+    let options = ResolverOptions {
+        local_names: true,
+        ..Default::default()
+    };
+    let mut ast = resolve_with("{ var a = 1; var b = 2; print a; }", options);
+    let Statement::Block(block) = &mut ast[0] else {
+        unreachable!()
+    };
+    let Statement::Print(print) = &mut block.statements[2] else {
+        unreachable!()
+    };
+    let Expr::Var(var) = &mut print.expr else {
+        unreachable!()
+    };
+    var.target = Some(IdentTarget {
+        scope_count: 0,
+        index: 1,
+    });
+    let err = Interpreter::with_output(io::sink())
+        .interpret(&ast)
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "from line 1 column 31 to line 1 column 32: `a` is resolved to the slot of `b`"
+    );
+}
+
+#[test]
+fn nan_and_infinity() {
+    let src = r#"
+        print nan();
+        print -infinity();
+        print nan() == nan();
+        print nan() < 1 or nan() > 1;
+        print infinity() == 1 / 0;
+    "#;
+    assert_eq!(run(src).unwrap(), "NaN\n-Infinity\ntrue\nfalse\ntrue\n");
+}
+
+#[test]
+fn bignum() {
+    let src = r#"
+        var factorial = big(1);
+        for (var i = 1; i <= 30; i = i + 1) factorial = bmul(factorial, big(i));
+        print bto_string(factorial);
+        print bto_string(badd(big("-1000000000000000000000"), big(1)));
+        print bto_string(badd(big(5), big("-5")));
+        print bto_string(bmul(big(-3), big(100000000000000000000)));
+    "#;
+    let ast = resolve(src);
+    let output = Buffer::default();
+    let mut interpreter = Interpreter::with_output(output.clone());
+    define_bignum(&mut interpreter);
+    interpreter.interpret(&ast).unwrap();
+    assert_eq!(
+        output.take(),
+        "265252859812191058636308480000000\n\
+         -999999999999999999999\n\
+         0\n\
+         -300000000000000000000\n"
+    );
+
+    for (src, error) in [
+        (
+            "big(1.5);",
+            "Invalid argument to `big`: `1.5` isn't an integer",
+        ),
+        (
+            "big(\"12a\");",
+            "Invalid argument to `big`: `12a` isn't an integer",
+        ),
+        (
+            "badd(big(1), 2);",
+            "TypeError: expected `bignum` handle, found `number`",
+        ),
+    ] {
+        let mut interpreter = Interpreter::with_output(io::sink());
+        define_bignum(&mut interpreter);
+        let err = interpreter.interpret(&resolve(src)).unwrap_err();
+        assert_eq!(err.to_string(), error);
+    }
+}
+
+#[test]
+fn string_builder() {
+    let src = r#"
+        var builder = StringBuilder();
+        for (var i = 0; i < 3; i = i + 1) sb_append(sb_append(builder, i), ",");
+        print sb_to_string(sb_append(builder, nil));
+        print sb_to_string(StringBuilder()) == "";
+    "#;
+    assert_eq!(run(src).unwrap(), "0,1,2,nil\ntrue\n");
+    assert_eq!(
+        run("sb_append(\"text\", 1);").unwrap_err().to_string(),
+        "TypeError: expected `StringBuilder` handle, found `string`"
+    );
+}
+
+#[test]
+fn printf() {
+    let src = r#"
+        var x = 1;
+        printf("x={} y={}\n", x, "two");
+        printf("{{}} {}}}", nil);
+        print printf("");
+    "#;
+    assert_eq!(run(src).unwrap(), "x=1 y=two\n{} nil}nil\n");
+    for (src, error) in [
+        (
+            "printf(\"{} {}\", 1);",
+            "Invalid argument to `printf`: missing argument for `{}` at offset 3",
+        ),
+        (
+            "printf(\"{}\", 1, 2, 3);",
+            "Invalid argument to `printf`: 2 argument(s) left without a `{}`",
+        ),
+        (
+            "printf(\"a { b\");",
+            "Invalid argument to `printf`: unmatched `{` at offset 2, use `{{` for a literal brace",
+        ),
+        (
+            "printf(1);",
+            "Invalid argument to `printf`: format must be a string, found number",
+        ),
+    ] {
+        assert_eq!(run(src).unwrap_err().to_string(), error);
+    }
+    assert!(run("printf();")
+        .unwrap_err()
+        .to_string()
+        .starts_with("Expected at least 1 arguments, but got 0"));
+}
+
+#[test]
+fn process_natives() {
+    assert_eq!(run("print getenv(\"LOX_SURELY_UNSET\");").unwrap(), "nil\n");
+    assert!(matches!(
+        *run("fun f() { exit(3); } f(); print 1;").unwrap_err(),
+        RuntimeError::Exit(3)
+    ));
+    assert_eq!(
+        run("exit(1.5);").unwrap_err().to_string(),
+        "Invalid argument to `exit`: 1.5 isn't a valid exit code"
+    );
+    assert_eq!(
+        run("system(\"echo hi\");").unwrap_err().to_string(),
+        "Permission denied: the host doesn't allow `exec`"
+    );
+
+    let ast = resolve("print getenv(\"HOME\");");
+    let output = Buffer::default();
+    let mut interpreter = Interpreter::with_output(output.clone());
+    interpreter.set_host(DeterministicHost::new(0));
+    interpreter.interpret(&ast).unwrap();
+    assert_eq!(output.take(), "nil\n");
+}
+
+#[test]
+fn permissions() {
+    let ast = resolve("print getenv(\"HOME\");");
+    let mut interpreter = Interpreter::with_output(Buffer::default());
+    interpreter.set_permissions(Permissions::none());
+    let err = interpreter.interpret(&ast).unwrap_err();
+    assert!(matches!(
+        *err,
+        RuntimeError::PermissionDenied {
+            capability: Capability::Env
+        }
+    ));
+
+    assert!(Permissions::all().allows(Capability::Exec));
+    assert!(!Permissions::default().allows(Capability::Exec));
+    assert!(Permissions::default().allows(Capability::FsRead));
+}
+
+#[cfg(unix)]
+#[test]
+fn system() {
+    let ast = resolve("print system(\"echo hi\"); print system(\"exit 2\");");
+    let output = Buffer::default();
+    let mut interpreter = Interpreter::with_output(output.clone());
+    interpreter.set_permissions(Permissions {
+        exec: true,
+        ..Default::default()
+    });
+    interpreter.interpret(&ast).unwrap();
+    assert_eq!(output.take(), "hi\n0\n2\n");
+}
+
+#[test]
+fn json() {
+    let src = r#"
+        print json_encode(nil) + " " + json_encode(true) + " " + json_encode(-1.5);
+        print json_encode("a \"quoted\"\n\ttab");
+        print json_decode(" 12.5e1 ") + 1;
+        print json_decode("\"\\u00e9\\ud83d\\ude00 \\/\"");
+        print json_decode("null") == nil;
+        print json_decode(json_encode("round\ttrip")) == "round\ttrip";
+    "#;
+    assert_eq!(
+        run(src).unwrap(),
+        "null true -1.5\n\"a \\\"quoted\\\"\\n\\ttab\"\n126\né😀 /\ntrue\ntrue\n"
+    );
+    for (src, error) in [
+        (
+            "json_encode(clock);",
+            "Invalid argument to `json_encode`: native function can't be represented in JSON",
+        ),
+        (
+            "json_encode(infinity());",
+            "Invalid argument to `json_encode`: Infinity can't be represented in JSON",
+        ),
+        (
+            "json_decode(\"[1, 2]\");",
+            "Invalid argument to `json_decode`: arrays at offset 0 aren't supported, as Lox has no containers",
+        ),
+        (
+            "json_decode(\"01\");",
+            "Invalid argument to `json_decode`: unexpected `1` at offset 1",
+        ),
+        (
+            "json_decode(\"\\\"\\\\x\\\"\");",
+            "Invalid argument to `json_decode`: invalid escape at offset 1",
+        ),
+        (
+            "json_decode(\"nul\");",
+            "Invalid argument to `json_decode`: invalid literal at offset 0",
+        ),
+        (
+            "json_decode(1);",
+            "Invalid argument to `json_decode`: expected a string, found number",
+        ),
+    ] {
+        assert_eq!(run(src).unwrap_err().to_string(), error, "{src}");
+    }
+}
+
+#[test]
+fn reference_equality() {
+    let src = r#"
+        fun f() {}
+        class A {}
+        var a = A();
+        var g = f;
+        print f == g;
+        print A == A;
+        print a == a;
+        print a == A();
+    "#;
+    assert_eq!(run(src).unwrap(), "true\ntrue\ntrue\nfalse\n");
+}
+
+#[test]
+fn reflection() {
+    let src = r#"
+        class A { init() {} name() {} }
+        class B < A { name() {} size() {} }
+        var b = B();
+        print class_of(b) == B;
+        print superclass(class_of(b)) == A;
+        print superclass(A);
+        for (var i = 0; i < method_count(B); i = i + 1) printf("{} ", method_name(B, i));
+    "#;
+    assert_eq!(run(src).unwrap(), "true\ntrue\nnil\ninit name size ");
+    for (src, error) in [
+        (
+            "class_of(1);",
+            "Invalid argument to `class_of`: expected an instance, found number",
+        ),
+        (
+            "superclass(\"A\");",
+            "Invalid argument to `superclass`: expected a class, found string",
+        ),
+        (
+            "class A {} method_name(A, 0);",
+            "Invalid argument to `method_name`: index 0 is out of range for 0 method(s)",
+        ),
+        (
+            "class A { f() {} } method_name(A, 0.5);",
+            "Invalid argument to `method_name`: index 0.5 is out of range for 1 method(s)",
+        ),
+    ] {
+        assert_eq!(run(src).unwrap_err().to_string(), error);
+    }
+}
+
+#[test]
+fn remove_global() {
+    let output = Buffer::default();
+    let mut interpreter = Interpreter::with_output(output.clone());
+    interpreter
+        .interpret(&resolve(
+            "var a = 1; var x = \"x\"; var c = 3; fun f() { return x + c; } print f();",
+        ))
+        .unwrap();
+    assert!(interpreter.remove_global("a").is_some());
+    assert!(interpreter.remove_global("a").is_none());
+
+    // The slots cached by `f` have shifted.
+    let call = resolve("print f();");
+    interpreter
+        .interpret(&resolve("c = \"c\"; print f();"))
+        .unwrap();
+    // Functions don't capture globals.
+    interpreter.remove_global("x");
+    assert_eq!(
+        interpreter.interpret(&call).unwrap_err().to_string(),
+        "Undefined variable `x`"
+    );
+    interpreter.interpret(&resolve("var x = \"y\";")).unwrap();
+    interpreter.interpret(&call).unwrap();
+    assert_eq!(output.take(), "x3\nxc\nyc\n");
+}
+
+#[test]
+fn stable_order() {
+    let src = r#"
+        class A { f() {} g() {} h() {} }
+        class B < A { z() {} g() {} a() {} }
+        var zeta = 1;
+        var alpha = 2;
+        var zeta = 3;
+        var mid = 4;
+    "#;
+    let ast = resolve(src);
+    for _ in 0..5 {
+        let mut interpreter = Interpreter::with_output(io::sink());
+        let natives = interpreter.globals().count();
+        interpreter.interpret(&ast).unwrap();
+        let Some(Value::Class(class)) = interpreter.get_global("B") else {
+            panic!("`B` isn't a class");
+        };
+        assert_eq!(
+            class.method_names().collect::<Vec<_>>(),
+            ["f", "g", "h", "z", "a"]
+        );
+        assert_eq!(
+            interpreter
+                .globals()
+                .skip(natives)
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>(),
+            ["A", "B", "zeta", "alpha", "mid"]
+        );
+    }
+}
+
+#[test]
+fn recycled_environments() {
+    // Environments captured by closures mustn't be reused by later calls
+    // and blocks, while the others are.
+    let src = r#"
+        fun counter(start) {
+            var count = start;
+            fun next() { count = count + 1; return count; }
+            return next;
+        }
+        var a = counter(10);
+        var b = counter(20);
+        var closures = nil;
+        for (var i = 0; i < 3; i = i + 1) {
+            var captured = i;
+            fun get() { return captured; }
+            if (i == 1) closures = get;
+            { var scratch = "overwritten"; }
+        }
+        fun fill(x) { var y = x; return y; }
+        fill(99);
+        print a();
+        print b();
+        print a();
+        print closures();
+        print fill(1) + fill(2);
+    "#;
+    assert_eq!(run(src).unwrap(), "11\n21\n12\n1\n3\n");
+}
+
+#[test]
+fn cached_global_slots() {
+    // The slots cached in the AST by one interpreter mustn't be trusted by
+    // another one which defined its globals in a different order.
+    let ast = resolve("print a + b; b = b + 1; print b;");
+    let run_with = |globals: &[(&str, f64)]| {
+        let output = Buffer::default();
+        let mut interpreter = Interpreter::with_output(output.clone());
+        for &(name, value) in globals {
+            interpreter.set_global(name, Value::Number(value));
+        }
+        interpreter.interpret(&ast).unwrap();
+        interpreter.interpret(&ast).unwrap();
+        output.take()
+    };
+    assert_eq!(run_with(&[("a", 1.), ("b", 10.)]), "11\n11\n12\n12\n");
+    assert_eq!(run_with(&[("b", 10.), ("a", 2.)]), "12\n11\n13\n12\n");
+
+    // Nor after a restore forgets the globals defined since the snapshot.
+    let mut interpreter = Interpreter::with_output(io::sink());
+    let snapshot = interpreter.snapshot();
+    interpreter.interpret(&resolve("var x = 1;")).unwrap();
+    let read = resolve("var y = x;");
+    interpreter.interpret(&read).unwrap();
+    interpreter.restore(&snapshot);
+    interpreter
+        .interpret(&resolve("var z = 2; var x = 3;"))
+        .unwrap();
+    interpreter.interpret(&read).unwrap();
+    assert_eq!(interpreter.get_global("y"), Some(Value::Number(3.)));
+}
+
+#[test]
+fn cached_methods() {
+    // The same call site sees instances of several classes, and a field
+    // which shadows a method already cached.
+    let src = r#"
+        class A { name() { return "A"; } greet() { return "I'm " + this.name(); } }
+        class B < A { name() { return "B"; } }
+        class C < B {}
+        fun describe(x) { return x.greet(); }
+        for (var i = 0; i < 4; i = i + 1) {
+            var x = A();
+            if (i == 1) x = B();
+            if (i == 2) x = C();
+            if (i == 3) x.greet = nil;
+            print x.greet == nil ? "shadowed" : describe(x);
+        }
+    "#;
+    assert_eq!(run(src).unwrap(), "I'm A\nI'm B\nI'm B\nshadowed\n");
+}
+
+#[test]
+fn module_paths() {
+    let root = env::temp_dir().join(format!("lox_module_paths_{}", process::id()));
+    let (app, lib) = (root.join("app"), root.join("lib"));
+    for dir in [&app, &lib] {
+        fs::create_dir_all(dir).unwrap();
+    }
+    for file in [
+        app.join("main.lox"),
+        app.join("util.lox"),
+        lib.join("util.lox"),
+        lib.join("list.lox"),
+    ] {
+        fs::write(file, "").unwrap();
+    }
+    let main = app.join("main.lox");
+    let modules = ModulePath::from_path_list(env::join_paths([&lib]).unwrap());
+    let resolve = |name| modules.resolve(Permissions::default(), Some(&main), name);
+
+    // Modules next to the importer shadow the ones on the search path.
+    let util = app.join("util.lox").canonicalize().unwrap();
+    assert_eq!(resolve("util"), Ok(util.clone()));
+    assert_eq!(resolve("../app/util.lox"), Ok(util));
+    assert_eq!(
+        resolve("list"),
+        Ok(lib.join("list.lox").canonicalize().unwrap())
+    );
+
+    let Err(ModuleError::NotFound(err)) = resolve("missing") else {
+        panic!("`missing` was found");
+    };
+    assert_eq!(
+        err.searched,
+        [app.join("missing.lox"), lib.join("missing.lox")]
+    );
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "module `missing` not found, searched: `{}`, `{}`",
+            app.join("missing.lox").display(),
+            lib.join("missing.lox").display()
+        )
+    );
+
+    assert_eq!(
+        modules.resolve(Permissions::none(), Some(&main), "util"),
+        Err(ModuleError::PermissionDenied {
+            capability: Capability::FsRead
+        })
+    );
+    assert_eq!(
+        ModulePath::from_env(Permissions::none()).unwrap_err(),
+        ModuleError::PermissionDenied {
+            capability: Capability::Env
+        }
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
@@ -0,0 +1,244 @@
+//! A log of what the interpreter does, to debug scripts without a debugger.
+
+use std::io::Write;
+
+use lox_ast::{Statement, Variable};
+use lox_lexer::{SourceMap, Span};
+
+use crate::{error::IResult, value::Value};
+
+/// Logs every statement executed and every variable defined or assigned.
+pub struct Tracer {
+    output: Box<dyn Write>,
+    /// Only trace inside calls of this function, if set.
+    function: Option<String>,
+    /// Number of calls of `function` which haven't returned yet.
+    calls: usize,
+    explain: Option<Explain>,
+}
+
+/// The expressions being evaluated, logged as a tree once the outermost one
+/// is done.
+struct Explain {
+    /// The scripts, to show the source of the expressions.
+    sources: SourceMap,
+    max_depth: usize,
+    /// Number of expressions being evaluated, including the hidden ones.
+    depth: usize,
+    /// The steps of the expressions being evaluated down to `max_depth`.
+    frames: Vec<Frame>,
+}
+
+#[derive(Default)]
+struct Frame {
+    steps: Vec<Step>,
+    /// Whether steps deeper than `max_depth` were left out.
+    elided: bool,
+}
+
+enum Step {
+    Value {
+        span: Span,
+        value: String,
+        frame: Frame,
+    },
+    /// Something logged while evaluating an expression, e.g. an assignment.
+    Note(String),
+}
+
+impl Tracer {
+    pub fn new(output: impl Write + 'static) -> Self {
+        Self {
+            output: Box::new(output),
+            function: None,
+            calls: 0,
+            explain: None,
+        }
+    }
+
+    /// Also logs every expression evaluated in `src`, as a tree of the values
+    /// of its sub-expressions down to `max_depth` levels, with the variables
+    /// assigned and the statements executed by calls along the way.
+    pub fn explain(self, src: &str, max_depth: usize) -> Self {
+        let mut sources = SourceMap::new();
+        sources.add("", src);
+        self.explain_sources(sources, max_depth)
+    }
+
+    /// Like [`Tracer::explain`], for scripts made of several sources, e.g.
+    /// the files given on the command line.
+    pub fn explain_sources(mut self, sources: SourceMap, max_depth: usize) -> Self {
+        self.explain = Some(Explain {
+            sources,
+            max_depth: max_depth.max(1),
+            depth: 0,
+            frames: Vec::new(),
+        });
+        self
+    }
+
+    /// Only traces while a function or method named `function` is running,
+    /// including the functions it calls.
+    pub fn only_in(mut self, function: &str) -> Self {
+        self.function = Some(function.to_string());
+        self
+    }
+
+    fn is_active(&self) -> bool {
+        self.function.is_none() || self.calls > 0
+    }
+
+    /// Records a call of `function`, returning whether [`Tracer::exit`]
+    /// must be called when it returns.
+    pub(crate) fn enter(&mut self, function: &str) -> bool {
+        let traced = self.function.as_deref() == Some(function);
+        if traced {
+            self.calls += 1;
+        }
+        traced
+    }
+
+    pub(crate) fn exit(&mut self) {
+        self.calls -= 1;
+    }
+
+    pub(crate) fn statement(&mut self, stmt: &Statement) {
+        if !self.is_active() {
+            return;
+        }
+        let kind = match stmt {
+            Statement::Print(_) => "print",
+            Statement::Expression(_) => "expression",
+            Statement::Var(_) => "var",
+            Statement::Block(_) => "block",
+            Statement::If(_) => "if",
+            Statement::While(_) => "while",
+            Statement::FnDecl(_) => "fun",
+            Statement::Return(_) => "return",
+            Statement::ClassDecl(_) => "class",
+        };
+        self.record(format!("{}: {kind}", stmt.get_span().start));
+    }
+
+    pub(crate) fn define(&mut self, var: &Variable, value: &Value) {
+        if self.is_active() {
+            self.record(format!(
+                "{}: define `{var}` = {value}",
+                var.ident.span.start
+            ));
+        }
+    }
+
+    pub(crate) fn assign(&mut self, var: &Variable, old: &Value, new: &Value) {
+        if self.is_active() {
+            self.record(format!(
+                "{}: assign `{var}` = {new} (was {old})",
+                var.ident.span.start
+            ));
+        }
+    }
+
+    /// Records the start of the evaluation of an expression, returning
+    /// whether [`Tracer::exit_expr`] must be called when it's done.
+    pub(crate) fn enter_expr(&mut self) -> bool {
+        if !self.is_active() {
+            return false;
+        }
+        let Some(explain) = &mut self.explain else {
+            return false;
+        };
+        if explain.depth < explain.max_depth {
+            explain.frames.push(Frame::default());
+        } else if let Some(frame) = explain.frames.last_mut() {
+            frame.elided = true;
+        }
+        explain.depth += 1;
+        true
+    }
+
+    pub(crate) fn exit_expr(&mut self, span: Span, result: &IResult<Value>) {
+        let Some(explain) = &mut self.explain else {
+            return;
+        };
+        explain.depth -= 1;
+        if explain.depth >= explain.max_depth {
+            return;
+        }
+        let step = Step::Value {
+            span,
+            value: match result {
+                Ok(Value::String(s)) => format!("{:?}", &**s),
+                Ok(value) => value.to_string(),
+                Err(_) => "error".to_string(),
+            },
+            frame: explain.frames.pop().unwrap_or_default(),
+        };
+        if let Some(parent) = explain.frames.last_mut() {
+            parent.steps.push(step);
+            return;
+        }
+        let mut lines = Vec::new();
+        step.render(&explain.sources, "", "", &mut lines);
+        for line in lines {
+            self.log(format_args!("{line}"));
+        }
+    }
+
+    /// Logs `message`, or adds it to the tree of the expression being
+    /// explained.
+    fn record(&mut self, message: String) {
+        if let Some(explain) = &mut self.explain {
+            if let Some(frame) = explain.frames.last_mut() {
+                if explain.depth > explain.max_depth {
+                    frame.elided = true;
+                } else {
+                    frame.steps.push(Step::Note(message));
+                }
+                return;
+            }
+        }
+        self.log(format_args!("{message}"));
+    }
+
+    fn log(&mut self, message: std::fmt::Arguments) {
+        // Tracing is best effort, it mustn't make the script fail.
+        let _ = writeln!(self.output, "trace: {message}");
+    }
+}
+
+impl Step {
+    /// Renders the step as a line starting with `connector`, followed by the
+    /// lines of its sub-steps starting with `prefix`.
+    fn render(&self, sources: &SourceMap, connector: &str, prefix: &str, lines: &mut Vec<String>) {
+        let (span, value, frame) = match self {
+            Step::Value { span, value, frame } => (span, value, frame),
+            Step::Note(message) => {
+                lines.push(format!("{connector}{message}"));
+                return;
+            }
+        };
+        let source = sources
+            .text_at(*span)
+            .split_whitespace()
+            .collect::<Vec<_>>();
+        lines.push(format!("{connector}{} => {value}", source.join(" ")));
+
+        let count = frame.steps.len() + usize::from(frame.elided);
+        for (i, step) in frame.steps.iter().enumerate() {
+            let (connector, indent) = if i + 1 == count {
+                ("└─ ", "   ")
+            } else {
+                ("├─ ", "│  ")
+            };
+            step.render(
+                sources,
+                &format!("{prefix}{connector}"),
+                &format!("{prefix}{indent}"),
+                lines,
+            );
+        }
+        if frame.elided {
+            lines.push(format!("{prefix}└─ …"));
+        }
+    }
+}
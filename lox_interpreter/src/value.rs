@@ -1,23 +1,54 @@
-use std::{cell::RefCell, collections::HashMap, fmt::Display, ptr, rc::Rc};
-use lox_ast::{ClassDecl, FnDecl, IdentTarget, Lit, Variable};
+use indexmap::IndexMap;
+use lox_ast::{
+    number::{DisplayNumber, Number},
+    ClassDecl, FnDecl, Lit, MethodCache, Variable,
+};
+use lox_lexer::Span;
+use smallvec::SmallVec;
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::HashMap,
+    fmt::{Debug, Display},
+    rc::Rc,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
+#[cfg(feature = "async")]
+use crate::future::AsyncNativeFunction;
 use crate::{
-    environment::{Env, Environment},
+    environment::{Env, Environment, Names, SlotError},
     error::{IResult, RuntimeError},
     interpreter::Interpreter,
 };
 
+/// The arguments of a call, stored inline unless there are many of them, so
+/// that most calls don't allocate.
+pub type Arguments = SmallVec<[Value; 4]>;
+
 pub trait Callable {
     fn arity(&self) -> u8;
 
-    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Value>) -> IResult<Value>;
+    /// Whether the callable accepts extra arguments after the first `arity`.
+    fn variadic(&self) -> bool {
+        false
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, arguments: Arguments) -> IResult<Value>;
+
+    /// The declaration of the function which is called, `None` for natives.
+    fn declaration(&self) -> Option<&FnDecl> {
+        None
+    }
 }
 
 #[derive(Debug)]
 pub struct NativeFunction {
     pub name: &'static str,
+    /// The minimum number of arguments if `variadic`, the exact one otherwise.
     pub arity: u8,
-    pub fun: fn(&mut Interpreter, Vec<Value>) -> IResult<Value>,
+    pub variadic: bool,
+    pub fun: fn(&mut Interpreter, Arguments) -> IResult<Value>,
 }
 
 impl PartialEq for NativeFunction {
@@ -31,14 +62,20 @@ impl Callable for NativeFunction {
         self.arity
     }
 
-    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Value>) -> IResult<Value> {
+    fn variadic(&self) -> bool {
+        self.variadic
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, arguments: Arguments) -> IResult<Value> {
         (self.fun)(interpreter, arguments)
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Function {
-    pub declaration: FnDecl,
+    /// Shared by the methods bound from the function, so that they use the
+    /// same caches.
+    pub declaration: Rc<FnDecl>,
     pub closure: Option<Env>,
 }
 
@@ -47,21 +84,49 @@ impl Callable for Function {
         self.declaration.params.len() as u8
     }
 
-    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Value>) -> IResult<Value> {
-        let mut environment =
-            Environment::new(self.declaration.num_of_locals, self.closure.clone());
-        for (name, value) in self.declaration.params.iter().zip(arguments) {
-            environment.assign(name.target.unwrap(), value)
+    fn call(&self, interpreter: &mut Interpreter, arguments: Arguments) -> IResult<Value> {
+        let declaration = &self.declaration;
+        let environment = interpreter.pool.take(
+            declaration.num_of_locals,
+            Names::of(&declaration.local_names),
+            self.closure.clone(),
+        );
+        for (param, value) in declaration.params.iter().zip(arguments) {
+            let assigned = param
+                .target
+                .ok_or(SlotError::Missing)
+                .and_then(|target| {
+                    environment
+                        .borrow_mut()
+                        .assign(target, &param.ident.name, value)
+                })
+                .map_err(|err| RuntimeError::slot(param, err));
+            if let Err(err) = assigned {
+                interpreter.pool.recycle(environment);
+                return Err(err);
+            }
         }
         interpreter.execute_block(&self.declaration.body, environment)
     }
+
+    fn declaration(&self) -> Option<&FnDecl> {
+        Some(&self.declaration)
+    }
 }
 
+/// Source of the ids of classes, shared by all interpreters since they can
+/// run the same AST and so fill the same [`MethodCache`]s.
+static NEXT_CLASS_ID: AtomicU64 = AtomicU64::new(0);
+
 #[derive(Debug)]
 pub struct Class {
     pub var: Variable,
     pub super_class: Option<Rc<Class>>,
-    pub methods: HashMap<String, Function>,
+    id: u64,
+    /// The methods of the class and the ones it inherits, so that finding
+    /// a method doesn't walk the super classes. They are in the order they
+    /// were first declared, an override taking the place of the method.
+    methods: IndexMap<String, Function>,
 }
 
 impl Class {
@@ -70,47 +135,56 @@ impl Class {
         super_class: Option<Rc<Class>>,
         environment: Option<Env>,
     ) -> Self {
+        let mut methods = match &super_class {
+            Some(super_class) => super_class.methods.clone(),
+            None => IndexMap::new(),
+        };
         let environment = match super_class.clone() {
             Some(super_class) => {
-                let mut environment = Environment::new(1, environment);
-                environment.assign(
-                    IdentTarget {
-                        scope_count: 0,
-                        index: 0,
-                    },
-                    Value::Class(super_class),
-                );
+                let environment =
+                    Environment::implicit("super", Value::Class(super_class), environment);
                 Some(Rc::new(environment.into()))
             }
             None => environment,
         };
 
+        for method in class.methods.iter() {
+            let function = Function {
+                declaration: Rc::new(method.clone()),
+                closure: environment.clone(),
+            };
+            methods.insert(method.var.ident.name.to_string(), function);
+        }
+
         Self {
             var: class.var.clone(),
             super_class,
-            methods: class
-                .methods
-                .iter()
-                .map(|method| {
-                    (
-                        method.var.ident.name.to_string(),
-                        Function {
-                            declaration: method.clone(),
-                            closure: environment.clone(),
-                        },
-                    )
-                })
-                .collect(),
+            id: NEXT_CLASS_ID.fetch_add(1, Ordering::Relaxed),
+            methods,
         }
     }
 
     #[inline]
     pub fn get_method(&self, name: &str) -> Option<&Function> {
-        self.methods.get(name).or_else(|| {
-            self.super_class
-                .as_ref()
-                .and_then(|super_class| super_class.get_method(name))
-        })
+        self.methods.get(name)
+    }
+
+    /// The names of the methods of the class, including the inherited ones,
+    /// in the order they were first declared.
+    pub fn method_names(&self) -> impl ExactSizeIterator<Item = &str> {
+        self.methods.keys().map(String::as_str)
+    }
+
+    /// Finds a method like [`Class::get_method`], trying the method found
+    /// by the same expression the last time first.
+    #[inline]
+    pub fn get_method_cached(&self, name: &str, cache: &MethodCache) -> Option<&Function> {
+        if let Some(index) = cache.get(self.id) {
+            return Some(&self.methods[index]);
+        }
+        let (index, _, method) = self.methods.get_full(name)?;
+        cache.set(self.id, index);
+        Some(method)
     }
 }
 
@@ -119,7 +193,7 @@ impl Callable for Rc<Class> {
         self.get_method("init").map(|m| m.arity()).unwrap_or(0)
     }
 
-    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Value>) -> IResult<Value> {
+    fn call(&self, interpreter: &mut Interpreter, arguments: Arguments) -> IResult<Value> {
         let instance = Rc::new(RefCell::new(Instance {
             class: Rc::clone(self),
             fields: Default::default(),
@@ -141,6 +215,10 @@ impl Callable for Rc<Class> {
 
         Ok(Value::Instance(instance))
     }
+
+    fn declaration(&self) -> Option<&FnDecl> {
+        self.get_method("init").map(|init| &*init.declaration)
+    }
 }
 
 #[derive(Debug)]
@@ -150,11 +228,25 @@ pub struct Instance {
 }
 
 impl Instance {
+    pub fn class(&self) -> &Rc<Class> {
+        &self.class
+    }
+
     pub fn get(instance: Rc<RefCell<Self>>, field: &str) -> IResult<Value> {
+        Self::get_cached(instance, field, &MethodCache::default())
+    }
+
+    /// Gets a field like [`Instance::get`], caching the method found in
+    /// `cache`.
+    pub fn get_cached(
+        instance: Rc<RefCell<Self>>,
+        field: &str,
+        cache: &MethodCache,
+    ) -> IResult<Value> {
         let this = instance.borrow();
         match this.fields.get(field) {
             Some(value) => Ok(value.clone()),
-            None => match this.class.get_method(field) {
+            None => match this.class.get_method_cached(field, cache) {
                 Some(method) => Ok(Value::Function(Rc::new(Self::bind_method(
                     instance.clone(),
                     method,
@@ -167,14 +259,8 @@ impl Instance {
     }
 
     pub fn bind_method(instance: Rc<RefCell<Self>>, method: &Function) -> Function {
-        let mut closure = Environment::new(1, method.closure.clone());
-        closure.assign(
-            IdentTarget {
-                scope_count: 0,
-                index: 0,
-            },
-            Value::Instance(instance),
-        );
+        let closure =
+            Environment::implicit("this", Value::Instance(instance), method.closure.clone());
         Function {
             declaration: method.declaration.clone(),
             closure: Some(Rc::new(closure.into())),
@@ -186,28 +272,81 @@ impl Instance {
     }
 }
 
+/// Instances already copied by [`Value::deep_copy`], so that shared and cyclic
+/// references are preserved in the copy.
+pub(crate) type CopiedInstances = HashMap<*const RefCell<Instance>, Rc<RefCell<Instance>>>;
+
+/// An opaque host object, e.g. a file or a database connection, which scripts
+/// can only pass around and hand back to natives.
+#[derive(Clone)]
+pub struct Handle {
+    type_name: &'static str,
+    value: Rc<dyn Any>,
+}
+
+impl Handle {
+    /// Wraps `value`, naming it `type_name` in messages shown to scripts.
+    pub fn new<T: Any>(type_name: &'static str, value: T) -> Self {
+        Self {
+            type_name,
+            value: Rc::new(value),
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.value.downcast_ref()
+    }
+}
+
+impl Debug for Handle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Handle({})", self.type_name)
+    }
+}
+
+impl PartialEq for Handle {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.value, &other.value)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Value {
     Number(f64),
-    String(String),
+    /// Only produced if `LanguageConfig::integers` is enabled.
+    Integer(i64),
+    /// Shared, so that copying a value doesn't copy its characters.
+    String(Rc<str>),
     Bool(bool),
     Nil,
     NativeFunction(Rc<NativeFunction>),
+    #[cfg(feature = "async")]
+    AsyncNativeFunction(Rc<AsyncNativeFunction>),
     Function(Rc<Function>),
     Class(Rc<Class>),
     Instance(Rc<RefCell<Instance>>),
+    Handle(Handle),
 }
 
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
+        if let (Some(n1), Some(n2)) = (self.as_number(), other.as_number()) {
+            return n1.equals(n2);
+        }
         match (self, other) {
-            (Self::Number(n1), Self::Number(n2)) => n1 == n2,
             (Self::String(s1), Self::String(s2)) => s1 == s2,
             (Self::Bool(b1), Self::Bool(b2)) => b1 == b2,
             (Self::NativeFunction(f1), Self::NativeFunction(f2)) => f1 == f2,
-            (Self::Function(f1), Self::Function(f2)) => ptr::eq(f1, f2),
-            (Self::Class(f1), Self::Class(f2)) => ptr::eq(f1, f2),
-            (Self::Instance(f1), Self::Instance(f2)) => ptr::eq(f1, f2),
+            #[cfg(feature = "async")]
+            (Self::AsyncNativeFunction(f1), Self::AsyncNativeFunction(f2)) => f1 == f2,
+            (Self::Function(f1), Self::Function(f2)) => Rc::ptr_eq(f1, f2),
+            (Self::Class(f1), Self::Class(f2)) => Rc::ptr_eq(f1, f2),
+            (Self::Instance(f1), Self::Instance(f2)) => Rc::ptr_eq(f1, f2),
+            (Self::Handle(h1), Self::Handle(h2)) => h1 == h2,
             (Self::Nil, Self::Nil) => true,
             _ => false,
         }
@@ -215,9 +354,19 @@ impl PartialEq for Value {
 }
 
 impl Value {
+    /// The value if it's a number, either an integer or a float.
+    pub fn as_number(&self) -> Option<Number> {
+        match self {
+            Value::Number(n) => Some(Number::Float(*n)),
+            Value::Integer(n) => Some(Number::Integer(*n)),
+            _ => None,
+        }
+    }
+
     pub fn as_bool(&self) -> bool {
         match self {
             Value::Number(num) => *num != 0.0,
+            Value::Integer(num) => *num != 0,
             Value::String(s) => !s.is_empty(),
             Value::Bool(b) => *b,
             Value::Nil => false,
@@ -227,24 +376,68 @@ impl Value {
 
     pub fn type_name(&self) -> &'static str {
         match self {
-            Value::Number(_) => "number",
+            Value::Number(_) | Value::Integer(_) => "number",
             Value::String(_) => "string",
             Value::Bool(_) => "bool",
             Value::Nil => "nil",
             Value::NativeFunction(_) => "native function",
+            #[cfg(feature = "async")]
+            Value::AsyncNativeFunction(_) => "native function",
             Value::Function(_) => "function",
             Value::Class(_) => "class",
             Value::Instance(_) => "instance",
+            Value::Handle(handle) => handle.type_name(),
         }
     }
+
+    /// Copies instances recursively, sharing everything else by reference.
+    pub(crate) fn deep_copy(&self, copied: &mut CopiedInstances) -> Value {
+        let Value::Instance(instance) = self else {
+            return self.clone();
+        };
+        if let Some(copy) = copied.get(&Rc::as_ptr(instance)) {
+            return Value::Instance(copy.clone());
+        }
+
+        let original = instance.borrow();
+        let copy = Rc::new(RefCell::new(Instance {
+            class: original.class.clone(),
+            fields: HashMap::with_capacity(original.fields.len()),
+        }));
+        copied.insert(Rc::as_ptr(instance), copy.clone());
+        for (name, value) in original.fields.iter() {
+            let value = value.deep_copy(copied);
+            copy.borrow_mut().fields.insert(name.to_string(), value);
+        }
+        Value::Instance(copy)
+    }
+
+    /// Gets the host object of a handle created with [`Handle::new`], failing
+    /// with a message naming `expected` if `self` isn't a handle of type `T`.
+    pub fn downcast_handle<T: Any>(&self, expected: &'static str) -> IResult<&T> {
+        match self {
+            Value::Handle(handle) => handle.downcast_ref(),
+            _ => None,
+        }
+        .ok_or_else(|| {
+            RuntimeError::InvalidHandle {
+                expected,
+                found: self.type_name(),
+                // Filled in with the span of the call by the interpreter.
+                span: Span::dummy(),
+            }
+            .to_box()
+        })
+    }
 }
 
-impl From<Lit> for Value {
-    fn from(value: Lit) -> Self {
+impl From<&Lit> for Value {
+    fn from(value: &Lit) -> Self {
         match value {
-            Lit::Number(n) => Value::Number(n),
-            Lit::String(s) => Value::String(s),
-            Lit::Bool(b) => Value::Bool(b),
+            Lit::Number(n) => Value::Number(*n),
+            Lit::Integer(n) => Value::Integer(*n),
+            Lit::String(s) => Value::String(s.as_str().into()),
+            Lit::Bool(b) => Value::Bool(*b),
             Lit::Nil => Value::Nil,
         }
     }
@@ -262,23 +455,42 @@ impl From<f64> for Value {
     }
 }
 
+impl From<Number> for Value {
+    fn from(value: Number) -> Self {
+        match value {
+            Number::Integer(n) => Self::Integer(n),
+            Number::Float(n) => Self::Number(n),
+        }
+    }
+}
+
 impl From<String> for Value {
     fn from(value: String) -> Self {
-        Self::String(value)
+        Self::String(value.into())
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::String(value.into())
     }
 }
 
 impl Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Value::Number(n) => write!(f, "{n}"),
+            Value::Number(n) => write!(f, "{}", DisplayNumber(*n)),
+            Value::Integer(n) => write!(f, "{n}"),
             Value::String(s) => write!(f, "{s}"),
             Value::Bool(b) => write!(f, "{b}"),
             Value::Nil => write!(f, "nil"),
             Value::NativeFunction(fun) => write!(f, "<native function {}>", fun.name),
+            #[cfg(feature = "async")]
+            Value::AsyncNativeFunction(fun) => write!(f, "<native function {}>", fun.name),
             Value::Function(fun) => write!(f, "<function {}>", fun.declaration.var),
             Value::Class(class) => write!(f, "<class {}>", class.var),
             Value::Instance(instance) => write!(f, "<{} instance>", instance.borrow().class.var),
+            Value::Handle(handle) => write!(f, "<{} handle>", handle.type_name()),
         }
     }
 }
@@ -1,56 +1,556 @@
-use lox_interpreter::interpret;
-use lox_resolver::Resolver;
+use lox_analysis::{
+    metrics,
+    metrics::{to_json, to_table},
+    CallGraph,
+};
+use lox_ast::{dot::to_dot, validate::is_valid};
+use lox_interpreter::{
+    bignum::define_bignum,
+    error::{IResult, RuntimeError},
+    Interpreter, Permissions, Tracer, Value,
+};
+use lox_lexer::{source, LanguageConfig, SourceId, SourceMap, Span};
+use lox_lint::{LintConfig, Linter};
+use lox_parser::parser::Ast;
+use lox_resolver::{Resolver, ResolverOptions};
 use std::{
+    cell::RefCell,
     env, fs,
     io::{self, Write},
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    process,
 };
 
-fn run(src: &str) {
-    match lox_parser::parse(src) {
-        Ok(mut ast) => match Resolver::default().resolve(&mut ast) {
-            Some(errors) => errors.iter().for_each(|e| eprintln!("{e}")),
-            None => {
-                println!("{ast:?}");
-                if let Err(err) = interpret(&ast) {
-                    println!("{err}");
-                }
+mod serve;
+#[cfg(test)]
+mod test;
+
+/// How to check and run scripts.
+struct Options {
+    lint: LintConfig,
+    language: LanguageConfig,
+    /// Set by `--trace-eval[=<function>]`, with the function to trace if any.
+    trace: Option<Option<String>>,
+    /// Set by `--explain[=<depth>]`, with the depth of the expression trees.
+    explain: Option<usize>,
+    /// Set by `--emit=<kind>`, to print a representation of scripts instead
+    /// of running them.
+    emit: Option<Emit>,
+    /// Set by `--allow-exec`, which lets scripts run commands with `system`.
+    allow_exec: bool,
+    /// Set by `--lossy`, which replaces invalid UTF-8 in scripts instead of
+    /// rejecting them.
+    lossy: bool,
+}
+
+/// What `--emit` prints.
+enum Emit {
+    /// The AST as a Graphviz DOT graph.
+    AstDot,
+}
+
+/// Checks and runs the source `source` of `sources` with `interpreter`,
+/// whose globals may have been defined by previous sources, e.g. the previous
+/// lines of the REPL or the previous files of the command line. Diagnostics
+/// are labelled with the name of their source if `label` is set. Returns
+/// whether the script ran without errors.
+fn run(
+    interpreter: &mut Interpreter,
+    sources: &SourceMap,
+    source: SourceId,
+    label: bool,
+    options: &Options,
+) -> bool {
+    let globals = interpreter
+        .globals()
+        .map(|(name, _)| name)
+        .collect::<Vec<_>>();
+    let file = label.then(|| sources.name(source));
+    let Some(ast) = check(
+        sources,
+        source,
+        &options.lint,
+        &options.language,
+        &globals,
+        &mut Labeled::new(io::stderr(), file),
+    ) else {
+        return false;
+    };
+    if let Some(Emit::AstDot) = options.emit {
+        print!("{}", to_dot(&ast));
+        return true;
+    }
+    if options.trace.is_some() || options.explain.is_some() {
+        let mut tracer = Tracer::new(io::stderr());
+        if let Some(Some(function)) = &options.trace {
+            tracer = tracer.only_in(function);
+        }
+        if let Some(depth) = options.explain {
+            tracer = tracer.explain_sources(sources.clone(), depth);
+        }
+        interpreter.set_tracer(tracer);
+    }
+    let result = match interpret(interpreter, &ast) {
+        Ok(result) => result,
+        Err(err) => {
+            let file = file.map(|file| err.span.map_or(file, |span| sources.name(span.source)));
+            let rendered = render_internal_error(sources, &err);
+            let _ = Labeled::new(io::stdout(), file).write_all(rendered.as_bytes());
+            return false;
+        }
+    };
+    match result.map_err(|err| *err) {
+        Err(RuntimeError::Exit(code)) => process::exit(code),
+        Err(err) => {
+            // The error may come from a function defined by a previous source.
+            let file = file.map(|file| err.span().map_or(file, |span| sources.name(span.source)));
+            let rendered = render_runtime_error(sources, &err);
+            let _ = Labeled::new(io::stdout(), file).write_all(rendered.as_bytes());
+            false
+        }
+        Ok(_) => true,
+    }
+}
+
+/// A panic of the interpreter, i.e. one of its bugs rather than an error of
+/// the script.
+struct InternalError {
+    /// The statement being executed.
+    span: Option<Span>,
+    /// The message of the panic and where it was raised.
+    message: String,
+}
+
+thread_local! {
+    /// The message of the last panic caught by [`interpret`].
+    static PANIC: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Runs `ast` with `interpreter`, catching its panics to report them as
+/// internal errors rather than with a Rust backtrace, which would confuse
+/// script authors. The interpreter keeps its globals after a panic.
+fn interpret(interpreter: &mut Interpreter, ast: &Ast) -> Result<IResult<Value>, InternalError> {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|info| {
+        let message = info.payload_as_str().unwrap_or("unknown panic");
+        let message = match info.location() {
+            Some(location) => format!("{message}, at {location}"),
+            None => message.to_string(),
+        };
+        PANIC.set(Some(message));
+    }));
+    let result = panic::catch_unwind(AssertUnwindSafe(|| interpreter.interpret(ast)));
+    panic::set_hook(hook);
+    result.map_err(|_| {
+        let span = interpreter.statement_span();
+        interpreter.reset_scopes();
+        InternalError {
+            span,
+            message: PANIC.take().unwrap_or_default(),
+        }
+    })
+}
+
+/// Prefixes the diagnostics written to it with the name of a file, i.e. the
+/// lines which aren't part of snippets, which start with a space or a line
+/// number.
+struct Labeled<'a, W> {
+    output: W,
+    file: Option<&'a str>,
+    line_start: bool,
+}
+
+impl<'a, W: Write> Labeled<'a, W> {
+    fn new(output: W, file: Option<&'a str>) -> Self {
+        Self {
+            output,
+            file,
+            line_start: true,
+        }
+    }
+}
+
+impl<W: Write> Write for Labeled<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let Some(file) = self.file else {
+            return self.output.write(buf);
+        };
+        for line in buf.split_inclusive(|&b| b == b'\n') {
+            let header = line
+                .first()
+                .is_some_and(|&b| b != b' ' && !b.is_ascii_digit());
+            if self.line_start && header {
+                write!(self.output, "{file}: ")?;
             }
-        },
+            self.output.write_all(line)?;
+            self.line_start = line.ends_with(b"\n");
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+fn new_interpreter(options: &Options) -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    configure(&mut interpreter, options);
+    interpreter
+}
+
+/// Applies the language and permissions of `options` to `interpreter`, and
+/// defines the natives of the CLI.
+fn configure(interpreter: &mut Interpreter, options: &Options) {
+    interpreter.set_language(&options.language);
+    interpreter.set_permissions(Permissions {
+        exec: options.allow_exec,
+        ..Default::default()
+    });
+    define_bignum(interpreter);
+}
+
+/// Parses and resolves the source `source` of `sources`, writing its
+/// diagnostics to `output`, and returns the AST if it can be run. `globals`
+/// are the names already defined when it runs, e.g. by the previous requests
+/// of a `serve` session.
+fn check(
+    sources: &SourceMap,
+    source: SourceId,
+    config: &LintConfig,
+    language: &LanguageConfig,
+    globals: &[&str],
+    output: &mut impl Write,
+) -> Option<Ast> {
+    let mut ast = match lox_parser::parse_source(sources, source, language).into_result() {
+        Ok(ast) => ast,
         Err(errors) => {
             for error in errors.iter() {
-                eprintln!("{error}");
+                write!(output, "{}", sources.render(error, Some(error.span()))).unwrap();
             }
+            return None;
         }
+    };
+
+    // Debug builds record the names of locals, so that slot mismatches are
+    // reported with both names.
+    let mut resolver = Resolver::new(ResolverOptions {
+        local_names: cfg!(debug_assertions),
+        max_depth: language.max_depth,
+        ..Default::default()
+    });
+    for name in globals {
+        resolver.define_global(name);
+    }
+    let errors = resolver.resolve(&mut ast);
+    // The linter isn't guarded against nesting too deep, which is one of the
+    // errors, so only the warnings of the resolver are reported with them.
+    let linted = if errors.is_none() { &ast[..] } else { &[] };
+    let denied = Linter::new(config.clone()).report(linted, &resolver.take_warnings(), output);
+    match errors {
+        Some(errors) => {
+            for error in errors.iter() {
+                write!(output, "{}", sources.render(error, Some(error.span()))).unwrap();
+            }
+            None
+        }
+        None if denied || !is_valid(&ast, output) => None,
+        None => Some(ast),
+    }
+}
+
+fn render_runtime_error(sources: &SourceMap, error: &RuntimeError) -> String {
+    let mut rendered = sources.render(error, error.span());
+    if let Some((note, span)) = error.note() {
+        rendered.push_str(&format!("note: {note}\n{}", sources.snippet(span)));
     }
+    rendered
+}
+
+fn render_internal_error(sources: &SourceMap, error: &InternalError) -> String {
+    let at = error
+        .span
+        .map_or(String::new(), |span| format!(" at {}", span.start));
+    let mut rendered = sources.render(
+        format!("internal interpreter error{at}, please report it"),
+        error.span,
+    );
+    rendered.push_str(&format!("note: panicked: {}\n", error.message));
+    rendered
 }
 
-fn run_interactively() {
+/// Runs each line in the same interpreter, so that the globals it defines
+/// can be used by the next ones. Besides Lox, a line can be a command:
+/// `@q` quits and `@delete <name>` removes a global.
+fn run_interactively(options: &Options) {
+    let mut interpreter = new_interpreter(options);
+    // Keeps the lines, which functions defined by them point into.
+    let mut sources = SourceMap::new();
     loop {
         print!(">");
         io::stdout().flush().unwrap();
         let mut content = String::new();
         io::stdin().read_line(&mut content).unwrap();
 
-        if content.trim() == "@q" {
+        match content.trim() {
+            "@q" => return,
+            command if is_command(command, "@delete") => {
+                if let Err(err) = delete_global(&mut interpreter, command) {
+                    eprintln!("{err}");
+                }
+            }
+            _ => {
+                let source = sources.add("<repl>", content);
+                run(&mut interpreter, &sources, source, false, options);
+            }
+        }
+    }
+}
+
+/// Whether `line` is the command `name`, alone or followed by arguments.
+fn is_command(line: &str, name: &str) -> bool {
+    line.split_whitespace().next() == Some(name)
+}
+
+/// Runs `@delete <name>`, see [`Interpreter::remove_global`].
+fn delete_global(interpreter: &mut Interpreter, command: &str) -> Result<(), String> {
+    let mut args = command.split_whitespace().skip(1);
+    let (Some(name), None) = (args.next(), args.next()) else {
+        return Err("usage: @delete <name>".to_string());
+    };
+    match interpreter.remove_global(name) {
+        Some(_) => Ok(()),
+        None => Err(format!("no global named `{name}`")),
+    }
+}
+
+/// Reads a script, exiting with an error if it can't be read or isn't
+/// UTF-8 unless `lossy` is set, see [`source::read_script`].
+fn read_script(file_path: &str, lossy: bool) -> String {
+    source::read_script(file_path, lossy, &mut io::stderr()).unwrap_or_else(|| process::exit(1))
+}
+
+/// Runs the files in order with the same interpreter, e.g. a library then a
+/// script using it, stopping at the first one which fails. Diagnostics are
+/// labelled with the name of their file if there are several.
+fn run_files(file_paths: &[String], options: &Options) {
+    let mut interpreter = new_interpreter(options);
+    let mut sources = SourceMap::new();
+    for file_path in file_paths {
+        let source = sources.add(file_path, read_script(file_path, options.lossy));
+        if !run(
+            &mut interpreter,
+            &sources,
+            source,
+            file_paths.len() > 1,
+            options,
+        ) {
             return;
         }
+    }
+}
 
-        run(&content);
+/// `lox doc [--html] <file>` prints the documentation of a script.
+fn document(args: &[String], lossy: bool) {
+    let (html, file_path) = match args {
+        [flag, file_path] if flag == "--html" => (true, file_path),
+        [file_path] => (false, file_path),
+        _ => {
+            eprintln!("usage: lox doc [--html] <file>");
+            process::exit(2);
+        }
+    };
+    let content = read_script(file_path, lossy);
+    let ast = match lox_parser::parse(&content).into_result() {
+        Ok(ast) => ast,
+        Err(errors) => {
+            errors.iter().for_each(|e| eprintln!("{e}"));
+            process::exit(1);
+        }
+    };
+    let title = Path::new(file_path)
+        .file_stem()
+        .map_or(file_path.into(), |stem| stem.to_string_lossy());
+    if html {
+        print!("{}", lox_doc::html(&title, &ast));
+    } else {
+        print!("{}", lox_doc::markdown(&title, &ast));
     }
 }
 
-fn run_from_file(file_path: &str) {
-    let content =
-        fs::read_to_string(file_path).unwrap_or_else(|_| panic!("Cannot read file `{file_path}`"));
-    run(&content);
+/// `lox --call-graph <file>` prints the call graph of a script in the DOT
+/// language.
+fn call_graph(args: &[String], lossy: bool) {
+    let [file_path] = args else {
+        eprintln!("usage: lox --call-graph <file>");
+        process::exit(2);
+    };
+    let content = read_script(file_path, lossy);
+    match lox_parser::parse(&content).into_result() {
+        Ok(ast) => print!("{}", CallGraph::new(&ast).to_dot()),
+        Err(errors) => {
+            errors.iter().for_each(|e| eprintln!("{e}"));
+            process::exit(1);
+        }
+    }
+}
+
+/// `lox metrics [--json] <file>` prints the size and complexity of the
+/// functions of a script.
+fn print_metrics(args: &[String], lossy: bool) {
+    let (json, file_path) = match args {
+        [flag, file_path] if flag == "--json" => (true, file_path),
+        [file_path] => (false, file_path),
+        _ => {
+            eprintln!("usage: lox metrics [--json] <file>");
+            process::exit(2);
+        }
+    };
+    let content = read_script(file_path, lossy);
+    let ast = match lox_parser::parse(&content).into_result() {
+        Ok(ast) => ast,
+        Err(errors) => {
+            errors.iter().for_each(|e| eprintln!("{e}"));
+            process::exit(1);
+        }
+    };
+    let metrics = metrics(&ast);
+    if json {
+        print!("{}", to_json(&metrics));
+    } else {
+        print!("{}", to_table(&metrics));
+    }
+}
+
+/// `lox fmt [--check] <files>` formats scripts in place, or with `--check`
+/// only lists the ones which aren't formatted, failing if there are any.
+fn format(args: &[String]) {
+    let (check, files) = match args {
+        [flag, files @ ..] if flag == "--check" => (true, files),
+        files => (false, files),
+    };
+    if files.is_empty() {
+        eprintln!("usage: lox fmt [--check] <files>");
+        process::exit(2);
+    }
+
+    let mut failed = false;
+    for file_path in files {
+        // Strictly, not to write replaced bytes back.
+        let content = read_script(file_path, false);
+        let formatted = match lox_fmt::format(&content) {
+            Ok(formatted) => formatted,
+            Err(errors) => {
+                errors.iter().for_each(|e| eprintln!("{file_path}: {e}"));
+                failed = true;
+                continue;
+            }
+        };
+        if formatted == content {
+            continue;
+        }
+        if check {
+            println!("{file_path} is not formatted");
+            failed = true;
+        } else {
+            fs::write(file_path, formatted)
+                .unwrap_or_else(|_| panic!("Cannot write file `{file_path}`"));
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
 }
 
 fn main() {
-    let args: Vec<_> = env::args().collect();
+    let mut config = LintConfig::default();
+    let mut args = config.parse_args(env::args()).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        process::exit(2);
+    });
+    // `--strict` disables the extensions which jlox doesn't support.
+    let mut language = match args.iter().position(|arg| arg == "--strict") {
+        Some(i) => {
+            args.remove(i);
+            LanguageConfig::strict()
+        }
+        None => LanguageConfig::default(),
+    };
+    // `--integers` keeps integer literals and arithmetic on them exact.
+    if let Some(i) = args.iter().position(|arg| arg == "--integers") {
+        args.remove(i);
+        language.integers = true;
+    }
+    // `--trace-eval[=<function>]` logs the statements executed and the
+    // variables assigned to stderr.
+    let trace = args
+        .iter()
+        .position(|arg| arg == "--trace-eval" || arg.starts_with("--trace-eval="))
+        .map(|i| {
+            args.remove(i)
+                .strip_prefix("--trace-eval=")
+                .map(str::to_string)
+        });
+    // `--explain[=<depth>]` also logs the values of the sub-expressions of
+    // every expression evaluated, as trees down to `depth` levels.
+    let explain = args
+        .iter()
+        .position(|arg| arg == "--explain" || arg.starts_with("--explain="))
+        .map(|i| match args.remove(i).strip_prefix("--explain=") {
+            None => 3,
+            Some(depth) => depth.parse().unwrap_or_else(|_| {
+                eprintln!("invalid depth for --explain: `{depth}`");
+                process::exit(2);
+            }),
+        });
+    let emit = args
+        .iter()
+        .position(|arg| arg.starts_with("--emit="))
+        .map(|i| match &args.remove(i)["--emit=".len()..] {
+            "ast-dot" => Emit::AstDot,
+            kind => {
+                eprintln!("unknown kind for --emit: `{kind}`, expected `ast-dot`");
+                process::exit(2);
+            }
+        });
+    let allow_exec = match args.iter().position(|arg| arg == "--allow-exec") {
+        Some(i) => {
+            args.remove(i);
+            true
+        }
+        None => false,
+    };
+    let lossy = match args.iter().position(|arg| arg == "--lossy") {
+        Some(i) => {
+            args.remove(i);
+            true
+        }
+        None => false,
+    };
+    let options = Options {
+        lint: config,
+        language,
+        trace,
+        explain,
+        emit,
+        allow_exec,
+        lossy,
+    };
 
     if args.len() == 1 {
-        run_interactively();
+        run_interactively(&options);
+    } else if args[1] == "serve" {
+        serve::serve(io::stdin().lock(), &mut io::stdout(), &options);
+    } else if args[1] == "doc" {
+        document(&args[2..], options.lossy);
+    } else if args[1] == "fmt" {
+        format(&args[2..]);
+    } else if args[1] == "metrics" {
+        print_metrics(&args[2..], options.lossy);
+    } else if args[1] == "--call-graph" {
+        call_graph(&args[2..], options.lossy);
     } else {
-        run_from_file(&args[1]);
+        run_files(&args[1..], &options);
     }
 }
@@ -0,0 +1,257 @@
+//! `lox serve` evaluates scripts sent as JSON lines on stdin, e.g. by a
+//! notebook or a web playground, and replies to each with a JSON line on
+//! stdout. Each session has its own interpreter, so the globals defined by a
+//! request can be used by the next ones of the same session.
+//!
+//! A request is an object with a `source` to evaluate in a `session`, named
+//! by a string and `""` if omitted, or with `"close": true` to drop the
+//! session. An `id` of any type is echoed in the reply, which also holds
+//! whether the evaluation succeeded, what the script printed and the
+//! diagnostics:
+//!
+//! ```text
+//! {"id": 1, "session": "a", "source": "var x = 1; print x + 1;"}
+//! {"id": 1, "session": "a", "ok": true, "output": "2\n", "diagnostics": ""}
+//! ```
+
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    io::{BufRead, Write},
+    iter::Peekable,
+    str::CharIndices,
+};
+
+use lox_interpreter::{Buffer, Interpreter};
+use lox_lexer::{
+    json::{decode_string, quote},
+    SourceMap,
+};
+
+use crate::{check, configure, interpret, render_internal_error, render_runtime_error, Options};
+
+struct Session {
+    interpreter: Interpreter,
+    output: Buffer,
+    /// The sources of the requests, which functions defined by them point
+    /// into.
+    sources: SourceMap,
+}
+
+/// A JSON scalar, the only values requests hold.
+enum Json {
+    Null,
+    Bool(bool),
+    /// The text of the number, to echo it unchanged.
+    Number(String),
+    String(String),
+}
+
+impl Json {
+    fn encode(&self) -> String {
+        match self {
+            Json::Null => "null".to_string(),
+            Json::Bool(b) => b.to_string(),
+            Json::Number(n) => n.clone(),
+            Json::String(s) => quote(s),
+        }
+    }
+}
+
+struct Request {
+    id: Json,
+    session: String,
+    source: Option<String>,
+    close: bool,
+}
+
+/// Answers the requests read from `input` until it ends.
+pub(crate) fn serve(input: impl BufRead, output: &mut impl Write, options: &Options) {
+    let mut sessions = HashMap::new();
+    for line in input.lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match parse_request(&line) {
+            Ok(request) => answer(&mut sessions, request, options),
+            Err(err) => format!(
+                "{{\"ok\": false, \"diagnostics\": {}}}",
+                quote(&format!("error: invalid request: {err}\n"))
+            ),
+        };
+        if writeln!(output, "{reply}")
+            .and_then(|_| output.flush())
+            .is_err()
+        {
+            break;
+        }
+    }
+}
+
+fn answer(sessions: &mut HashMap<String, Session>, request: Request, options: &Options) -> String {
+    let mut reply = format!(
+        "{{\"id\": {}, \"session\": {}",
+        request.id.encode(),
+        quote(&request.session)
+    );
+    if request.close {
+        let closed = sessions.remove(&request.session).is_some();
+        let _ = write!(reply, ", \"ok\": {closed}}}");
+        return reply;
+    }
+    let Some(src) = request.source else {
+        reply.push_str(", \"ok\": false, \"diagnostics\": \"error: missing `source`\\n\"}");
+        return reply;
+    };
+
+    let session = sessions.entry(request.session).or_insert_with(|| {
+        let output = Buffer::default();
+        let mut interpreter = Interpreter::with_output(output.clone());
+        configure(&mut interpreter, options);
+        Session {
+            interpreter,
+            output,
+            sources: SourceMap::new(),
+        }
+    });
+    let globals = session
+        .interpreter
+        .globals()
+        .map(|(name, _)| name)
+        .collect::<Vec<_>>();
+    let source = session.sources.add("<request>", src);
+    let mut diagnostics = Vec::new();
+    let ok = match check(
+        &session.sources,
+        source,
+        &options.lint,
+        &options.language,
+        &globals,
+        &mut diagnostics,
+    ) {
+        Some(ast) => {
+            let rendered = match interpret(&mut session.interpreter, &ast) {
+                Ok(Ok(_)) => None,
+                Ok(Err(err)) => Some(render_runtime_error(&session.sources, &err)),
+                Err(err) => Some(render_internal_error(&session.sources, &err)),
+            };
+            if let Some(rendered) = &rendered {
+                diagnostics.extend_from_slice(rendered.as_bytes());
+            }
+            rendered.is_none()
+        }
+        None => false,
+    };
+    let _ = write!(
+        reply,
+        ", \"ok\": {ok}, \"output\": {}, \"diagnostics\": {}}}",
+        quote(&session.output.take()),
+        quote(&String::from_utf8_lossy(&diagnostics))
+    );
+    reply
+}
+
+fn parse_request(line: &str) -> Result<Request, String> {
+    let mut request = Request {
+        id: Json::Null,
+        session: String::new(),
+        source: None,
+        close: false,
+    };
+    for (key, value) in parse_object(line)? {
+        match (key.as_str(), value) {
+            ("id", id) => request.id = id,
+            ("session", Json::String(session)) => request.session = session,
+            ("source", Json::String(source)) => request.source = Some(source),
+            ("close", Json::Bool(close)) => request.close = close,
+            ("session" | "source" | "close", _) => return Err(format!("invalid `{key}`")),
+            _ => return Err(format!("unknown field `{key}`")),
+        }
+    }
+    Ok(request)
+}
+
+/// Parses an object whose values are all scalars.
+fn parse_object(text: &str) -> Result<Vec<(String, Json)>, String> {
+    let mut chars = text.char_indices().peekable();
+    let mut fields = Vec::new();
+    skip_whitespace(&mut chars);
+    expect(&mut chars, '{')?;
+    skip_whitespace(&mut chars);
+    if chars.next_if(|&(_, c)| c == '}').is_none() {
+        loop {
+            skip_whitespace(&mut chars);
+            let start = expect(&mut chars, '"')?;
+            let key = decode_string(&mut chars, start)?;
+            skip_whitespace(&mut chars);
+            expect(&mut chars, ':')?;
+            skip_whitespace(&mut chars);
+            fields.push((key, parse_scalar(&mut chars)?));
+            skip_whitespace(&mut chars);
+            match chars.next() {
+                Some((_, ',')) => {}
+                Some((_, '}')) => break,
+                _ => return Err("expected `,` or `}`".to_string()),
+            }
+        }
+    }
+    skip_whitespace(&mut chars);
+    match chars.next() {
+        None => Ok(fields),
+        Some((_, c)) => Err(format!("unexpected `{c}` after the object")),
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<CharIndices>) {
+    while chars
+        .next_if(|(_, c)| matches!(c, ' ' | '\t' | '\r'))
+        .is_some()
+    {}
+}
+
+/// Consumes `expected`, returning its offset.
+fn expect(chars: &mut Peekable<CharIndices>, expected: char) -> Result<usize, String> {
+    match chars.next() {
+        Some((offset, c)) if c == expected => Ok(offset),
+        Some((_, c)) => Err(format!("expected `{expected}`, found `{c}`")),
+        None => Err(format!("expected `{expected}`, found the end of the line")),
+    }
+}
+
+fn parse_scalar(chars: &mut Peekable<CharIndices>) -> Result<Json, String> {
+    match chars.peek() {
+        Some(&(start, '"')) => {
+            chars.next();
+            decode_string(chars, start).map(Json::String)
+        }
+        Some((_, '-' | '0'..='9')) => {
+            let mut number = String::new();
+            while let Some((_, c)) =
+                chars.next_if(|(_, c)| c.is_ascii_digit() || "+-.eE".contains(*c))
+            {
+                number.push(c);
+            }
+            number
+                .parse::<f64>()
+                .map(|_| Json::Number(number.clone()))
+                .map_err(|_| format!("invalid number `{number}`"))
+        }
+        Some((_, 'n' | 't' | 'f')) => {
+            let mut word = String::new();
+            while let Some((_, c)) = chars.next_if(|(_, c)| c.is_ascii_alphabetic()) {
+                word.push(c);
+            }
+            match word.as_str() {
+                "null" => Ok(Json::Null),
+                "true" => Ok(Json::Bool(true)),
+                "false" => Ok(Json::Bool(false)),
+                _ => Err(format!("invalid literal `{word}`")),
+            }
+        }
+        Some((_, c)) => Err(format!("unexpected `{c}`, values must be scalars")),
+        None => Err("unexpected end of the line".to_string()),
+    }
+}
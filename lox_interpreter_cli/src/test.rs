@@ -0,0 +1,276 @@
+use std::{env, fs, io, path::Path, rc::Rc};
+
+use lox_interpreter::{value::NativeFunction, Interpreter, Value};
+use lox_lexer::{LanguageConfig, SourceMap};
+use lox_lint::LintConfig;
+
+use crate::{
+    check, delete_global, interpret, is_command, render_internal_error, render_runtime_error,
+    serve::serve, Labeled, Options,
+};
+
+/// Renders the diagnostics of checking and running `src`.
+fn diagnostics(src: &str) -> String {
+    let mut sources = SourceMap::new();
+    let source = sources.add("script.lox", src);
+    let mut output = Vec::new();
+    if let Some(ast) = check(
+        &sources,
+        source,
+        &LintConfig::default(),
+        &LanguageConfig::default(),
+        &[],
+        &mut output,
+    ) {
+        if let Err(err) = Interpreter::with_output(io::sink()).interpret(&ast) {
+            let rendered = render_runtime_error(&sources, &err);
+            output.extend_from_slice(rendered.as_bytes());
+        }
+    }
+    String::from_utf8(output).unwrap()
+}
+
+/// Compares the diagnostics of each `golden/*.lox` script with the
+/// `.stderr` file next to it. Set `UPDATE_GOLDEN=1` to rewrite the files
+/// instead, then review the changes.
+#[test]
+fn golden() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("golden");
+    let update = env::var_os("UPDATE_GOLDEN").is_some();
+    let mut mismatches = Vec::new();
+
+    let mut scripts = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lox"))
+        .collect::<Vec<_>>();
+    scripts.sort();
+    assert!(!scripts.is_empty());
+
+    for script in scripts {
+        let actual = diagnostics(&fs::read_to_string(&script).unwrap());
+        let golden = script.with_extension("stderr");
+        if update {
+            fs::write(&golden, actual).unwrap();
+        } else if fs::read_to_string(&golden).ok().as_deref() != Some(actual.as_str()) {
+            mismatches.push(format!("{}:\n{actual}", golden.display()));
+        }
+    }
+    assert!(
+        mismatches.is_empty(),
+        "diagnostics differ from the golden files:\n\n{}",
+        mismatches.join("\n")
+    );
+}
+
+#[test]
+fn serve_sessions() {
+    let requests = r#"{"id": 1, "session": "a", "source": "var x = 1; print x;"}
+{"id": "two", "session": "b", "source": "var x = \"b\";"}
+{"id": 3, "session": "a", "source": "x = x + 1;\nprint x;"}
+{"session": "b", "source": "print y;"}
+{"session": "a", "close": true}
+{"session": "a", "source": "print x;"}
+{"source": [1]}
+{"session": "c", "source": "print \"\u00e9\b\";"}
+{"source": "print 1;	"}
+"#;
+    let options = Options {
+        lint: LintConfig::default(),
+        language: LanguageConfig::default(),
+        trace: None,
+        explain: None,
+        emit: None,
+        allow_exec: false,
+        lossy: false,
+    };
+    let mut output = Vec::new();
+    serve(requests.as_bytes(), &mut output, &options);
+    let replies = String::from_utf8(output).unwrap();
+    let replies = replies.lines().collect::<Vec<_>>();
+    assert_eq!(
+        replies[..3],
+        [
+            r#"{"id": 1, "session": "a", "ok": true, "output": "1\n", "diagnostics": ""}"#,
+            r#"{"id": "two", "session": "b", "ok": true, "output": "", "diagnostics": ""}"#,
+            r#"{"id": 3, "session": "a", "ok": true, "output": "2\n", "diagnostics": ""}"#,
+        ]
+    );
+    assert!(replies[3].starts_with(r#"{"id": null, "session": "b", "ok": false, "output": "", "diagnostics": "error: Undefined variable"#));
+    assert_eq!(replies[4], r#"{"id": null, "session": "a", "ok": true}"#);
+    assert!(replies[5].contains(r#""ok": false"#));
+    assert_eq!(
+        replies[6],
+        r#"{"ok": false, "diagnostics": "error: invalid request: unexpected `[`, values must be scalars\n"}"#
+    );
+    assert_eq!(
+        replies[7],
+        r#"{"id": null, "session": "c", "ok": true, "output": "é\b\n", "diagnostics": ""}"#
+    );
+    assert_eq!(
+        replies[8],
+        r#"{"ok": false, "diagnostics": "error: invalid request: unescaped control character at offset 20\n"}"#
+    );
+}
+
+#[test]
+fn repl_delete_global() {
+    let mut interpreter = Interpreter::with_output(io::sink());
+    // Checks a line of the REPL, returning its diagnostics.
+    let check_line = |interpreter: &Interpreter, src: &str| {
+        let globals = interpreter
+            .globals()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        let mut sources = SourceMap::new();
+        let source = sources.add("<repl>", src);
+        let mut output = Vec::new();
+        let ast = check(
+            &sources,
+            source,
+            &LintConfig::default(),
+            &LanguageConfig::default(),
+            &globals,
+            &mut output,
+        );
+        (ast, String::from_utf8(output).unwrap())
+    };
+
+    let (ast, diagnostics) = check_line(&interpreter, "var x = 1;");
+    assert_eq!(diagnostics, "");
+    interpreter.interpret(&ast.unwrap()).unwrap();
+    assert_eq!(check_line(&interpreter, "x = 2;").1, "");
+
+    assert!(is_command("@delete x", "@delete"));
+    assert!(is_command("@delete", "@delete"));
+    assert!(!is_command("@deletefoo x", "@delete"));
+    assert_eq!(delete_global(&mut interpreter, "@delete x"), Ok(()));
+    assert_eq!(
+        delete_global(&mut interpreter, "@delete x"),
+        Err("no global named `x`".to_string())
+    );
+    assert_eq!(
+        delete_global(&mut interpreter, "@delete"),
+        Err("usage: @delete <name>".to_string())
+    );
+    let (ast, diagnostics) = check_line(&interpreter, "x = 2;");
+    assert!(diagnostics.starts_with("warning[undeclared-assignment]"));
+    assert_eq!(
+        interpreter
+            .interpret(&ast.unwrap())
+            .unwrap_err()
+            .to_string(),
+        "Undefined variable `x`"
+    );
+}
+
+#[test]
+fn labeled_diagnostics() {
+    let mut sources = SourceMap::new();
+    let source = sources.add("main.lox", "fun f(name) {}\nreturn 1;");
+    let mut output = Vec::new();
+    let ast = check(
+        &sources,
+        source,
+        &LintConfig::default(),
+        &LanguageConfig::default(),
+        &[],
+        &mut Labeled::new(&mut output, Some("main.lox")),
+    );
+    assert!(ast.is_none());
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "main.lox: warning[unused-parameter]: from line 1 column 7 to line 1 column 11: unused parameter `name`, prefix it with `_` if it's intended\n\
+         main.lox: error: Can't use `return` outside of a function, from line 2 column 1 to line 2 column 10\n  |\n2 | return 1;\n  | ^^^^^^^^^\n"
+    );
+}
+
+#[test]
+fn runtime_error_in_previous_source() {
+    let mut sources = SourceMap::new();
+    let mut interpreter = Interpreter::with_output(io::sink());
+    let mut run = |name: &str, src: &str| {
+        let source = sources.add(name, src);
+        let mut output = Vec::new();
+        let ast = check(
+            &sources,
+            source,
+            &LintConfig::default(),
+            &LanguageConfig::default(),
+            &interpreter
+                .globals()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>(),
+            &mut output,
+        )
+        .unwrap();
+        interpreter
+            .interpret(&ast)
+            .map_err(|err| render_runtime_error(&sources, &err))
+    };
+
+    run("lib.lox", "fun half(x) {\n    return x / \"2\";\n}").unwrap();
+    let err = run("main.lox", "print half(1);").unwrap_err();
+    assert_eq!(
+        err,
+        "error: TypeError: expected `number`, found `string`\n  |\n2 |     return x / \"2\";\n  |                ^^^\n"
+    );
+}
+
+#[test]
+fn internal_error() {
+    let mut interpreter = Interpreter::with_output(io::sink());
+    interpreter.set_global(
+        "boom",
+        Value::NativeFunction(Rc::new(NativeFunction {
+            name: "boom",
+            arity: 0,
+            variadic: false,
+            fun: |_, _| panic!("boom"),
+        })),
+    );
+    let mut sources = SourceMap::new();
+    let source = sources.add("script.lox", "var a = 1;\n{\n    boom();\n}");
+    let check = |sources: &SourceMap, source| {
+        check(
+            sources,
+            source,
+            &LintConfig::default(),
+            &LanguageConfig::default(),
+            &["boom"],
+            &mut io::sink(),
+        )
+        .unwrap()
+    };
+    let ast = check(&sources, source);
+
+    let Err(err) = interpret(&mut interpreter, &ast) else {
+        panic!("the panic wasn't caught");
+    };
+    let rendered = render_internal_error(&sources, &err);
+    assert!(
+        rendered.starts_with(
+            "error: internal interpreter error at line 3 column 5, please report it\n  |\n3 |     boom();\n  |     ^^^^^^^\nnote: panicked: boom, at "
+        ),
+        "{rendered}"
+    );
+
+    // The globals are still usable.
+    let source = sources.add("script.lox", "print a;");
+    assert!(
+        interpret(&mut interpreter, &check(&sources, source)).is_ok_and(|result| result.is_ok())
+    );
+}
+
+#[test]
+fn long_chain_is_not_linted() {
+    // Deep enough for linting to overflow the stack of a test thread.
+    let src = format!("print {};", ["1"; 5_000].join("+"));
+    let diagnostics = diagnostics(&src);
+    assert!(
+        diagnostics
+            .starts_with("error: from line 1 column 7 to line 1 column 9608: nesting is too deep"),
+        "{}",
+        &diagnostics[..200]
+    );
+}
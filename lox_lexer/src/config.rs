@@ -0,0 +1,105 @@
+use alloc::vec::Vec;
+use core::fmt::Display;
+
+use crate::Keyword;
+
+/// A non-standard addition to the language of the book.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Extension {
+    /// `condition ? truthy : falsy`.
+    Ternary,
+    /// `/* */` comments inside other `/* */` comments.
+    NestedComments,
+    /// `r"..."` and `r#"..."#` strings without escapes.
+    RawStrings,
+}
+
+impl Extension {
+    /// A description of the construct which needs the extension.
+    pub fn construct(self) -> &'static str {
+        match self {
+            Extension::Ternary => "conditional expression",
+            Extension::NestedComments => "nested comment",
+            Extension::RawStrings => "raw string",
+        }
+    }
+}
+
+impl Display for Extension {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Extension::Ternary => write!(f, "ternary"),
+            Extension::NestedComments => write!(f, "nested-comments"),
+            Extension::RawStrings => write!(f, "raw-strings"),
+        }
+    }
+}
+
+/// The default of [`LanguageConfig::max_depth`], low enough for the parser
+/// and the resolver to stay within the 2 MiB stack of a spawned thread.
+pub const DEFAULT_MAX_DEPTH: usize = 200;
+
+/// What dividing a number by zero evaluates to at runtime.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DivisionByZero {
+    /// `inf`, `-inf` or `NaN`, following IEEE 754 as jlox does.
+    #[default]
+    Infinity,
+    /// A runtime error.
+    Error,
+}
+
+/// Which extensions the lexer and parser accept. The default enables all of
+/// them, while [`LanguageConfig::strict`] is compatible with jlox.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LanguageConfig {
+    pub ternary: bool,
+    pub nested_comments: bool,
+    pub raw_strings: bool,
+    /// Extension keywords to reserve, e.g. for `break` and `continue`.
+    pub keywords: Vec<Keyword>,
+    /// How deeply statements and expressions may nest before the parser and
+    /// the resolver give up, instead of overflowing the stack.
+    pub max_depth: usize,
+    /// Shared by the interpreter and the VM so that both agree.
+    pub division_by_zero: DivisionByZero,
+    /// Lexes number literals without a fractional part as integers, which
+    /// stay exact until an operation overflows or divides them.
+    pub integers: bool,
+}
+
+impl LanguageConfig {
+    pub fn strict() -> Self {
+        Self {
+            ternary: false,
+            nested_comments: false,
+            raw_strings: false,
+            keywords: Vec::new(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            division_by_zero: DivisionByZero::Infinity,
+            integers: false,
+        }
+    }
+
+    pub fn is_enabled(&self, extension: Extension) -> bool {
+        match extension {
+            Extension::Ternary => self.ternary,
+            Extension::NestedComments => self.nested_comments,
+            Extension::RawStrings => self.raw_strings,
+        }
+    }
+}
+
+impl Default for LanguageConfig {
+    fn default() -> Self {
+        Self {
+            ternary: true,
+            nested_comments: true,
+            raw_strings: true,
+            keywords: Vec::new(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            division_by_zero: DivisionByZero::Infinity,
+            integers: false,
+        }
+    }
+}
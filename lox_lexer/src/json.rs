@@ -0,0 +1,86 @@
+//! JSON string quoting and decoding, for the hosts and tools which read or
+//! write JSON of their own.
+
+use alloc::{format, string::String};
+use core::{fmt::Write, iter::Peekable, str::CharIndices};
+
+/// Encodes `s` as a JSON string, escaping the characters JSON requires to.
+pub fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            '\u{8}' => quoted.push_str("\\b"),
+            '\u{c}' => quoted.push_str("\\f"),
+            // Writing to a `String` can't fail.
+            c if c < ' ' => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Decodes the rest of a JSON string whose opening quote, at offset `start`,
+/// was consumed from `chars`.
+pub fn decode_string(chars: &mut Peekable<CharIndices>, start: usize) -> Result<String, String> {
+    let mut s = String::new();
+    loop {
+        let Some((offset, c)) = chars.next() else {
+            return Err(format!("unterminated string at offset {start}"));
+        };
+        match c {
+            '"' => return Ok(s),
+            '\\' => s.push(escape(chars, offset)?),
+            c if c < ' ' => return Err(format!("unescaped control character at offset {offset}")),
+            c => s.push(c),
+        }
+    }
+}
+
+/// Reads the escape whose `\` is at `offset`.
+fn escape(chars: &mut Peekable<CharIndices>, offset: usize) -> Result<char, String> {
+    let invalid = || format!("invalid escape at offset {offset}");
+    Ok(match chars.next().ok_or_else(invalid)?.1 {
+        '"' => '"',
+        '\\' => '\\',
+        '/' => '/',
+        'b' => '\u{8}',
+        'f' => '\u{c}',
+        'n' => '\n',
+        'r' => '\r',
+        't' => '\t',
+        'u' => {
+            let high = hex(chars).ok_or_else(invalid)?;
+            let code = if (0xd800..0xdc00).contains(&high) {
+                // A high surrogate must be followed by a low one.
+                let low = match (chars.next(), chars.next()) {
+                    (Some((_, '\\')), Some((_, 'u'))) => hex(chars),
+                    _ => None,
+                }
+                .filter(|low| (0xdc00..0xe000).contains(low))
+                .ok_or_else(invalid)?;
+                0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+            } else {
+                high
+            };
+            char::from_u32(code).ok_or_else(invalid)?
+        }
+        _ => return Err(invalid()),
+    })
+}
+
+/// Reads the 4 hexadecimal digits of a `\u` escape.
+fn hex(chars: &mut Peekable<CharIndices>) -> Option<u32> {
+    (0..4).try_fold(0, |code, _| {
+        let (_, c) = chars.next()?;
+        Some(code * 16 + c.to_digit(16)?)
+    })
+}
@@ -1,10 +1,26 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+mod config;
+pub mod json;
+mod line_index;
+#[cfg(feature = "std")]
+pub mod source;
+mod source_map;
 mod span;
+#[cfg(test)]
+mod test;
 mod token;
 
-use std::{char, str::Chars};
+use alloc::{collections::BTreeSet, vec::Vec};
+use core::{char, mem, ops::Range, str::Chars};
 
 use crate::token::KEY_WORDS_MAP;
 
+pub use config::{DivisionByZero, Extension, LanguageConfig, DEFAULT_MAX_DEPTH};
+pub use line_index::LineIndex;
+pub use source_map::SourceMap;
 pub use span::*;
 pub use token::*;
 
@@ -26,11 +42,48 @@ fn is_digit(c: char) -> bool {
     c.is_ascii_digit()
 }
 
+fn is_token_start(c: char) -> bool {
+    is_whitespace(c)
+        || is_ident_start(c)
+        || is_digit(c)
+        || matches!(
+            c,
+            '!' | ','
+                | '.'
+                | '='
+                | '>'
+                | '<'
+                | '{'
+                | '}'
+                | '('
+                | ')'
+                | '+'
+                | '-'
+                | '*'
+                | ';'
+                | '/'
+                | '?'
+                | ':'
+                | '"'
+        )
+}
+
 pub struct Lexer<'a> {
     chars: Chars<'a>,
     src: &'a str,
     current_position: Position,
     byte_pos: usize,
+    /// The source of the spans of the tokens.
+    source: SourceId,
+    comments: bool,
+    /// Extension keywords which are reserved.
+    extensions: BTreeSet<Keyword>,
+    nested_comments: bool,
+    raw_strings: bool,
+    /// Lexes literals without a fractional part as integers.
+    integers: bool,
+    /// Uses of disabled extensions, which are lexed as if they were enabled.
+    disabled_uses: Vec<(Extension, Span)>,
 }
 
 impl<'a> Lexer<'a> {
@@ -40,10 +93,96 @@ impl<'a> Lexer<'a> {
             chars: src.chars(),
             current_position: Position { line: 1, column: 1 },
             byte_pos: 0,
+            source: SourceId::default(),
+            comments: false,
+            extensions: BTreeSet::new(),
+            nested_comments: true,
+            raw_strings: true,
+            integers: false,
+            disabled_uses: Vec::new(),
+        }
+    }
+
+    /// Lexes `src` from the byte offset `start`, e.g. to re-lex an edited part
+    /// of a file, giving spans relative to the whole of `src`.
+    pub fn with_offset(src: &'a str, start: usize) -> Self {
+        Self::with_range(src, start..src.len())
+    }
+
+    /// Lexes the bytes of `src` in `range` as if the rest of `src` was there,
+    /// i.e. with spans relative to the whole of `src`, stopping at the end of
+    /// `range`.
+    ///
+    /// Panics if `range` isn't on char boundaries.
+    pub fn with_range(src: &'a str, range: Range<usize>) -> Self {
+        let before = &src[..range.start];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Self {
+            src: &src[..range.end],
+            chars: src[range.clone()].chars(),
+            current_position: Position {
+                line: before.matches('\n').count() as u32 + 1,
+                column: before[line_start..].chars().count() as u32 + 1,
+            },
+            byte_pos: range.start,
+            source: SourceId::default(),
+            comments: false,
+            extensions: BTreeSet::new(),
+            nested_comments: true,
+            raw_strings: true,
+            integers: false,
+            disabled_uses: Vec::new(),
+        }
+    }
+
+    /// Tags the spans of the tokens with `source`, the id of `src` in a
+    /// [`SourceMap`].
+    pub fn with_source(mut self, source: SourceId) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Produces ordinary comments as [`TokenType::Comment`] instead of skipping
+    /// them, for tools which have to preserve them.
+    pub fn with_comments(mut self) -> Self {
+        self.comments = true;
+        self
+    }
+
+    /// Enables the extensions of `config`, reporting uses of the others in
+    /// [`Lexer::take_disabled_uses`].
+    pub fn with_config(mut self, config: &LanguageConfig) -> Self {
+        self.nested_comments = config.nested_comments;
+        self.raw_strings = config.raw_strings;
+        self.integers = config.integers;
+        self.with_extension_keywords(config.keywords.iter().copied())
+    }
+
+    /// Takes the extensions which were used though they are disabled, with
+    /// the spans of their uses.
+    pub fn take_disabled_uses(&mut self) -> Vec<(Extension, Span)> {
+        mem::take(&mut self.disabled_uses)
+    }
+
+    /// Reserves the extension keywords in `keywords`, which are otherwise
+    /// lexed as identifiers.
+    pub fn with_extension_keywords(mut self, keywords: impl IntoIterator<Item = Keyword>) -> Self {
+        self.extensions
+            .extend(keywords.into_iter().filter(|kw| kw.is_extension()));
+        self
+    }
+
+    pub fn next_token(&mut self) -> Token<'a> {
+        let token = self.next_raw_token();
+        Token {
+            token_type: token.token_type(self.src),
+            span: token.span,
         }
     }
 
-    pub fn next_token(&mut self) -> Token {
+    /// Lexes the next token without its value, which [`RawToken::text`] and
+    /// [`RawToken::token_type`] get from the source when needed.
+    pub fn next_raw_token(&mut self) -> RawToken {
         if let Some(token) = self.skip() {
             return token;
         }
@@ -53,67 +192,72 @@ impl<'a> Lexer<'a> {
 
         let first_char = match self.bump() {
             Some(c) => c,
-            None => return self.yield_token(TokenType::Eof, start),
+            None => return self.yield_token(TokenKind::Eof, start),
         };
 
-        let token_type = match first_char {
+        let kind = match first_char {
             '!' => {
                 if self.test_and_bump('=') {
-                    TokenType::BangEqual
+                    TokenKind::BangEqual
                 } else {
-                    TokenType::Bang
+                    TokenKind::Bang
                 }
             }
-            ',' => TokenType::Comma,
-            '.' => TokenType::Dot,
+            ',' => TokenKind::Comma,
+            '.' => TokenKind::Dot,
             '=' => {
                 if self.test_and_bump('=') {
-                    TokenType::EqualEqual
+                    TokenKind::EqualEqual
                 } else {
-                    TokenType::Equal
+                    TokenKind::Equal
                 }
             }
             '>' => {
                 if self.test_and_bump('=') {
-                    TokenType::GreaterEqual
+                    TokenKind::GreaterEqual
                 } else {
-                    TokenType::Greater
+                    TokenKind::Greater
                 }
             }
             '<' => {
                 if self.test_and_bump('=') {
-                    TokenType::LessEqual
+                    TokenKind::LessEqual
                 } else {
-                    TokenType::Less
+                    TokenKind::Less
                 }
             }
-            '{' => TokenType::LeftBrace,
-            '}' => TokenType::RightBrace,
-            '(' => TokenType::LeftParen,
-            ')' => TokenType::RightParen,
-            '+' => TokenType::Plus,
-            '-' => TokenType::Minus,
-            '*' => TokenType::Star,
-            ';' => TokenType::Semicolon,
-            '/' => TokenType::Slash,
-            '?' => TokenType::Question,
-            ':' => TokenType::Colon,
+            '{' => TokenKind::LeftBrace,
+            '}' => TokenKind::RightBrace,
+            '(' => TokenKind::LeftParen,
+            ')' => TokenKind::RightParen,
+            '+' => TokenKind::Plus,
+            '-' => TokenKind::Minus,
+            '*' => TokenKind::Star,
+            ';' => TokenKind::Semicolon,
+            '/' => TokenKind::Slash,
+            '?' => TokenKind::Question,
+            ':' => TokenKind::Colon,
             '"' => self.string(),
-            '0'..='9' => TokenType::Literal(Literal::Number(self.number())),
+            'r' if self.raw_string_ahead() => self.raw_string(start),
+            '0'..='9' => self.number(),
             c if is_ident_start(c) => self.identifier(),
-            c => TokenType::Unknown(c),
+            _ => self.unknown(),
         };
 
-        self.yield_token(token_type, start)
+        self.yield_token(kind, start)
     }
 
-    fn yield_token(&self, token_type: TokenType, start: Position) -> Token {
-        Token {
-            token_type,
+    /// Makes a token from `byte_pos` and `start` to the current position.
+    fn yield_token(&self, kind: TokenKind, start: Position) -> RawToken {
+        RawToken {
+            kind,
             span: Span {
                 start,
                 end: self.current_position,
+                source: self.source,
             },
+            start: self.byte_pos,
+            end: self.get_current_pos(),
         }
     }
 
@@ -163,21 +307,31 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    fn skip(&mut self) -> Option<Token> {
+    fn skip(&mut self) -> Option<RawToken> {
         loop {
             match self.peek() {
                 '/' => {
                     if self.peek_next() == '/' {
-                        self.skip_white(|c| c != '\n' && c != '\r');
+                        let comment = self.line_comment();
+                        if comment.is_some() {
+                            return comment;
+                        }
                     } else if self.peek_next() == '*' {
-                        let comments = self.skip_multiline_comment();
-                        if comments.is_some() {
-                            return comments;
+                        let comment = self.skip_multiline_comment();
+                        if comment.is_some() {
+                            return comment;
                         }
                     } else {
                         return None;
                     }
                 }
+                // A `#!` line starting the file, to run scripts directly.
+                '#' if self.peek_next() == '!' && self.get_current_pos() == 0 => {
+                    let comment = self.line_comment();
+                    if comment.is_some() {
+                        return comment;
+                    }
+                }
                 c if is_whitespace(c) => {
                     self.bump();
                 }
@@ -186,31 +340,66 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    fn skip_multiline_comment(&mut self) -> Option<Token> {
+    /// Skips a line comment, returning it if it's a doc comment or comments
+    /// are kept.
+    fn line_comment(&mut self) -> Option<RawToken> {
+        let start = self.current_position;
+        self.update_byte_pos();
+        self.skip_white(|c| c != '\n' && c != '\r');
+        let comment = &self.src[self.byte_pos..self.get_current_pos()];
+
+        // `////` is an ordinary comment, as in Rust.
+        let kind = if comment.starts_with("///") && !comment.starts_with("////") {
+            TokenKind::DocComment
+        } else if self.comments {
+            TokenKind::Comment
+        } else {
+            return None;
+        };
+        Some(self.yield_token(kind, start))
+    }
+
+    fn skip_multiline_comment(&mut self) -> Option<RawToken> {
         let mut level = 1;
         let start = self.current_position;
+        self.update_byte_pos();
 
         self.bump();
         self.bump();
 
-        while let Some(c) = self.bump() {
+        loop {
+            let position = self.current_position;
+            let Some(c) = self.bump() else {
+                break;
+            };
             match c {
                 '/' if self.peek() == '*' => {
                     level += 1;
                     self.bump();
+                    if !self.nested_comments {
+                        let span = Span {
+                            start: position,
+                            end: self.current_position,
+                            source: self.source,
+                        };
+                        self.disabled_uses.push((Extension::NestedComments, span));
+                    }
                 }
                 '*' if self.peek() == '/' => {
                     level -= 1;
                     self.bump();
                     if level == 0 {
-                        return None;
+                        if !self.comments {
+                            return None;
+                        }
+                        return Some(self.yield_token(TokenKind::Comment, start));
                     }
                 }
                 _ => {}
             }
         }
 
-        Some(self.yield_token(TokenType::UnterminatedComment, start))
+        Some(self.yield_token(TokenKind::UnterminatedComment, start))
     }
 
     fn is_eof(&self) -> bool {
@@ -223,49 +412,101 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    fn string(&mut self) -> TokenType {
-        let mut result = String::new();
+    /// Finds the end of a string, whose content is unescaped by
+    /// [`RawToken::token_type`].
+    fn string(&mut self) -> TokenKind {
         while let Some(c) = self.bump() {
             match c {
-                '\\' => match self.bump().unwrap_or(EOF_CHAR) {
-                    '\\' => result.push('\\'),
-                    'n' => result.push('\n'),
-                    'r' => result.push('\r'),
-                    't' => result.push('\t'),
-                    '"' => result.push('"'),
-                    _ => todo!(),
-                },
-                '"' => return TokenType::Literal(Literal::String(result)),
-                ch => result.push(ch),
+                // The escaped character can't end the string.
+                '\\' if self.bump().is_none() => break,
+                '"' => return TokenKind::String,
+                _ => {}
+            }
+        }
+        TokenKind::UnterminatedString
+    }
+
+    /// Whether `r` starts a raw string, i.e. is followed by `#`s and `"`.
+    fn raw_string_ahead(&self) -> bool {
+        self.chars.as_str().trim_start_matches('#').starts_with('"')
+    }
+
+    /// Finds the end of a raw string `r#"..."#` whose `r` is lexed, which is
+    /// closed by a `"` followed by as many `#` as it was opened with.
+    fn raw_string(&mut self, start: Position) -> TokenKind {
+        let mut hashes = 0;
+        while self.test_and_bump('#') {
+            hashes += 1;
+        }
+        self.bump();
+
+        let mut kind = TokenKind::UnterminatedString;
+        while let Some(c) = self.bump() {
+            let rest = self.chars.as_str().as_bytes();
+            if c == '"' && rest.len() >= hashes && rest[..hashes].iter().all(|&b| b == b'#') {
+                for _ in 0..hashes {
+                    self.bump();
+                }
+                kind = TokenKind::String;
+                break;
             }
         }
 
-        TokenType::UnterminatedString
+        if !self.raw_strings {
+            let span = Span {
+                start,
+                end: self.current_position,
+                source: self.source,
+            };
+            self.disabled_uses.push((Extension::RawStrings, span));
+        }
+        kind
     }
 
-    fn identifier(&mut self) -> TokenType {
-        let start = self.byte_pos;
+    fn unknown(&mut self) -> TokenKind {
+        while !self.is_eof() && !is_token_start(self.peek()) {
+            self.bump();
+        }
+        TokenKind::Unknown
+    }
+
+    fn identifier(&mut self) -> TokenKind {
         self.skip_white(is_ident_continue);
-        let end = self.get_current_pos();
-        let ident = &self.src[start..end];
+        let ident = &self.src[self.byte_pos..self.get_current_pos()];
 
         match KEY_WORDS_MAP.get(ident) {
-            Some(&kw) => TokenType::Keyword(kw),
-            None => TokenType::Identifier(ident.to_string()),
+            Some(&kw) if !kw.is_extension() || self.extensions.contains(&kw) => {
+                TokenKind::Keyword(kw)
+            }
+            _ => TokenKind::Identifier,
         }
     }
 
-    fn number(&mut self) -> f64 {
-        let start = self.byte_pos;
-
+    fn number(&mut self) -> TokenKind {
         self.skip_white(is_digit);
-        if self.peek() == '.' && is_digit(self.peek_next()) {
+        let fractional = self.peek() == '.' && is_digit(self.peek_next());
+        if fractional {
             self.bump();
             self.skip_white(is_digit);
         }
 
-        let end = self.get_current_pos();
-
-        self.src[start..end].parse().unwrap()
+        let text = &self.src[self.byte_pos..self.get_current_pos()];
+        // Integers too large for `i64` are promoted to floats.
+        if self.integers && !fractional && text.parse::<i64>().is_ok() {
+            return TokenKind::Integer;
+        }
+        match parse_float(text) {
+            Some(_) => TokenKind::Number,
+            None => TokenKind::NumberOutOfRange,
+        }
     }
 }
+
+/// Parses a number literal, `None` if it can't be represented without
+/// becoming infinite or zero.
+pub(crate) fn parse_float(text: &str) -> Option<f64> {
+    // Only digits and a dot were lexed, so parsing can only lose range.
+    text.parse::<f64>()
+        .ok()
+        .filter(|&n| n.is_finite() && (n != 0.0 || !text.contains(|c| matches!(c, '1'..='9'))))
+}
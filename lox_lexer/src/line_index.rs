@@ -0,0 +1,79 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::{Position, Span};
+
+/// Converts between byte offsets in a source and the positions of spans.
+pub struct LineIndex<'a> {
+    src: &'a str,
+    /// Byte offsets of the start of each line.
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(src: &'a str) -> Self {
+        Self {
+            src,
+            line_starts: [0]
+                .into_iter()
+                .chain(src.match_indices('\n').map(|(i, _)| i + 1))
+                .collect(),
+        }
+    }
+
+    pub fn offset(&self, position: Position) -> usize {
+        let Some(&line_start) = self.line_starts.get(position.line as usize - 1) else {
+            return self.src.len();
+        };
+        self.src[line_start..]
+            .char_indices()
+            .nth(position.column as usize - 1)
+            .map_or(self.src.len(), |(i, _)| line_start + i)
+    }
+
+    /// The position of the character at `offset`, which is clamped to the
+    /// length of the source.
+    pub fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.src.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let line_start = self.line_starts[line - 1];
+        Position {
+            line: line as u32,
+            column: self.src[line_start..offset].chars().count() as u32 + 1,
+        }
+    }
+
+    pub fn text(&self, span: Span) -> &'a str {
+        &self.src[self.offset(span.start)..self.offset(span.end)]
+    }
+
+    /// Renders the line where `span` starts, underlining the span with carets
+    /// up to the end of the line. Dummy spans render as nothing.
+    pub fn snippet(&self, span: Span) -> String {
+        let line = span.start.line as usize;
+        let Some(&line_start) = line.checked_sub(1).and_then(|i| self.line_starts.get(i)) else {
+            return String::new();
+        };
+        let line_end = self.src[line_start..]
+            .find('\n')
+            .map_or(self.src.len(), |i| line_start + i);
+        let text = self.src[line_start..line_end].trim_end_matches('\r');
+        let start = self.offset(span.start).min(line_start + text.len());
+        let end = self.offset(span.end).clamp(start, line_start + text.len());
+
+        // Keep tabs so that the carets line up with the text.
+        let padding = self.src[line_start..start]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        let carets = "^".repeat(self.src[start..end].chars().count().max(1));
+        let width = line.to_string().len();
+        format!(
+            "{:width$} |\n{line} | {text}\n{:width$} | {padding}{carets}\n",
+            "", ""
+        )
+    }
+}
@@ -0,0 +1,130 @@
+//! Reading source files, which may start with a UTF-8 byte order mark or not
+//! be valid UTF-8 at all, e.g. when written by other editors.
+
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{LineIndex, Position};
+
+#[cfg(test)]
+mod test;
+
+const BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// A sequence of bytes which isn't UTF-8, at `position` in the decoded text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidUtf8 {
+    pub position: Position,
+}
+
+impl Display for InvalidUtf8 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid UTF-8 at {}", self.position)
+    }
+}
+
+impl Error for InvalidUtf8 {}
+
+/// The text of a source file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Source {
+    pub text: String,
+    /// The invalid sequences replaced with U+FFFD when decoding lossily.
+    pub replaced: Vec<InvalidUtf8>,
+}
+
+#[derive(Debug)]
+pub enum SourceError {
+    Io { path: PathBuf, error: io::Error },
+    InvalidUtf8 { path: PathBuf, error: InvalidUtf8 },
+}
+
+impl Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceError::Io { path, error } => {
+                write!(f, "cannot read `{}`: {error}", path.display())
+            }
+            SourceError::InvalidUtf8 { path, error } => write!(f, "`{}`: {error}", path.display()),
+        }
+    }
+}
+
+impl Error for SourceError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SourceError::Io { error, .. } => Some(error),
+            SourceError::InvalidUtf8 { error, .. } => Some(error),
+        }
+    }
+}
+
+/// Reads the file at `path`, see [`decode`].
+pub fn read_source(path: impl AsRef<Path>, lossy: bool) -> Result<Source, SourceError> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).map_err(|error| SourceError::Io {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    decode(&bytes, lossy).map_err(|error| SourceError::InvalidUtf8 {
+        path: path.to_path_buf(),
+        error,
+    })
+}
+
+/// Reads a script given on the command line, see [`read_source`], writing
+/// each replaced sequence to `output` as a warning. If it can't be read, the
+/// error is written instead, with a hint to run with `--lossy` if the script
+/// isn't UTF-8.
+pub fn read_script(path: &str, lossy: bool, output: &mut impl Write) -> Option<String> {
+    match read_source(path, lossy) {
+        Ok(source) => {
+            for invalid in source.replaced {
+                let _ = writeln!(output, "warning: `{path}`: {invalid}, replaced with U+FFFD");
+            }
+            Some(source.text)
+        }
+        Err(err) => {
+            let _ = writeln!(output, "error: {err}");
+            if let SourceError::InvalidUtf8 { .. } = err {
+                let _ = writeln!(output, "run with `--lossy` to replace invalid UTF-8");
+            }
+            None
+        }
+    }
+}
+
+/// Decodes the bytes of a source without its byte order mark. Invalid UTF-8
+/// is an error, unless `lossy` is set, in which case each invalid sequence is
+/// replaced with U+FFFD and recorded in [`Source::replaced`].
+pub fn decode(bytes: &[u8], lossy: bool) -> Result<Source, InvalidUtf8> {
+    let bytes = bytes.strip_prefix(BOM).unwrap_or(bytes);
+    let mut text = String::with_capacity(bytes.len());
+    let mut offsets = Vec::new();
+    for chunk in bytes.utf8_chunks() {
+        text.push_str(chunk.valid());
+        if chunk.invalid().is_empty() {
+            continue;
+        }
+        if !lossy {
+            return Err(InvalidUtf8 {
+                position: LineIndex::new(&text).position(text.len()),
+            });
+        }
+        offsets.push(text.len());
+        text.push(char::REPLACEMENT_CHARACTER);
+    }
+    let index = LineIndex::new(&text);
+    let replaced = offsets
+        .into_iter()
+        .map(|offset| InvalidUtf8 {
+            position: index.position(offset),
+        })
+        .collect();
+    Ok(Source { text, replaced })
+}
@@ -0,0 +1,56 @@
+use crate::Position;
+
+use super::{decode, read_script, InvalidUtf8};
+
+#[test]
+fn decode_sources() {
+    let source = decode(b"\xef\xbb\xbfprint 1;", false).unwrap();
+    assert_eq!(source.text, "print 1;");
+
+    let bytes = b"print 1;\nprint \"\xff\xfe\";";
+    let invalid = InvalidUtf8 {
+        position: Position { line: 2, column: 8 },
+    };
+    assert_eq!(decode(bytes, false), Err(invalid));
+    assert_eq!(invalid.to_string(), "invalid UTF-8 at line 2 column 8");
+
+    let source = decode(bytes, true).unwrap();
+    assert_eq!(source.text, "print 1;\nprint \"\u{fffd}\u{fffd}\";");
+    assert_eq!(source.replaced[0], invalid);
+    assert_eq!(source.replaced.len(), 2);
+}
+
+#[test]
+fn read_scripts() {
+    let path = std::env::temp_dir().join(format!("lox_read_scripts_{}.lox", std::process::id()));
+    let path_str = path.to_str().unwrap();
+    std::fs::write(&path, b"print \"\xff\";").unwrap();
+    let read = |lossy| {
+        let mut output = Vec::new();
+        let text = read_script(path_str, lossy, &mut output);
+        (text, String::from_utf8(output).unwrap())
+    };
+
+    assert_eq!(
+        read(true),
+        (
+            Some("print \"\u{fffd}\";".to_string()),
+            format!(
+                "warning: `{path_str}`: invalid UTF-8 at line 1 column 8, replaced with U+FFFD\n"
+            )
+        )
+    );
+    assert_eq!(
+        read(false),
+        (
+            None,
+            format!("error: `{path_str}`: invalid UTF-8 at line 1 column 8\nrun with `--lossy` to replace invalid UTF-8\n")
+        )
+    );
+
+    std::fs::remove_file(&path).unwrap();
+    let (text, output) = read(true);
+    assert_eq!(text, None);
+    assert!(output.starts_with(&format!("error: cannot read `{path_str}`: ")));
+    assert_eq!(output.lines().count(), 1);
+}
@@ -0,0 +1,69 @@
+//! The sources of a program, e.g. the files given on the command line or the
+//! lines of the REPL, which spans refer to by their [`SourceId`].
+
+use alloc::{format, string::String, vec::Vec};
+use core::fmt::Display;
+
+use crate::{LineIndex, SourceId, Span};
+
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    /// The name and text of each source, indexed by id.
+    sources: Vec<(String, String)>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the source `text` named `name`, e.g. its path, returning its id
+    /// to lex it with [`Lexer::with_source`](crate::Lexer::with_source).
+    pub fn add(&mut self, name: impl Into<String>, text: impl Into<String>) -> SourceId {
+        self.sources.push((name.into(), text.into()));
+        SourceId(self.sources.len() as u32 - 1)
+    }
+
+    /// The name of the source `id`.
+    ///
+    /// # Panics
+    ///
+    /// If `id` wasn't returned by [`SourceMap::add`] on this map, as for
+    /// [`SourceMap::text`].
+    pub fn name(&self, id: SourceId) -> &str {
+        &self.sources[id.0 as usize].0
+    }
+
+    pub fn text(&self, id: SourceId) -> &str {
+        &self.sources[id.0 as usize].1
+    }
+
+    pub fn index(&self, id: SourceId) -> LineIndex<'_> {
+        LineIndex::new(self.text(id))
+    }
+
+    /// The text of `span` in its source, empty for unknown sources.
+    pub fn text_at(&self, span: Span) -> &str {
+        match self.sources.get(span.source.0 as usize) {
+            Some((_, text)) => LineIndex::new(text).text(span),
+            None => "",
+        }
+    }
+
+    /// Renders the line where `span` starts in its source, see
+    /// [`LineIndex::snippet`]. Spans of unknown sources, e.g. dummy spans
+    /// when the map is empty, render as nothing.
+    pub fn snippet(&self, span: Span) -> String {
+        match self.sources.get(span.source.0 as usize) {
+            Some((_, text)) => LineIndex::new(text).snippet(span),
+            None => String::new(),
+        }
+    }
+
+    /// Renders an error with the code at `span` underlined, as the CLIs
+    /// print it.
+    pub fn render(&self, message: impl Display, span: Option<Span>) -> String {
+        let snippet = span.map_or(String::new(), |span| self.snippet(span));
+        format!("error: {message}\n{snippet}")
+    }
+}
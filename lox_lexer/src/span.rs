@@ -1,25 +1,32 @@
-use std::fmt::Display;
+use core::fmt::Display;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Position {
     pub line: u32,
     pub column: u32,
 }
 
 impl Display for Position {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "line {} column {}", self.line, self.column)
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Identifies the source a span is in, see [`SourceMap`](crate::SourceMap).
+/// The default is the first source added to a map, so spans of a single
+/// source don't have to care about it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SourceId(pub(crate) u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Span {
     pub start: Position,
     pub end: Position,
+    pub source: SourceId,
 }
 
 impl Display for Span {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "from {} to {}", self.start, self.end)
     }
 }
@@ -35,10 +42,16 @@ impl Span {
         self
     }
 
+    #[inline]
+    pub fn contains(&self, position: Position) -> bool {
+        self.start <= position && position < self.end
+    }
+
     pub fn dummy() -> Self {
         Self {
             start: Position { line: 0, column: 0 },
             end: Position { line: 0, column: 0 },
+            source: SourceId::default(),
         }
     }
 }
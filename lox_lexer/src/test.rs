@@ -0,0 +1,390 @@
+use alloc::{
+    borrow::Cow,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use proptest::prelude::*;
+
+use crate::{
+    json, Keyword, LanguageConfig, Lexer, LineIndex, Literal, SourceMap, Span, Token, TokenKind,
+    TokenType,
+};
+
+/// Lexes `src` up to and including the first [`TokenType::Eof`], failing if
+/// there are more tokens than characters.
+fn lex<'a>(mut lexer: Lexer<'a>, src: &str) -> Result<Vec<Token<'a>>, TestCaseError> {
+    let mut tokens = Vec::new();
+    loop {
+        let token = lexer.next_token();
+        let eof = matches!(token.token_type, TokenType::Eof);
+        tokens.push(token);
+        if eof {
+            return Ok(tokens);
+        }
+        prop_assert!(tokens.len() <= src.chars().count(), "no Eof in {src:?}");
+    }
+}
+
+/// Checks that the tokens of `src` are in bounds, in order and don't overlap,
+/// and that only trivia accepted by `is_trivia` lies between them.
+fn check_spans(
+    src: &str,
+    tokens: &[Token],
+    is_trivia: impl Fn(&str) -> bool,
+) -> Result<(), TestCaseError> {
+    let index = LineIndex::new(src);
+    let mut covered = 0;
+    for token in tokens {
+        let start = index.offset(token.span.start);
+        let end = index.offset(token.span.end);
+        prop_assert!(
+            start <= end && end <= src.len(),
+            "{token:?} is out of bounds"
+        );
+        prop_assert!(covered <= start, "{token:?} overlaps the previous token");
+        prop_assert_eq!(index.position(start), token.span.start);
+        prop_assert_eq!(index.position(end), token.span.end);
+
+        let gap = &src[covered..start];
+        prop_assert!(is_trivia(gap), "{gap:?} before {token:?} isn't trivia");
+        covered = end;
+    }
+
+    let eof = tokens.last().unwrap();
+    prop_assert_eq!(index.offset(eof.span.start), src.len());
+    prop_assert_eq!(eof.span.start, eof.span.end);
+    Ok(())
+}
+
+/// Whether `gap` lexes to nothing, i.e. it only contains whitespace and
+/// ordinary comments.
+fn is_skipped(gap: &str) -> bool {
+    matches!(Lexer::new(gap).next_token().token_type, TokenType::Eof)
+}
+
+fn is_whitespace(gap: &str) -> bool {
+    gap.trim().is_empty()
+}
+
+/// Sources made of Lox's characters, which are much more likely than
+/// arbitrary strings to contain tokens and comments.
+fn lox_source() -> impl Strategy<Value = String> {
+    prop::string::string_regex(
+        r#"([a-z0-9_. \t\r\n"/*!=<>(){};+\-?:,\\@#é€]|//|/\*|\*/|\r\n){0,48}"#,
+    )
+    .unwrap()
+}
+
+proptest! {
+    #[test]
+    fn spans_cover_source(src in lox_source()) {
+        let tokens = lex(Lexer::new(&src), &src)?;
+        check_spans(&src, &tokens, is_skipped)?;
+    }
+
+    #[test]
+    fn spans_cover_source_with_comments(src in lox_source()) {
+        let tokens = lex(Lexer::new(&src).with_comments(), &src)?;
+        check_spans(&src, &tokens, is_whitespace)?;
+    }
+
+    #[test]
+    fn spans_of_arbitrary_input(src in any::<String>()) {
+        let tokens = lex(Lexer::new(&src), &src)?;
+        check_spans(&src, &tokens, is_skipped)?;
+    }
+
+    #[test]
+    fn lex_from_offset(src in lox_source(), pick in any::<prop::sample::Index>()) {
+        let tokens = lex(Lexer::new(&src), &src)?;
+        let index = LineIndex::new(&src);
+        let skip = pick.index(tokens.len());
+        let start = index.offset(tokens[skip].span.start);
+
+        let rest = lex(Lexer::with_offset(&src, start), &src)?;
+        prop_assert_eq!(format!("{:?}", &tokens[skip..]), format!("{rest:?}"));
+    }
+
+    #[test]
+    fn raw_tokens_match_spans(src in lox_source()) {
+        let index = LineIndex::new(&src);
+        let mut lexer = Lexer::new(&src).with_comments();
+        loop {
+            let token = lexer.next_raw_token();
+            prop_assert_eq!(index.offset(token.span.start), token.start);
+            prop_assert_eq!(index.offset(token.span.end), token.end);
+            prop_assert_eq!(token.text(&src), index.text(token.span));
+            if token.kind == TokenKind::Eof {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn kinds_of_tokens(src in lox_source()) {
+        let mut lexer = Lexer::new(&src).with_comments();
+        let mut raw_lexer = Lexer::new(&src).with_comments();
+        loop {
+            let token = lexer.next_token();
+            let raw = raw_lexer.next_raw_token();
+            prop_assert_eq!(token.token_type.kind(), raw.kind);
+            prop_assert_eq!(&token.token_type, &raw.token_type(&src));
+            if raw.kind == TokenKind::Eof {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn eof_repeats(src in lox_source()) {
+        let mut lexer = Lexer::new(&src);
+        while !matches!(lexer.next_token().token_type, TokenType::Eof) {}
+        for _ in 0..2 {
+            prop_assert!(matches!(lexer.next_token().token_type, TokenType::Eof));
+        }
+    }
+}
+
+#[test]
+fn lex_range() {
+    let src = "var a = 1;\nprint a + 2;\nprint b;";
+    let start = src.find("a +").unwrap();
+    let end = src.find(" 2").unwrap();
+    let mut lexer = Lexer::with_range(src, start..end);
+    let tokens = (0..3)
+        .map(|_| {
+            let token = lexer.next_token();
+            format!("{} {}", token.token_type, token.span)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        tokens,
+        [
+            "a from line 2 column 7 to line 2 column 8",
+            "+ from line 2 column 9 to line 2 column 10",
+            "end of input from line 2 column 10 to line 2 column 10",
+        ]
+    );
+}
+
+#[test]
+fn shebang() {
+    let src = "#!/usr/bin/env lox\nprint 1; #!";
+    let tokens = lex(Lexer::new(src), src).unwrap();
+    let tokens = tokens
+        .iter()
+        .map(|token| format!("{} {}", token.token_type, token.span))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        tokens[..2],
+        [
+            "print from line 2 column 1 to line 2 column 6",
+            "1 from line 2 column 7 to line 2 column 8",
+        ]
+    );
+    // Only a `#!` starting the file is skipped.
+    assert_eq!(tokens.len(), 6);
+
+    let mut lexer = Lexer::new(src).with_comments();
+    let token = lexer.next_token();
+    assert_eq!(
+        token.token_type,
+        TokenType::Comment("#!/usr/bin/env lox".into())
+    );
+    assert_eq!(
+        token.span.to_string(),
+        "from line 1 column 1 to line 1 column 19"
+    );
+}
+
+#[test]
+fn number_out_of_range() {
+    let huge = format!("1{}", "0".repeat(400));
+    let tiny = format!("0.{}1", "0".repeat(400));
+    let zero = format!("0.{}", "0".repeat(400));
+    for (src, in_range) in [(&huge, false), (&tiny, false), (&zero, true)] {
+        let token = Lexer::new(src).next_token();
+        match token.token_type {
+            TokenType::Literal(Literal::Number(_)) => assert!(in_range, "{src}"),
+            TokenType::NumberOutOfRange(text) => {
+                assert!(!in_range, "{src}");
+                assert_eq!(&text, src);
+            }
+            t => panic!("unexpected token {t:?}"),
+        }
+    }
+}
+
+#[test]
+fn extension_keywords() {
+    let src = "break import while";
+    let kinds = |mut lexer: Lexer| {
+        [(); 3].map(|_| match lexer.next_token().token_type {
+            TokenType::Keyword(kw) => Some(kw),
+            _ => None,
+        })
+    };
+
+    assert_eq!(kinds(Lexer::new(src)), [None, None, Some(Keyword::While)]);
+    assert_eq!(
+        kinds(Lexer::new(src).with_extension_keywords([Keyword::Import])),
+        [None, Some(Keyword::Import), Some(Keyword::While)]
+    );
+}
+
+#[test]
+fn integer_literals() {
+    let config = LanguageConfig {
+        integers: true,
+        ..Default::default()
+    };
+    let literal = |src: &'static str, config: &LanguageConfig| match Lexer::new(src)
+        .with_config(config)
+        .next_token()
+        .token_type
+    {
+        TokenType::Literal(literal) => literal,
+        t => panic!("unexpected token {t:?}"),
+    };
+
+    assert!(matches!(literal("42", &config), Literal::Integer(42)));
+    assert!(matches!(literal("42.5", &config), Literal::Number(n) if n == 42.5));
+    assert!(matches!(
+        literal("42", &LanguageConfig::default()),
+        Literal::Number(n) if n == 42.0
+    ));
+    // Too large for `i64`.
+    assert!(matches!(
+        literal("9223372036854775808", &config),
+        Literal::Number(_)
+    ));
+}
+
+#[test]
+fn borrowed_text() {
+    let src = "name \"plain\" \"escaped\\n\" \"line\r\nbreak\" \"unterminated";
+    let mut lexer = Lexer::new(src);
+    let mut texts = Vec::new();
+    loop {
+        match lexer.next_token().token_type {
+            TokenType::Identifier(text)
+            | TokenType::Literal(Literal::String(text))
+            | TokenType::UnterminatedString(text) => texts.push(text),
+            TokenType::Eof => break,
+            t => panic!("unexpected token {t:?}"),
+        }
+    }
+    let borrowed: Vec<_> = texts
+        .iter()
+        .map(|text| matches!(text, Cow::Borrowed(_)))
+        .collect();
+    assert_eq!(
+        texts,
+        ["name", "plain", "escaped\n", "line\nbreak", "unterminated"]
+    );
+    assert_eq!(borrowed, [true, true, false, false, true]);
+}
+
+#[test]
+fn raw_strings() {
+    let src = "r\"C:\\dir\\n\" r#\"say \"hi\"\"# r##\"a\r\n\"#b\"## r\"open";
+    let mut lexer = Lexer::new(src);
+    let tokens = (0..5)
+        .map(|_| {
+            let token = lexer.next_token();
+            format!("{:?} {}", token.token_type, token.span)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        tokens,
+        [
+            r#"Literal(String("C:\\dir\\n")) from line 1 column 1 to line 1 column 12"#,
+            r#"Literal(String("say \"hi\"")) from line 1 column 13 to line 1 column 26"#,
+            r##"Literal(String("a\n\"#b")) from line 1 column 27 to line 2 column 7"##,
+            r#"UnterminatedString("open") from line 2 column 8 to line 2 column 14"#,
+            "Eof from line 2 column 14 to line 2 column 14",
+        ]
+    );
+
+    // `r` not followed by a quote is still an identifier.
+    let mut lexer = Lexer::new("r #");
+    assert!(matches!(lexer.next_token().token_type, TokenType::Identifier(text) if text == "r"));
+}
+
+#[test]
+fn source_map() {
+    let mut sources = SourceMap::new();
+    assert_eq!(sources.snippet(Span::dummy()), "");
+
+    sources.add("a.lox", "print 1;");
+    let b = sources.add("b.lox", "var b = 2;");
+    let mut lexer = Lexer::new(sources.text(b)).with_source(b);
+    lexer.next_token();
+    let token = lexer.next_token();
+    assert_eq!(token.span.source, b);
+    assert_eq!(sources.name(token.span.source), "b.lox");
+    assert_eq!(sources.text_at(token.span), "b");
+}
+
+#[test]
+fn token_starts_match_dispatch() {
+    for c in (0..=0x7f).map(char::from).chain(['é', '\u{a0}', '€']) {
+        let src = c.to_string();
+        let dispatched = Lexer::new(&src).next_raw_token().kind != TokenKind::Unknown;
+        assert_eq!(crate::is_token_start(c), dispatched, "{c:?}");
+    }
+}
+
+#[test]
+fn unknown_runs() {
+    let texts = |src| {
+        let mut lexer = Lexer::new(src);
+        let mut texts = Vec::new();
+        loop {
+            match lexer.next_token().token_type {
+                TokenType::Eof => return texts,
+                TokenType::Unknown(text) => texts.push(Some(text)),
+                _ => texts.push(None),
+            }
+        }
+    };
+    assert_eq!(
+        texts("a @#$ b@€&c #~"),
+        [
+            None,
+            Some("@#$".into()),
+            None,
+            Some("@€&".into()),
+            None,
+            Some("#~".into()),
+        ]
+    );
+}
+
+#[test]
+fn json_strings() {
+    // Only the control characters JSON requires to are escaped.
+    let quoted = json::quote("\"\\\u{8}\u{c}\n\r\t\u{1}\u{7f}é");
+    assert_eq!(quoted, "\"\\\"\\\\\\b\\f\\n\\r\\t\\u0001\u{7f}é\"");
+
+    let decode = |text: &str| {
+        let mut chars = text.char_indices().peekable();
+        chars.next();
+        json::decode_string(&mut chars, 0).map(|s| (s, chars.next()))
+    };
+    assert_eq!(
+        decode(&quoted),
+        Ok(("\"\\\u{8}\u{c}\n\r\t\u{1}\u{7f}é".to_string(), None))
+    );
+    assert_eq!(decode(r#""a" b"#), Ok(("a".to_string(), Some((3, ' ')))));
+    assert_eq!(
+        decode("\"a\nb\""),
+        Err("unescaped control character at offset 2".to_string())
+    );
+    assert_eq!(
+        decode("\"a"),
+        Err("unterminated string at offset 0".to_string())
+    );
+}
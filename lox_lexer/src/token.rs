@@ -1,23 +1,38 @@
-use std::fmt::Display;
+use alloc::{borrow::Cow, string::String};
+use core::fmt::Display;
 
-use crate::span::Span;
+use crate::{parse_float, span::Span};
 use phf::phf_map;
 
 macro_rules! keywords {
-    ($($expr: expr => $name: ident),+) => {
-        #[derive(Debug, Clone, Copy)]
+    (
+        $($expr: expr => $name: ident),+;
+        extensions: $($ext_expr: expr => $ext_name: ident),+
+    ) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
         pub enum Keyword {
             $($name,)+
+            $($ext_name,)+
         }
 
          pub(crate) static KEY_WORDS_MAP: phf::Map<&'static str, Keyword> = phf_map! {
             $($expr => Keyword::$name,)+
+            $($ext_expr => Keyword::$ext_name,)+
         };
 
+        impl Keyword {
+            /// Whether the keyword is only reserved by a language extension,
+            /// being an identifier otherwise.
+            pub fn is_extension(self) -> bool {
+                matches!(self, $(Keyword::$ext_name)|+)
+            }
+        }
+
         impl Display for Keyword {
-            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
                 match self {
                     $(Keyword::$name => write!(f, $expr),)+
+                    $(Keyword::$ext_name => write!(f, $ext_expr),)+
                 }
             }
         }
@@ -40,35 +55,55 @@ keywords!(
     "this" => This,
     "true" => True,
     "var" => Var,
-    "while" => While
+    "while" => While;
+    extensions:
+    "break" => Break,
+    "const" => Const,
+    "continue" => Continue,
+    "export" => Export,
+    "import" => Import
 );
 
-#[derive(Debug, Clone)]
-pub enum Literal {
-    String(String),
+/// The text of tokens borrows from the source, except for strings with
+/// escapes and tokens made [`TokenType::into_owned`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal<'a> {
+    String(Cow<'a, str>),
     Number(f64),
+    /// Only lexed if `LanguageConfig::integers` is enabled.
+    Integer(i64),
 }
 
-#[derive(Debug, Clone)]
-pub enum TokenType {
+/// Not `Eq` because of the values of number literals, compare
+/// [`TokenType::kind`]s to ignore values.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenType<'a> {
     Bang,
     BangEqual,
     Colon,
     Comma,
+    /// A comment including its delimiters, or a `#!` line starting the file,
+    /// only produced by [`Lexer::with_comments`](crate::Lexer::with_comments).
+    Comment(Cow<'a, str>),
+    /// The text of a `///` comment, without the slashes and the following space.
+    DocComment(Cow<'a, str>),
     Dot,
     Eof,
     Equal,
     EqualEqual,
     Greater,
     GreaterEqual,
-    Identifier(String),
+    Identifier(Cow<'a, str>),
     Keyword(Keyword),
     LeftBrace,
     LeftParen,
     Less,
     LessEqual,
-    Literal(Literal),
+    Literal(Literal<'a>),
     Minus,
+    /// A number literal which can't be represented without becoming infinite
+    /// or zero, with its text.
+    NumberOutOfRange(Cow<'a, str>),
     Plus,
     Question,
     RightBrace,
@@ -76,18 +111,108 @@ pub enum TokenType {
     Semicolon,
     Slash,
     Star,
-    Unknown(char),
+    /// A run of characters which can't start any token.
+    Unknown(Cow<'a, str>),
     UnterminatedComment,
-    UnterminatedString,
+    /// A string missing its closing quote, with the content lexed until the end
+    /// of input. The span of the token starts at the opening quote.
+    UnterminatedString(Cow<'a, str>),
+}
+
+impl TokenType<'_> {
+    /// The kind of the token, without its text or value.
+    pub fn kind(&self) -> TokenKind {
+        match self {
+            TokenType::Bang => TokenKind::Bang,
+            TokenType::BangEqual => TokenKind::BangEqual,
+            TokenType::Colon => TokenKind::Colon,
+            TokenType::Comma => TokenKind::Comma,
+            TokenType::Comment(_) => TokenKind::Comment,
+            TokenType::DocComment(_) => TokenKind::DocComment,
+            TokenType::Dot => TokenKind::Dot,
+            TokenType::Eof => TokenKind::Eof,
+            TokenType::Equal => TokenKind::Equal,
+            TokenType::EqualEqual => TokenKind::EqualEqual,
+            TokenType::Greater => TokenKind::Greater,
+            TokenType::GreaterEqual => TokenKind::GreaterEqual,
+            TokenType::Identifier(_) => TokenKind::Identifier,
+            TokenType::Keyword(kw) => TokenKind::Keyword(*kw),
+            TokenType::LeftBrace => TokenKind::LeftBrace,
+            TokenType::LeftParen => TokenKind::LeftParen,
+            TokenType::Less => TokenKind::Less,
+            TokenType::LessEqual => TokenKind::LessEqual,
+            TokenType::Literal(Literal::String(_)) => TokenKind::String,
+            TokenType::Literal(Literal::Number(_)) => TokenKind::Number,
+            TokenType::Literal(Literal::Integer(_)) => TokenKind::Integer,
+            TokenType::Minus => TokenKind::Minus,
+            TokenType::NumberOutOfRange(_) => TokenKind::NumberOutOfRange,
+            TokenType::Plus => TokenKind::Plus,
+            TokenType::Question => TokenKind::Question,
+            TokenType::RightBrace => TokenKind::RightBrace,
+            TokenType::RightParen => TokenKind::RightParen,
+            TokenType::Semicolon => TokenKind::Semicolon,
+            TokenType::Slash => TokenKind::Slash,
+            TokenType::Star => TokenKind::Star,
+            TokenType::Unknown(_) => TokenKind::Unknown,
+            TokenType::UnterminatedComment => TokenKind::UnterminatedComment,
+            TokenType::UnterminatedString(_) => TokenKind::UnterminatedString,
+        }
+    }
+
+    /// Copies the text of the token, e.g. to keep it in an error which
+    /// outlives the source.
+    pub fn into_owned(self) -> TokenType<'static> {
+        fn owned(text: Cow<str>) -> Cow<'static, str> {
+            Cow::Owned(text.into_owned())
+        }
+
+        match self {
+            TokenType::Bang => TokenType::Bang,
+            TokenType::BangEqual => TokenType::BangEqual,
+            TokenType::Colon => TokenType::Colon,
+            TokenType::Comma => TokenType::Comma,
+            TokenType::Comment(comment) => TokenType::Comment(owned(comment)),
+            TokenType::DocComment(doc) => TokenType::DocComment(owned(doc)),
+            TokenType::Dot => TokenType::Dot,
+            TokenType::Eof => TokenType::Eof,
+            TokenType::Equal => TokenType::Equal,
+            TokenType::EqualEqual => TokenType::EqualEqual,
+            TokenType::Greater => TokenType::Greater,
+            TokenType::GreaterEqual => TokenType::GreaterEqual,
+            TokenType::Identifier(ident) => TokenType::Identifier(owned(ident)),
+            TokenType::Keyword(kw) => TokenType::Keyword(kw),
+            TokenType::LeftBrace => TokenType::LeftBrace,
+            TokenType::LeftParen => TokenType::LeftParen,
+            TokenType::Less => TokenType::Less,
+            TokenType::LessEqual => TokenType::LessEqual,
+            TokenType::Literal(Literal::String(s)) => TokenType::Literal(Literal::String(owned(s))),
+            TokenType::Literal(Literal::Number(n)) => TokenType::Literal(Literal::Number(n)),
+            TokenType::Literal(Literal::Integer(n)) => TokenType::Literal(Literal::Integer(n)),
+            TokenType::Minus => TokenType::Minus,
+            TokenType::NumberOutOfRange(text) => TokenType::NumberOutOfRange(owned(text)),
+            TokenType::Plus => TokenType::Plus,
+            TokenType::Question => TokenType::Question,
+            TokenType::RightBrace => TokenType::RightBrace,
+            TokenType::RightParen => TokenType::RightParen,
+            TokenType::Semicolon => TokenType::Semicolon,
+            TokenType::Slash => TokenType::Slash,
+            TokenType::Star => TokenType::Star,
+            TokenType::Unknown(s) => TokenType::Unknown(owned(s)),
+            TokenType::UnterminatedComment => TokenType::UnterminatedComment,
+            TokenType::UnterminatedString(s) => TokenType::UnterminatedString(owned(s)),
+        }
+    }
 }
 
-impl Display for TokenType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl Display for TokenType<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             TokenType::Bang => write!(f, "!"),
             TokenType::BangEqual => write!(f, "!="),
             TokenType::Colon => write!(f, ":"),
             TokenType::Comma => write!(f, ","),
+            TokenType::Comment(comment) => write!(f, "{comment}"),
+            TokenType::DocComment(doc) => write!(f, "/// {doc}"),
             TokenType::Dot => write!(f, "."),
             TokenType::Eof => write!(f, "end of input"),
             TokenType::Equal => write!(f, "="),
@@ -102,7 +227,9 @@ impl Display for TokenType {
             TokenType::LessEqual => write!(f, "<="),
             TokenType::Literal(Literal::String(s)) => write!(f, "\"{s}\""),
             TokenType::Literal(Literal::Number(n)) => write!(f, "{n}"),
+            TokenType::Literal(Literal::Integer(n)) => write!(f, "{n}"),
             TokenType::Minus => write!(f, "-"),
+            TokenType::NumberOutOfRange(text) => write!(f, "{text}"),
             TokenType::Plus => write!(f, "+"),
             TokenType::Question => write!(f, "?"),
             TokenType::RightBrace => write!(f, "}}"),
@@ -110,15 +237,223 @@ impl Display for TokenType {
             TokenType::Semicolon => write!(f, ";"),
             TokenType::Slash => write!(f, "/"),
             TokenType::Star => write!(f, "*"),
-            TokenType::Unknown(c) => write!(f, "{c}"),
+            TokenType::Unknown(s) => write!(f, "{s}"),
             TokenType::UnterminatedComment => write!(f, "unterminated comment"),
-            TokenType::UnterminatedString => write!(f, "unterminated string"),
+            TokenType::UnterminatedString(_) => write!(f, "unterminated string"),
+        }
+    }
+}
+
+/// The kind of a token, which its text completes into a [`TokenType`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    Bang,
+    BangEqual,
+    Colon,
+    Comma,
+    Comment,
+    DocComment,
+    Dot,
+    Eof,
+    Equal,
+    EqualEqual,
+    Greater,
+    GreaterEqual,
+    Identifier,
+    Keyword(Keyword),
+    LeftBrace,
+    LeftParen,
+    Less,
+    LessEqual,
+    String,
+    Number,
+    Integer,
+    Minus,
+    NumberOutOfRange,
+    Plus,
+    Question,
+    RightBrace,
+    RightParen,
+    Semicolon,
+    Slash,
+    Star,
+    Unknown,
+    UnterminatedComment,
+    UnterminatedString,
+}
+
+/// Describes tokens of the kind, e.g. in the list of tokens a parser
+/// expected.
+impl Display for TokenKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let text = match self {
+            TokenKind::Bang => "`!`",
+            TokenKind::BangEqual => "`!=`",
+            TokenKind::Colon => "`:`",
+            TokenKind::Comma => "`,`",
+            TokenKind::Comment => "comment",
+            TokenKind::DocComment => "doc comment",
+            TokenKind::Dot => "`.`",
+            TokenKind::Eof => "end of input",
+            TokenKind::Equal => "`=`",
+            TokenKind::EqualEqual => "`==`",
+            TokenKind::Greater => "`>`",
+            TokenKind::GreaterEqual => "`>=`",
+            TokenKind::Identifier => "identifier",
+            TokenKind::Keyword(kw) => return write!(f, "`{kw}`"),
+            TokenKind::LeftBrace => "`{`",
+            TokenKind::LeftParen => "`(`",
+            TokenKind::Less => "`<`",
+            TokenKind::LessEqual => "`<=`",
+            TokenKind::String => "string",
+            TokenKind::Number | TokenKind::Integer => "number",
+            TokenKind::Minus => "`-`",
+            TokenKind::NumberOutOfRange => "number out of range",
+            TokenKind::Plus => "`+`",
+            TokenKind::Question => "`?`",
+            TokenKind::RightBrace => "`}`",
+            TokenKind::RightParen => "`)`",
+            TokenKind::Semicolon => "`;`",
+            TokenKind::Slash => "`/`",
+            TokenKind::Star => "`*`",
+            TokenKind::Unknown => "unknown characters",
+            TokenKind::UnterminatedComment => "unterminated comment",
+            TokenKind::UnterminatedString => "unterminated string",
+        };
+        f.write_str(text)
+    }
+}
+
+/// A token without its text, made by [`Lexer::next_raw_token`](crate::Lexer::next_raw_token)
+/// without allocating.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawToken {
+    pub kind: TokenKind,
+    pub span: Span,
+    /// Byte offsets of the token in the source.
+    pub start: usize,
+    pub end: usize,
+}
+
+impl RawToken {
+    /// The text of the token in the source it was lexed from.
+    #[inline]
+    pub fn text<'a>(&self, src: &'a str) -> &'a str {
+        &src[self.start..self.end]
+    }
+
+    /// The token with its value, e.g. a string with its escapes replaced.
+    #[inline]
+    pub fn token_type<'a>(&self, src: &'a str) -> TokenType<'a> {
+        let text = self.text(src);
+        match self.kind {
+            TokenKind::Bang => TokenType::Bang,
+            TokenKind::BangEqual => TokenType::BangEqual,
+            TokenKind::Colon => TokenType::Colon,
+            TokenKind::Comma => TokenType::Comma,
+            TokenKind::Comment => TokenType::Comment(Cow::Borrowed(text)),
+            TokenKind::DocComment => {
+                let doc = &text[3..];
+                let doc = doc.strip_prefix(' ').unwrap_or(doc).trim_end();
+                TokenType::DocComment(Cow::Borrowed(doc))
+            }
+            TokenKind::Dot => TokenType::Dot,
+            TokenKind::Eof => TokenType::Eof,
+            TokenKind::Equal => TokenType::Equal,
+            TokenKind::EqualEqual => TokenType::EqualEqual,
+            TokenKind::Greater => TokenType::Greater,
+            TokenKind::GreaterEqual => TokenType::GreaterEqual,
+            TokenKind::Identifier => TokenType::Identifier(Cow::Borrowed(text)),
+            TokenKind::Keyword(kw) => TokenType::Keyword(kw),
+            TokenKind::LeftBrace => TokenType::LeftBrace,
+            TokenKind::LeftParen => TokenType::LeftParen,
+            TokenKind::Less => TokenType::Less,
+            TokenKind::LessEqual => TokenType::LessEqual,
+            TokenKind::String if text.starts_with('r') => {
+                TokenType::Literal(Literal::String(raw_content(text, true)))
+            }
+            TokenKind::String => {
+                TokenType::Literal(Literal::String(unescape(&text[1..text.len() - 1])))
+            }
+            TokenKind::Number => TokenType::Literal(Literal::Number(
+                parse_float(text).expect("lexed as a number in range"),
+            )),
+            TokenKind::Integer => TokenType::Literal(Literal::Integer(
+                text.parse().expect("lexed as an integer in range"),
+            )),
+            TokenKind::Minus => TokenType::Minus,
+            TokenKind::NumberOutOfRange => TokenType::NumberOutOfRange(Cow::Borrowed(text)),
+            TokenKind::Plus => TokenType::Plus,
+            TokenKind::Question => TokenType::Question,
+            TokenKind::RightBrace => TokenType::RightBrace,
+            TokenKind::RightParen => TokenType::RightParen,
+            TokenKind::Semicolon => TokenType::Semicolon,
+            TokenKind::Slash => TokenType::Slash,
+            TokenKind::Star => TokenType::Star,
+            TokenKind::Unknown => TokenType::Unknown(Cow::Borrowed(text)),
+            TokenKind::UnterminatedComment => TokenType::UnterminatedComment,
+            TokenKind::UnterminatedString if text.starts_with('r') => {
+                TokenType::UnterminatedString(raw_content(text, false))
+            }
+            TokenKind::UnterminatedString => TokenType::UnterminatedString(unescape(&text[1..])),
+        }
+    }
+}
+
+/// Gets the content of a raw string `r#"..."#` without its delimiters, the
+/// closing one being missing unless `terminated`. Only `\r\n` is replaced, by
+/// `\n`.
+fn raw_content(text: &str, terminated: bool) -> Cow<'_, str> {
+    let quoted = text[1..].trim_start_matches('#');
+    let hashes = text.len() - 1 - quoted.len();
+    let content = if terminated {
+        &quoted[1..quoted.len() - 1 - hashes]
+    } else {
+        &quoted[1..]
+    };
+    if content.contains("\r\n") {
+        Cow::Owned(content.replace("\r\n", "\n"))
+    } else {
+        Cow::Borrowed(content)
+    }
+}
+
+/// Replaces the escapes of the content of a string, and reads `\r\n` as `\n`.
+/// Unknown escapes are kept as they are, a `\` at the end is dropped.
+fn unescape(content: &str) -> Cow<'_, str> {
+    if !content.contains(['\\', '\r']) {
+        return Cow::Borrowed(content);
+    }
+
+    let mut chars = content.chars().peekable();
+    let mut next = move || match chars.next() {
+        Some('\r') if chars.peek() == Some(&'\n') => chars.next(),
+        c => c,
+    };
+    let mut result = String::with_capacity(content.len());
+    while let Some(c) = next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match next() {
+            Some('\\') => result.push('\\'),
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('t') => result.push('\t'),
+            Some('"') => result.push('"'),
+            Some(c) => {
+                result.push('\\');
+                result.push(c);
+            }
+            None => break,
         }
     }
+    Cow::Owned(result)
 }
 
 #[derive(Debug)]
-pub struct Token {
-    pub token_type: TokenType,
+pub struct Token<'a> {
+    pub token_type: TokenType<'a>,
     pub span: Span,
 }
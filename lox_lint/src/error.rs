@@ -0,0 +1,48 @@
+use crate::rule::{Level, Rule};
+use lox_lexer::Span;
+use std::fmt::Display;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Lint {
+    #[error("{0}: assignment used as a condition, did you mean `==`?\n  note: wrap the assignment in parentheses to silence this warning")]
+    AssignmentInCondition(Span),
+    #[error("{span}: condition is always {value}")]
+    ConstantCondition { span: Span, value: bool },
+    #[error("{0}: division by zero")]
+    DivisionByZero(Span),
+    #[error("{0}: unreachable statement")]
+    DeadCode(Span),
+    #[error("{pos}: `{name}` shadows the variable defined at {shadowed}")]
+    Shadowing {
+        pos: Span,
+        name: String,
+        shadowed: Span,
+    },
+}
+
+impl Lint {
+    pub fn rule(&self) -> Rule {
+        match self {
+            Lint::AssignmentInCondition(_) => Rule::AssignmentInCondition,
+            Lint::ConstantCondition { .. } => Rule::ConstantCondition,
+            Lint::DeadCode(_) => Rule::DeadCode,
+            Lint::DivisionByZero(_) => Rule::DivisionByZero,
+            Lint::Shadowing { .. } => Rule::Shadowing,
+        }
+    }
+}
+
+/// A lint or resolver warning, together with the level of its rule.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub rule: Rule,
+    pub level: Level,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}[{}]: {}", self.level, self.rule, self.message)
+    }
+}
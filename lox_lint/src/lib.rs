@@ -0,0 +1,11 @@
+pub mod error;
+pub mod linter;
+pub mod rule;
+#[cfg(test)]
+mod test;
+
+pub use crate::{
+    error::{Diagnostic, Lint},
+    linter::Linter,
+    rule::{Level, LintConfig, Rule},
+};
@@ -0,0 +1,255 @@
+use crate::{
+    error::{Diagnostic, Lint},
+    rule::{Level, LintConfig, Rule},
+};
+use lox_ast::{
+    number::Number,
+    visit::{walk_binary, walk_expr, walk_stmt, walk_ternary, Visitor},
+    *,
+};
+use lox_lexer::Span;
+use lox_resolver::ResolverError;
+use std::{collections::HashMap, io::Write, mem};
+
+/// Reports suspicious but valid code in a parsed program.
+#[derive(Default)]
+pub struct Linter {
+    config: LintConfig,
+    lints: Vec<Lint>,
+    globals: HashMap<String, Span>,
+    scopes: Vec<HashMap<String, Span>>,
+}
+
+impl Linter {
+    pub fn new(config: LintConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Lints `ast`, leaving out the lints of allowed rules.
+    pub fn lint(&mut self, ast: &[Statement]) -> Box<[Diagnostic]> {
+        self.visit_statements(ast);
+        mem::take(&mut self.lints)
+            .into_iter()
+            .filter_map(|lint| self.diagnostic(lint.rule(), &lint))
+            .collect()
+    }
+
+    /// Applies the rule levels to the warnings reported by the resolver.
+    pub fn check_resolver_warnings(&self, warnings: &[ResolverError]) -> Box<[Diagnostic]> {
+        warnings
+            .iter()
+            .filter_map(|warning| {
+                let rule = match warning {
+                    ResolverError::UndefinedVar(..) => Rule::UndeclaredAssignment,
+                    ResolverError::UnusedVar(..) => Rule::UnusedVariable,
+                    ResolverError::UnusedParam(..) => Rule::UnusedParameter,
+                    ResolverError::UselessExpression(_) | ResolverError::UselessComparison(_) => {
+                        Rule::UselessExpression
+                    }
+                    // The other variants are only reported as errors.
+                    _ => return None,
+                };
+                self.diagnostic(rule, warning)
+            })
+            .collect()
+    }
+
+    /// Writes the lints of `ast` and the warnings of the resolver to `output`,
+    /// returning whether any of them is denied.
+    pub fn report(
+        &mut self,
+        ast: &[Statement],
+        warnings: &[ResolverError],
+        output: &mut impl Write,
+    ) -> bool {
+        let diagnostics = self.check_resolver_warnings(warnings);
+        let lints = self.lint(ast);
+        let mut denied = false;
+        for diagnostic in diagnostics.iter().chain(lints.iter()) {
+            writeln!(output, "{diagnostic}").unwrap();
+            denied |= diagnostic.level == Level::Deny;
+        }
+        denied
+    }
+
+    fn diagnostic(&self, rule: Rule, message: &impl ToString) -> Option<Diagnostic> {
+        match self.config.level(rule) {
+            Level::Allow => None,
+            level => Some(Diagnostic {
+                rule,
+                level,
+                message: message.to_string(),
+            }),
+        }
+    }
+
+    fn visit_statements(&mut self, statements: &[Statement]) {
+        let mut diverged = false;
+        for stmt in statements {
+            if diverged {
+                self.lints.push(Lint::DeadCode(stmt.get_span()));
+                diverged = false;
+            }
+            walk_stmt(self, stmt);
+            diverged |= diverges(stmt);
+        }
+    }
+
+    fn declare(&mut self, var: &Variable) {
+        let ident = &var.ident;
+        let Some((scope, enclosing)) = self.scopes.split_last_mut() else {
+            self.globals.insert(ident.name.to_string(), ident.span);
+            return;
+        };
+        // Redefining a variable in the same scope is an error of the resolver.
+        if let Some(&shadowed) = enclosing
+            .iter()
+            .rev()
+            .chain([&self.globals])
+            .find_map(|scope| scope.get(&*ident.name))
+        {
+            self.lints.push(Lint::Shadowing {
+                pos: ident.span,
+                name: ident.name.to_string(),
+                shadowed,
+            });
+        }
+        scope.insert(ident.name.to_string(), ident.span);
+    }
+
+    fn lint_function(&mut self, function: &FnDecl) {
+        self.scopes.push(HashMap::new());
+        for param in function.params.iter() {
+            self.declare(param);
+        }
+        self.visit_statements(&function.body);
+        self.scopes.pop();
+    }
+
+    /// Reports an assignment used as a condition, unless it's parenthesized to
+    /// show that it's intended, as in `while ((line = next()))`.
+    fn check_condition(&mut self, condition: &Expr) {
+        if let Expr::Assign(_) | Expr::Set(_) = condition {
+            self.lints
+                .push(Lint::AssignmentInCondition(condition.get_span()));
+        }
+        if let Some(value) = condition.constant_value() {
+            self.lints.push(Lint::ConstantCondition {
+                span: condition.get_span(),
+                value: value.is_truthy(),
+            });
+        }
+    }
+}
+
+/// Whether the statements following `stmt` are unreachable.
+fn diverges(stmt: &Statement) -> bool {
+    match stmt {
+        Statement::Return(_) => true,
+        Statement::Block(block) => block.statements.iter().any(diverges),
+        Statement::If(If {
+            then_branch,
+            else_branch: Some(else_branch),
+            ..
+        }) => diverges(then_branch) && diverges(else_branch),
+        _ => false,
+    }
+}
+
+impl Visitor for Linter {
+    type Result = ();
+
+    fn visit_if(&mut self, if_stmt: &If) -> Self::Result {
+        self.check_condition(&if_stmt.condition);
+        walk_expr(self, &if_stmt.condition);
+        walk_stmt(self, &if_stmt.then_branch);
+        if let Some(else_branch) = &if_stmt.else_branch {
+            walk_stmt(self, else_branch);
+        }
+    }
+
+    fn visit_while(&mut self, while_stmt: &While) -> Self::Result {
+        // `while (true)` and `for (;;)` are the usual ways to write a loop
+        // which is left with `return`.
+        let condition = &while_stmt.condition;
+        if !matches!(
+            condition.ungroup(),
+            Expr::Literal(Literal {
+                value: Lit::Bool(true),
+                ..
+            })
+        ) {
+            self.check_condition(condition);
+        }
+        walk_expr(self, &while_stmt.condition);
+        walk_stmt(self, &while_stmt.body);
+    }
+
+    fn visit_ternary(&mut self, ternary: &Ternary) -> Self::Result {
+        self.check_condition(&ternary.condition);
+        walk_ternary(self, ternary)
+    }
+
+    fn visit_binary(&mut self, binary: &BinaryExpr) -> Self::Result {
+        // Whether it fails at runtime depends on `LanguageConfig`, but either
+        // way it's unlikely to be intended.
+        if matches!(binary.operator, BinaryOp::Divide)
+            && binary
+                .right
+                .constant_value()
+                .and_then(|value| value.as_number())
+                .is_some_and(Number::is_zero)
+        {
+            self.lints
+                .push(Lint::DivisionByZero(binary.right.get_span()));
+        }
+        walk_binary(self, binary)
+    }
+
+    fn visit_block(&mut self, block: &Block) -> Self::Result {
+        self.scopes.push(HashMap::new());
+        self.visit_statements(&block.statements);
+        self.scopes.pop();
+    }
+
+    fn visit_var_decl(&mut self, var_decl: &VarDecl) -> Self::Result {
+        if let Some(initializer) = &var_decl.initializer {
+            walk_expr(self, initializer);
+        }
+        self.declare(&var_decl.var);
+    }
+
+    fn visit_function(&mut self, function: &FnDecl) -> Self::Result {
+        self.declare(&function.var);
+        self.lint_function(function);
+    }
+
+    fn visit_class(&mut self, class: &ClassDecl) -> Self::Result {
+        self.declare(&class.var);
+        for method in class.methods.iter() {
+            self.lint_function(method);
+        }
+    }
+
+    fn visit_return(&mut self, return_stmt: &Return) -> Self::Result {
+        if let Some(expr) = &return_stmt.expr {
+            walk_expr(self, expr);
+        }
+    }
+
+    fn visit_fn_call(&mut self, fn_call: &FnCall) -> Self::Result {
+        walk_expr(self, &fn_call.callee);
+        for argument in fn_call.arguments.iter() {
+            walk_expr(self, argument);
+        }
+    }
+
+    fn visit_super(&mut self, _super_expr: &Super) -> Self::Result {}
+
+    fn visit_literal(&mut self, _literal: &Literal) -> Self::Result {}
+
+    fn visit_var(&mut self, _var: &Variable) -> Self::Result {}
+}
@@ -0,0 +1,139 @@
+use std::{collections::HashMap, fmt::Display, str::FromStr};
+use thiserror::Error;
+
+/// A named class of lints. The id of a rule is stable, it is printed with each
+/// diagnostic and used to configure the rule, e.g. `--deny dead-code`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    AssignmentInCondition,
+    ConstantCondition,
+    DeadCode,
+    DivisionByZero,
+    Shadowing,
+    UndeclaredAssignment,
+    UnusedParameter,
+    UnusedVariable,
+    UselessExpression,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 9] = [
+        Rule::AssignmentInCondition,
+        Rule::ConstantCondition,
+        Rule::DeadCode,
+        Rule::DivisionByZero,
+        Rule::Shadowing,
+        Rule::UndeclaredAssignment,
+        Rule::UnusedParameter,
+        Rule::UnusedVariable,
+        Rule::UselessExpression,
+    ];
+
+    pub fn id(self) -> &'static str {
+        match self {
+            Rule::AssignmentInCondition => "assignment-in-condition",
+            Rule::ConstantCondition => "constant-condition",
+            Rule::DeadCode => "dead-code",
+            Rule::DivisionByZero => "division-by-zero",
+            Rule::Shadowing => "shadowing",
+            Rule::UndeclaredAssignment => "undeclared-assignment",
+            Rule::UnusedParameter => "unused-parameter",
+            Rule::UnusedVariable => "unused-variable",
+            Rule::UselessExpression => "useless-expression",
+        }
+    }
+
+    /// Shadowing is often intended, so it has to be enabled explicitly.
+    pub fn default_level(self) -> Level {
+        match self {
+            Rule::Shadowing => Level::Allow,
+            _ => Level::Warn,
+        }
+    }
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+impl FromStr for Rule {
+    type Err = LintConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Rule::ALL
+            .into_iter()
+            .find(|rule| rule.id() == s)
+            .ok_or_else(|| LintConfigError::UnknownRule(s.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Level::Allow => "allow",
+            Level::Warn => "warning",
+            Level::Deny => "error",
+        })
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum LintConfigError {
+    #[error("unknown lint rule `{0}`, expected one of {}", Rule::ALL.map(Rule::id).join(", "))]
+    UnknownRule(String),
+    #[error("missing lint rule after `{0}`")]
+    MissingRule(String),
+}
+
+/// The level of each rule, rules which aren't set use [`Rule::default_level`].
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    levels: HashMap<Rule, Level>,
+}
+
+impl LintConfig {
+    pub fn set(&mut self, rule: Rule, level: Level) -> &mut Self {
+        self.levels.insert(rule, level);
+        self
+    }
+
+    pub fn level(&self, rule: Rule) -> Level {
+        self.levels
+            .get(&rule)
+            .copied()
+            .unwrap_or_else(|| rule.default_level())
+    }
+
+    /// Applies the `--allow`, `--warn` and `--deny` flags in `args`, each
+    /// followed by a rule id, and returns the remaining arguments.
+    pub fn parse_args(
+        &mut self,
+        args: impl IntoIterator<Item = String>,
+    ) -> Result<Vec<String>, LintConfigError> {
+        let mut rest = vec![];
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let level = match arg.as_str() {
+                "--allow" => Level::Allow,
+                "--warn" => Level::Warn,
+                "--deny" => Level::Deny,
+                _ => {
+                    rest.push(arg);
+                    continue;
+                }
+            };
+            let rule = args.next().ok_or(LintConfigError::MissingRule(arg))?;
+            self.set(rule.parse()?, level);
+        }
+        Ok(rest)
+    }
+}
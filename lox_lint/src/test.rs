@@ -0,0 +1,182 @@
+use crate::{Level, LintConfig, Linter, Rule};
+use lox_resolver::Resolver;
+
+fn lint(src: &str, config: LintConfig) -> Vec<(Rule, Level)> {
+    let mut ast = lox_parser::parse(src).into_result().unwrap();
+    let mut resolver = Resolver::default();
+    assert!(resolver.resolve(&mut ast).is_none());
+    let mut linter = Linter::new(config);
+    let warnings = linter.check_resolver_warnings(&resolver.take_warnings());
+    let lints = linter.lint(&ast);
+    warnings
+        .iter()
+        .chain(lints.iter())
+        .map(|diagnostic| (diagnostic.rule, diagnostic.level))
+        .collect()
+}
+
+#[test]
+fn rule_ids() {
+    for rule in Rule::ALL {
+        assert_eq!(rule.id().parse::<Rule>().unwrap(), rule);
+    }
+    assert!("unused".parse::<Rule>().is_err());
+}
+
+#[test]
+fn unused_variable() {
+    let src = "fun f(_x) { var a = 1; var b = 2; print b; b = 3; }";
+    assert_eq!(
+        lint(src, LintConfig::default()),
+        [(Rule::UnusedVariable, Level::Warn)]
+    );
+}
+
+#[test]
+fn unused_parameter() {
+    let src = "fun f(a, b, _c) { return b; } class A { m(x) {} }";
+    assert_eq!(
+        lint(src, LintConfig::default()),
+        [
+            (Rule::UnusedParameter, Level::Warn),
+            (Rule::UnusedParameter, Level::Warn)
+        ]
+    );
+    let src = "fun f(_a) { var _b = _a; }";
+    assert_eq!(lint(src, LintConfig::default()), []);
+}
+
+#[test]
+fn constant_condition() {
+    let src = r#"
+        if (1 < 2) print 1;
+        if ("a" + 1 == "a1") print 2;
+        var x = !nil ? 1 : 2;
+        while (0) print x;
+        while (true) print x;
+        for (;;) print x;
+        if (x) print x;
+        if (x and false) print x;
+    "#;
+    assert_eq!(
+        lint(src, LintConfig::default()),
+        [(Rule::ConstantCondition, Level::Warn); 4]
+    );
+}
+
+#[test]
+fn division_by_zero() {
+    let src = "var x = 1; print x / 0; print x / (1 - 1); print x / -0.0; print x / 0.5;";
+    assert_eq!(
+        lint(src, LintConfig::default()),
+        [(Rule::DivisionByZero, Level::Warn); 3]
+    );
+    // The folded condition isn't reported as constant.
+    let src = "if (1 / 0 > 1) print 1;";
+    assert_eq!(
+        lint(src, LintConfig::default()),
+        [(Rule::DivisionByZero, Level::Warn)]
+    );
+}
+
+#[test]
+fn assignment_in_condition() {
+    let src = "var a; if (a = 1) print a; while ((a = nil)) print a;";
+    assert_eq!(
+        lint(src, LintConfig::default()),
+        [(Rule::AssignmentInCondition, Level::Warn)]
+    );
+
+    // Fields and `for` loops are checked too, while the condition of a
+    // ternary can only be an assignment in parentheses.
+    let src = r#"
+        class A {} var a = A(); var b;
+        while (a.x = b) print a;
+        for (; b = nil;) print b;
+        print (b = a) ? 1 : 2;
+        if (a == b) print a;
+    "#;
+    assert_eq!(
+        lint(src, LintConfig::default()),
+        [(Rule::AssignmentInCondition, Level::Warn); 2]
+    );
+
+    let mut config = LintConfig::default();
+    config.set(Rule::AssignmentInCondition, Level::Allow);
+    assert_eq!(lint(src, config), []);
+}
+
+#[test]
+fn dead_code() {
+    let src = r#"
+fun f(x) {
+    if (x) { return 1; } else return 2;
+    print "unreachable";
+    print "reported once";
+}
+f(1);"#;
+    assert_eq!(
+        lint(src, LintConfig::default()),
+        [(Rule::DeadCode, Level::Warn)]
+    );
+}
+
+#[test]
+fn shadowing_is_allowed_by_default() {
+    let src = "var a = 1; { var a = 2; print a; } fun f(a) { print a; } f(a);";
+    assert_eq!(lint(src, LintConfig::default()), []);
+
+    let mut config = LintConfig::default();
+    config.set(Rule::Shadowing, Level::Warn);
+    assert_eq!(
+        lint(src, config),
+        [
+            (Rule::Shadowing, Level::Warn),
+            (Rule::Shadowing, Level::Warn)
+        ]
+    );
+}
+
+#[test]
+fn configure_from_args() {
+    let mut config = LintConfig::default();
+    let args = [
+        "lox",
+        "--deny",
+        "unused-variable",
+        "main.lox",
+        "--allow",
+        "dead-code",
+    ];
+    let rest = config.parse_args(args.map(String::from)).unwrap();
+    assert_eq!(rest, ["lox", "main.lox"]);
+
+    let src = "fun f() { var a; return; print 1; } f();";
+    assert_eq!(lint(src, config), [(Rule::UnusedVariable, Level::Deny)]);
+
+    assert!(LintConfig::default()
+        .parse_args(["--deny".to_string()])
+        .is_err());
+}
+
+#[test]
+fn report() {
+    let mut ast = lox_parser::parse("fun f() { var a; } g = 1;")
+        .into_result()
+        .unwrap();
+    let mut resolver = Resolver::default();
+    assert!(resolver.resolve(&mut ast).is_none());
+    let warnings = resolver.take_warnings();
+
+    let mut output = Vec::new();
+    assert!(!Linter::new(LintConfig::default()).report(&ast, &warnings, &mut output));
+    assert_eq!(String::from_utf8(output).unwrap().lines().count(), 2);
+
+    let mut config = LintConfig::default();
+    config.set(Rule::UnusedVariable, Level::Deny);
+    config.set(Rule::UndeclaredAssignment, Level::Allow);
+    let mut output = Vec::new();
+    assert!(Linter::new(config).report(&ast, &warnings, &mut output));
+    let output = String::from_utf8(output).unwrap();
+    assert_eq!(output.lines().count(), 1, "{output}");
+}
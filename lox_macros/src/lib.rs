@@ -4,7 +4,17 @@ mod utils;
 use operations::derive_operations;
 use proc_macro::TokenStream;
 
-#[proc_macro_derive(OpCodec)]
+/// Derives the encoding and decoding of operations and an executor trait with
+/// a method for each of them.
+///
+/// With `#[opcodec(context)]`, the trait takes a context type parameter, a
+/// reference to which is passed to each method, e.g. the constants of the
+/// code being executed.
+///
+/// Fields marked `#[opcodec(skip)]` are neither encoded nor passed to the
+/// executor, and those marked `#[opcodec(with = "path")]` are encoded with
+/// `path::encode` and decoded with `path::decode`.
+#[proc_macro_derive(OpCodec, attributes(opcodec))]
 pub fn operations(input: TokenStream) -> TokenStream {
     derive_operations(input)
 }
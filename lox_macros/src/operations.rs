@@ -1,65 +1,174 @@
 use proc_macro::TokenStream;
 use quote::{format_ident, quote};
-use syn::{parse_macro_input, Data, DataEnum, DeriveInput, Fields, Ident, Variant, Visibility};
+use syn::{
+    parse_macro_input, Data, DataEnum, DeriveInput, Field, Fields, Ident, LitStr, Path, Type,
+    Variant, Visibility,
+};
 
 use crate::utils::camel_to_snake;
 
 struct OpField {
     ident: Ident,
     fields: Fields,
+    operands: Vec<Operand>,
+}
+
+/// A field of an operation, set by `#[opcodec(...)]` attributes.
+struct Operand {
+    name: Ident,
+    ty: Type,
+    /// Neither encoded nor passed to the executor.
+    skip: bool,
+    /// A module with the `encode` and `decode` functions to use instead of
+    /// the `Encode` and `Decode` impls of the type.
+    with: Option<Path>,
+}
+
+impl Operand {
+    fn new(i: usize, field: &Field) -> syn::Result<Self> {
+        let mut operand = Self {
+            name: field
+                .ident
+                .clone()
+                .unwrap_or_else(|| format_ident!("arg{i}")),
+            ty: field.ty.clone(),
+            skip: false,
+            with: None,
+        };
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("opcodec"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("skip") {
+                    operand.skip = true;
+                    Ok(())
+                } else if meta.path.is_ident("with") {
+                    operand.with = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `skip` or `with`"))
+                }
+            })?;
+        }
+        Ok(operand)
+    }
 }
 
 pub fn derive_operations(input: TokenStream) -> TokenStream {
     let DeriveInput {
-        vis, ident, data, ..
+        attrs,
+        vis,
+        ident,
+        data,
+        ..
     } = parse_macro_input!(input as DeriveInput);
 
+    let mut context = false;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("opcodec")) {
+        let result = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("context") {
+                context = true;
+                Ok(())
+            } else {
+                Err(meta.error("expected `context`"))
+            }
+        });
+        if let Err(err) = result {
+            return err.to_compile_error().into();
+        }
+    }
+
     let op_fields = if let Data::Enum(DataEnum { variants, .. }) = data {
         variants
             .into_iter()
-            .map(|Variant { ident, fields, .. }| OpField { ident, fields })
-            .collect::<Vec<_>>()
+            .map(|Variant { ident, fields, .. }| {
+                let operands = fields
+                    .iter()
+                    .enumerate()
+                    .map(|(i, field)| Operand::new(i, field))
+                    .collect::<syn::Result<_>>()?;
+                Ok(OpField {
+                    ident,
+                    fields,
+                    operands,
+                })
+            })
+            .collect::<syn::Result<Vec<_>>>()
     } else {
-        unimplemented!()
+        Err(syn::Error::new_spanned(
+            &ident,
+            "`OpCodec` can only be derived for enums",
+        ))
+    };
+    let op_fields = match op_fields {
+        Ok(op_fields) => op_fields,
+        Err(err) => return err.to_compile_error().into(),
     };
 
     let encoder = derive_encode_for_operation(&ident, &op_fields);
 
-    let executor = get_executor(&vis, &ident, &op_fields);
+    let decoder = derive_decode_for_operation(&ident, &op_fields);
+
+    let sizes = get_operand_sizes(&ident, &op_fields);
+
+    let executor = get_executor(&vis, &ident, &op_fields, context);
 
     quote! {
         #encoder
 
+        #decoder
+
+        #sizes
+
         #executor
     }
     .into()
 }
 
+fn named_operands_error(fields: &Fields) -> proc_macro2::TokenStream {
+    syn::Error::new_spanned(fields, "named operands are not supported").to_compile_error()
+}
+
 fn derive_encode_for_operation(ident: &Ident, op_fields: &[OpField]) -> proc_macro2::TokenStream {
-    let encoders = op_fields
-        .iter()
-        .enumerate()
-        .map(|(op_code, OpField { ident, fields })| {
+    let encoders = op_fields.iter().enumerate().map(
+        |(
+            op_code,
+            OpField {
+                ident,
+                fields,
+                operands,
+            },
+        )| {
             let op_code = op_code as u8;
             match fields {
-                Fields::Named(_) => todo!(),
-                Fields::Unnamed(fields_unnamed) => {
-                    let fields = fields_unnamed
-                        .unnamed
-                        .iter()
-                        .enumerate()
-                        .map(|(idx, _)| format_ident!("arg{idx}"))
-                        .collect::<Vec<_>>();
+                Fields::Named(_) => {
+                    let err = named_operands_error(fields);
+                    quote!(Self::#ident { .. } => #err)
+                }
+                Fields::Unnamed(_) => {
+                    let bindings = operands.iter().map(|operand| match operand.skip {
+                        true => format_ident!("_"),
+                        false => operand.name.clone(),
+                    });
+                    let encoders = operands.iter().filter(|operand| !operand.skip).map(
+                        |Operand { name, with, .. }| match with {
+                            Some(with) => quote!(#with::encode(#name, writer);),
+                            None => quote!(#name.encode(writer);),
+                        },
+                    );
                     quote! {
-                        Self::#ident(#(#fields,)*) => {
+                        Self::#ident(#(#bindings,)*) => {
                             writer.write(&[#op_code]);
-                            #(#fields.encode(writer);)*
+                            #(#encoders)*
                         }
                     }
                 }
                 Fields::Unit => quote!(Self::#ident => writer.write(&[#op_code])),
             }
-        });
+        },
+    );
 
     quote! {
         impl<Writer: Write> Encode<Writer> for #ident {
@@ -80,33 +189,135 @@ fn derive_encode_for_operation(ident: &Ident, op_fields: &[OpField]) -> proc_mac
     }
 }
 
+fn derive_decode_for_operation(ident: &Ident, op_fields: &[OpField]) -> proc_macro2::TokenStream {
+    let decoders = op_fields.iter().enumerate().map(
+        |(
+            op_code,
+            OpField {
+                ident,
+                fields,
+                operands,
+            },
+        )| {
+            let op_code = op_code as u8;
+            match fields {
+                Fields::Named(_) => {
+                    let err = named_operands_error(fields);
+                    quote!(#op_code => #err)
+                }
+                Fields::Unnamed(_) => {
+                    let decoders = operands.iter().map(|Operand { ty, skip, with, .. }| {
+                        let decode = match with {
+                            Some(with) => quote!(#with::decode(&buf[current..])),
+                            None => quote!(<#ty>::decode(&buf[current..])),
+                        };
+                        match skip {
+                            // Skipped operands aren't encoded, so they can't be
+                            // recovered.
+                            true => quote!(Default::default()),
+                            false => quote!({
+                                let (value, size) = #decode?;
+                                current += size;
+                                value
+                            }),
+                        }
+                    });
+                    quote! {
+                        #op_code => {
+                            let mut current = 1;
+                            let operation = Self::#ident(#(#decoders,)*);
+                            Ok((operation, current))
+                        }
+                    }
+                }
+                Fields::Unit => quote!(#op_code => Ok((Self::#ident, 1))),
+            }
+        },
+    );
+
+    quote! {
+        impl Decode for #ident {
+            fn decode(buf: &[u8]) -> DecodeResult<Self> {
+                let Some(&op_code) = buf.first() else {
+                    return Err(Box::new(DecoderErrorDetail::NoEnoughData { expected: 1, rem: 0 }));
+                };
+                match op_code {
+                    #(#decoders,)*
+                    _ => Err(Box::new(DecoderErrorDetail::UnknownOpcode(op_code))),
+                }
+            }
+        }
+    }
+}
+
+fn get_operand_sizes(ident: &Ident, op_fields: &[OpField]) -> proc_macro2::TokenStream {
+    let count = op_fields.len();
+    let sizes = op_fields.iter().map(|OpField { operands, .. }| {
+        let operands = operands.iter().filter(|operand| !operand.skip);
+        if operands.clone().any(|operand| operand.with.is_some()) {
+            quote!(None)
+        } else {
+            let types = operands.map(|operand| &operand.ty);
+            quote!(Some(0 #(+ <#types as FixedSize>::SIZE)*))
+        }
+    });
+
+    quote! {
+        impl #ident {
+            /// The number of bytes of the operands of each opcode, `None` if
+            /// it varies.
+            pub const OPERAND_SIZES: [Option<usize>; #count] = [#(#sizes,)*];
+
+            /// The number of bytes of the instruction starting with `opcode`,
+            /// `None` if it varies or the opcode is unknown.
+            pub fn size_of_instruction(opcode: u8) -> Option<usize> {
+                Some(1 + (*Self::OPERAND_SIZES.get(opcode as usize)?)?)
+            }
+        }
+    }
+}
+
 fn get_executor(
     vis: &Visibility,
     ident: &Ident,
     op_fields: &[OpField],
+    context: bool,
 ) -> proc_macro2::TokenStream {
     let trait_name = format_ident!("{}Executor", ident);
     let name = format_ident!("{}", camel_to_snake(&ident.to_string()));
     let executor_engine = format_ident!("execute_{name}");
 
+    // The context is passed by the engine to every method of the trait.
+    let (trait_generics, engine_generics, context_param, context_arg) = if context {
+        (
+            quote!(<Context: ?Sized>),
+            quote!(<Context: ?Sized, E: #trait_name<Context>>),
+            quote!(, context: &Context),
+            quote!(context,),
+        )
+    } else {
+        (quote!(), quote!(<E: #trait_name>), quote!(), quote!())
+    };
+
     let (executor_fns, decoder_arms): (Vec<_>, Vec<_>) = op_fields
         .iter().enumerate()
-        .map(|(op_code,OpField { ident, fields })| {
+        .map(|(op_code, OpField { ident, operands, .. })| {
             let op_code = op_code as u8;
             let fn_name = format_ident!("{}", camel_to_snake(&ident.to_string()));
 
-            let (params, (args, arg_names)): (Vec<_>, (Vec<_>, Vec<_>)) = fields
+            let (params, (args, arg_names)): (Vec<_>, (Vec<_>, Vec<_>)) = operands
                 .iter()
-                .enumerate()
-                .map(|(i, arg)| {
-                    let name = arg.ident.clone().unwrap_or_else(|| format_ident!("arg{i}"));
-                    let ty = &arg.ty;
-
+                .filter(|operand| !operand.skip)
+                .map(|Operand { name, ty, with, .. }| {
+                    let decode = match with {
+                        Some(with) => quote!(#with::decode(&buf[current..])),
+                        None => quote!(<#ty>::decode(&buf[current..])),
+                    };
                     (
                         quote!(#name: #ty),
                         (
                             quote! {
-                                let (#name, size) = match <#ty>::decode(&buf[current..]) {
+                                let (#name, size) = match #decode {
                                     Ok(v) => v,
                                     Err(err) => return Err(DecoderError::from_detail(current, *err).into()),
                                 };
@@ -119,29 +330,41 @@ fn get_executor(
                 .unzip();
 
             (
-                quote!(fn #fn_name(&mut self #(, #params)*) -> ExecutorResult<RuntimeError>;),
+                quote!(fn #fn_name(&mut self #context_param #(, #params)*) -> ExecutorResult<RuntimeError>;),
                 quote!(#op_code => {
-                    let mut current = next_code_index + 1;
+                    let offset = next_code_index;
+                    let mut current = offset + 1;
                     #(#args)*
                     next_code_index = current;
-                    executor.#fn_name(#(#arg_names,)*)?;
+                    if let Err(error) = executor.#fn_name(#context_arg #(#arg_names,)*) {
+                        return Err(ExecutorError::RuntimeError { error, offset });
+                    }
                 }),
             )
         })
         .unzip();
 
     quote! {
-        #vis trait #trait_name: Sized {
+        #vis trait #trait_name #trait_generics: Sized {
             #(#executor_fns)*
+
+            /// Returns the byte offset to continue at if the last operation
+            /// jumped, instead of the operation following it.
+            fn take_jump(&mut self) -> Option<usize> {
+                None
+            }
         }
 
-        #vis fn #executor_engine<E: #trait_name>(executor: &mut E, buf: &[u8]) -> ExecutorResult<ExecutorError> {
+        #vis fn #executor_engine #engine_generics(executor: &mut E #context_param, buf: &[u8]) -> ExecutorResult<ExecutorError> {
             let mut next_code_index = 0;
             while next_code_index < buf.len() {
                 let code = buf[next_code_index];
                 match code {
                     #(#decoder_arms,)*
-                    _ => unimplemented!()
+                    _ => return Err(ExecutorError::unknown_opcode(buf, next_code_index)),
+                }
+                if let Some(target) = executor.take_jump() {
+                    next_code_index = target;
                 }
             }
 
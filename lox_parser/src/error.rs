@@ -1,23 +1,72 @@
-use lox_lexer::{Span, TokenType};
+use std::fmt::{self, Display, Formatter};
+
+use lox_lexer::{Extension, Span, TokenKind, TokenType};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum ParserError {
-    #[error("{1}: unexpected token `{0}`")]
-    UnexpectedToken(TokenType, Span),
+    #[error("{span}: unexpected token `{found}`{expected}")]
+    UnexpectedToken {
+        found: TokenType<'static>,
+        /// Kinds of tokens which would have been accepted instead.
+        expected: Expected,
+        span: Span,
+    },
+    #[error("{1}: unknown character(s) `{0}`")]
+    UnknownCharacters(String, Span),
     #[error("{span}: expect {expected}, found `{found}`")]
     ExpectStructure {
         expected: &'static str,
-        found: TokenType,
+        found: TokenType<'static>,
         span: Span,
     },
+    #[error("{1}: number `{0}` is out of range")]
+    NumberOutOfRange(String, Span),
+    #[error("{span}: {} requires the `{extension}` extension", extension.construct())]
+    ExtensionRequired { extension: Extension, span: Span },
+    #[error("{0}: nesting is too deep")]
+    TooDeep(Span),
     #[error("{0}: too many parameters")]
     TooManyParameters(Span),
     #[error("Invalid left value in assignment, {0}")]
     InvalidLeftValue(Span),
+    #[error("{end}: unterminated string, the string starts at {start}")]
+    UnterminatedString {
+        content: String,
+        /// The opening quote.
+        start: Span,
+        /// Where the closing quote is missing.
+        end: Span,
+    },
 }
 
 impl ParserError {
+    pub fn span(&self) -> Span {
+        match self {
+            ParserError::UnexpectedToken { span, .. }
+            | ParserError::UnknownCharacters(_, span)
+            | ParserError::ExpectStructure { span, .. }
+            | ParserError::NumberOutOfRange(_, span)
+            | ParserError::ExtensionRequired { span, .. }
+            | ParserError::TooDeep(span)
+            | ParserError::TooManyParameters(span)
+            | ParserError::InvalidLeftValue(span)
+            | ParserError::UnterminatedString { end: span, .. } => *span,
+        }
+    }
+
+    pub(crate) fn unexpected_token(
+        found: TokenType,
+        expected: Vec<TokenKind>,
+        span: Span,
+    ) -> Box<Self> {
+        Box::new(Self::UnexpectedToken {
+            found: found.into_owned(),
+            expected: Expected(expected.into_boxed_slice()),
+            span,
+        })
+    }
+
     pub(crate) fn expect_structure(
         expected: &'static str,
         found: TokenType,
@@ -25,10 +74,31 @@ impl ParserError {
     ) -> Box<Self> {
         Box::new(Self::ExpectStructure {
             expected,
-            found,
+            found: found.into_owned(),
             span,
         })
     }
 }
 
+/// The kinds of tokens expected where an unexpected token is found, in the
+/// order the grammar tries them.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Expected(pub Box<[TokenKind]>);
+
+impl Display for Expected {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &*self.0 {
+            [] => Ok(()),
+            [kind] => write!(f, ", expected {kind}"),
+            [first, kinds @ .., last] => {
+                write!(f, ", expected one of {first}")?;
+                for kind in kinds {
+                    write!(f, ", {kind}")?;
+                }
+                write!(f, " or {last}")
+            }
+        }
+    }
+}
+
 pub type PResult<T> = Result<T, Box<ParserError>>;
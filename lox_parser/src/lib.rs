@@ -1,11 +1,46 @@
-use lox_lexer::Lexer;
+use error::ParserError;
+use lox_ast::{Expr, Statement};
+use lox_lexer::{LanguageConfig, Lexer, SourceId, SourceMap};
 use parser::{Parser, ParserResult};
 
 pub mod error;
 pub mod parser;
 mod precedence;
+#[cfg(test)]
+mod test;
 
 pub fn parse(src: &str) -> ParserResult {
     let mut parser = Parser::new(Lexer::new(src));
     parser.parse()
 }
+
+/// Parses `src` with only the extensions enabled in `config`.
+pub fn parse_with_config(src: &str, config: &LanguageConfig) -> ParserResult {
+    let mut parser = Parser::new(Lexer::new(src).with_config(config)).with_config(config);
+    parser.parse()
+}
+
+/// Parses the source `source` of `sources` with only the extensions enabled
+/// in `config`, so that the spans of the AST and errors refer to it.
+pub fn parse_source(
+    sources: &SourceMap,
+    source: SourceId,
+    config: &LanguageConfig,
+) -> ParserResult {
+    let lexer = Lexer::new(sources.text(source))
+        .with_config(config)
+        .with_source(source);
+    let mut parser = Parser::new(lexer).with_config(config);
+    parser.parse()
+}
+
+/// Parses `src` as a single expression, e.g. for evaluating an expression
+/// without wrapping it in a statement.
+pub fn parse_expr(src: &str) -> Result<Expr, Box<[ParserError]>> {
+    Parser::new(Lexer::new(src)).parse_expr()
+}
+
+/// Parses `src` as a single declaration or statement.
+pub fn parse_declaration(src: &str) -> Result<Statement, Box<[ParserError]>> {
+    Parser::new(Lexer::new(src)).parse_declaration()
+}
@@ -2,47 +2,95 @@ use crate::{
     error::{PResult, ParserError},
     precedence::Operator,
 };
+use fnv::FnvBuildHasher;
 use lox_ast::*;
-use lox_lexer::{Keyword, Lexer, Literal, Span, Token, TokenType};
-use std::mem;
+use lox_lexer::{
+    Extension, Keyword, LanguageConfig, Lexer, Literal, Position, Span, Token, TokenKind,
+    TokenType, DEFAULT_MAX_DEPTH,
+};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, VecDeque},
+    mem,
+    sync::Arc,
+};
 
 pub struct Parser<'a> {
     lexer: Lexer<'a>,
-    token: Option<Token>,
+    /// Tokens which have been peeked but not consumed yet.
+    tokens: VecDeque<Token<'a>>,
+    /// Names of the identifiers parsed so far, so that each name is only
+    /// allocated once. FNV is much faster than the default hasher on names,
+    /// which are short and can't be chosen to collide by anyone but the
+    /// author of the file.
+    names: HashMap<&'a str, Arc<str>, FnvBuildHasher>,
+    /// Doc comments keyed by the start of the token following them.
+    docs: HashMap<Position, String>,
     errors: Vec<ParserError>,
+    /// Kinds of tokens checked for since the last consumed token, which are
+    /// reported as expected if the next token turns out to be unexpected.
+    expected: Vec<TokenKind>,
+    /// Whether the ternary operator is enabled.
+    ternary: bool,
+    /// Number of declarations, statements and expressions being parsed.
+    depth: usize,
+    max_depth: usize,
+    /// Set once the input nests too deeply, after which the rest of it is
+    /// skipped.
+    too_deep: bool,
 }
 
-macro_rules! eat {
-    ($self: expr, $token_type: pat) => {{
-        let next_token = $self.next_token();
-        match next_token.token_type {
-            $token_type => next_token.span,
-            t => return Err(p(ParserError::UnexpectedToken(t, next_token.span))),
-        }
-    }};
+pub type Ast = Vec<Statement>;
+/// The statements which could be parsed, along with the errors of those which
+/// couldn't and are left out.
+#[derive(Debug)]
+pub struct ParserResult {
+    pub ast: Ast,
+    pub errors: Box<[ParserError]>,
 }
 
-macro_rules! match_keyword {
-    ($self: expr, $kw: pat) => {
-        matches!($self.look_ahead(), TokenType::Keyword($kw))
-    };
-}
+impl ParserResult {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
 
-pub type Ast = Vec<Statement>;
-pub type ParserResult = Result<Ast, Box<[ParserError]>>;
+    /// Returns the AST only if all of it could be parsed, e.g. to run it.
+    pub fn into_result(self) -> Result<Ast, Box<[ParserError]>> {
+        if self.errors.is_empty() {
+            Ok(self.ast)
+        } else {
+            Err(self.errors)
+        }
+    }
+}
 
 impl<'a> Parser<'a> {
     pub fn new(lexer: Lexer<'a>) -> Self {
         Self {
             lexer,
-            token: None,
+            tokens: VecDeque::new(),
+            names: HashMap::default(),
+            docs: HashMap::new(),
             errors: vec![],
+            expected: vec![],
+            ternary: true,
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            too_deep: false,
         }
     }
 
+    /// Enables the extensions of `config`, which should also be passed to
+    /// [`Lexer::with_config`].
+    pub fn with_config(mut self, config: &LanguageConfig) -> Self {
+        self.ternary = config.ternary;
+        self.max_depth = config.max_depth;
+        self
+    }
+
     pub fn parse(&mut self) -> ParserResult {
         let mut statements = vec![];
-        while !matches!(self.look_ahead(), TokenType::Eof) {
+        while !self.check(TokenKind::Eof) {
             match self.declaration() {
                 Ok(stmt) => statements.push(stmt),
                 Err(err) => {
@@ -51,31 +99,166 @@ impl<'a> Parser<'a> {
                 }
             }
         }
-        if !self.errors.is_empty() {
-            Err(mem::take(&mut self.errors).into_boxed_slice())
-        } else {
-            Ok(statements)
+        ParserResult {
+            ast: statements,
+            errors: self.take_errors(),
+        }
+    }
+
+    /// Parses input made of a single expression.
+    pub fn parse_expr(&mut self) -> Result<Expr, Box<[ParserError]>> {
+        let result = self.expression();
+        self.finish(result)
+    }
+
+    /// Parses input made of a single declaration or statement.
+    pub fn parse_declaration(&mut self) -> Result<Statement, Box<[ParserError]>> {
+        let result = self.declaration();
+        self.finish(result)
+    }
+
+    /// Checks that the input ends after `result`, returning it if there are no
+    /// errors.
+    fn finish<T>(&mut self, result: PResult<T>) -> Result<T, Box<[ParserError]>> {
+        let item = match result {
+            Ok(item) => {
+                if let Err(err) = self.eat(TokenKind::Eof) {
+                    self.errors.push(*err);
+                }
+                Some(item)
+            }
+            Err(err) => {
+                self.errors.push(*err);
+                None
+            }
+        };
+        match item {
+            Some(item) if self.errors.is_empty() => Ok(item),
+            _ => Err(self.take_errors()),
+        }
+    }
+
+    /// Takes the errors, leaving out those caused by skipping the input after
+    /// it nests too deeply, e.g. every enclosing block missing its `}`.
+    fn take_errors(&mut self) -> Box<[ParserError]> {
+        let mut errors = mem::take(&mut self.errors);
+        if let Some(i) = errors
+            .iter()
+            .position(|err| matches!(err, ParserError::TooDeep(_)))
+        {
+            errors.truncate(i + 1);
+        }
+        errors.into_boxed_slice()
+    }
+
+    /// Parses with `parse` one level deeper, failing if the input nests too
+    /// deeply for the parser and later passes to recurse without overflowing
+    /// the stack.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> PResult<T>) -> PResult<T> {
+        if self.depth >= self.max_depth {
+            self.peek_nth(0);
+            self.too_deep = true;
+            return Err(p(ParserError::TooDeep(self.tokens[0].span)));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Gets the next token from the lexer, reporting and skipping runs of
+    /// unknown characters so that they don't cascade into more errors. Doc
+    /// comments are set aside for the declaration they precede.
+    fn lex(&mut self) -> Token<'a> {
+        let mut doc: Option<String> = None;
+        loop {
+            let token = self.lexer.next_token();
+            for (extension, span) in self.lexer.take_disabled_uses() {
+                self.errors
+                    .push(ParserError::ExtensionRequired { extension, span });
+            }
+            match token.token_type {
+                TokenType::Unknown(chars) => self.errors.push(ParserError::UnknownCharacters(
+                    chars.into_owned(),
+                    token.span,
+                )),
+                TokenType::DocComment(line) => match &mut doc {
+                    Some(doc) => {
+                        doc.push('\n');
+                        doc.push_str(&line);
+                    }
+                    None => doc = Some(line.into_owned()),
+                },
+                _ => {
+                    if let Some(doc) = doc {
+                        self.docs.insert(token.span.start, doc);
+                    }
+                    return token;
+                }
+            }
         }
     }
 
-    fn next_token(&mut self) -> Token {
-        match self.token.take() {
+    /// Takes the doc comment before the token starting at `span`.
+    fn take_doc(&mut self, span: Span) -> Option<String> {
+        self.docs.remove(&span.start)
+    }
+
+    fn next_token(&mut self) -> Token<'a> {
+        self.expected.clear();
+        match self.tokens.pop_front() {
             Some(token) => token,
-            None => self.lexer.next_token(),
+            None => self.lex(),
+        }
+    }
+
+    fn look_ahead(&mut self) -> &TokenType<'a> {
+        self.peek_nth(0)
+    }
+
+    /// Whether the next token is of the given kind.
+    fn check(&mut self, kind: TokenKind) -> bool {
+        let found = self.look_ahead().kind() == kind;
+        if !found {
+            self.expect(kind);
+        }
+        found
+    }
+
+    fn expect(&mut self, kind: TokenKind) {
+        if !self.expected.contains(&kind) {
+            self.expected.push(kind);
+        }
+    }
+
+    /// Consumes the next token, which must be of the given kind.
+    fn eat(&mut self, kind: TokenKind) -> PResult<Span> {
+        if self.check(kind) {
+            return Ok(self.next_token().span);
         }
+        let expected = std::mem::take(&mut self.expected);
+        let next_token = self.next_token();
+        Err(ParserError::unexpected_token(
+            next_token.token_type,
+            expected,
+            next_token.span,
+        ))
     }
 
-    fn look_ahead(&mut self) -> &TokenType {
-        &self
-            .token
-            .get_or_insert_with(|| self.lexer.next_token())
-            .token_type
+    /// Peeks the `n`th upcoming token without consuming anything, `peek_nth(0)`
+    /// being the next token.
+    fn peek_nth(&mut self, n: usize) -> &TokenType<'a> {
+        while self.tokens.len() <= n {
+            let token = self.lex();
+            self.tokens.push_back(token);
+        }
+        &self.tokens[n].token_type
     }
 
     fn get_identifier(&mut self) -> PResult<Ident> {
         let next_token = self.next_token();
         match next_token.token_type {
-            TokenType::Identifier(name) => Ok(Ident::from_name(name, next_token.span)),
+            TokenType::Identifier(name) => Ok(Ident::from_name(self.intern(name), next_token.span)),
             t => Err(ParserError::expect_structure(
                 "identifier",
                 t,
@@ -84,7 +267,25 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Shares the names of identifiers, see `names`.
+    fn intern(&mut self, name: Cow<'a, str>) -> Arc<str> {
+        match name {
+            Cow::Borrowed(name) => self
+                .names
+                .entry(name)
+                .or_insert_with(|| name.into())
+                .clone(),
+            Cow::Owned(name) => name.into(),
+        }
+    }
+
     fn synchronize(&mut self) {
+        if self.too_deep {
+            while !self.check(TokenKind::Eof) {
+                self.next_token();
+            }
+            return;
+        }
         loop {
             match self.look_ahead() {
                 TokenType::Eof => return,
@@ -110,22 +311,25 @@ impl<'a> Parser<'a> {
     }
 
     fn declaration(&mut self) -> PResult<Statement> {
-        match self.look_ahead() {
-            TokenType::Keyword(Keyword::Var) => self.var_decl(),
+        self.nested(|this| match this.look_ahead() {
+            TokenType::Keyword(Keyword::Var) => this.var_decl(),
             TokenType::Keyword(Keyword::Fun) => {
-                self.next_token();
-                Ok(Statement::FnDecl(self.function()?))
+                let start = this.next_token().span;
+                let mut function = this.function()?;
+                function.span = start.extends_with(&function.span);
+                function.doc = this.take_doc(start);
+                Ok(Statement::FnDecl(function))
             }
-            TokenType::Keyword(Keyword::Class) => self.class(),
-            _ => self.statement(),
-        }
+            TokenType::Keyword(Keyword::Class) => this.class(),
+            _ => this.statement(),
+        })
     }
 
     fn var_decl(&mut self) -> PResult<Statement> {
-        self.next_token();
+        let start = self.next_token().span;
         let next_token = self.next_token();
         let name = match next_token.token_type {
-            TokenType::Identifier(ident) => ident,
+            TokenType::Identifier(ident) => self.intern(ident),
             t => {
                 return Err(ParserError::expect_structure(
                     "identifier",
@@ -135,16 +339,17 @@ impl<'a> Parser<'a> {
             }
         };
 
-        let initializer = if matches!(self.look_ahead(), TokenType::Equal) {
+        let initializer = if self.check(TokenKind::Equal) {
             self.next_token();
             Some(self.expression()?)
         } else {
             None
         };
 
-        eat!(self, TokenType::Semicolon);
+        let end = self.eat(TokenKind::Semicolon)?;
 
         Ok(Statement::Var(VarDecl {
+            span: start.extends_with(&end),
             var: Variable::from_name(name, next_token.span),
             initializer,
         }))
@@ -153,54 +358,59 @@ impl<'a> Parser<'a> {
     fn function(&mut self) -> PResult<FnDecl> {
         let ident = self.get_identifier()?;
 
-        let start = eat!(self, TokenType::LeftParen);
+        let start = self.eat(TokenKind::LeftParen)?;
 
         let mut parameters = vec![];
-        if !matches!(self.look_ahead(), TokenType::RightParen) {
+        if !self.check(TokenKind::RightParen) {
             loop {
                 parameters.push(self.get_identifier()?.into());
-                match self.look_ahead() {
-                    TokenType::Comma => {
-                        self.next_token();
-                    }
-                    _ => break,
+                if !self.check(TokenKind::Comma) {
+                    break;
                 }
+                self.next_token();
             }
         }
-        let end = eat!(self, TokenType::RightParen);
+        let end = self.eat(TokenKind::RightParen)?;
 
         if parameters.len() > 255 {
             self.errors
                 .push(ParserError::TooManyParameters(start.extends_with(&end)));
         }
 
+        let (body, body_span) = self.block()?;
+
         Ok(FnDecl {
+            span: ident.span.extends_with(&body_span),
+            doc: self.take_doc(ident.span),
             var: ident.into(),
             params: parameters.into_boxed_slice(),
-            body: self.block()?,
+            body,
             num_of_locals: 0,
+            local_names: None,
         })
     }
 
     fn class(&mut self) -> PResult<Statement> {
-        self.next_token();
+        let start = self.next_token().span;
         let ident = self.get_identifier()?;
 
-        let super_class = if matches!(self.look_ahead(), TokenType::Less) {
+        let super_class = if self.check(TokenKind::Less) {
             self.next_token();
             Some(self.get_identifier()?)
         } else {
             None
         };
 
-        eat!(self, TokenType::LeftBrace);
+        self.eat(TokenKind::LeftBrace)?;
         let mut methods = vec![];
-        while !matches!(self.look_ahead(), TokenType::RightBrace) {
+        while !self.check(TokenKind::RightBrace) {
             methods.push(self.function()?);
         }
-        eat!(self, TokenType::RightBrace);
+        let end = self.eat(TokenKind::RightBrace)?;
 
         Ok(Statement::ClassDecl(ClassDecl {
+            span: start.extends_with(&end),
+            doc: self.take_doc(start),
             var: ident.into(),
             super_class: super_class.map(From::from),
             methods: methods.into_boxed_slice(),
@@ -208,38 +418,44 @@ impl<'a> Parser<'a> {
     }
 
     fn statement(&mut self) -> PResult<Statement> {
-        match self.look_ahead() {
-            TokenType::Keyword(Keyword::Print) => self.print_statement(),
-            TokenType::LeftBrace => Ok(Statement::Block(Block::new(self.block()?))),
-            TokenType::Keyword(Keyword::If) => self.if_statement(),
-            TokenType::Keyword(Keyword::While) => self.while_statement(),
-            TokenType::Keyword(Keyword::For) => self.for_statement(),
-            TokenType::Keyword(Keyword::Return) => self.return_statement(),
-            _ => self.expression_statement(),
-        }
+        self.nested(|this| match this.look_ahead() {
+            TokenType::Keyword(Keyword::Print) => this.print_statement(),
+            TokenType::LeftBrace => {
+                let (statements, span) = this.block()?;
+                Ok(Statement::Block(Block::new(statements, span)))
+            }
+            TokenType::Keyword(Keyword::If) => this.if_statement(),
+            TokenType::Keyword(Keyword::While) => this.while_statement(),
+            TokenType::Keyword(Keyword::For) => this.for_statement(),
+            TokenType::Keyword(Keyword::Return) => this.return_statement(),
+            _ => this.expression_statement(),
+        })
     }
 
     fn print_statement(&mut self) -> PResult<Statement> {
-        self.next_token();
-        let stmt = Statement::Print(Print {
-            expr: self.expression()?,
-        });
-        eat!(self, TokenType::Semicolon);
-        Ok(stmt)
+        let start = self.next_token().span;
+        let expr = self.expression()?;
+        let end = self.eat(TokenKind::Semicolon)?;
+        Ok(Statement::Print(Print {
+            span: start.extends_with(&end),
+            expr,
+        }))
     }
 
     fn if_statement(&mut self) -> PResult<Statement> {
-        self.next_token();
-        eat!(self, TokenType::LeftParen);
+        let start = self.next_token().span;
+        self.eat(TokenKind::LeftParen)?;
         let condition = self.expression()?;
-        eat!(self, TokenType::RightParen);
+        self.eat(TokenKind::RightParen)?;
         let then_branch = Box::new(self.statement()?);
-        let else_branch = if match_keyword!(self, Keyword::Else) {
+        let else_branch = if self.check(TokenKind::Keyword(Keyword::Else)) {
+            self.next_token();
             Some(Box::new(self.statement()?))
         } else {
             None
         };
         Ok(Statement::If(If {
+            span: start.extends_with(&else_branch.as_ref().unwrap_or(&then_branch).get_span()),
             condition,
             then_branch,
             else_branch,
@@ -247,17 +463,23 @@ impl<'a> Parser<'a> {
     }
 
     fn while_statement(&mut self) -> PResult<Statement> {
-        self.next_token();
-        eat!(self, TokenType::LeftParen);
+        let start = self.next_token().span;
+        self.eat(TokenKind::LeftParen)?;
         let condition = self.expression()?;
-        eat!(self, TokenType::RightParen);
+        self.eat(TokenKind::RightParen)?;
         let body = Box::new(self.statement()?);
-        Ok(Statement::While(While { condition, body }))
+        Ok(Statement::While(While {
+            span: start.extends_with(&body.get_span()),
+            condition,
+            body,
+        }))
     }
 
+    /// Desugars `for` into a `while` loop, all the synthesized nodes taking the
+    /// span of the whole `for` statement.
     fn for_statement(&mut self) -> PResult<Statement> {
-        self.next_token();
-        eat!(self, TokenType::LeftParen);
+        let start = self.next_token().span;
+        self.eat(TokenKind::LeftParen)?;
         let initializer = match self.look_ahead() {
             TokenType::Semicolon => {
                 self.next_token();
@@ -271,59 +493,74 @@ impl<'a> Parser<'a> {
             TokenType::Semicolon => None,
             _ => Some(self.expression()?),
         };
+        self.eat(TokenKind::Semicolon)?;
 
         let increment = match self.look_ahead() {
             TokenType::RightParen => None,
             _ => Some(self.expression()?),
         };
-        eat!(self, TokenType::RightParen);
+        self.eat(TokenKind::RightParen)?;
 
         let body = self.statement()?;
+        let span = start.extends_with(&body.get_span());
 
         let inner = Statement::While(While {
+            span,
             condition: condition.unwrap_or(Expr::literal(Lit::Bool(true), Span::dummy())),
             body: match increment {
                 Some(increment) => Box::new(Statement::Block(Block::new(
-                    [body, Statement::Expression(Expression { expr: increment })].into(),
+                    [
+                        body,
+                        Statement::Expression(Expression {
+                            span: increment.get_span(),
+                            expr: increment,
+                        }),
+                    ]
+                    .into(),
+                    span,
                 ))),
                 None => Box::new(body),
             },
         });
 
         Ok(match initializer {
-            Some(initializer) => Statement::Block(Block::new([initializer, inner].into())),
+            Some(initializer) => Statement::Block(Block::new([initializer, inner].into(), span)),
             None => inner,
         })
     }
 
     fn return_statement(&mut self) -> PResult<Statement> {
-        let token = self.next_token();
-        let expr = if !matches!(self.look_ahead(), TokenType::Semicolon) {
+        let start = self.next_token().span;
+        let expr = if !self.check(TokenKind::Semicolon) {
             Some(self.expression()?)
         } else {
             None
         };
 
-        eat!(self, TokenType::Semicolon);
+        let end = self.eat(TokenKind::Semicolon)?;
 
         Ok(Statement::Return(Return {
-            span: token.span,
+            span: start.extends_with(&end),
             expr,
         }))
     }
 
     fn expression_statement(&mut self) -> PResult<Statement> {
-        let stmt = Statement::Expression(Expression {
-            expr: self.expression()?,
-        });
-        eat!(self, TokenType::Semicolon);
-        Ok(stmt)
+        let expr = self.expression()?;
+        let end = self.eat(TokenKind::Semicolon)?;
+        Ok(Statement::Expression(Expression {
+            span: expr.get_span().extends_with(&end),
+            expr,
+        }))
     }
 
-    fn block(&mut self) -> PResult<Box<[Statement]>> {
-        self.next_token();
+    fn block(&mut self) -> PResult<(Box<[Statement]>, Span)> {
+        let start = self.next_token().span;
         let mut statements = vec![];
-        while !matches!(self.look_ahead(), TokenType::RightBrace) {
+        while !matches!(
+            self.look_ahead().kind(),
+            TokenKind::RightBrace | TokenKind::Eof
+        ) {
             match self.declaration() {
                 Ok(stmt) => statements.push(stmt),
                 Err(err) => {
@@ -333,9 +570,9 @@ impl<'a> Parser<'a> {
             }
         }
 
-        eat!(self, TokenType::RightBrace);
+        let end = self.eat(TokenKind::RightBrace)?;
 
-        Ok(statements.into_boxed_slice())
+        Ok((statements.into_boxed_slice(), start.extends_with(&end)))
     }
 
     fn expression(&mut self) -> PResult<Expr> {
@@ -343,6 +580,11 @@ impl<'a> Parser<'a> {
     }
 
     fn expr_precedence(&mut self, op: Operator) -> PResult<Expr> {
+        self.nested(|this| this.operand_and_operators(op))
+    }
+
+    /// Parses an operand followed by the operators binding tighter than `op`.
+    fn operand_and_operators(&mut self, op: Operator) -> PResult<Expr> {
         let next_token = self.next_token();
 
         let mut expr = match next_token.token_type {
@@ -350,32 +592,33 @@ impl<'a> Parser<'a> {
                 Keyword::False => Expr::literal(Lit::Bool(false), next_token.span),
                 Keyword::True => Expr::literal(Lit::Bool(true), next_token.span),
                 Keyword::Nil => Expr::literal(Lit::Nil, next_token.span),
-                Keyword::This => {
-                    Expr::Var(Variable::from_name("this".to_string(), next_token.span))
-                }
+                Keyword::This => Expr::Var(Variable::from_name("this", next_token.span)),
                 Keyword::Super => Expr::Super(Super {
-                    var: Variable::from_name("super".to_string(), next_token.span),
+                    var: Variable::from_name("super", next_token.span),
+                    this: Variable::from_name("this", next_token.span),
                     method: {
-                        eat!(self, TokenType::Dot);
+                        self.eat(TokenKind::Dot)?;
                         self.get_identifier()?
                     },
                 }),
                 kw => {
-                    return Err(Box::new(ParserError::UnexpectedToken(
+                    return Err(ParserError::unexpected_token(
                         TokenType::Keyword(kw),
+                        vec![],
                         next_token.span,
-                    )))
+                    ))
                 }
             },
             TokenType::LeftParen => {
                 let grouped = self.expression()?;
-                let Span { end, .. } = eat!(self, TokenType::RightParen);
-                Expr::group(grouped, next_token.span.start, end)
+                let end = self.eat(TokenKind::RightParen)?;
+                Expr::group(grouped, next_token.span.extends_with(&end))
             }
             TokenType::Literal(lit) => Expr::literal(
                 match lit {
-                    Literal::String(s) => Lit::String(s),
+                    Literal::String(s) => Lit::String(s.into_owned()),
                     Literal::Number(n) => Lit::Number(n),
+                    Literal::Integer(n) => Lit::Integer(n),
                 },
                 next_token.span,
             ),
@@ -384,11 +627,37 @@ impl<'a> Parser<'a> {
                 next_token.span,
                 self.expr_precedence(Operator::Prefix)?,
             ),
-            TokenType::Identifier(name) => Expr::Var(Variable::from_name(name, next_token.span)),
+            TokenType::Identifier(name) => {
+                Expr::Var(Variable::from_name(self.intern(name), next_token.span))
+            }
+            TokenType::NumberOutOfRange(text) => {
+                return Err(p(ParserError::NumberOutOfRange(
+                    text.into_owned(),
+                    next_token.span,
+                )))
+            }
+            TokenType::UnterminatedString(content) => {
+                let Span { start, end, source } = next_token.span;
+                let mut quote_end = start;
+                quote_end.column += 1;
+                return Err(p(ParserError::UnterminatedString {
+                    content: content.into_owned(),
+                    start: Span {
+                        start,
+                        end: quote_end,
+                        source,
+                    },
+                    end: Span {
+                        start: end,
+                        end,
+                        source,
+                    },
+                }));
+            }
             t => {
                 return Err(p(ParserError::ExpectStructure {
                     expected: "expression",
-                    found: t,
+                    found: t.into_owned(),
                     span: next_token.span,
                 }))
             }
@@ -400,8 +669,14 @@ impl<'a> Parser<'a> {
                     let next_token = self.next_token();
                     expr = match next_op {
                         Operator::Ternary => {
+                            if !self.ternary {
+                                self.errors.push(ParserError::ExtensionRequired {
+                                    extension: Extension::Ternary,
+                                    span: next_token.span,
+                                });
+                            }
                             let truthy = self.expression()?;
-                            eat!(self, TokenType::Colon);
+                            self.eat(TokenKind::Colon)?;
                             Expr::ternary(expr, truthy, self.expr_precedence(next_op)?)
                         }
                         Operator::Assign => match expr {
@@ -432,19 +707,17 @@ impl<'a> Parser<'a> {
     fn fn_call(&mut self, callee: Expr) -> PResult<Expr> {
         let mut arguments = vec![];
 
-        if !matches!(self.look_ahead(), TokenType::RightParen) {
+        if !self.check(TokenKind::RightParen) {
             loop {
                 arguments.push(self.expression()?);
-                match self.look_ahead() {
-                    TokenType::Comma => {
-                        self.next_token();
-                    }
-                    _ => break,
+                if !self.check(TokenKind::Comma) {
+                    break;
                 }
+                self.next_token();
             }
         }
 
-        let Span { end, .. } = eat!(self, TokenType::RightParen);
+        let Span { end, .. } = self.eat(TokenKind::RightParen)?;
         Ok(Expr::FnCall(FnCall {
             callee: Box::new(callee),
             arguments: arguments.into_boxed_slice(),
@@ -0,0 +1,256 @@
+use lox_lexer::{Keyword, LanguageConfig, SourceMap};
+
+use lox_ast::{Expr, Lit, Statement};
+
+use crate::{
+    error::ParserError, parse, parse_declaration, parse_expr, parse_source, parse_with_config,
+};
+
+fn errors(src: &str, config: &LanguageConfig) -> Vec<String> {
+    match parse_with_config(src, config).into_result() {
+        Ok(_) => Vec::new(),
+        Err(errors) => errors.iter().map(|e| e.to_string()).collect(),
+    }
+}
+
+#[test]
+fn strict_mode() {
+    let src = "print true ? 1 : 2; /* outer /* inner */ */ print r\"raw\";";
+    assert!(parse(src).is_ok());
+    assert_eq!(
+        errors(src, &LanguageConfig::strict()),
+        [
+            "from line 1 column 12 to line 1 column 13: conditional expression requires the `ternary` extension",
+            "from line 1 column 30 to line 1 column 32: nested comment requires the `nested-comments` extension",
+            "from line 1 column 51 to line 1 column 57: raw string requires the `raw-strings` extension",
+        ]
+    );
+}
+
+#[test]
+fn extension_keywords() {
+    let src = "var break = 1;";
+    assert!(parse(src).is_ok());
+    let config = LanguageConfig {
+        keywords: vec![Keyword::Break],
+        ..Default::default()
+    };
+    assert_eq!(errors(src, &config).len(), 1);
+}
+
+#[test]
+fn single_expression() {
+    assert!(matches!(parse_expr("1 + 2 * x"), Ok(Expr::Binary(_))));
+    assert!(matches!(parse_expr("f(a)\n"), Ok(Expr::FnCall(_))));
+
+    let errors = parse_expr("1 + 2;").unwrap_err();
+    assert_eq!(
+        errors[0].to_string(),
+        "from line 1 column 6 to line 1 column 7: unexpected token `;`, expected end of input"
+    );
+    let errors = parse_expr("1 @ 2").unwrap_err();
+    assert_eq!(errors.len(), 2);
+}
+
+#[test]
+fn raw_string_literal() {
+    let src = "r#\"\\d+ \"quoted\"\n\"# + x";
+    let Ok(Expr::Binary(binary)) = parse_expr(src) else {
+        panic!("expected a binary expression");
+    };
+    let Expr::Literal(literal) = &*binary.left else {
+        panic!("expected a literal");
+    };
+    assert!(matches!(&literal.value, Lit::String(s) if s == "\\d+ \"quoted\"\n"));
+    assert_eq!(
+        literal.span.to_string(),
+        "from line 1 column 1 to line 2 column 3"
+    );
+}
+
+#[test]
+fn single_declaration() {
+    assert!(matches!(
+        parse_declaration("fun f() { return 1; }"),
+        Ok(Statement::FnDecl(_))
+    ));
+    assert!(matches!(
+        parse_declaration("print 1;"),
+        Ok(Statement::Print(_))
+    ));
+
+    let errors = parse_declaration("var a = 1; var b = 2;").unwrap_err();
+    assert_eq!(
+        errors[0].to_string(),
+        "from line 1 column 12 to line 1 column 15: unexpected token `var`, expected end of input"
+    );
+    assert!(parse_declaration("var a = ;").is_err());
+}
+
+#[test]
+fn expected_tokens() {
+    let config = LanguageConfig::default();
+    assert_eq!(
+        errors("print f(a b);", &config),
+        ["from line 1 column 11 to line 1 column 12: unexpected token `b`, expected one of `,` or `)`"]
+    );
+    assert_eq!(
+        errors("class A < B ;", &config),
+        ["from line 1 column 13 to line 1 column 14: unexpected token `;`, expected `{`"]
+    );
+    assert_eq!(
+        errors("var a = 1 print a;", &config),
+        ["from line 1 column 11 to line 1 column 16: unexpected token `print`, expected `;`"]
+    );
+}
+
+#[test]
+fn partial_ast() {
+    let result = parse("var a = 1;\nvar b = ;\nprint a;\nfun f( {}");
+    assert!(!result.is_ok());
+    assert_eq!(result.errors.len(), 2);
+    assert!(matches!(
+        &result.ast[..],
+        [Statement::Var(_), Statement::Print(_)]
+    ));
+}
+
+#[test]
+fn too_deep() {
+    let config = LanguageConfig {
+        max_depth: 10,
+        ..Default::default()
+    };
+    let nested = |depth| format!("{}1{};", "(".repeat(depth), ")".repeat(depth));
+    assert!(parse_with_config(&nested(3), &config).is_ok());
+    assert_eq!(
+        errors(&nested(20), &config),
+        ["from line 1 column 9 to line 1 column 10: nesting is too deep"]
+    );
+
+    let blocks = format!("{}print 1;{}", "{".repeat(20), "}".repeat(20));
+    assert_eq!(
+        errors(&blocks, &config),
+        ["from line 1 column 6 to line 1 column 7: nesting is too deep"]
+    );
+
+    // The default limit must not overflow the stack of a test thread.
+    let depth = lox_lexer::DEFAULT_MAX_DEPTH;
+    assert!(parse(&nested(depth / 2)).is_ok());
+    assert_eq!(parse(&nested(depth)).errors.len(), 1);
+}
+
+#[test]
+fn shared_names() {
+    let ast = parse("var count = 1; count = count + 1;")
+        .into_result()
+        .unwrap();
+    let [Statement::Var(decl), Statement::Expression(assign)] = &ast[..] else {
+        panic!("unexpected statements {ast:?}");
+    };
+    let Expr::Assign(assign) = &assign.expr else {
+        panic!("unexpected expression {:?}", assign.expr);
+    };
+    assert!(std::sync::Arc::ptr_eq(
+        &decl.var.ident.name,
+        &assign.var.ident.name
+    ));
+}
+
+#[test]
+fn ast_dot() {
+    let ast = parse("print -a + \"b\";").into_result().unwrap();
+    assert_eq!(
+        lox_ast::dot::to_dot(&ast),
+        r#"digraph ast {
+  node [shape=box, fontname=monospace];
+  n0 [label="program"];
+  n1 [label="print\nfrom line 1 column 1 to line 1 column 16"];
+  n2 [label="binary +\nfrom line 1 column 7 to line 1 column 15"];
+  n3 [label="unary -\nfrom line 1 column 7 to line 1 column 9"];
+  n4 [label="variable a\nfrom line 1 column 8 to line 1 column 9"];
+  n3 -> n4;
+  n2 -> n3 [label="left"];
+  n5 [label="literal \"b\"\nfrom line 1 column 12 to line 1 column 15"];
+  n2 -> n5 [label="right"];
+  n1 -> n2;
+  n0 -> n1;
+}
+"#
+    );
+}
+
+#[test]
+fn spans_of_sources() {
+    let mut sources = SourceMap::new();
+    let first = sources.add("a.lox", "print 1;");
+    let second = sources.add("b.lox", "print (1);\nprint 1 +;");
+    assert_ne!(first, second);
+
+    let config = LanguageConfig::default();
+    let ast = parse_source(&sources, first, &config)
+        .into_result()
+        .unwrap();
+    assert_eq!(ast[0].get_span().source, first);
+
+    let errors = parse_source(&sources, second, &config)
+        .into_result()
+        .unwrap_err();
+    let span = errors[0].span();
+    assert_eq!(span.source, second);
+    assert_eq!(sources.name(span.source), "b.lox");
+    assert_eq!(
+        sources.snippet(span),
+        "  |\n2 | print 1 +;\n  |          ^\n"
+    );
+}
+
+#[test]
+fn unterminated_string() {
+    let result = parse("print 1;\nprint \"ab\\tc\n;");
+    let [error @ ParserError::UnterminatedString {
+        content,
+        start,
+        end,
+    }] = &result.errors[..]
+    else {
+        panic!("{:?}", result.errors);
+    };
+    assert_eq!(content, "ab\tc\n;");
+    assert_eq!((start.start.line, start.start.column), (2, 7));
+    assert_eq!((start.end.line, start.end.column), (2, 8));
+    assert_eq!((end.start.line, end.start.column), (3, 2));
+    assert_eq!(end.start, end.end);
+    assert_eq!(error.span(), *end);
+}
+
+#[test]
+fn if_else() {
+    let ast = parse("if (true) print 1; else print 2; print 3;")
+        .into_result()
+        .unwrap();
+    let [Statement::If(statement), Statement::Print(_)] = &ast[..] else {
+        panic!("{ast:?}");
+    };
+    assert!(matches!(
+        statement.else_branch.as_deref(),
+        Some(Statement::Print(_))
+    ));
+}
+
+#[test]
+fn for_condition_needs_semicolon() {
+    assert_eq!(
+        errors("for (var i = 0; i < 1 i = i + 1) {}", &Default::default()),
+        ["from line 1 column 23 to line 1 column 24: unexpected token `i`, expected `;`"]
+    );
+}
+
+#[test]
+fn unclosed_block() {
+    assert_eq!(
+        errors("{ print 1;", &Default::default()),
+        ["from line 1 column 11 to line 1 column 11: unexpected token `end of input`, expected `}`"]
+    );
+    assert_eq!(errors("fun f() { {", &Default::default()).len(), 2);
+}
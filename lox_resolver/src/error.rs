@@ -13,6 +13,8 @@ pub enum ResolverError {
     },
     #[error("{0}: unused variable `{1}`")]
     UnusedVar(Span, String),
+    #[error("{0}: unused parameter `{1}`, prefix it with `_` if it's intended")]
+    UnusedParam(Span, String),
     #[error("Can't use `return` outside of a function, {0}")]
     InvalidReturn(Span),
     #[error("Can't return value in constructor, {0}")]
@@ -23,4 +25,51 @@ pub enum ResolverError {
     InvalidSuper(Span),
     #[error("Can't use `super` in a class with no superclass, {0}")]
     NotSubClass(Span),
+    #[error("{0}: expression statement has no effect")]
+    UselessExpression(Span),
+    #[error("{0}: comparison has no effect, did you mean `=`?")]
+    UselessComparison(Span),
+    #[error("{0}: nesting is too deep")]
+    TooDeep(Span),
+    #[error("{pos}: class `{name}` is defined at {defined_at}")]
+    RedefineClass {
+        pos: Span,
+        name: String,
+        defined_at: Span,
+    },
+    #[error("{pos}: method `{name}` of class `{class}` is defined at {defined_at}")]
+    DuplicateMethod {
+        pos: Span,
+        class: String,
+        name: String,
+        defined_at: Span,
+    },
+}
+
+impl ResolverError {
+    pub fn span(&self) -> Span {
+        match self {
+            ResolverError::UndefinedVar(span, _)
+            | ResolverError::RedefineVar { pos: span, .. }
+            | ResolverError::UnusedVar(span, _)
+            | ResolverError::UnusedParam(span, _)
+            | ResolverError::InvalidReturn(span)
+            | ResolverError::ReturnInConstructor(span)
+            | ResolverError::InvalidThis(span)
+            | ResolverError::InvalidSuper(span)
+            | ResolverError::NotSubClass(span)
+            | ResolverError::UselessExpression(span)
+            | ResolverError::UselessComparison(span)
+            | ResolverError::TooDeep(span)
+            | ResolverError::RedefineClass { pos: span, .. }
+            | ResolverError::DuplicateMethod { pos: span, .. } => *span,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Severity {
+    #[default]
+    Warning,
+    Error,
 }
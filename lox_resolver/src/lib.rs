@@ -1,4 +1,11 @@
 pub mod error;
 pub mod resolver;
+pub mod semantic;
+#[cfg(test)]
+mod test;
 
-pub use crate::{error::ResolverError, resolver::Resolver};
+pub use crate::{
+    error::{ResolverError, Severity},
+    resolver::{Resolver, ResolverOptions},
+    semantic::{Reference, SemanticKind, SemanticToken},
+};
@@ -1,23 +1,40 @@
-use crate::error::ResolverError;
+use crate::{
+    error::{ResolverError, Severity},
+    semantic::{Reference, SemanticKind, SemanticToken},
+};
 use lox_ast::{
     visit_mut::{walk_expr, walk_stmt, VisitorMut},
     *,
 };
-use lox_lexer::Span;
+use lox_lexer::{Span, DEFAULT_MAX_DEPTH};
 use lox_parser::parser::Ast;
-use std::{collections::HashMap, mem};
+use std::{
+    collections::{HashMap, HashSet},
+    mem,
+    sync::Arc,
+};
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 enum VariableStatus {
     Declared,
     Initialized,
     Used,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum VariableKind {
+    Local,
+    Parameter,
+    Class,
+    /// `this` and `super`, which are declared by the resolver itself.
+    Implicit,
+}
+
 struct VarInfo {
     index: IdentIndex,
     defined_at: Span,
     status: VariableStatus,
+    kind: VariableKind,
 }
 
 #[derive(Default)]
@@ -26,9 +43,15 @@ struct Scope {
 }
 
 impl Scope {
-    fn declare(&mut self, name: &str, span: Span, initialized: bool) -> Result<IdentIndex, Span> {
+    fn declare(
+        &mut self,
+        name: &str,
+        span: Span,
+        initialized: bool,
+        kind: VariableKind,
+    ) -> Result<IdentIndex, (IdentIndex, Span)> {
         match self.variables.get(name) {
-            Some(var) => Err(var.defined_at),
+            Some(var) => Err((var.index, var.defined_at)),
             None => {
                 let index = self.variables.len() as IdentIndex;
                 self.variables.insert(
@@ -41,6 +64,7 @@ impl Scope {
                         } else {
                             VariableStatus::Declared
                         },
+                        kind,
                     },
                 );
                 Ok(index)
@@ -48,10 +72,11 @@ impl Scope {
         }
     }
 
-    fn access(&mut self, name: &str, status: VariableStatus) -> Option<IdentIndex> {
+    fn access(&mut self, name: &str, status: VariableStatus) -> Option<&VarInfo> {
         self.variables.get_mut(name).map(|var| {
-            var.status = status;
-            var.index
+            // Assigning to a variable which has been read doesn't make it unused.
+            var.status = var.status.max(status);
+            &*var
         })
     }
 }
@@ -73,17 +98,74 @@ enum FunctionType {
     Method,
 }
 
+#[derive(Debug, Clone)]
+pub struct ResolverOptions {
+    /// How to report assignments to names that are never declared.
+    pub undeclared_assignment: Severity,
+    /// Whether to classify identifiers, see [`Resolver::take_semantic_tokens`].
+    pub semantic_tokens: bool,
+    /// Whether to record what each name refers to, see
+    /// [`Resolver::take_references`].
+    pub references: bool,
+    /// Whether to record the names of the locals of blocks and functions in
+    /// `local_names`, e.g. so that debuggers can show them.
+    pub local_names: bool,
+    /// How deeply statements and expressions may nest, which should be the
+    /// `max_depth` of the `LanguageConfig` the program is parsed with.
+    pub max_depth: usize,
+}
+
+impl Default for ResolverOptions {
+    fn default() -> Self {
+        Self {
+            undeclared_assignment: Severity::default(),
+            semantic_tokens: false,
+            references: false,
+            local_names: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct Resolver {
+    options: ResolverOptions,
     scopes: Vec<Scope>,
+    /// Globals and their first declaration, `None` for globals of the host.
+    globals: HashMap<String, Option<Span>>,
+    global_classes: HashMap<String, Span>,
+    undeclared_assignments: Vec<(Span, String)>,
+    semantic_tokens: Vec<SemanticToken>,
+    references: Vec<Reference>,
+    /// Names which aren't local, classified when the whole program is visited.
+    global_references: Vec<(Span, String)>,
+    /// Fields accessed with `.`, which are methods if any class declares them.
+    properties: Vec<(Span, String)>,
+    method_names: HashSet<String>,
     errors: Vec<ResolverError>,
+    warnings: Vec<ResolverError>,
     class_type: ClassType,
     function_type: FunctionType,
+    /// Number of statements and expressions being resolved.
+    depth: usize,
 }
 
 impl Resolver {
+    pub fn new(options: ResolverOptions) -> Self {
+        Self {
+            options,
+            ..Default::default()
+        }
+    }
+
+    /// Annotates `ast` with the targets of its variables. Everything that can be
+    /// resolved is annotated even if errors are returned, so that tools can
+    /// still work with broken programs, e.g. the partial AST of
+    /// [`ParserResult`](lox_parser::parser::ParserResult).
     pub fn resolve(&mut self, ast: &mut Ast) -> Option<Box<[ResolverError]>> {
         ast.iter_mut().for_each(|stmt| self.visit_stmt(stmt));
+        self.check_undeclared_assignments();
+        self.resolve_global_references();
         if self.errors.is_empty() {
             None
         } else {
@@ -91,34 +173,165 @@ impl Resolver {
         }
     }
 
+    /// Takes the warnings reported by previous calls of [`Resolver::resolve`].
+    pub fn take_warnings(&mut self) -> Box<[ResolverError]> {
+        mem::take(&mut self.warnings).into_boxed_slice()
+    }
+
+    /// Takes the identifiers classified by previous calls of
+    /// [`Resolver::resolve`], ordered by position. Only produced if
+    /// [`ResolverOptions::semantic_tokens`] is set.
+    pub fn take_semantic_tokens(&mut self) -> Box<[SemanticToken]> {
+        let mut tokens = mem::take(&mut self.semantic_tokens);
+        tokens.sort_by_key(|token| (token.span.start.line, token.span.start.column));
+        tokens.into_boxed_slice()
+    }
+
+    /// Takes the references recorded by previous calls of
+    /// [`Resolver::resolve`], ordered by position. Only produced if
+    /// [`ResolverOptions::references`] is set.
+    pub fn take_references(&mut self) -> Box<[Reference]> {
+        let mut references = mem::take(&mut self.references);
+        references.sort_by_key(|reference| reference.span.start);
+        references.into_boxed_slice()
+    }
+
+    /// Registers a global defined by the host, e.g. a native function.
+    pub fn define_global(&mut self, name: &str) {
+        self.globals.insert(name.to_string(), None);
+    }
+
+    fn report(&mut self, severity: Severity, error: ResolverError) {
+        match severity {
+            Severity::Warning => self.warnings.push(error),
+            Severity::Error => self.errors.push(error),
+        }
+    }
+
+    /// Globals can be declared after a function assigning to them, so the check
+    /// is deferred until the whole program has been visited.
+    fn check_undeclared_assignments(&mut self) {
+        for (span, name) in mem::take(&mut self.undeclared_assignments) {
+            if !self.globals.contains_key(&name) {
+                self.report(
+                    self.options.undeclared_assignment,
+                    ResolverError::UndefinedVar(span, name),
+                );
+            }
+        }
+    }
+
+    fn classify(&mut self, span: Span, kind: SemanticKind) {
+        if self.options.semantic_tokens {
+            self.semantic_tokens.push(SemanticToken { span, kind });
+        }
+    }
+
+    fn record(&mut self, span: Span, name: &str, declaration: Option<Span>) {
+        if self.options.references {
+            self.references.push(Reference {
+                span,
+                name: name.to_string(),
+                declaration,
+            });
+        }
+    }
+
+    fn resolve_global_references(&mut self) {
+        for (span, name) in mem::take(&mut self.global_references) {
+            let declaration = self.globals.get(&name);
+            let kind = if self.global_classes.contains_key(&name) {
+                SemanticKind::Class
+            } else if declaration.is_some() {
+                SemanticKind::Global
+            } else {
+                SemanticKind::Unresolved
+            };
+            let declaration = declaration.copied().flatten();
+            self.classify(span, kind);
+            self.record(span, &name, declaration);
+        }
+        for (span, name) in mem::take(&mut self.properties) {
+            if self.method_names.contains(&name) {
+                self.classify(span, SemanticKind::Method);
+            }
+        }
+    }
+
     fn declare(&mut self, var: &mut Variable, initialized: bool) {
+        self.declare_kind(var, initialized, VariableKind::Local);
+    }
+
+    fn declare_kind(&mut self, var: &mut Variable, initialized: bool, kind: VariableKind) {
+        let ident = &var.ident;
+        let declaration = if self.scopes.is_empty() {
+            *self
+                .globals
+                .entry(ident.name.to_string())
+                .or_insert(Some(ident.span))
+        } else {
+            Some(ident.span)
+        };
+        self.record(ident.span, &ident.name, declaration);
+        self.classify(
+            var.ident.span,
+            match kind {
+                VariableKind::Class => SemanticKind::Class,
+                _ if self.scopes.is_empty() => SemanticKind::Global,
+                VariableKind::Parameter => SemanticKind::Parameter,
+                _ => SemanticKind::Local,
+            },
+        );
         if let Some(scope) = self.scopes.last_mut() {
-            match scope.declare(&var.ident.name, var.ident.span, initialized) {
+            match scope.declare(&var.ident.name, var.ident.span, initialized, kind) {
                 Ok(index) => {
                     var.target = Some(IdentTarget {
                         scope_count: 0,
                         index,
                     })
                 }
-                Err(defined_at) => self.errors.push(ResolverError::RedefineVar {
-                    pos: var.ident.span,
-                    name: var.ident.name.to_string(),
-                    defined_at,
-                }),
+                Err((index, defined_at)) => {
+                    // Refer to the first declaration so the target is still set.
+                    var.target = Some(IdentTarget {
+                        scope_count: 0,
+                        index,
+                    });
+                    self.errors.push(ResolverError::RedefineVar {
+                        pos: var.ident.span,
+                        name: var.ident.name.to_string(),
+                        defined_at,
+                    })
+                }
             }
         }
     }
 
     fn access(&mut self, var: &mut Variable, status: VariableStatus) {
+        let mut found = None;
         for (scope_count, scope) in self.scopes.iter_mut().rev().enumerate() {
-            if let Some(index) = scope.access(&var.ident.name, status) {
+            if let Some(info) = scope.access(&var.ident.name, status) {
                 var.target = Some(IdentTarget {
                     scope_count: scope_count as u16,
-                    index,
+                    index: info.index,
                 });
+                found = Some((info.kind, info.defined_at));
                 break;
             }
         }
+        let span = var.ident.span;
+        if let Some((_, defined_at)) = found {
+            self.record(span, &var.ident.name, Some(defined_at));
+        }
+        match found.map(|(kind, _)| kind) {
+            Some(VariableKind::Local) => self.classify(span, SemanticKind::Local),
+            Some(VariableKind::Parameter) => self.classify(span, SemanticKind::Parameter),
+            Some(VariableKind::Class) => self.classify(span, SemanticKind::Class),
+            Some(VariableKind::Implicit) => {}
+            None if self.options.semantic_tokens || self.options.references => self
+                .global_references
+                .push((span, var.ident.name.to_string())),
+            None => {}
+        }
     }
 
     fn assign(&mut self, var: &mut Variable) {
@@ -135,55 +348,161 @@ impl Resolver {
 
     fn start_class_scope(&mut self, span: Span, is_super_class: bool) {
         let mut scope = Scope::default();
-        let _ = scope.declare(if is_super_class { "super" } else { "this" }, span, true);
+        let _ = scope.declare(
+            if is_super_class { "super" } else { "this" },
+            span,
+            true,
+            VariableKind::Implicit,
+        );
         self.scopes.push(scope);
     }
 
-    fn end_scope(&mut self) -> IdentIndex {
-        self.scopes.pop().unwrap().variables.len() as IdentIndex
+    /// Returns the number of locals of the scope, and their names if
+    /// [`ResolverOptions::local_names`] is set.
+    fn end_scope(&mut self) -> (IdentIndex, Option<Arc<[String]>>) {
+        let scope = self.scopes.pop().unwrap();
+        // Names starting with `_` are unused on purpose, e.g. parameters
+        // required by a callback's signature.
+        let mut unused: Vec<_> = scope
+            .variables
+            .iter()
+            .filter(|(name, var)| {
+                var.kind != VariableKind::Implicit
+                    && var.status != VariableStatus::Used
+                    && !name.starts_with('_')
+            })
+            .map(|(name, var)| (var.defined_at, name, var.kind))
+            .collect();
+        unused.sort_by_key(|(span, ..)| (span.start.line, span.start.column));
+        for (span, name, kind) in unused {
+            self.warnings.push(match kind {
+                VariableKind::Parameter => ResolverError::UnusedParam(span, name.to_string()),
+                _ => ResolverError::UnusedVar(span, name.to_string()),
+            });
+        }
+        let names = self.options.local_names.then(|| {
+            let mut names = vec![String::new(); scope.variables.len()];
+            for (name, var) in scope.variables.iter() {
+                names[var.index as usize] = name.to_string();
+            }
+            names.into()
+        });
+        (scope.variables.len() as IdentIndex, names)
+    }
+
+    fn check_class_redeclaration(&mut self, class: &ClassDecl) {
+        if !self.scopes.is_empty() {
+            // Local classes are reported as redefined variables by `declare`.
+            return;
+        }
+        let ident = &class.var.ident;
+        match self.global_classes.get(&*ident.name) {
+            Some(&defined_at) => self.errors.push(ResolverError::RedefineClass {
+                pos: ident.span,
+                name: ident.name.to_string(),
+                defined_at,
+            }),
+            None => {
+                self.global_classes
+                    .insert(ident.name.to_string(), ident.span);
+            }
+        }
+    }
+
+    fn check_duplicate_methods(&mut self, class: &ClassDecl) {
+        let mut defined = HashMap::new();
+        for method in class.methods.iter() {
+            let ident = &method.var.ident;
+            if let Some(&defined_at) = defined.get(&*ident.name) {
+                self.errors.push(ResolverError::DuplicateMethod {
+                    pos: ident.span,
+                    class: class.var.ident.name.to_string(),
+                    name: ident.name.to_string(),
+                    defined_at,
+                });
+            } else {
+                defined.insert(&*ident.name, ident.span);
+            }
+        }
+    }
+
+    /// Resolves with `resolve` one level deeper, skipping the node at `span`
+    /// if it nests too deeply.
+    fn nested(&mut self, span: Span, resolve: impl FnOnce(&mut Self)) {
+        if self.depth >= self.options.max_depth {
+            self.errors.push(ResolverError::TooDeep(span));
+            return;
+        }
+        self.depth += 1;
+        resolve(self);
+        self.depth -= 1;
     }
 
     fn resolve_function(&mut self, function: &mut FnDecl) {
         self.start_scope();
         for param in function.params.iter_mut() {
-            self.declare(param, true);
+            self.declare_kind(param, true, VariableKind::Parameter);
         }
         for stmt in function.body.iter_mut() {
-            walk_stmt(self, stmt);
+            self.visit_stmt(stmt);
         }
-        function.num_of_locals = self.end_scope();
+        (function.num_of_locals, function.local_names) = self.end_scope();
     }
 }
 
 impl VisitorMut for Resolver {
     type Result = ();
 
+    fn visit_stmt(&mut self, stmt: &mut Statement) -> Self::Result {
+        self.nested(stmt.get_span(), |this| walk_stmt(this, stmt));
+    }
+
+    fn visit_expr(&mut self, expr: &mut Expr) -> Self::Result {
+        self.nested(expr.get_span(), |this| walk_expr(this, expr));
+    }
+
+    fn visit_expression(&mut self, expression: &mut Expression) -> Self::Result {
+        let expr = &expression.expr;
+        if !expr.has_side_effects() {
+            self.warnings.push(match expr.ungroup() {
+                Expr::Binary(BinaryExpr {
+                    operator: BinaryOp::Equal,
+                    ..
+                }) => ResolverError::UselessComparison(expr.get_span()),
+                _ => ResolverError::UselessExpression(expr.get_span()),
+            });
+        }
+        self.visit_expr(&mut expression.expr);
+    }
+
     fn visit_if(&mut self, if_stmt: &mut If) -> Self::Result {
-        walk_expr(self, &mut if_stmt.condition);
-        walk_stmt(self, &mut if_stmt.then_branch);
+        self.visit_expr(&mut if_stmt.condition);
+        self.visit_stmt(&mut if_stmt.then_branch);
         if let Some(else_branch) = &mut if_stmt.else_branch {
-            walk_stmt(self, else_branch);
+            self.visit_stmt(else_branch);
         }
     }
 
     fn visit_while(&mut self, while_stmt: &mut While) -> Self::Result {
-        walk_expr(self, &mut while_stmt.condition);
-        walk_stmt(self, &mut while_stmt.body);
+        self.visit_expr(&mut while_stmt.condition);
+        self.visit_stmt(&mut while_stmt.body);
     }
 
     fn visit_block(&mut self, block: &mut Block) -> Self::Result {
         self.start_scope();
         for stmt in block.statements.iter_mut() {
-            walk_stmt(self, stmt);
+            self.visit_stmt(stmt);
         }
-        block.num_of_locals = self.end_scope();
+        (block.num_of_locals, block.local_names) = self.end_scope();
     }
 
     fn visit_var_decl(&mut self, var_decl: &mut VarDecl) -> Self::Result {
         self.declare(&mut var_decl.var, false);
         if let Some(expr) = &mut var_decl.initializer {
-            walk_expr(self, expr);
-            self.assign(&mut var_decl.var);
+            self.visit_expr(expr);
+            if let Some(scope) = self.scopes.last_mut() {
+                scope.access(&var_decl.var.ident.name, VariableStatus::Initialized);
+            }
         }
     }
 
@@ -195,19 +514,25 @@ impl VisitorMut for Resolver {
     }
 
     fn visit_class(&mut self, class: &mut ClassDecl) -> Self::Result {
-        self.declare(&mut class.var, true);
+        self.check_class_redeclaration(class);
+        self.declare_kind(&mut class.var, true, VariableKind::Class);
+        self.check_duplicate_methods(class);
         let previous_class_type = mem::replace(&mut self.class_type, ClassType::Class);
         if let Some(super_class) = &mut class.super_class {
-            self.start_class_scope(super_class.ident.span, true);
             self.get(super_class);
+            self.start_class_scope(super_class.ident.span, true);
             self.class_type = ClassType::SubClass;
         }
 
         self.start_class_scope(class.var.ident.span, false);
         for method in class.methods.iter_mut() {
+            self.classify(method.var.ident.span, SemanticKind::Method);
+            if self.options.semantic_tokens {
+                self.method_names.insert(method.var.ident.name.to_string());
+            }
             let previous_fn_type = mem::replace(
                 &mut self.function_type,
-                if method.var.ident.name == "init" {
+                if &*method.var.ident.name == "init" {
                     FunctionType::Initializer
                 } else {
                     FunctionType::Method
@@ -233,22 +558,48 @@ impl VisitorMut for Resolver {
                 self.errors
                     .push(ResolverError::ReturnInConstructor(return_stmt.span));
             }
-            walk_expr(self, expr);
+            self.visit_expr(expr);
         }
     }
 
     fn visit_fn_call(&mut self, fn_call: &mut FnCall) -> Self::Result {
-        walk_expr(self, &mut fn_call.callee);
+        self.visit_expr(&mut fn_call.callee);
         for expr in fn_call.arguments.iter_mut() {
-            walk_expr(self, expr);
+            self.visit_expr(expr);
+        }
+    }
+
+    fn visit_assign(&mut self, assign: &mut Assign) -> Self::Result {
+        self.visit_expr(&mut assign.value);
+        self.assign(&mut assign.var);
+        if assign.var.target.is_none() {
+            self.undeclared_assignments
+                .push((assign.var.ident.span, assign.var.ident.name.to_string()));
         }
     }
 
+    fn visit_get(&mut self, get: &mut Get) -> Self::Result {
+        self.visit_expr(&mut get.object);
+        if self.options.semantic_tokens {
+            self.properties
+                .push((get.field.span, get.field.name.to_string()));
+        }
+    }
+
+    fn visit_set(&mut self, set: &mut Set) -> Self::Result {
+        self.visit_get(&mut set.target);
+        self.visit_expr(&mut set.value);
+    }
+
     fn visit_literal(&mut self, _literal: &mut Literal) -> Self::Result {}
 
     fn visit_super(&mut self, super_expr: &mut Super) -> Self::Result {
         match self.class_type {
-            ClassType::SubClass => self.get(&mut super_expr.var),
+            ClassType::SubClass => {
+                self.get(&mut super_expr.var);
+                self.get(&mut super_expr.this);
+                self.classify(super_expr.method.span, SemanticKind::Method);
+            }
             ClassType::Class => self
                 .errors
                 .push(ResolverError::NotSubClass(super_expr.var.ident.span)),
@@ -259,7 +610,7 @@ impl VisitorMut for Resolver {
     }
 
     fn visit_var(&mut self, var: &mut Variable) -> Self::Result {
-        if var.ident.name == "this" && matches!(self.function_type, FunctionType::None) {
+        if &*var.ident.name == "this" && matches!(self.function_type, FunctionType::None) {
             self.errors.push(ResolverError::InvalidThis(var.ident.span));
         }
         self.get(var);
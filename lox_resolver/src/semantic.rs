@@ -0,0 +1,32 @@
+use lox_lexer::Span;
+
+/// What an identifier refers to, for highlighting by editors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SemanticKind {
+    Parameter,
+    Local,
+    Global,
+    Method,
+    Class,
+    /// A name which isn't declared anywhere.
+    Unresolved,
+}
+
+/// The classification of an identifier, see [`ResolverOptions::semantic_tokens`](crate::ResolverOptions::semantic_tokens).
+#[derive(Debug, Clone, Copy)]
+pub struct SemanticToken {
+    pub span: Span,
+    pub kind: SemanticKind,
+}
+
+/// An occurrence of a name and the declaration it refers to, see
+/// [`ResolverOptions::references`](crate::ResolverOptions::references).
+/// Declarations are references to themselves, except that redeclared globals
+/// refer to their first declaration.
+#[derive(Debug, Clone)]
+pub struct Reference {
+    pub span: Span,
+    pub name: String,
+    /// `None` for globals defined by the host and names which aren't declared.
+    pub declaration: Option<Span>,
+}
@@ -0,0 +1,205 @@
+use crate::{Resolver, ResolverError, ResolverOptions, SemanticKind, Severity};
+use lox_ast::Statement;
+
+#[test]
+fn semantic_tokens() {
+    let src = r#"
+class A {
+    get(x) { return this.get; }
+}
+class B < A {
+    get(y) { var z = y; return super.get(z) + missing; }
+}
+fun main() { return B().get(global); }
+var global;
+"#;
+    let mut ast = lox_parser::parse(src).into_result().unwrap();
+    let mut resolver = Resolver::new(ResolverOptions {
+        semantic_tokens: true,
+        ..Default::default()
+    });
+    assert!(resolver.resolve(&mut ast).is_none());
+
+    let tokens: Vec<_> = resolver
+        .take_semantic_tokens()
+        .iter()
+        .map(|token| {
+            let line = src.lines().nth(token.span.start.line as usize - 1).unwrap();
+            let start = token.span.start.column as usize - 1;
+            let end = token.span.end.column as usize - 1;
+            (line[start..end].to_string(), token.kind)
+        })
+        .collect();
+    use SemanticKind::*;
+    let expected = [
+        ("A", Class),
+        ("get", Method),
+        ("x", Parameter),
+        ("get", Method),
+        ("B", Class),
+        ("A", Class),
+        ("get", Method),
+        ("y", Parameter),
+        ("z", Local),
+        ("y", Parameter),
+        ("get", Method),
+        ("z", Local),
+        ("missing", Unresolved),
+        ("main", Global),
+        ("B", Class),
+        ("get", Method),
+        ("global", Global),
+        ("global", Global),
+    ];
+    assert_eq!(
+        tokens,
+        expected.map(|(name, kind)| (name.to_string(), kind))
+    );
+}
+
+#[test]
+fn useless_parenthesized_comparison() {
+    let mut ast = lox_parser::parse("var a; ((a == 1)); (a + 1);")
+        .into_result()
+        .unwrap();
+    let mut resolver = Resolver::default();
+    assert!(resolver.resolve(&mut ast).is_none());
+    let warnings = resolver.take_warnings();
+    assert!(matches!(
+        &warnings[..],
+        [
+            ResolverError::UselessComparison(comparison),
+            ResolverError::UselessExpression(expression),
+        ] if comparison.start.column == 8 && expression.start.column == 20
+    ));
+}
+
+#[test]
+fn partial_resolution() {
+    let src = "{ var a = 1; var a = 2; print a; } fun f(x, x) { return x; }";
+    let mut ast = lox_parser::parse(src).into_result().unwrap();
+    let errors = Resolver::default().resolve(&mut ast).unwrap();
+    assert_eq!(errors.len(), 2);
+
+    let [Statement::Block(block), Statement::FnDecl(function)] = &ast[..] else {
+        panic!("{ast:?}");
+    };
+    let [Statement::Var(first), Statement::Var(second), _] = &block.statements[..] else {
+        panic!("{block:?}");
+    };
+    assert_eq!(first.var.target, second.var.target);
+    assert_eq!(block.num_of_locals, 1);
+    assert!(function.params.iter().all(|param| param.target.is_some()));
+}
+
+#[test]
+fn too_deep() {
+    let src = format!("print {}1;", "-".repeat(20));
+    let mut ast = lox_parser::parse(&src).into_result().unwrap();
+    let mut resolver = Resolver::new(ResolverOptions {
+        max_depth: 10,
+        ..Default::default()
+    });
+    let errors = resolver.resolve(&mut ast).unwrap();
+    assert_eq!(
+        errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+        ["from line 1 column 16 to line 1 column 28: nesting is too deep"]
+    );
+}
+
+/// Resolves `src`, returning the names of the undeclared variables assigned
+/// to which were reported as warnings, and those reported as errors.
+fn undeclared_assignments(resolver: &mut Resolver, src: &str) -> (Vec<String>, Vec<String>) {
+    let mut ast = lox_parser::parse(src).into_result().unwrap();
+    let errors = resolver.resolve(&mut ast).unwrap_or_default();
+    let names = |diagnostics: &[ResolverError]| {
+        diagnostics
+            .iter()
+            .map(|diagnostic| match diagnostic {
+                ResolverError::UndefinedVar(_, name) => name.clone(),
+                other => panic!("unexpected {other}"),
+            })
+            .collect()
+    };
+    (names(&resolver.take_warnings()), names(&errors))
+}
+
+#[test]
+fn undeclared_assignment() {
+    let src = "fun f() { later = 1; missing = 2; } var later; clock = 3; f();";
+    let mut resolver = Resolver::default();
+    assert_eq!(
+        undeclared_assignments(&mut resolver, src),
+        (vec!["missing".to_string(), "clock".to_string()], vec![])
+    );
+
+    // Natives are globals of the host, which mustn't be reported.
+    let mut resolver = Resolver::new(ResolverOptions {
+        undeclared_assignment: Severity::Error,
+        ..Default::default()
+    });
+    resolver.define_global("clock");
+    assert_eq!(
+        undeclared_assignments(&mut resolver, src),
+        (vec![], vec!["missing".to_string()])
+    );
+}
+
+#[test]
+fn redefined_classes_and_methods() {
+    let src = "class A { f() {} g() {} f() {} }\nclass A {}\n{ class B {} }\nclass B {}";
+    let mut ast = lox_parser::parse(src).into_result().unwrap();
+    let errors = Resolver::default().resolve(&mut ast).unwrap();
+    let [ResolverError::DuplicateMethod {
+        pos,
+        class,
+        name,
+        defined_at,
+    }, ResolverError::RedefineClass {
+        pos: class_pos,
+        name: class_name,
+        defined_at: class_defined_at,
+    }] = &errors[..]
+    else {
+        panic!("{errors:?}");
+    };
+    assert_eq!((class.as_str(), name.as_str()), ("A", "f"));
+    assert_eq!((pos.start.column, defined_at.start.column), (25, 11));
+    assert_eq!(class_name, "A");
+    assert_eq!((class_pos.start.line, class_pos.start.column), (2, 7));
+    assert_eq!(
+        (class_defined_at.start.line, class_defined_at.start.column),
+        (1, 7)
+    );
+}
+
+#[test]
+fn side_effects() {
+    let src = "var a; var b; fun f() {}\nf(); a = 1; a.x = 2; (f()); a == f(); -(a = 3);\na == b; (a); -a; a.x; a ? b : 1;";
+    let mut ast = lox_parser::parse(src).into_result().unwrap();
+    let mut resolver = Resolver::default();
+    assert!(resolver.resolve(&mut ast).is_none());
+    let warnings: Vec<_> = resolver
+        .take_warnings()
+        .iter()
+        .map(|warning| {
+            let kind = match warning {
+                ResolverError::UselessComparison(_) => "comparison",
+                ResolverError::UselessExpression(_) => "expression",
+                other => panic!("unexpected {other}"),
+            };
+            let span = warning.span();
+            (kind, span.start.line, span.start.column)
+        })
+        .collect();
+    assert_eq!(
+        warnings,
+        [
+            ("comparison", 3, 1),
+            ("expression", 3, 9),
+            ("expression", 3, 14),
+            ("expression", 3, 18),
+            ("expression", 3, 23),
+        ]
+    );
+}
@@ -1,26 +1,95 @@
-use std::{env, fs};
+use std::{env, io, process};
 
-use lox_bytecode::compiler::Compiler;
-use lox_resolver::Resolver;
+use lox_ast::validate::is_valid;
+use lox_bytecode::{compiler::Compiler, disassembler, vm::Vm};
+use lox_lexer::{source, LanguageConfig, SourceMap};
+use lox_lint::{LintConfig, Linter};
+use lox_resolver::{Resolver, ResolverOptions};
 
-fn run_from_file(file_path: &str) {
-    let content =
-        fs::read_to_string(file_path).unwrap_or_else(|_| panic!("Cannot read file `{file_path}`"));
-    match lox_parser::parse(&content) {
-        Ok(mut ast) => match Resolver::default().resolve(&mut ast) {
-            Some(errors) => errors.iter().for_each(|e| eprintln!("{e}")),
-            None => {
-                let mut compiler = Compiler::default();
-                compiler.compile(&ast);
-                println!("{:?}", compiler);
+fn run_from_file(
+    file_path: &str,
+    config: &LintConfig,
+    language: &LanguageConfig,
+    disassemble: bool,
+    lossy: bool,
+) {
+    let Some(content) = source::read_script(file_path, lossy, &mut io::stderr()) else {
+        process::exit(1);
+    };
+    let mut sources = SourceMap::new();
+    let source = sources.add(file_path, content);
+    match lox_parser::parse_source(&sources, source, language).into_result() {
+        Ok(mut ast) => {
+            let mut resolver = Resolver::new(ResolverOptions {
+                max_depth: language.max_depth,
+                ..Default::default()
+            });
+            let errors = resolver.resolve(&mut ast);
+            // The linter isn't guarded against nesting too deep, which is one
+            // of the errors, so only the warnings of the resolver are
+            // reported with them.
+            let linted = if errors.is_none() { &ast[..] } else { &[] };
+            let denied = Linter::new(config.clone()).report(
+                linted,
+                &resolver.take_warnings(),
+                &mut io::stderr(),
+            );
+            match errors {
+                Some(errors) => errors.iter().for_each(|e| eprintln!("{e}")),
+                None if denied || !is_valid(&ast, &mut io::stderr()) => {}
+                None => {
+                    let mut compiler = Compiler::default();
+                    compiler.compile(&ast);
+                    #[allow(unused_mut)]
+                    let mut chunk = compiler.finish();
+                    if disassemble {
+                        #[cfg(feature = "comments")]
+                        chunk.attach_comments(sources.text(source));
+                        print!("{}", disassembler::disassemble(&chunk));
+                        return;
+                    }
+                    if let Err(err) = Vm::new().with_config(language).run(&chunk) {
+                        eprint!("{}", sources.render(&err, err.span()));
+                    }
+                }
             }
-        },
+        }
         Err(errors) => errors.iter().for_each(|e| eprintln!("{e}")),
     }
 }
 
 fn main() {
-    let args: Vec<_> = env::args().collect();
+    let mut config = LintConfig::default();
+    let mut args = config.parse_args(env::args()).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        process::exit(2);
+    });
+    // `--strict` disables the extensions which jlox doesn't support.
+    let language = match args.iter().position(|arg| arg == "--strict") {
+        Some(i) => {
+            args.remove(i);
+            LanguageConfig::strict()
+        }
+        None => LanguageConfig::default(),
+    };
 
-    run_from_file(&args[1]);
+    // `--disassemble` lists the compiled operations instead of running them.
+    let disassemble = match args.iter().position(|arg| arg == "--disassemble") {
+        Some(i) => {
+            args.remove(i);
+            true
+        }
+        None => false,
+    };
+
+    // `--lossy` replaces invalid UTF-8 in the script instead of rejecting it.
+    let lossy = match args.iter().position(|arg| arg == "--lossy") {
+        Some(i) => {
+            args.remove(i);
+            true
+        }
+        None => false,
+    };
+
+    run_from_file(&args[1], &config, &language, disassemble, lossy);
 }
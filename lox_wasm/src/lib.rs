@@ -0,0 +1,71 @@
+//! Runs scripts for the online playground, built for
+//! `wasm32-unknown-unknown`.
+//!
+//! [`run`] only takes and returns strings. With the `bindgen` feature, it's
+//! exported to JavaScript by `wasm-bindgen`, which returns the fields of
+//! [`Run`] through getters.
+
+use lox_interpreter::{Buffer, Interpreter, Permissions};
+use lox_lexer::{LanguageConfig, SourceMap};
+use lox_resolver::{Resolver, ResolverOptions};
+#[cfg(feature = "bindgen")]
+use wasm_bindgen::prelude::wasm_bindgen;
+
+#[cfg(test)]
+mod test;
+
+/// What running a script produced.
+#[cfg_attr(feature = "bindgen", wasm_bindgen(getter_with_clone))]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Run {
+    /// What the script printed.
+    pub output: String,
+    /// The errors of the script, rendered with the code they point at.
+    pub diagnostics: String,
+}
+
+/// Parses, resolves and runs `src` in a fresh interpreter which can't touch
+/// anything outside of it, see [`Permissions::none`].
+#[cfg_attr(feature = "bindgen", wasm_bindgen)]
+pub fn run(src: &str) -> Run {
+    let mut sources = SourceMap::new();
+    let source = sources.add("script.lox", src);
+    let mut run = Run::default();
+    let language = LanguageConfig::default();
+    let mut ast = match lox_parser::parse_source(&sources, source, &language).into_result() {
+        Ok(ast) => ast,
+        Err(errors) => {
+            for error in errors.iter() {
+                run.diagnostics += &sources.render(error, Some(error.span()));
+            }
+            return run;
+        }
+    };
+    let output = Buffer::default();
+    let mut interpreter = Interpreter::with_output(output.clone());
+    interpreter.set_language(&language);
+    interpreter.set_permissions(Permissions::none());
+
+    let mut resolver = Resolver::new(ResolverOptions {
+        max_depth: language.max_depth,
+        ..Default::default()
+    });
+    for (name, _) in interpreter.globals() {
+        resolver.define_global(name);
+    }
+    if let Some(errors) = resolver.resolve(&mut ast) {
+        for error in errors.iter() {
+            run.diagnostics += &sources.render(error, Some(error.span()));
+        }
+        return run;
+    }
+
+    if let Err(err) = interpreter.interpret(&ast) {
+        run.diagnostics += &sources.render(&err, err.span());
+        if let Some((note, span)) = err.note() {
+            run.diagnostics += &format!("note: {note}\n{}", sources.snippet(span));
+        }
+    }
+    run.output = output.take();
+    run
+}
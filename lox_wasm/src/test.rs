@@ -0,0 +1,29 @@
+use crate::{run, Run};
+
+#[test]
+fn output_and_diagnostics() {
+    assert_eq!(
+        run("print 1 + 2;"),
+        Run {
+            output: "3\n".to_string(),
+            diagnostics: String::new(),
+        }
+    );
+
+    let run = run("print \"a\";\nprint -\"b\";");
+    assert_eq!(run.output, "a\n");
+    assert!(run.diagnostics.starts_with("error: "));
+    assert!(run.diagnostics.contains("2 | print -\"b\";"));
+}
+
+#[test]
+fn no_permissions() {
+    assert!(run("system(\"true\");")
+        .diagnostics
+        .contains("Permission denied"));
+}
+
+#[test]
+fn natives_are_globals() {
+    assert_eq!(run("clock = 1;").diagnostics, "");
+}